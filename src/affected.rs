@@ -0,0 +1,614 @@
+//! Affected-repo detection for CI orchestrators
+//!
+//! Changed files are mapped to the workspace members that contain them, so a
+//! change to one member of a multi-crate repo only affects that member and
+//! the crates that (transitively) depend on it, in any repo.
+
+use crate::args;
+use crate::config::{ChangesConfig, Config};
+use crate::glob;
+use crate::graph::CrateGraph;
+use crate::{CommandResult, PlannedCommand};
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Why a repo is considered affected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// Files changed in the repo itself
+    Changed,
+    /// Depends on a crate in a changed repo
+    Dependent,
+    /// Change detection failed, so the repo is assumed affected
+    Unknown,
+}
+
+impl Reason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Reason::Changed => "changed",
+            Reason::Dependent => "dependent",
+            Reason::Unknown => "unknown",
+        }
+    }
+}
+
+/// An affected repo and its affected crates
+#[derive(Debug, Clone)]
+pub struct AffectedRepo {
+    pub path: String,
+    pub reason: Reason,
+    pub changed_files: Vec<String>,
+    pub crates: Vec<String>,
+    /// Why change detection failed, for `Reason::Unknown`
+    pub error: Option<String>,
+    /// Only Cargo.lock changed, so dependents in other repos are unaffected
+    pub lockfile_only: bool,
+    /// Command to run for this repo, when requested with `--command`
+    pub command: Option<String>,
+}
+
+impl AffectedRepo {
+    /// Command to run for `subcommand`, taking the lockfile fast path into account
+    pub fn command_for(&self, subcommand: &str, changes: &ChangesConfig) -> String {
+        match changes.lockfile_only.get(subcommand) {
+            Some(fast_path) if self.lockfile_only => fast_path.clone(),
+            _ => format!("cargo {subcommand}"),
+        }
+    }
+}
+
+/// Result of affected detection
+#[derive(Debug, Clone)]
+pub struct Affected {
+    pub since: String,
+    pub repos: Vec<AffectedRepo>,
+}
+
+impl Affected {
+    /// Paths of all affected repos, in plan order
+    pub fn repo_paths(&self) -> Vec<String> {
+        self.repos.iter().map(|r| r.path.clone()).collect()
+    }
+
+    /// Fill in each repo's command for `subcommand`
+    pub fn assign_commands(&mut self, subcommand: &str, changes: &ChangesConfig) {
+        for repo in &mut self.repos {
+            repo.command = Some(repo.command_for(subcommand, changes));
+        }
+    }
+
+    /// Swap in the `[changes.lockfile_only]` fast path for the planned
+    /// commands of repos where only Cargo.lock changed
+    ///
+    /// Only `<cargo> <subcommand>` is replaced, so the user's arguments and
+    /// the flags `cargo` carries (e.g. the root `--config`) are kept.
+    pub fn apply_fast_paths(
+        &self,
+        commands: &mut [PlannedCommand],
+        cargo: &str,
+        subcommand: &str,
+        changes: &ChangesConfig,
+    ) {
+        let Some(fast_path) = changes.lockfile_only.get(subcommand) else {
+            return;
+        };
+        let fast_path = match fast_path.strip_prefix("cargo ") {
+            Some(rest) => format!("{cargo} {rest}"),
+            None => fast_path.clone(),
+        };
+        let prefix = format!("{cargo} {subcommand}");
+        for repo in self.repos.iter().filter(|r| r.lockfile_only) {
+            for planned in commands.iter_mut().filter(|c| c.dir == repo.path) {
+                planned.cmd = match planned.cmd.strip_prefix(&prefix) {
+                    Some(rest) if rest.is_empty() || rest.starts_with(' ') => {
+                        format!("{fast_path}{rest}")
+                    }
+                    _ => fast_path.clone(),
+                };
+            }
+        }
+    }
+
+    /// Stable JSON representation consumed by external tools
+    pub fn to_json(&self) -> serde_json::Value {
+        let crates: BTreeSet<&str> = self
+            .repos
+            .iter()
+            .flat_map(|r| r.crates.iter().map(String::as_str))
+            .collect();
+        json!({
+            "since": self.since,
+            "repos": self.repos.iter().map(|r| json!({
+                "path": r.path,
+                "reason": r.reason.as_str(),
+                "changed_files": r.changed_files,
+                "crates": r.crates,
+                "error": r.error,
+                "lockfile_only": r.lockfile_only,
+                "command": r.command,
+            })).collect::<Vec<_>>(),
+            "crates": crates,
+        })
+    }
+
+    /// Human-readable summary
+    pub fn render_text(&self) -> String {
+        if self.repos.is_empty() {
+            return format!("No repos affected since {}", self.since);
+        }
+        let mut out = format!("Affected since {}:\n", self.since);
+        for repo in &self.repos {
+            let detail = match repo.reason {
+                Reason::Changed if repo.lockfile_only => "Cargo.lock only".to_string(),
+                Reason::Changed => format!("{} changed file(s)", repo.changed_files.len()),
+                Reason::Dependent => "depends on affected crates".to_string(),
+                Reason::Unknown => format!(
+                    "change detection failed: {}",
+                    repo.error.as_deref().unwrap_or("unknown error")
+                ),
+            };
+            out.push_str(&format!(
+                "  {} ({detail}) [{}]",
+                repo.path,
+                repo.crates.join(", ")
+            ));
+            if let Some(command) = &repo.command {
+                out.push_str(&format!(" -> {command}"));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Changed files of `repo`, excluding ignored files and those that belong to
+/// nested repos
+pub(crate) fn repo_changes(
+    repo: &str,
+    repos: &[String],
+    cwd: &Path,
+    since: &str,
+    ignore: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let mut files = crate::git::changed_files(&crate::project_path(cwd, repo), since)?;
+    let nested: Vec<String> = repos
+        .iter()
+        .filter(|other| other.as_str() != repo && other.as_str() != ".")
+        .filter_map(|other| {
+            if repo == "." {
+                Some(other.clone())
+            } else {
+                other.strip_prefix(&format!("{repo}/")).map(str::to_string)
+            }
+        })
+        .collect();
+    files.retain(|f| {
+        !nested
+            .iter()
+            .any(|n| f == n || f.starts_with(&format!("{n}/")))
+            && !glob::matches_any(ignore, f)
+    });
+    Ok(files)
+}
+
+/// Files at a repo's top level that affect every crate in it
+const WORKSPACE_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    "rust-toolchain",
+    "rust-toolchain.toml",
+];
+
+/// Crates of `repo` owned by the changed `files`
+///
+/// Each file belongs to the crate with the deepest root containing it.
+/// Workspace-level files, and files outside every crate, affect all crates
+/// of the repo.
+fn owning_crates(
+    graph: &CrateGraph,
+    repo: &str,
+    repo_dir: &Path,
+    files: &[String],
+) -> BTreeSet<usize> {
+    let all: BTreeSet<usize> = graph.crates_in_repo(repo).collect();
+    let repo_dir = repo_dir
+        .canonicalize()
+        .unwrap_or_else(|_| repo_dir.to_path_buf());
+    let roots: Vec<(usize, PathBuf)> = all
+        .iter()
+        .map(|&i| {
+            let root = &graph.crates[i].root;
+            (i, root.canonicalize().unwrap_or_else(|_| root.clone()))
+        })
+        .collect();
+
+    let mut owners = BTreeSet::new();
+    for file in files {
+        if WORKSPACE_FILES.contains(&file.as_str()) || file.starts_with(".cargo/") {
+            return all;
+        }
+        let path = repo_dir.join(file);
+        let owner = roots
+            .iter()
+            .filter(|(_, root)| path.starts_with(root))
+            .max_by_key(|(_, root)| root.components().count());
+        match owner {
+            Some((i, _)) => {
+                owners.insert(*i);
+            }
+            None => return all,
+        }
+    }
+    owners
+}
+
+/// A repo with direct changes
+struct DirectChange {
+    repo: String,
+    reason: Reason,
+    files: Vec<String>,
+    error: Option<String>,
+    lockfile_only: bool,
+}
+
+/// Determine which of `repos` are affected by changes since `since`
+///
+/// Files matching any of the `ignore` globs are not considered changes.
+pub fn compute(
+    repos: &[String],
+    cwd: &Path,
+    since: &str,
+    ignore: &[String],
+) -> anyhow::Result<Affected> {
+    // A repo whose metadata can't be loaded counts as affected, with the error
+    let (graph, failed) = CrateGraph::load_partial(repos, cwd);
+
+    let mut direct = Vec::new();
+    let mut seeds = BTreeSet::new();
+    // Crates affected only through their own repo's lockfile
+    let mut local = BTreeSet::new();
+    for repo in repos {
+        if let Some((_, error)) = failed.iter().find(|(r, _)| r == repo) {
+            direct.push(DirectChange {
+                repo: repo.clone(),
+                reason: Reason::Unknown,
+                files: vec![],
+                error: Some(error.clone()),
+                lockfile_only: false,
+            });
+            continue;
+        }
+        let change = match repo_changes(repo, repos, cwd, since, ignore) {
+            Ok(files) if files.is_empty() => continue,
+            Ok(files) => {
+                let lockfile_only = files.iter().all(|f| f == "Cargo.lock");
+                if lockfile_only {
+                    local.extend(graph.crates_in_repo(repo));
+                } else {
+                    let repo_dir = crate::project_path(cwd, repo);
+                    seeds.extend(owning_crates(&graph, repo, &repo_dir, &files));
+                }
+                DirectChange {
+                    repo: repo.clone(),
+                    reason: Reason::Changed,
+                    files,
+                    error: None,
+                    lockfile_only,
+                }
+            }
+            Err(e) => {
+                seeds.extend(graph.crates_in_repo(repo));
+                DirectChange {
+                    repo: repo.clone(),
+                    reason: Reason::Unknown,
+                    files: vec![],
+                    error: Some(e.to_string()),
+                    lockfile_only: false,
+                }
+            }
+        };
+        direct.push(change);
+    }
+
+    let mut affected_crates = graph.dependents(&seeds);
+    affected_crates.extend(local);
+
+    let mut result = Vec::new();
+    for repo in repos {
+        let crates: Vec<String> = graph
+            .crates_in_repo(repo)
+            .filter(|i| affected_crates.contains(i))
+            .map(|i| graph.crates[i].name.clone())
+            .collect();
+        if let Some(change) = direct.iter().find(|c| &c.repo == repo) {
+            result.push(AffectedRepo {
+                path: repo.clone(),
+                reason: change.reason,
+                changed_files: change.files.clone(),
+                crates,
+                error: change.error.clone(),
+                lockfile_only: change.lockfile_only,
+                command: None,
+            });
+        } else if !crates.is_empty() {
+            result.push(AffectedRepo {
+                path: repo.clone(),
+                reason: Reason::Dependent,
+                changed_files: vec![],
+                crates,
+                error: None,
+                lockfile_only: false,
+                command: None,
+            });
+        }
+    }
+
+    Ok(Affected {
+        since: since.to_string(),
+        repos: result,
+    })
+}
+
+/// Handle `meta cargo affected --since <ref> [--format text|json] [--command <sub>]`
+pub(crate) fn execute(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    config: &Config,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let Some(since) = args::take_value(&mut args, "--since") else {
+        return CommandResult::Error("affected requires --since <ref>".to_string());
+    };
+    let format = args::take_value(&mut args, "--format").unwrap_or_else(|| "text".to_string());
+    if format != "text" && format != "json" {
+        return CommandResult::Error(format!(
+            "unsupported format '{format}' (expected text or json)"
+        ));
+    }
+
+    let subcommand = args::take_value(&mut args, "--command");
+
+    let mut affected = match compute(repos, cwd, &since, &config.changes.ignore) {
+        Ok(a) => a,
+        Err(e) => return CommandResult::Error(format!("Failed to compute affected repos: {e}")),
+    };
+    if let Some(sub) = &subcommand {
+        affected.assign_commands(sub, &config.changes);
+    }
+    if format == "json" {
+        CommandResult::Message(
+            serde_json::to_string_pretty(&affected.to_json()).unwrap_or_default(),
+        )
+    } else {
+        CommandResult::Message(affected.render_text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    fn crate_repo(root: &Path, name: &str, deps: &str) {
+        let dir = root.join(name);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n{deps}"),
+        )
+        .unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+        git(&dir, &["init", "-q"]);
+        git(&dir, &["add", "-A"]);
+        git(&dir, &["commit", "-q", "-m", "init"]);
+    }
+
+    #[test]
+    fn test_changed_repo_and_dependents() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        crate_repo(root, "core", "");
+        crate_repo(root, "app", "core = { path = \"../core\" }\n");
+        crate_repo(root, "other", "");
+        std::fs::write(root.join("core/src/lib.rs"), "pub fn f() {}\n").unwrap();
+
+        let repos = vec!["app".to_string(), "core".to_string(), "other".to_string()];
+        let affected = compute(&repos, root, "HEAD", &[]).unwrap();
+
+        assert_eq!(affected.repo_paths(), vec!["app", "core"]);
+        assert_eq!(affected.repos[0].reason, Reason::Dependent);
+        assert_eq!(affected.repos[1].reason, Reason::Changed);
+        assert_eq!(affected.repos[1].changed_files, vec!["src/lib.rs"]);
+
+        let json = affected.to_json();
+        assert_eq!(json["crates"], json!(["app", "core"]));
+        assert_eq!(json["repos"][0]["reason"], "dependent");
+    }
+
+    #[test]
+    fn test_change_maps_to_workspace_member() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let ws = root.join("ws");
+        for member in ["a", "b"] {
+            std::fs::create_dir_all(ws.join(member).join("src")).unwrap();
+            std::fs::write(
+                ws.join(member).join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"{member}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"
+                ),
+            )
+            .unwrap();
+            std::fs::write(ws.join(member).join("src/lib.rs"), "").unwrap();
+        }
+        std::fs::write(
+            ws.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"a\", \"b\"]\nresolver = \"2\"\n",
+        )
+        .unwrap();
+        git(&ws, &["init", "-q"]);
+        git(&ws, &["add", "-A"]);
+        git(&ws, &["commit", "-q", "-m", "init"]);
+        crate_repo(root, "app", "a = { path = \"../ws/a\" }\n");
+
+        std::fs::write(ws.join("b/README.md"), "docs\n").unwrap();
+        let repos = vec!["app".to_string(), "ws".to_string()];
+        let affected = compute(&repos, root, "HEAD", &[]).unwrap();
+        assert_eq!(affected.repo_paths(), vec!["ws"]);
+        assert_eq!(affected.repos[0].crates, vec!["b"]);
+
+        std::fs::write(
+            ws.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"a\", \"b\"]\n",
+        )
+        .unwrap();
+        let affected = compute(&repos, root, "HEAD", &[]).unwrap();
+        assert_eq!(affected.repo_paths(), vec!["app", "ws"]);
+        assert_eq!(affected.repos[1].crates, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_ignored_files_are_not_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        crate_repo(root, "core", "");
+        std::fs::create_dir_all(root.join("core/docs")).unwrap();
+        std::fs::write(root.join("core/docs/guide.md"), "docs\n").unwrap();
+        std::fs::write(root.join("core/README.md"), "readme\n").unwrap();
+
+        let repos = vec!["core".to_string()];
+        let ignore = vec!["**/*.md".to_string()];
+        let affected = compute(&repos, root, "HEAD", &ignore).unwrap();
+        assert!(affected.repos.is_empty());
+
+        std::fs::write(root.join("core/src/lib.rs"), "pub fn f() {}\n").unwrap();
+        let affected = compute(&repos, root, "HEAD", &ignore).unwrap();
+        assert_eq!(affected.repos[0].changed_files, vec!["src/lib.rs"]);
+    }
+
+    #[test]
+    fn test_lockfile_only_change_uses_fast_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        crate_repo(root, "core", "");
+        crate_repo(root, "app", "core = { path = \"../core\" }\n");
+        std::fs::write(root.join("core/Cargo.lock"), "version = 4\n").unwrap();
+
+        let repos = vec!["app".to_string(), "core".to_string()];
+        let mut affected = compute(&repos, root, "HEAD", &[]).unwrap();
+        assert_eq!(affected.repo_paths(), vec!["core"]);
+        assert!(affected.repos[0].lockfile_only);
+
+        let config =
+            Config::parse("[changes.lockfile_only]\ntest = \"cargo check --locked\"\n").unwrap();
+        affected.assign_commands("test", &config.changes);
+        assert_eq!(
+            affected.repos[0].command.as_deref(),
+            Some("cargo check --locked")
+        );
+        affected.assign_commands("build", &config.changes);
+        assert_eq!(affected.repos[0].command.as_deref(), Some("cargo build"));
+    }
+
+    #[test]
+    fn test_affected_flag_narrows_the_plan() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        crate_repo(root, "core", "");
+        crate_repo(root, "app", "core = { path = \"../core\" }\n");
+        crate_repo(root, "other", "");
+        std::fs::write(root.join("core/src/lib.rs"), "pub fn f() {}\n").unwrap();
+
+        let projects = vec!["app".to_string(), "core".to_string(), "other".to_string()];
+        let args = vec!["--affected".to_string(), "--release".to_string()];
+        match crate::execute_command("cargo build", &args, false, &projects, root) {
+            CommandResult::Plan(commands, _) => {
+                let dirs: Vec<&str> = commands.iter().map(|c| c.dir.as_str()).collect();
+                assert_eq!(dirs, vec!["app", "core"]);
+                assert!(commands[0].cmd.ends_with("cargo build --release"));
+            }
+            _ => panic!("Expected Plan result"),
+        }
+    }
+
+    #[test]
+    fn test_affected_flag_uses_lockfile_fast_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        crate_repo(root, "core", "");
+        crate_repo(root, "app", "");
+        std::fs::write(root.join("core/Cargo.lock"), "version = 4\n").unwrap();
+        std::fs::write(root.join("app/src/lib.rs"), "pub fn f() {}\n").unwrap();
+        std::fs::write(
+            root.join(crate::config::CONFIG_FILE),
+            "[changes.lockfile_only]\ntest = \"cargo check --locked\"\n",
+        )
+        .unwrap();
+
+        let projects = vec!["app".to_string(), "core".to_string()];
+        let args = vec!["--affected".to_string()];
+        match crate::execute_command("cargo test", &args, false, &projects, root) {
+            CommandResult::Plan(commands, _) => {
+                let plan: Vec<(&str, &str)> = commands
+                    .iter()
+                    .map(|c| (c.dir.as_str(), c.cmd.as_str()))
+                    .collect();
+                assert_eq!(
+                    plan,
+                    vec![("app", "cargo test"), ("core", "cargo check --locked")]
+                );
+            }
+            _ => panic!("Expected Plan result"),
+        }
+    }
+
+    #[test]
+    fn test_lockfile_fast_path_keeps_args_and_root_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        crate_repo(root, "core", "");
+        std::fs::write(root.join("core/Cargo.lock"), "version = 4\n").unwrap();
+        std::fs::create_dir_all(root.join(".cargo")).unwrap();
+        std::fs::write(root.join(".cargo/config.toml"), "").unwrap();
+        std::fs::write(
+            root.join(crate::config::CONFIG_FILE),
+            "[changes.lockfile_only]\ntest = \"cargo check --locked\"\n",
+        )
+        .unwrap();
+
+        let projects = vec!["core".to_string()];
+        let args = vec!["--affected".to_string(), "--release".to_string()];
+        match crate::execute_command("cargo test", &args, false, &projects, root) {
+            CommandResult::Plan(commands, _) => {
+                let cmd = &commands[0].cmd;
+                assert!(cmd.starts_with("cargo --config "), "{cmd}");
+                assert!(cmd.ends_with(" check --locked --release"), "{cmd}");
+            }
+            _ => panic!("Expected Plan result"),
+        }
+    }
+
+    #[test]
+    fn test_execute_requires_since() {
+        let result = execute(&[], &[], Path::new("."), &Config::default());
+        match result {
+            CommandResult::Error(msg) => assert!(msg.contains("--since")),
+            _ => panic!("Expected Error result"),
+        }
+    }
+}
@@ -0,0 +1,374 @@
+//! `meta cargo versions`: external dependency versions across repos
+//!
+//! Every manifest of every repo is scanned for registry dependencies (path,
+//! git and `workspace = true` entries, and crates defined in the meta
+//! workspace, are left out). A crate required with different versions in
+//! different places is reported with where each requirement comes from.
+//!
+//! `--fix` raises the requirements of each semver-compatible group (same
+//! major, or same minor for `0.x`) to the highest version in the group.
+//! Requirements other than plain versions (`>=1, <2`, `=1.2.3`, `*`) are
+//! reported but never rewritten.
+
+use crate::rename_dep::{is_dependency_table, key_is};
+use crate::{args, metadata, project_path, CommandResult};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A registry dependency declared in a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration {
+    /// Package name (`package = "..."` for renamed dependencies)
+    pub name: String,
+    /// Requirement as written, e.g. `1.0.150` or `^0.4`
+    pub req: String,
+    pub manifest: PathBuf,
+}
+
+/// Requirement without the default `^` operator
+fn normalized(req: &str) -> &str {
+    let req = req.trim();
+    req.strip_prefix('^').unwrap_or(req)
+}
+
+/// Components of a plain requirement, `None` for ranges, pins and wildcards
+fn plain_version(req: &str) -> Option<Vec<u64>> {
+    let req = normalized(req);
+    if req.is_empty() {
+        return None;
+    }
+    req.split('.').map(|p| p.parse().ok()).collect()
+}
+
+/// Versions with the same key are semver compatible
+pub(crate) fn compat_key(version: &[u64]) -> Vec<u64> {
+    let end = version
+        .iter()
+        .position(|&p| p != 0)
+        .unwrap_or(version.len().saturating_sub(1));
+    version[..=end.min(version.len().saturating_sub(1))].to_vec()
+}
+
+fn padded(version: &[u64]) -> [u64; 3] {
+    let mut out = [0; 3];
+    for (slot, part) in out.iter_mut().zip(version) {
+        *slot = *part;
+    }
+    out
+}
+
+/// Registry dependencies of a parsed manifest, as `(name, requirement)`
+fn dependencies(manifest: &toml::Table) -> Vec<(String, String)> {
+    let mut tables: Vec<&toml::Table> = Vec::new();
+    for kind in ["dependencies", "dev-dependencies", "build-dependencies"] {
+        if let Some(t) = manifest.get(kind).and_then(|v| v.as_table()) {
+            tables.push(t);
+        }
+    }
+    if let Some(targets) = manifest.get("target").and_then(|v| v.as_table()) {
+        for target in targets.values().filter_map(|v| v.as_table()) {
+            for kind in ["dependencies", "dev-dependencies", "build-dependencies"] {
+                if let Some(t) = target.get(kind).and_then(|v| v.as_table()) {
+                    tables.push(t);
+                }
+            }
+        }
+    }
+    if let Some(t) = manifest
+        .get("workspace")
+        .and_then(|w| w.get("dependencies"))
+        .and_then(|v| v.as_table())
+    {
+        tables.push(t);
+    }
+
+    let mut found = Vec::new();
+    for table in tables {
+        for (key, value) in table {
+            let (name, req) = match value {
+                toml::Value::String(req) => (key.clone(), req.clone()),
+                toml::Value::Table(entry) => {
+                    if ["path", "git", "workspace"]
+                        .iter()
+                        .any(|k| entry.contains_key(*k))
+                    {
+                        continue;
+                    }
+                    let Some(req) = entry.get("version").and_then(|v| v.as_str()) else {
+                        continue;
+                    };
+                    let name = entry.get("package").and_then(|v| v.as_str()).unwrap_or(key);
+                    (name.to_string(), req.to_string())
+                }
+                _ => continue,
+            };
+            found.push((name, req));
+        }
+    }
+    found
+}
+
+/// Declarations of external dependencies in every manifest of `repos`
+fn scan(repos: &[String], cwd: &Path) -> Vec<Declaration> {
+    let mut manifests: Vec<PathBuf> = repos
+        .iter()
+        .flat_map(|r| metadata::manifest_paths(&project_path(cwd, r)))
+        .collect();
+    manifests.sort();
+    manifests.dedup();
+
+    let mut local = Vec::new();
+    let mut declarations = Vec::new();
+    for path in manifests {
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let Ok(manifest) = toml::from_str::<toml::Table>(&text) else {
+            continue;
+        };
+        if let Some(name) = manifest
+            .get("package")
+            .and_then(|p| p.get("name"))
+            .and_then(|n| n.as_str())
+        {
+            local.push(name.to_string());
+        }
+        for (name, req) in dependencies(&manifest) {
+            declarations.push(Declaration {
+                name,
+                req,
+                manifest: path.clone(),
+            });
+        }
+    }
+    declarations.retain(|d| !local.contains(&d.name));
+    declarations
+}
+
+/// Crates required with more than one (normalized) requirement
+fn mismatches(declarations: &[Declaration]) -> BTreeMap<&str, BTreeMap<&str, Vec<&Declaration>>> {
+    let mut by_name: BTreeMap<&str, BTreeMap<&str, Vec<&Declaration>>> = BTreeMap::new();
+    for d in declarations {
+        by_name
+            .entry(d.name.as_str())
+            .or_default()
+            .entry(normalized(&d.req))
+            .or_default()
+            .push(d);
+    }
+    by_name.retain(|_, reqs| reqs.len() > 1);
+    by_name
+}
+
+/// Requirement each plain requirement of a crate is raised to, keyed by the
+/// normalized requirement; already-highest ones are left out
+fn alignment<'a>(reqs: impl Iterator<Item = &'a str>) -> BTreeMap<String, String> {
+    let mut groups: BTreeMap<Vec<u64>, Vec<(&str, Vec<u64>)>> = BTreeMap::new();
+    for req in reqs {
+        if let Some(version) = plain_version(req) {
+            groups
+                .entry(compat_key(&version))
+                .or_default()
+                .push((req, version));
+        }
+    }
+    let mut targets = BTreeMap::new();
+    for members in groups.values() {
+        let Some((highest, _)) = members.iter().max_by_key(|(req, v)| (padded(v), req.len()))
+        else {
+            continue;
+        };
+        for (req, _) in members {
+            if req != highest {
+                targets.insert(req.to_string(), highest.to_string());
+            }
+        }
+    }
+    targets
+}
+
+/// A manifest with requirement `from` of `name` replaced by `to`, or `None`
+fn rewrite_manifest(text: &str, name: &str, from: &str, to: &str) -> Option<String> {
+    let mut section = String::new();
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        let mut line = line.to_string();
+        if trimmed.starts_with('[') {
+            section = trimmed
+                .trim_matches(|c| c == '[' || c == ']')
+                .trim()
+                .to_string();
+        } else {
+            let in_table = is_dependency_table(&section)
+                && (key_is(&line, name) || line.contains(&format!("package = \"{name}\"")));
+            let in_entry = section
+                .strip_suffix(&format!(".{name}"))
+                .is_some_and(is_dependency_table)
+                && key_is(&line, "version");
+            if in_table || in_entry {
+                line = line.replacen(&format!("\"{from}\""), &format!("\"{to}\""), 1);
+            }
+        }
+        out.push_str(&line);
+    }
+    (out != text).then_some(out)
+}
+
+/// Handle `meta cargo versions [--fix]`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let fix = args::take_flag(&mut args, "--fix");
+    let declarations = scan(repos, cwd);
+    let found = mismatches(&declarations);
+    if found.is_empty() {
+        return CommandResult::Message(
+            "Every external dependency has a single version requirement across repos".to_string(),
+        );
+    }
+
+    let cwd_canon = cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf());
+    let label = |p: &Path| {
+        p.strip_prefix(&cwd_canon)
+            .or_else(|_| p.strip_prefix(cwd))
+            .unwrap_or(p)
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    let mut out = String::new();
+    let mut edits: BTreeMap<PathBuf, String> = BTreeMap::new();
+    let mut unfixable = 0;
+    for (name, reqs) in &found {
+        out.push_str(&format!("{name}: {} requirements\n", reqs.len()));
+        for (req, decls) in reqs {
+            let mut files: Vec<String> = decls.iter().map(|d| label(&d.manifest)).collect();
+            files.dedup();
+            out.push_str(&format!("    {req:<12} {}\n", files.join(", ")));
+        }
+        let targets = alignment(reqs.keys().copied());
+        if targets.len() + 1 < reqs.len() {
+            unfixable += 1;
+        }
+        if !fix {
+            continue;
+        }
+        for (from, to) in &targets {
+            for d in &reqs[from.as_str()] {
+                let text = match edits.get(&d.manifest) {
+                    Some(text) => text.clone(),
+                    None => match std::fs::read_to_string(&d.manifest) {
+                        Ok(text) => text,
+                        Err(_) => continue,
+                    },
+                };
+                let new_req = if d.req.trim().starts_with('^') {
+                    format!("^{to}")
+                } else {
+                    to.clone()
+                };
+                if let Some(updated) = rewrite_manifest(&text, name, &d.req, &new_req) {
+                    edits.insert(d.manifest.clone(), updated);
+                }
+            }
+        }
+    }
+
+    if !fix {
+        out.push_str(&format!(
+            "\n{} crates with mismatched requirements; `meta cargo versions --fix` aligns compatible ones\n",
+            found.len()
+        ));
+        return CommandResult::Error(out);
+    }
+    for (path, text) in &edits {
+        if let Err(e) = std::fs::write(path, text) {
+            return CommandResult::Error(format!("{out}Failed to write {}: {e}", path.display()));
+        }
+        out.push_str(&format!("updated {}\n", label(path)));
+    }
+    out.push_str(&format!(
+        "\n{} manifests updated; Cargo.lock files refresh on the next build\n",
+        edits.len()
+    ));
+    if unfixable > 0 {
+        out.push_str(&format!(
+            "{unfixable} crates still have incompatible or non-plain requirements\n"
+        ));
+    }
+    CommandResult::Message(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_alignment_per_compatible_group() {
+        let targets = alignment(
+            [
+                "1.0.150", "1.0.200", "1.0", "2.1", "0.3.1", "0.4", ">=1, <3",
+            ]
+            .into_iter(),
+        );
+        assert_eq!(targets.get("1.0.150").map(String::as_str), Some("1.0.200"));
+        assert_eq!(targets.get("1.0").map(String::as_str), Some("1.0.200"));
+        assert!(!targets.contains_key("1.0.200"));
+        assert!(!targets.contains_key("2.1"));
+        assert!(!targets.contains_key("0.3.1"));
+        assert!(!targets.contains_key(">=1, <3"));
+    }
+
+    #[test]
+    fn test_rewrite_manifest_forms() {
+        let text = r#"[dependencies]
+serde = "1.0.150"
+json = { package = "serde_json", version = "1.0.1" }
+
+[dev-dependencies.serde]
+version = "1.0.150"
+features = ["derive"]
+"#;
+        let updated = rewrite_manifest(text, "serde", "1.0.150", "1.0.200").unwrap();
+        assert!(!updated.contains("\"1.0.150\""), "{updated}");
+        assert_eq!(updated.matches("\"1.0.200\"").count(), 2, "{updated}");
+        let updated = rewrite_manifest(text, "serde_json", "1.0.1", "1.0.9").unwrap();
+        assert!(updated.contains("json = { package = \"serde_json\", version = \"1.0.9\" }"));
+        assert!(rewrite_manifest(text, "tokio", "1", "2").is_none());
+    }
+
+    #[test]
+    fn test_versions_report_and_fix() {
+        let temp_dir = TempDir::new().unwrap();
+        let manifests = [
+            ("core", "[package]\nname = \"core\"\n\n[dependencies]\nserde = \"1.0.150\"\nlog = \"0.4\"\n"),
+            ("app", "[package]\nname = \"app\"\n\n[dependencies]\nserde = { version = \"^1.0.200\" }\ncore = { path = \"../core\", version = \"0.1\" }\nlog = \"0.4\"\n"),
+        ];
+        for (repo, text) in manifests {
+            std::fs::create_dir(temp_dir.path().join(repo)).unwrap();
+            std::fs::write(temp_dir.path().join(repo).join("Cargo.toml"), text).unwrap();
+        }
+        let repos: Vec<String> = vec!["core".to_string(), "app".to_string()];
+        match execute(&[], &repos, temp_dir.path()) {
+            CommandResult::Error(out) => {
+                assert!(out.contains("serde: 2 requirements"), "{out}");
+                assert!(out.contains("    1.0.150      core/Cargo.toml"), "{out}");
+                assert!(!out.contains("log"), "{out}");
+            }
+            _ => panic!("Expected Error result"),
+        }
+        match execute(&["--fix".to_string()], &repos, temp_dir.path()) {
+            CommandResult::Message(out) => {
+                assert!(out.contains("updated core/Cargo.toml"), "{out}")
+            }
+            _ => panic!("Expected Message result"),
+        }
+        let core =
+            std::fs::read_to_string(temp_dir.path().join("core").join("Cargo.toml")).unwrap();
+        assert!(core.contains("serde = \"1.0.200\""), "{core}");
+        match execute(&[], &repos, temp_dir.path()) {
+            CommandResult::Message(_) => {}
+            _ => panic!("Expected Message result"),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Extraction of meta-rust flags from the argument list
+//!
+//! meta-rust flags are mixed in with the cargo arguments. Everything after a
+//! literal `--` belongs to cargo (or the test binary) and is never inspected.
+
+/// Index of the `--` separator, or the length of `args` if there is none
+fn boundary(args: &[String]) -> usize {
+    args.iter().position(|a| a == "--").unwrap_or(args.len())
+}
+
+/// Remove `--name <value>` or `--name=value` from `args`, returning the value
+pub(crate) fn take_value(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let end = boundary(args);
+    let prefix = format!("{name}=");
+    for i in 0..end {
+        if args[i] == name && i + 1 < end {
+            let value = args.remove(i + 1);
+            args.remove(i);
+            return Some(value);
+        }
+        if let Some(value) = args[i].strip_prefix(&prefix) {
+            let value = value.to_string();
+            args.remove(i);
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Remove a boolean `--name` flag from `args`, returning whether it was present
+pub(crate) fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let end = boundary(args);
+    match args[..end].iter().position(|a| a == name) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Remove `--name` or `--name=value` from `args`
+///
+/// Returns `None` when absent and `Some(None)` for the bare flag. Unlike
+/// [`take_value`], the following argument is never consumed.
+pub(crate) fn take_optional_value(args: &mut Vec<String>, name: &str) -> Option<Option<String>> {
+    let end = boundary(args);
+    let prefix = format!("{name}=");
+    for i in 0..end {
+        if args[i] == name {
+            args.remove(i);
+            return Some(None);
+        }
+        if let Some(value) = args[i].strip_prefix(&prefix) {
+            let value = value.to_string();
+            args.remove(i);
+            return Some(Some(value));
+        }
+    }
+    None
+}
+
+/// Owned copies of `args`, for tests
+#[cfg(test)]
+pub(crate) fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_value_both_forms() {
+        let mut args = strings(&["--since", "main", "--format=json", "--release"]);
+        assert_eq!(take_value(&mut args, "--since"), Some("main".to_string()));
+        assert_eq!(take_value(&mut args, "--format"), Some("json".to_string()));
+        assert_eq!(args, strings(&["--release"]));
+    }
+
+    #[test]
+    fn test_take_optional_value() {
+        let mut args = strings(&["--ci-log-groups", "--release"]);
+        assert_eq!(
+            take_optional_value(&mut args, "--ci-log-groups"),
+            Some(None)
+        );
+        assert_eq!(args, strings(&["--release"]));
+
+        let mut args = strings(&["--ci-log-groups=gitlab"]);
+        assert_eq!(
+            take_optional_value(&mut args, "--ci-log-groups"),
+            Some(Some("gitlab".to_string()))
+        );
+        assert_eq!(take_optional_value(&mut args, "--ci-log-groups"), None);
+    }
+
+    #[test]
+    fn test_flags_after_separator_are_untouched() {
+        let mut args = strings(&["--", "--since", "main"]);
+        assert_eq!(take_value(&mut args, "--since"), None);
+        assert_eq!(args.len(), 3);
+
+        let mut args = strings(&["--tap-per-test", "--", "--tap-per-test"]);
+        assert!(take_flag(&mut args, "--tap-per-test"));
+        assert!(!take_flag(&mut args, "--tap-per-test"));
+        assert_eq!(args, strings(&["--", "--tap-per-test"]));
+    }
+}
@@ -0,0 +1,258 @@
+//! `meta cargo audit`: one vulnerability report for every repo
+//!
+//! `cargo audit --json` runs in each repo and the advisories are merged by
+//! RUSTSEC id, so an advisory against a crate locked in several repos shows
+//! up once with the list of repos affected. Vulnerabilities fail the run;
+//! warnings (unmaintained, unsound, yanked) only do with `--deny warnings` or
+//! `--deny <kind>`, as with cargo audit itself.
+
+use crate::runner::{self, RunOutcome};
+use crate::{args, CommandResult, PlannedCommand};
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Warning kinds `--deny` accepts besides `warnings`
+const WARNING_KINDS: &[&str] = &["unmaintained", "unsound", "yanked"];
+
+/// One advisory (or yanked crate) across all repos
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Finding {
+    /// `None` for a vulnerability, else the warning kind
+    warning: Option<String>,
+    package: String,
+    title: String,
+    /// Locked versions of the package that are affected
+    versions: BTreeSet<String>,
+    patched: Vec<String>,
+    repos: BTreeSet<String>,
+}
+
+/// Findings keyed by advisory id (`yanked:<crate>` for yanked crates)
+type Findings = BTreeMap<String, Finding>;
+
+fn strings(value: &serde_json::Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|a| {
+            a.iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Add the entry of one repo's report to `findings`
+fn add(findings: &mut Findings, repo: &str, entry: &serde_json::Value, warning: Option<&str>) {
+    let package = entry["package"]["name"].as_str().unwrap_or("?");
+    let version = entry["package"]["version"].as_str().unwrap_or("?");
+    let advisory = &entry["advisory"];
+    let (id, title) = match advisory["id"].as_str() {
+        Some(id) => (
+            id.to_string(),
+            advisory["title"].as_str().unwrap_or("").to_string(),
+        ),
+        None => (
+            format!("{}:{package}", warning.unwrap_or("warning")),
+            format!("{package} {version} is {}", warning.unwrap_or("flagged")),
+        ),
+    };
+    let finding = findings.entry(id).or_insert_with(|| Finding {
+        warning: warning.map(str::to_string),
+        package: package.to_string(),
+        title,
+        versions: BTreeSet::new(),
+        patched: strings(&entry["versions"]["patched"]),
+        repos: BTreeSet::new(),
+    });
+    finding.versions.insert(version.to_string());
+    finding.repos.insert(repo.to_string());
+}
+
+/// Merge the `cargo audit --json` output of `repo` into `findings`
+fn merge(findings: &mut Findings, repo: &str, report: &serde_json::Value) {
+    if let Some(list) = report["vulnerabilities"]["list"].as_array() {
+        for entry in list {
+            add(findings, repo, entry, None);
+        }
+    }
+    if let Some(warnings) = report["warnings"].as_object() {
+        for (kind, entries) in warnings {
+            for entry in entries.as_array().into_iter().flatten() {
+                let kind = entry["kind"].as_str().unwrap_or(kind);
+                add(findings, repo, entry, Some(kind));
+            }
+        }
+    }
+}
+
+/// The findings of all outcomes, and the repos whose audit did not report
+fn aggregate(outcomes: &[RunOutcome]) -> (Findings, Vec<(String, String)>) {
+    let mut findings = Findings::new();
+    let mut failures = Vec::new();
+    for outcome in outcomes {
+        // cargo audit exits non-zero when it finds vulnerabilities, so the
+        // report is what matters
+        match serde_json::from_str::<serde_json::Value>(outcome.stdout.trim()) {
+            Ok(report) if report.is_object() => merge(&mut findings, &outcome.dir, &report),
+            _ => {
+                let tail = outcome
+                    .stderr
+                    .trim()
+                    .lines()
+                    .last()
+                    .unwrap_or("")
+                    .to_string();
+                failures.push((outcome.dir.clone(), tail));
+            }
+        }
+    }
+    (findings, failures)
+}
+
+fn is_denied(finding: &Finding, deny: &[String]) -> bool {
+    match &finding.warning {
+        None => true,
+        Some(kind) => deny.iter().any(|d| d == "warnings" || d == kind),
+    }
+}
+
+fn render(
+    repos: usize,
+    findings: &Findings,
+    failures: &[(String, String)],
+    deny: &[String],
+) -> String {
+    let mut out = String::new();
+    for (id, f) in findings {
+        let label = match &f.warning {
+            None => "vulnerability".red().to_string(),
+            Some(kind) if is_denied(f, deny) => kind.red().to_string(),
+            Some(kind) => kind.yellow().to_string(),
+        };
+        let versions: Vec<&str> = f.versions.iter().map(String::as_str).collect();
+        out.push_str(&format!(
+            "{id} [{label}] {} {}: {}\n",
+            f.package,
+            versions.join(", "),
+            f.title
+        ));
+        if !f.patched.is_empty() {
+            out.push_str(&format!("  patched: {}\n", f.patched.join(", ")));
+        }
+        let affected: Vec<&str> = f.repos.iter().map(String::as_str).collect();
+        out.push_str(&format!("  repos: {}\n", affected.join(", ")));
+    }
+    for (repo, reason) in failures {
+        out.push_str(&format!(
+            "{} {repo}: cargo audit did not report: {reason}\n",
+            "✗".red()
+        ));
+    }
+    let vulnerabilities = findings.values().filter(|f| f.warning.is_none()).count();
+    out.push_str(&format!(
+        "\n{vulnerabilities} vulnerabilities, {} warnings across {repos} repos\n",
+        findings.len() - vulnerabilities
+    ));
+    out
+}
+
+/// Handle `meta cargo audit [--deny warnings|<kind>] [cargo audit args]`
+pub(crate) fn execute(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let mut deny = Vec::new();
+    while let Some(value) = args::take_value(&mut args, "--deny") {
+        for kind in value.split(',') {
+            if kind != "warnings" && !WARNING_KINDS.contains(&kind) {
+                return CommandResult::Error(format!(
+                    "invalid --deny '{kind}' (expected warnings, {})",
+                    WARNING_KINDS.join(", ")
+                ));
+            }
+            deny.push(kind.to_string());
+        }
+    }
+    let mut cmd = format!("{cargo} audit --json");
+    for arg in &args {
+        cmd.push(' ');
+        cmd.push_str(arg);
+    }
+    let commands: Vec<PlannedCommand> = repos
+        .iter()
+        .map(|repo| PlannedCommand {
+            dir: repo.clone(),
+            cmd: cmd.clone(),
+            env: None,
+        })
+        .collect();
+    let outcomes = runner::run_all(cwd, &commands, parallel);
+    let (findings, failures) = aggregate(&outcomes);
+    let text = render(repos.len(), &findings, &failures, &deny);
+    if !failures.is_empty() || findings.values().any(|f| is_denied(f, &deny)) {
+        CommandResult::Error(text)
+    } else {
+        CommandResult::Message(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIME: &str = r#"{"vulnerabilities":{"found":true,"count":1,"list":[{"advisory":{"id":"RUSTSEC-2020-0071","package":"time","title":"Potential segfault in the time crate"},"versions":{"patched":[">=0.2.23"]},"package":{"name":"time","version":"0.1.45"}}]},"warnings":{"unmaintained":[{"kind":"unmaintained","advisory":{"id":"RUSTSEC-2021-0139","package":"ansi_term","title":"ansi_term is Unmaintained"},"versions":{"patched":[]},"package":{"name":"ansi_term","version":"0.12.1"}}],"yanked":[{"kind":"yanked","advisory":null,"package":{"name":"futures-util","version":"0.3.20"}}]}}"#;
+
+    #[test]
+    fn test_advisories_dedupe_across_repos() {
+        let other = TIME.replace("0.1.45", "0.1.44");
+        let (findings, failures) = aggregate(&[
+            RunOutcome::fixture("core", false).with_stdout(TIME),
+            RunOutcome::fixture("app", false).with_stdout(&other),
+            RunOutcome::fixture("broken", false).with_stderr("error: Couldn't load Cargo.lock"),
+        ]);
+        assert_eq!(
+            failures,
+            vec![(
+                "broken".to_string(),
+                "error: Couldn't load Cargo.lock".to_string()
+            )]
+        );
+        assert_eq!(findings.len(), 3);
+        let time = &findings["RUSTSEC-2020-0071"];
+        assert_eq!(time.warning, None);
+        assert_eq!(time.repos.len(), 2);
+        assert_eq!(
+            time.versions.iter().collect::<Vec<_>>(),
+            vec!["0.1.44", "0.1.45"]
+        );
+        assert_eq!(
+            findings["yanked:futures-util"].warning.as_deref(),
+            Some("yanked")
+        );
+
+        let text = render(3, &findings, &failures, &[]);
+        assert!(text.contains("RUSTSEC-2020-0071"), "{text}");
+        assert!(text.contains("  repos: app, core\n"), "{text}");
+        assert!(text.contains("  patched: >=0.2.23\n"), "{text}");
+        assert!(
+            text.contains("1 vulnerabilities, 2 warnings across 3 repos"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn test_deny_warnings() {
+        let (findings, _) = aggregate(&[RunOutcome::fixture("core", false).with_stdout(TIME)]);
+        let unmaintained = &findings["RUSTSEC-2021-0139"];
+        assert!(is_denied(&findings["RUSTSEC-2020-0071"], &[]));
+        assert!(!is_denied(unmaintained, &[]));
+        assert!(is_denied(unmaintained, &["warnings".to_string()]));
+        assert!(is_denied(unmaintained, &["unmaintained".to_string()]));
+        assert!(!is_denied(unmaintained, &["yanked".to_string()]));
+    }
+}
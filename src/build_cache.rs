@@ -0,0 +1,203 @@
+//! How much of a build came from cache
+//!
+//! Derived from cargo's unit reports: `compiler-artifact` JSON messages carry
+//! a `fresh` flag, so in-process build, check and test runs ask for them with
+//! `--message-format=json-render-diagnostics` (diagnostics still print as
+//! usual). Human output alone prints `Compiling` for rebuilt units but
+//! `Fresh` only with `-v`, so a rebuild there is not known to be cold; only
+//! a build that printed nothing but `Finished` is known to be cached.
+
+use crate::PlannedCommand;
+
+/// Subcommands whose in-process runs report units as JSON
+const JSON_SUBCOMMANDS: &[&str] = &["build", "check", "test"];
+
+/// Message format giving unit JSON on stdout and human diagnostics on stderr
+const MESSAGE_FORMAT: &str = "--message-format=json-render-diagnostics";
+
+/// Fresh and rebuilt units of one run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UnitCounts {
+    pub fresh: usize,
+    pub compiled: usize,
+    /// Cargo printed `Finished`
+    pub finished: bool,
+    /// Counted from JSON messages rather than human output
+    pub json: bool,
+}
+
+/// Cache state of a whole run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheState {
+    /// Every unit was fresh
+    Cached,
+    /// Some units were rebuilt
+    Incremental,
+    /// Every unit was rebuilt
+    Cold,
+}
+
+impl CacheState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CacheState::Cached => "cached",
+            CacheState::Incremental => "incremental",
+            CacheState::Cold => "cold",
+        }
+    }
+}
+
+impl UnitCounts {
+    /// `None` when the output reported no units, e.g. for `cargo fmt`
+    pub fn state(self) -> Option<CacheState> {
+        match (self.fresh, self.compiled) {
+            (0, 0) if self.finished => Some(CacheState::Cached),
+            (0, 0) => None,
+            (_, 0) => Some(CacheState::Cached),
+            // Human output without `-v` never lists fresh units
+            (0, _) if !self.json => None,
+            (0, _) => Some(CacheState::Cold),
+            _ => Some(CacheState::Incremental),
+        }
+    }
+}
+
+/// Whether `line` is one of cargo's JSON messages
+pub fn is_message(line: &str) -> bool {
+    line.trim_start().starts_with("{\"reason\":")
+}
+
+/// Ask cargo for unit JSON in build, check and test commands
+///
+/// Left alone when the user picked a message format.
+pub(crate) fn request_messages(
+    commands: &mut [PlannedCommand],
+    cargo: &str,
+    sub: &str,
+    args: &[String],
+) {
+    if !JSON_SUBCOMMANDS.contains(&sub) || args.iter().any(|a| a.starts_with("--message-format")) {
+        return;
+    }
+    let invocation = format!("{cargo} {sub}");
+    for command in commands {
+        if let Some(at) = command.cmd.find(&invocation) {
+            command
+                .cmd
+                .insert_str(at + invocation.len(), &format!(" {MESSAGE_FORMAT}"));
+        }
+    }
+}
+
+/// Count the units reported in cargo output
+///
+/// Status lines are printed alongside JSON messages, so human lines only
+/// count when there are no messages.
+pub fn count_units(output: &str) -> UnitCounts {
+    let mut json = UnitCounts {
+        json: true,
+        ..UnitCounts::default()
+    };
+    let mut counts = UnitCounts::default();
+    for line in output.lines() {
+        let line = line.trim_start();
+        if line.starts_with('{') {
+            let Ok(message) = serde_json::from_str::<serde_json::Value>(line) else {
+                continue;
+            };
+            if message["reason"] != "compiler-artifact" {
+                continue;
+            }
+            match message["fresh"].as_bool() {
+                Some(true) => json.fresh += 1,
+                Some(false) => json.compiled += 1,
+                None => {}
+            }
+        } else if line.starts_with("Compiling ") {
+            counts.compiled += 1;
+        } else if line.starts_with("Fresh ") {
+            counts.fresh += 1;
+        } else if line.starts_with("Finished ") {
+            counts.finished = true;
+        }
+    }
+    if json.fresh + json.compiled > 0 {
+        json.finished = counts.finished;
+        json
+    } else {
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_and_human_units() {
+        let output = r#"{"reason":"compiler-artifact","package_id":"a","fresh":true}
+{"reason":"compiler-artifact","package_id":"b","fresh":false}
+{"reason":"build-finished","success":true}
+   Compiling app v0.1.0 (/ws/app)
+       Fresh serde v1.0.0
+    Finished `dev` profile [unoptimized + debuginfo] target(s) in 0.52s
+"#;
+        let counts = count_units(output);
+        assert_eq!(
+            counts,
+            UnitCounts {
+                fresh: 1,
+                compiled: 1,
+                finished: true,
+                json: true
+            }
+        );
+        assert_eq!(counts.state(), Some(CacheState::Incremental));
+
+        let human = "   Compiling app v0.1.0 (/ws/app)\n       Fresh serde v1.0.0\n";
+        assert_eq!(count_units(human).fresh, 1);
+        assert!(!count_units(human).json);
+    }
+
+    #[test]
+    fn test_cache_states() {
+        let state = |fresh, compiled, finished, json| {
+            UnitCounts {
+                fresh,
+                compiled,
+                finished,
+                json,
+            }
+            .state()
+        };
+        assert_eq!(state(3, 0, true, true), Some(CacheState::Cached));
+        assert_eq!(state(0, 3, true, true), Some(CacheState::Cold));
+        assert_eq!(state(0, 0, true, false), Some(CacheState::Cached));
+        assert_eq!(state(0, 0, false, false), None);
+        // `Compiling app` alone may be an edit over cached dependencies
+        assert_eq!(state(0, 1, true, false), None);
+        assert_eq!(state(2, 1, true, false), Some(CacheState::Incremental));
+    }
+
+    #[test]
+    fn test_request_messages() {
+        let command = |cmd: &str| PlannedCommand {
+            dir: "app".to_string(),
+            cmd: cmd.to_string(),
+            env: None,
+        };
+        let mut commands = vec![command("cargo test --release -- --nocapture")];
+        request_messages(&mut commands, "cargo", "test", &[]);
+        assert_eq!(
+            commands[0].cmd,
+            "cargo test --message-format=json-render-diagnostics --release -- --nocapture"
+        );
+
+        let mut commands = vec![command("cargo build --message-format=short")];
+        let args = vec!["--message-format=short".to_string()];
+        request_messages(&mut commands, "cargo", "build", &args);
+        assert_eq!(commands[0].cmd, "cargo build --message-format=short");
+        request_messages(&mut commands, "cargo", "fmt", &[]);
+        assert_eq!(commands[0].cmd, "cargo build --message-format=short");
+    }
+}
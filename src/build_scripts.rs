@@ -0,0 +1,277 @@
+//! `meta cargo build-scripts`: supply-chain view of every build script
+//!
+//! Lists each package in the dependency graphs of all repos that has a
+//! `build.rs`, with heuristic flags for native/system library use (`links`,
+//! `-sys` naming, build dependencies such as `cc`) and network access
+//! (HTTP/git build dependencies, URLs or sockets in the build script).
+
+use crate::args;
+use crate::metadata::{self, DependencyKind, Package};
+use crate::{project_path, CommandResult};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Build dependencies that compile or locate native code
+const NATIVE_BUILD_DEPS: &[&str] = &[
+    "autotools",
+    "bindgen",
+    "cc",
+    "cmake",
+    "cxx-build",
+    "nasm-rs",
+    "pkg-config",
+    "system-deps",
+    "vcpkg",
+];
+
+/// Build dependencies that can reach the network
+const NETWORK_BUILD_DEPS: &[&str] = &["attohttpc", "curl", "git2", "minreq", "reqwest", "ureq"];
+
+/// Fragments of build script code that suggest network access
+const NETWORK_MARKERS: &[&str] = &[
+    "http://",
+    "https://",
+    "TcpStream",
+    "\"curl\"",
+    "\"wget\"",
+    "\"git\"",
+];
+
+/// A package with a build script and what it appears to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildScript {
+    pub name: String,
+    pub version: String,
+    /// `local`, `registry` or `git`
+    pub source: &'static str,
+    pub links: Option<String>,
+    pub native: Vec<String>,
+    pub network: Vec<String>,
+    /// Repos whose dependency graph contains the package
+    pub repos: Vec<String>,
+}
+
+fn source_kind(source: Option<&str>) -> &'static str {
+    match source {
+        None => "local",
+        Some(s) if s.starts_with("git+") => "git",
+        Some(_) => "registry",
+    }
+}
+
+/// Network markers found in non-comment lines of `code`
+fn network_markers(code: &str) -> Vec<String> {
+    let code: Vec<&str> = code
+        .lines()
+        .map(str::trim_start)
+        .filter(|l| !l.starts_with("//"))
+        .collect();
+    NETWORK_MARKERS
+        .iter()
+        .filter(|m| code.iter().any(|l| l.contains(*m)))
+        .map(|m| format!("build.rs mentions {}", m.trim_matches('"')))
+        .collect()
+}
+
+/// Classify one package, or `None` if it has no build script
+fn classify(pkg: &Package) -> Option<BuildScript> {
+    let script = pkg
+        .targets
+        .iter()
+        .find(|t| t.kinds.iter().any(|k| k == "custom-build"))?;
+    let build_deps: Vec<&str> = pkg
+        .dependencies
+        .iter()
+        .filter(|d| d.kind == DependencyKind::Build)
+        .map(|d| d.name.as_str())
+        .collect();
+
+    let mut native = Vec::new();
+    if let Some(links) = &pkg.links {
+        native.push(format!("links = \"{links}\""));
+    }
+    if pkg.name.ends_with("-sys") {
+        native.push("-sys crate".to_string());
+    }
+    native.extend(
+        build_deps
+            .iter()
+            .filter(|d| NATIVE_BUILD_DEPS.contains(d))
+            .map(|d| format!("build-dep {d}")),
+    );
+
+    let mut network: Vec<String> = build_deps
+        .iter()
+        .filter(|d| NETWORK_BUILD_DEPS.contains(d))
+        .map(|d| format!("build-dep {d}"))
+        .collect();
+    if let Ok(code) = std::fs::read_to_string(&script.src_path) {
+        network.extend(network_markers(&code));
+    }
+
+    Some(BuildScript {
+        name: pkg.name.clone(),
+        version: pkg.version.clone(),
+        source: source_kind(pkg.source.as_deref()),
+        links: pkg.links.clone(),
+        native,
+        network,
+        repos: Vec::new(),
+    })
+}
+
+/// Merge per-repo package lists into one entry per package version
+fn collect(repos: Vec<(String, Vec<Package>)>) -> Vec<BuildScript> {
+    let mut scripts: BTreeMap<(String, String), BuildScript> = BTreeMap::new();
+    for (repo, packages) in repos {
+        for pkg in &packages {
+            let key = (pkg.name.clone(), pkg.version.clone());
+            if !scripts.contains_key(&key) {
+                let Some(script) = classify(pkg) else {
+                    continue;
+                };
+                scripts.insert(key.clone(), script);
+            }
+            let entry = scripts.get_mut(&key).expect("inserted above");
+            if !entry.repos.contains(&repo) {
+                entry.repos.push(repo.clone());
+            }
+        }
+    }
+    scripts.into_values().collect()
+}
+
+fn render_text(scripts: &[BuildScript], errors: &[String]) -> String {
+    let mut out = format!("{} packages with build scripts:\n", scripts.len());
+    for s in scripts {
+        out.push_str(&format!("  {} {} [{}]\n", s.name, s.version, s.source));
+        if !s.native.is_empty() {
+            out.push_str(&format!("      native: {}\n", s.native.join(", ")));
+        }
+        if !s.network.is_empty() {
+            out.push_str(&format!("      network: {}\n", s.network.join(", ")));
+        }
+        out.push_str(&format!("      used by: {}\n", s.repos.join(", ")));
+    }
+    for e in errors {
+        out.push_str(&format!("error: {e}\n"));
+    }
+    out
+}
+
+fn render_json(scripts: &[BuildScript], errors: &[String]) -> serde_json::Value {
+    json!({
+        "build_scripts": scripts.iter().map(|s| json!({
+            "name": s.name,
+            "version": s.version,
+            "source": s.source,
+            "links": s.links,
+            "native": s.native,
+            "network": s.network,
+            "repos": s.repos,
+        })).collect::<Vec<_>>(),
+        "errors": errors,
+    })
+}
+
+/// Handle `meta cargo build-scripts [--format text|json]`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let format = args::take_value(&mut args, "--format").unwrap_or_else(|| "text".to_string());
+    if format != "text" && format != "json" {
+        return CommandResult::Error(format!(
+            "unsupported format '{format}' (expected text or json)"
+        ));
+    }
+
+    let mut loaded = Vec::new();
+    let mut errors = Vec::new();
+    for repo in repos {
+        match metadata::load_all_packages(&project_path(cwd, repo)) {
+            Ok(packages) => loaded.push((repo.clone(), packages)),
+            Err(e) => errors.push(format!("{repo}: {e:#}")),
+        }
+    }
+    let scripts = collect(loaded);
+    let text = if format == "json" {
+        match serde_json::to_string_pretty(&render_json(&scripts, &errors)) {
+            Ok(t) => t,
+            Err(e) => return CommandResult::Error(format!("Failed to serialize report: {e}")),
+        }
+    } else {
+        render_text(&scripts, &errors)
+    };
+    if errors.is_empty() {
+        CommandResult::Message(text)
+    } else {
+        CommandResult::Error(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Dependency;
+
+    /// A crates.io `-sys`-style package with a build script at `script`
+    fn sys_package(name: &str, links: Option<&str>, script: &Path) -> Package {
+        let package = Package::fixture(name)
+            .with_version("1.0.0")
+            .in_registry()
+            .with_target("custom-build", script);
+        match links {
+            Some(links) => package.with_links(links),
+            None => package,
+        }
+    }
+
+    #[test]
+    fn test_classify_native_and_network() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let script = temp_dir.path().join("build.rs");
+        std::fs::write(
+            &script,
+            "// see https://example.com/docs\nfn main() { download(\"https://example.com/blob\"); }\n",
+        )
+        .unwrap();
+
+        let zstd = sys_package("zstd-sys", Some("zstd"), &script)
+            .with_dependency(Dependency::fixture("cc").with_kind(DependencyKind::Build))
+            .with_dependency(Dependency::fixture("pkg-config").with_kind(DependencyKind::Build));
+        let sys = classify(&zstd).unwrap();
+        assert_eq!(
+            sys.native,
+            vec![
+                "links = \"zstd\"",
+                "-sys crate",
+                "build-dep cc",
+                "build-dep pkg-config"
+            ]
+        );
+        assert_eq!(sys.network, vec!["build.rs mentions https://"]);
+        assert_eq!(sys.source, "registry");
+
+        let mut plain = sys_package("plain", None, Path::new("/missing/build.rs"));
+        assert!(classify(&plain).unwrap().network.is_empty());
+        plain.targets.clear();
+        assert!(classify(&plain).is_none());
+    }
+
+    #[test]
+    fn test_collect_merges_repos() {
+        let script = Path::new("/missing/build.rs");
+        let scripts = collect(vec![
+            (
+                "core".to_string(),
+                vec![sys_package("libz-sys", Some("z"), script)],
+            ),
+            (
+                "app".to_string(),
+                vec![sys_package("libz-sys", Some("z"), script)],
+            ),
+        ]);
+        assert_eq!(scripts.len(), 1);
+        assert_eq!(scripts[0].repos, vec!["core", "app"]);
+    }
+}
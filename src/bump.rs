@@ -0,0 +1,432 @@
+//! `meta cargo version <major|minor|patch|x.y.z>`: bump every crate at once
+//!
+//! Each crate of the meta workspace gets its new version in its own manifest,
+//! or in `[workspace.package]` when it inherits the workspace version. Path
+//! and git dependencies on the bumped crates, in every repo, then have their
+//! version requirements moved to the new versions so the workspace keeps
+//! resolving. Every Cargo.lock next to an edited workspace is refreshed with
+//! `cargo update --workspace --offline`. With `--commit` each changed repo
+//! gets one commit, tracked lockfiles included, and with `--tag` a tag per
+//! crate (`v<version>`, or `<crate>-v<version>` in repos with several crates).
+
+use crate::graph::CrateGraph;
+use crate::manifest::{self, VersionSource};
+use crate::{args, git, metadata, project_path, runner, CommandResult};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// The version after applying `level` (`major`, `minor`, `patch` or an
+/// explicit `x.y.z`) to `current`
+fn next_version(current: &str, level: &str) -> Result<String, String> {
+    let parse = |v: &str| -> Option<[u64; 3]> {
+        let parts: Vec<u64> = v
+            .split('.')
+            .map(|p| p.parse().ok())
+            .collect::<Option<_>>()?;
+        <[u64; 3]>::try_from(parts).ok()
+    };
+    if parse(level).is_some() {
+        return Ok(level.to_string());
+    }
+    let Some([major, minor, patch]) = parse(current) else {
+        return Err(format!(
+            "cannot bump '{current}': only plain x.y.z versions can be bumped by level"
+        ));
+    };
+    match level {
+        "major" => Ok(format!("{}.0.0", major + 1)),
+        "minor" => Ok(format!("{major}.{}.0", minor + 1)),
+        "patch" => Ok(format!("{major}.{minor}.{}", patch + 1)),
+        other => Err(format!(
+            "invalid version bump '{other}' (expected major, minor, patch or x.y.z)"
+        )),
+    }
+}
+
+/// Closest manifest above `crate_root` (inside `repo_dir`) declaring
+/// `[workspace.package]`
+fn workspace_manifest(crate_root: &Path, repo_dir: &Path) -> Option<PathBuf> {
+    let mut dir = crate_root.parent();
+    while let Some(d) = dir {
+        let path = d.join("Cargo.toml");
+        let declares = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|t| toml::from_str::<toml::Table>(&t).ok())
+            .is_some_and(|m| m.get("workspace").and_then(|w| w.get("package")).is_some());
+        if declares {
+            return Some(path);
+        }
+        if d == repo_dir || !d.starts_with(repo_dir) {
+            return None;
+        }
+        dir = d.parent();
+    }
+    None
+}
+
+/// A planned bump: new manifest contents and the version changes behind them
+#[derive(Debug, Default)]
+struct Plan {
+    /// `(crate, repo, old, new)`
+    bumps: Vec<(String, String, String, String)>,
+    /// Manifest -> (repo, new contents)
+    edits: BTreeMap<PathBuf, (String, String)>,
+}
+
+impl Plan {
+    fn edit(&mut self, path: &Path, repo: &str, f: impl Fn(&str) -> Option<String>) {
+        let current = match self.edits.get(path) {
+            Some((_, text)) => text.clone(),
+            None => match std::fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(_) => return,
+            },
+        };
+        if let Some(new) = f(&current) {
+            self.edits
+                .insert(path.to_path_buf(), (repo.to_string(), new));
+        }
+    }
+}
+
+fn plan(graph: &CrateGraph, repos: &[String], cwd: &Path, level: &str) -> Result<Plan, String> {
+    let canon = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+    let mut plan = Plan::default();
+    for node in &graph.crates {
+        let new = next_version(&node.version, level)?;
+        if new == node.version {
+            continue;
+        }
+        let text = std::fs::read_to_string(&node.manifest_path)
+            .map_err(|e| format!("{}: {e}", node.manifest_path.display()))?;
+        match manifest::package_version(&text) {
+            Some(VersionSource::Workspace) => {
+                let repo_dir = canon(&project_path(cwd, &node.repo));
+                let Some(root) = workspace_manifest(&canon(&node.root), &repo_dir) else {
+                    return Err(format!(
+                        "{}: inherits its version but no [workspace.package] was found",
+                        node.name
+                    ));
+                };
+                plan.edit(&root, &node.repo, |t| {
+                    manifest::set_workspace_version(t, &new)
+                });
+            }
+            _ => plan.edit(&canon(&node.manifest_path), &node.repo, |t| {
+                manifest::set_package_version(t, &new)
+            }),
+        }
+        plan.bumps.push((
+            node.name.clone(),
+            node.repo.clone(),
+            node.version.clone(),
+            new,
+        ));
+    }
+    for repo in repos {
+        for path in metadata::manifest_paths(&project_path(cwd, repo)) {
+            for (name, _, _, new) in plan.bumps.clone() {
+                plan.edit(&path, repo, |t| {
+                    manifest::set_dependency_version(t, &name, &new)
+                });
+            }
+        }
+    }
+    Ok(plan)
+}
+
+/// Existing Cargo.lock files of the workspaces the plan edits, by repo
+fn lockfiles(plan: &Plan, cwd: &Path) -> BTreeMap<String, BTreeSet<PathBuf>> {
+    let mut out: BTreeMap<String, BTreeSet<PathBuf>> = BTreeMap::new();
+    for (path, (repo, _)) in &plan.edits {
+        let repo_dir = project_path(cwd, repo);
+        let repo_dir = repo_dir.canonicalize().unwrap_or(repo_dir);
+        let mut dir = path.parent();
+        while let Some(d) = dir.filter(|d| d.starts_with(&repo_dir)) {
+            let lock = d.join("Cargo.lock");
+            if lock.is_file() {
+                out.entry(repo.clone()).or_default().insert(lock);
+                break;
+            }
+            dir = d.parent();
+        }
+    }
+    out
+}
+
+/// Re-resolve each lockfile against the edited manifests
+fn refresh_lockfiles(
+    cargo: &str,
+    locks: &BTreeMap<String, BTreeSet<PathBuf>>,
+) -> Result<(), String> {
+    for (repo, paths) in locks {
+        for path in paths {
+            let dir = path.parent().unwrap_or(path);
+            let out = runner::shell(&format!("{cargo} update --workspace --offline"))
+                .current_dir(dir)
+                .output()
+                .map_err(|e| format!("{repo}: failed to run cargo update: {e}"))?;
+            if !out.status.success() {
+                let stderr = String::from_utf8_lossy(&out.stderr);
+                return Err(format!(
+                    "{repo}: cargo update --workspace --offline failed in {}:\n{}",
+                    dir.display(),
+                    stderr.trim()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Commit the changed manifests and tracked lockfiles of each repo and tag
+/// the new versions
+fn commit_and_tag(
+    plan: &Plan,
+    locks: &BTreeMap<String, BTreeSet<PathBuf>>,
+    cwd: &Path,
+    tag: bool,
+) -> Result<Vec<String>, String> {
+    let mut by_repo: BTreeMap<&str, Vec<&PathBuf>> = BTreeMap::new();
+    for (path, (repo, _)) in &plan.edits {
+        by_repo.entry(repo).or_default().push(path);
+    }
+    for (repo, paths) in locks {
+        by_repo.entry(repo).or_default().extend(paths);
+    }
+    let mut notes = Vec::new();
+    for (repo, paths) in by_repo {
+        let dir = project_path(cwd, repo);
+        let bumps: Vec<_> = plan.bumps.iter().filter(|b| b.1 == repo).collect();
+        let message = match bumps.as_slice() {
+            [] => "Update dependency versions".to_string(),
+            [(name, _, _, new)] => format!("Release {name} {new}"),
+            many => format!(
+                "Release {}",
+                many.iter()
+                    .map(|(name, _, _, new)| format!("{name} {new}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        let mut add = vec!["add".to_string(), "--".to_string()];
+        for path in paths {
+            let path = path.display().to_string();
+            // An ignored lockfile stays out of the commit
+            let tracked = !path.ends_with("Cargo.lock")
+                || git::run(&dir, &["ls-files", "--", &path]).is_ok_and(|o| !o.trim().is_empty());
+            if tracked {
+                add.push(path);
+            }
+        }
+        let add: Vec<&str> = add.iter().map(String::as_str).collect();
+        git::run(&dir, &add).map_err(|e| format!("{repo}: {e:#}"))?;
+        git::run(&dir, &["commit", "-q", "-m", &message]).map_err(|e| format!("{repo}: {e:#}"))?;
+        notes.push(format!("committed {repo}: {message}"));
+        if tag {
+            for (name, _, _, new) in &bumps {
+                let tag = if bumps.len() == 1 {
+                    format!("v{new}")
+                } else {
+                    format!("{name}-v{new}")
+                };
+                git::run(&dir, &["tag", &tag]).map_err(|e| format!("{repo}: {e:#}"))?;
+                notes.push(format!("tagged {repo}: {tag}"));
+            }
+        }
+    }
+    Ok(notes)
+}
+
+/// Handle `meta cargo version <major|minor|patch|x.y.z> [--dry-run] [--commit]
+/// [--tag]`
+pub(crate) fn execute(cargo: &str, args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let dry_run = args::take_flag(&mut args, "--dry-run");
+    let tag = args::take_flag(&mut args, "--tag");
+    let commit = args::take_flag(&mut args, "--commit") || tag;
+    let Some(level) = args.first().cloned() else {
+        return CommandResult::Error(
+            "usage: meta cargo version <major|minor|patch|x.y.z> [--dry-run] [--commit] [--tag]"
+                .to_string(),
+        );
+    };
+    let graph = match CrateGraph::load(repos, cwd) {
+        Ok(g) => g,
+        Err(e) => return CommandResult::Error(format!("Failed to load crate graph: {e:#}")),
+    };
+    let plan = match plan(&graph, repos, cwd, &level) {
+        Ok(p) => p,
+        Err(e) => return CommandResult::Error(e),
+    };
+    if plan.bumps.is_empty() {
+        return CommandResult::Message("Every crate is already at that version".to_string());
+    }
+
+    let cwd_canon = cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf());
+    let mut out = String::new();
+    for (name, repo, old, new) in &plan.bumps {
+        out.push_str(&format!("{name} ({repo}): {old} -> {new}\n"));
+    }
+    let verb = if dry_run { "would update" } else { "updated" };
+    for (path, (_, text)) in &plan.edits {
+        if !dry_run {
+            if let Err(e) = std::fs::write(path, text) {
+                return CommandResult::Error(format!(
+                    "{out}Failed to write {}: {e}",
+                    path.display()
+                ));
+            }
+        }
+        let label = path.strip_prefix(&cwd_canon).unwrap_or(path);
+        out.push_str(&format!("{verb} {}\n", label.display()));
+    }
+    let locks = lockfiles(&plan, cwd);
+    for paths in locks.values() {
+        for path in paths {
+            let label = path.strip_prefix(&cwd_canon).unwrap_or(path);
+            out.push_str(&format!("{verb} {}\n", label.display()));
+        }
+    }
+    if dry_run {
+        return CommandResult::Message(out);
+    }
+    if let Err(e) = refresh_lockfiles(cargo, &locks) {
+        return CommandResult::Error(format!("{out}{e}"));
+    }
+    if commit {
+        match commit_and_tag(&plan, &locks, cwd, tag) {
+            Ok(notes) => {
+                for note in notes {
+                    out.push_str(&note);
+                    out.push('\n');
+                }
+            }
+            Err(e) => return CommandResult::Error(format!("{out}{e}")),
+        }
+    }
+    CommandResult::Message(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_next_version() {
+        assert_eq!(next_version("0.3.1", "patch").unwrap(), "0.3.2");
+        assert_eq!(next_version("0.3.1", "minor").unwrap(), "0.4.0");
+        assert_eq!(next_version("0.3.1", "major").unwrap(), "1.0.0");
+        assert_eq!(next_version("1.0.0-rc.1", "2.0.0").unwrap(), "2.0.0");
+        assert!(next_version("1.0.0-rc.1", "patch").is_err());
+        assert!(next_version("1.0.0", "huge").is_err());
+    }
+
+    #[test]
+    fn test_bump_updates_dependents() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let write = |path: &str, text: &str| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        };
+        write(
+            "core/Cargo.toml",
+            "[package]\nname = \"core\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        );
+        write("core/src/lib.rs", "");
+        write(
+            "app/Cargo.toml",
+            "[package]\nname = \"app\"\nversion = \"1.2.3\"\nedition = \"2021\"\n\n[dependencies]\ncore = { path = \"../core\", version = \"0.1.0\" }\n",
+        );
+        write("app/src/lib.rs", "");
+        let repos = vec!["core".to_string(), "app".to_string()];
+
+        match execute(
+            "cargo",
+            &["minor".to_string(), "--dry-run".to_string()],
+            &repos,
+            root,
+        ) {
+            CommandResult::Message(out) => {
+                assert!(out.contains("core (core): 0.1.0 -> 0.2.0"), "{out}");
+                assert!(out.contains("would update app/Cargo.toml"), "{out}");
+            }
+            _ => panic!("Expected Message result"),
+        }
+        match execute("cargo", &["minor".to_string()], &repos, root) {
+            CommandResult::Message(_) => {}
+            _ => panic!("Expected Message result"),
+        }
+        let app = std::fs::read_to_string(root.join("app/Cargo.toml")).unwrap();
+        assert!(app.contains("version = \"1.3.0\""), "{app}");
+        assert!(
+            app.contains("core = { path = \"../core\", version = \"0.2.0\" }"),
+            "{app}"
+        );
+    }
+
+    #[test]
+    fn test_bump_refreshes_and_commits_lockfile() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let write = |path: &str, text: &str| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        };
+        write(
+            "core/Cargo.toml",
+            "[package]\nname = \"core\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        );
+        write("core/src/lib.rs", "");
+        write(
+            "app/Cargo.toml",
+            "[package]\nname = \"app\"\nversion = \"1.2.3\"\nedition = \"2021\"\n\n[dependencies]\ncore = { path = \"../core\", version = \"0.1.0\" }\n",
+        );
+        write("app/src/lib.rs", "");
+        write(
+            "app/Cargo.lock",
+            "version = 3\n\n[[package]]\nname = \"app\"\nversion = \"1.2.3\"\ndependencies = [\n \"core\",\n]\n\n[[package]]\nname = \"core\"\nversion = \"0.1.0\"\n",
+        );
+        for repo in ["core", "app"] {
+            let dir = root.join(repo);
+            for args in [
+                &["init", "-q"][..],
+                &["config", "user.name", "test"],
+                &["config", "user.email", "test@example.com"],
+                &["add", "-A"],
+                &["commit", "-q", "-m", "init"],
+            ] {
+                git::run(&dir, args).unwrap();
+            }
+        }
+        let repos = vec!["core".to_string(), "app".to_string()];
+
+        match execute(
+            "cargo",
+            &["minor".to_string(), "--commit".to_string()],
+            &repos,
+            root,
+        ) {
+            CommandResult::Message(out) => {
+                assert!(out.contains("updated app/Cargo.lock"), "{out}");
+            }
+            CommandResult::Error(e) => panic!("{e}"),
+            _ => panic!("Expected Message result"),
+        }
+        let lock = std::fs::read_to_string(root.join("app/Cargo.lock")).unwrap();
+        assert!(
+            lock.contains("name = \"core\"\nversion = \"0.2.0\""),
+            "{lock}"
+        );
+        assert!(
+            lock.contains("name = \"app\"\nversion = \"1.3.0\""),
+            "{lock}"
+        );
+        let status = git::run(&root.join("app"), &["status", "--porcelain"]).unwrap();
+        assert!(status.trim().is_empty(), "{status}");
+    }
+}
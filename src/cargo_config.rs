@@ -0,0 +1,193 @@
+//! Organization-wide cargo settings from the meta root
+//!
+//! A `.cargo/config.toml` in the meta root is passed to every repo's cargo
+//! invocation with `--config <file>`. Cargo gives `--config` precedence over
+//! the repo's own config files, so profiles, registries and net options set
+//! there apply everywhere, including repos checked out outside the meta root.
+//!
+//! With `--remap-path-prefix` (or `[cargo] remap_path_prefix = true`), paths
+//! in panics and debug info are rewritten relative to the meta root, so logs
+//! from every repo and every machine read the same.
+
+use crate::config::Config;
+use std::path::{Path, PathBuf};
+
+/// Meta-root cargo config candidates, in the order cargo itself prefers them
+const CANDIDATES: &[&str] = &[".cargo/config.toml", ".cargo/config"];
+
+/// The meta-root cargo config file, if present and enabled
+pub(crate) fn root_config(cwd: &Path, config: &Config) -> Option<PathBuf> {
+    if !config.cargo.root_config {
+        return None;
+    }
+    CANDIDATES.iter().map(|c| cwd.join(c)).find(|p| p.is_file())
+}
+
+/// `cargo`, plus `--config` for the meta-root cargo config when there is one
+pub(crate) fn cargo(cwd: &Path, config: &Config) -> String {
+    match root_config(cwd, config) {
+        Some(path) => {
+            let path = path.canonicalize().unwrap_or(path);
+            format!("cargo --config \"{}\"", path.display())
+        }
+        None => "cargo".to_string(),
+    }
+}
+
+/// Set `RUSTC_WRAPPER` for the repos that have one configured
+pub(crate) fn apply_rustc_wrapper(
+    commands: &mut [crate::PlannedCommand],
+    cwd: &Path,
+    config: &Config,
+) {
+    for planned in commands {
+        let Some(wrapper) = config.rustc_wrapper(&planned.dir) else {
+            continue;
+        };
+        // Bare names are looked up on PATH, like cargo does
+        let wrapper = if wrapper.contains('/') && Path::new(wrapper).is_relative() {
+            cwd.join(wrapper).display().to_string()
+        } else {
+            wrapper.to_string()
+        };
+        planned
+            .env
+            .get_or_insert_with(Default::default)
+            .insert("RUSTC_WRAPPER".to_string(), wrapper);
+    }
+}
+
+/// `--remap-path-prefix` flags that turn absolute paths into meta-relative
+/// ones: the meta root and repos outside it become their meta paths, and
+/// `CARGO_HOME` becomes `/cargo`
+///
+/// The flags are the same for every repo, so a shared target dir is not
+/// rebuilt when switching between repos.
+pub(crate) fn remap_flags(commands: &[crate::PlannedCommand], cwd: &Path) -> Vec<String> {
+    let canon = |p: PathBuf| p.canonicalize().unwrap_or(p);
+    let root = canon(cwd.to_path_buf());
+    let prefix = |p: &Path| format!("{}/", p.display().to_string().trim_end_matches('/'));
+    let mut flags = vec![format!("--remap-path-prefix={}=", prefix(&root))];
+    for planned in commands {
+        let dir = canon(crate::project_path(cwd, &planned.dir));
+        let flag = format!(
+            "--remap-path-prefix={}={}/",
+            prefix(&dir),
+            planned.dir.trim_end_matches('/')
+        );
+        if !dir.starts_with(&root) && !flags.contains(&flag) {
+            flags.push(flag);
+        }
+    }
+    let cargo_home = std::env::var("CARGO_HOME")
+        .ok()
+        .or_else(|| std::env::var("HOME").ok().map(|h| format!("{h}/.cargo")));
+    if let Some(home) = cargo_home {
+        // rustc applies the last matching prefix, so this wins for a
+        // CARGO_HOME inside the meta root
+        flags.push(format!(
+            "--remap-path-prefix={}=/cargo/",
+            prefix(Path::new(&home))
+        ));
+    }
+    flags
+}
+
+/// Append [`remap_flags`] to the RUSTFLAGS of every command
+pub(crate) fn apply_remap_path_prefix(commands: &mut [crate::PlannedCommand], cwd: &Path) {
+    let flags = remap_flags(commands, cwd).join(" ");
+    let inherited = std::env::var("RUSTFLAGS").unwrap_or_default();
+    for planned in commands {
+        let env = planned.env.get_or_insert_with(Default::default);
+        let base = env.get("RUSTFLAGS").cloned().unwrap_or(inherited.clone());
+        env.insert(
+            "RUSTFLAGS".to_string(),
+            format!("{base} {flags}").trim().to_string(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_root_config_is_layered() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::default();
+        assert_eq!(cargo(temp_dir.path(), &config), "cargo");
+
+        std::fs::create_dir(temp_dir.path().join(".cargo")).unwrap();
+        std::fs::write(
+            temp_dir.path().join(".cargo/config.toml"),
+            "[net]\nretry = 5\n",
+        )
+        .unwrap();
+        let cmd = cargo(temp_dir.path(), &config);
+        assert!(cmd.starts_with("cargo --config \""));
+        assert!(cmd.ends_with("config.toml\""));
+
+        let disabled = Config::parse("[cargo]\nroot_config = false\n").unwrap();
+        assert_eq!(cargo(temp_dir.path(), &disabled), "cargo");
+    }
+
+    #[test]
+    fn test_rustc_wrapper_per_repo() {
+        let config = Config::parse(
+            "[cargo]\nrustc_wrapper = \"tools/time-rustc\"\n\n[repos.app]\nrustc_wrapper = \"sccache\"\n\n[repos.legacy]\nrustc_wrapper = \"\"\n",
+        )
+        .unwrap();
+        let mut commands: Vec<crate::PlannedCommand> = ["core", "app", "legacy"]
+            .iter()
+            .map(|dir| crate::PlannedCommand {
+                dir: dir.to_string(),
+                cmd: "cargo build".to_string(),
+                env: None,
+            })
+            .collect();
+        apply_rustc_wrapper(&mut commands, Path::new("/ws"), &config);
+        let wrapper = |i: usize| {
+            commands[i]
+                .env
+                .as_ref()
+                .map(|env| env["RUSTC_WRAPPER"].clone())
+        };
+        assert_eq!(wrapper(0).as_deref(), Some("/ws/tools/time-rustc"));
+        assert_eq!(wrapper(1).as_deref(), Some("sccache"));
+        assert_eq!(wrapper(2), None);
+    }
+
+    #[test]
+    fn test_remap_path_prefix_is_meta_relative() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("meta");
+        std::fs::create_dir_all(root.join("core")).unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("ext")).unwrap();
+        let mut commands: Vec<crate::PlannedCommand> = ["core", "../ext"]
+            .iter()
+            .map(|dir| crate::PlannedCommand {
+                dir: dir.to_string(),
+                cmd: "cargo build".to_string(),
+                env: None,
+            })
+            .collect();
+        commands[0].env = Some(
+            [("RUSTFLAGS".to_string(), "-C target-cpu=native".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        apply_remap_path_prefix(&mut commands, &root);
+
+        let root = root.canonicalize().unwrap();
+        let ext = temp_dir.path().join("ext").canonicalize().unwrap();
+        let flags = |i: usize| commands[i].env.as_ref().unwrap()["RUSTFLAGS"].clone();
+        assert!(flags(0).starts_with(&format!(
+            "-C target-cpu=native --remap-path-prefix={}/= --remap-path-prefix={}/=../ext/",
+            root.display(),
+            ext.display()
+        )));
+        assert!(flags(0).ends_with("=/cargo/"), "{}", flags(0));
+        assert!(flags(1).ends_with(flags(0).trim_start_matches("-C target-cpu=native ")));
+    }
+}
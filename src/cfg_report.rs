@@ -0,0 +1,704 @@
+//! `meta cargo cfg-report`: conditional compilation across repos
+//!
+//! Sources of every crate are scanned for `cfg(...)`, `cfg_attr(...)` and
+//! `cfg!(...)` predicates. Custom cfgs (anything rustc or cargo doesn't know)
+//! are listed with what sets them: `--cfg` in cargo config `rustflags`, in
+//! the `RUSTFLAGS` of a meta env profile, or `rustc-cfg` lines of a build
+//! script. A custom cfg a crate uses without declaring it, through
+//! `[lints.rust] unexpected_cfgs = { check-cfg = [...] }` (directly or via
+//! `lints.workspace = true`) or a build script's `rustc-check-cfg`, warns
+//! under check-cfg and is flagged. Feature gates are listed per crate, with
+//! features the crate doesn't declare flagged the same way.
+
+use crate::config::Config;
+use crate::env_audit::rust_files;
+use crate::graph::{CrateGraph, CrateNode};
+use crate::{args, project_path, CommandResult};
+use colored::Colorize;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Names rustc and cargo declare themselves
+const WELL_KNOWN: &[&str] = &[
+    "clippy",
+    "contract_checks",
+    "debug_assertions",
+    "doc",
+    "docsrs",
+    "doctest",
+    "feature",
+    "fmt_debug",
+    "miri",
+    "overflow_checks",
+    "panic",
+    "proc_macro",
+    "relocation_model",
+    "rustfmt",
+    "sanitize",
+    "sanitizer_cfi_generalize_pointers",
+    "sanitizer_cfi_normalize_integers",
+    "target_abi",
+    "target_arch",
+    "target_endian",
+    "target_env",
+    "target_family",
+    "target_feature",
+    "target_has_atomic",
+    "target_has_atomic_equal_alignment",
+    "target_has_atomic_load_store",
+    "target_os",
+    "target_pointer_width",
+    "target_thread_local",
+    "target_vendor",
+    "test",
+    "ub_checks",
+    "unix",
+    "windows",
+];
+
+/// A `name` or `name = "value"` predicate in a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Predicate {
+    name: String,
+    value: Option<String>,
+    line: u32,
+}
+
+/// `src` with comments blanked out, and a copy with string literals blanked
+/// as well; both keep every char at its index
+fn mask(src: &[char]) -> (Vec<char>, Vec<char>) {
+    let mut code = src.to_vec();
+    let mut bare = src.to_vec();
+    let at = |i: usize| src.get(i).copied().unwrap_or('\0');
+    let blank = |text: &mut Vec<char>, from: usize, to: usize| {
+        for c in &mut text[from..to.min(src.len())] {
+            if *c != '\n' {
+                *c = ' ';
+            }
+        }
+    };
+    let mut i = 0;
+    while i < src.len() {
+        if at(i) == '/' && at(i + 1) == '/' {
+            let end = (i..src.len())
+                .find(|&j| src[j] == '\n')
+                .unwrap_or(src.len());
+            blank(&mut code, i, end);
+            blank(&mut bare, i, end);
+            i = end;
+        } else if at(i) == '/' && at(i + 1) == '*' {
+            let end = (i + 2..src.len())
+                .find(|&j| src[j] == '*' && at(j + 1) == '/')
+                .map_or(src.len(), |j| j + 2);
+            blank(&mut code, i, end);
+            blank(&mut bare, i, end);
+            i = end;
+        } else if at(i) == '"' {
+            let mut j = i + 1;
+            while j < src.len() && src[j] != '"' {
+                j += if src[j] == '\\' { 2 } else { 1 };
+            }
+            blank(&mut bare, i + 1, j);
+            i = j + 1;
+        } else if at(i) == '\'' && (at(i + 2) == '\'' || at(i + 1) == '\\') {
+            // A char literal such as '"', not a lifetime
+            let end = (i + 2..src.len().min(i + 12))
+                .find(|&j| src[j] == '\'')
+                .map_or(i + 1, |j| j + 1);
+            blank(&mut bare, i + 1, end.saturating_sub(1));
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+    (code, bare)
+}
+
+/// Predicates of the cfg expression starting after the `(` at `i`; for
+/// `cfg_attr` only its first argument is one
+fn expression(
+    code: &[char],
+    mut i: usize,
+    first_arg_only: bool,
+    line_of: &dyn Fn(usize) -> u32,
+) -> Vec<Predicate> {
+    let at = |i: usize| code.get(i).copied().unwrap_or('\0');
+    let skip_ws = |mut i: usize| {
+        while at(i).is_whitespace() {
+            i += 1;
+        }
+        i
+    };
+    let mut found = Vec::new();
+    let mut depth = 1;
+    while i < code.len() && depth > 0 {
+        let c = at(i);
+        if c == '(' {
+            depth += 1;
+            i += 1;
+        } else if c == ')' {
+            depth -= 1;
+            i += 1;
+        } else if c == ',' && depth == 1 && first_arg_only {
+            break;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while at(i).is_alphanumeric() || at(i) == '_' {
+                i += 1;
+            }
+            let name: String = code[start..i].iter().collect();
+            let next = skip_ws(i);
+            if at(next) == '(' {
+                // all(...), any(...), not(...)
+                continue;
+            }
+            let mut value = None;
+            if at(next) == '=' {
+                let quote = skip_ws(next + 1);
+                if at(quote) == '"' {
+                    let end = (quote + 1..code.len())
+                        .find(|&j| code[j] == '"')
+                        .unwrap_or(code.len());
+                    value = Some(code[quote + 1..end].iter().collect());
+                    i = end + 1;
+                }
+            }
+            found.push(Predicate {
+                name,
+                value,
+                line: line_of(start),
+            });
+        } else {
+            i += 1;
+        }
+    }
+    found
+}
+
+/// Every cfg predicate in a Rust source file
+fn predicates(src: &str) -> Vec<Predicate> {
+    let chars: Vec<char> = src.chars().collect();
+    let (code, bare) = mask(&chars);
+    let line_of = |i: usize| 1 + chars[..i].iter().filter(|c| **c == '\n').count() as u32;
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i + 3 <= bare.len() {
+        if bare[i..i + 3] != ['c', 'f', 'g'] || (i > 0 && is_ident(bare[i - 1])) {
+            i += 1;
+            continue;
+        }
+        let mut j = i + 3;
+        let attr = bare[j..].starts_with(&['_', 'a', 't', 't', 'r']);
+        if attr {
+            j += 5;
+        }
+        if !attr && bare.get(j) == Some(&'!') {
+            j += 1;
+        }
+        while bare.get(j).is_some_and(|c| c.is_whitespace()) {
+            j += 1;
+        }
+        if bare.get(j) == Some(&'(') {
+            found.extend(expression(&code, j + 1, attr, &line_of));
+        }
+        i = j;
+    }
+    found
+}
+
+/// Names set with `--cfg name` or `--cfg=name` in a list of rustc flags
+fn flag_cfgs(flags: &[&str]) -> Vec<String> {
+    let mut found = Vec::new();
+    for (k, flag) in flags.iter().enumerate() {
+        let value = match flag.strip_prefix("--cfg=") {
+            Some(value) => value,
+            None if *flag == "--cfg" => flags.get(k + 1).copied().unwrap_or(""),
+            None => continue,
+        };
+        let name = value.split('=').next().unwrap_or(value).trim();
+        if !name.is_empty() {
+            found.push(name.to_string());
+        }
+    }
+    found
+}
+
+/// `--cfg` names in the `rustflags` of a cargo config file
+fn config_file_cfgs(path: &Path) -> Vec<String> {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    let Ok(table) = toml::from_str::<toml::Table>(&text) else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    let mut add = |value: Option<&toml::Value>| match value {
+        Some(toml::Value::String(s)) => {
+            found.extend(flag_cfgs(&s.split_whitespace().collect::<Vec<_>>()))
+        }
+        Some(toml::Value::Array(items)) => {
+            let flags: Vec<&str> = items.iter().filter_map(|v| v.as_str()).collect();
+            found.extend(flag_cfgs(&flags));
+        }
+        _ => {}
+    };
+    add(table.get("build").and_then(|b| b.get("rustflags")));
+    if let Some(targets) = table.get("target").and_then(|t| t.as_table()) {
+        for target in targets.values() {
+            add(target.get("rustflags"));
+        }
+    }
+    found
+}
+
+/// Names after `directive=` in build script source, e.g. `rustc-cfg=`
+fn directive_names(src: &str, directive: &str) -> Vec<String> {
+    src.match_indices(directive)
+        .filter(|(at, _)| !src[..*at].ends_with("check-"))
+        .filter_map(|(at, _)| {
+            let rest = &src[at + directive.len()..];
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (end > 0).then(|| rest[..end].to_string())
+        })
+        .collect()
+}
+
+/// Cfg names declared by `check-cfg` entries such as `cfg(foo, values("a"))`
+fn check_cfg_names(entries: &[&str]) -> Vec<String> {
+    let mut names = Vec::new();
+    for entry in entries {
+        let Some(inner) = entry.trim().strip_prefix("cfg(") else {
+            continue;
+        };
+        let inner = inner.split("values(").next().unwrap_or(inner);
+        for name in inner.split([',', ')']) {
+            let name = name.trim();
+            if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// `check-cfg` entries of a `[lints]` table
+fn lints_check_cfg(lints: Option<&toml::Value>) -> Vec<String> {
+    let entries = lints
+        .and_then(|l| l.get("rust"))
+        .and_then(|r| r.get("unexpected_cfgs"))
+        .and_then(|u| u.get("check-cfg"))
+        .and_then(|c| c.as_array());
+    let entries: Vec<&str> = entries
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str())
+        .collect();
+    check_cfg_names(&entries)
+}
+
+fn read_manifest(path: &Path) -> Option<toml::Table> {
+    toml::from_str(&std::fs::read_to_string(path).ok()?).ok()
+}
+
+/// Custom cfgs a crate declares for check-cfg
+fn declared_cfgs(node: &CrateNode, cwd: &Path) -> BTreeSet<String> {
+    let mut declared = BTreeSet::new();
+    let Some(manifest) = read_manifest(&node.manifest_path) else {
+        return declared;
+    };
+    let lints = manifest.get("lints");
+    let inherits = lints
+        .and_then(|l| l.get("workspace"))
+        .and_then(|w| w.as_bool())
+        .unwrap_or(false);
+    if inherits {
+        let stop = cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf());
+        let workspace = node
+            .root
+            .ancestors()
+            .take_while(|dir| dir.starts_with(&stop))
+            .filter_map(|dir| read_manifest(&dir.join("Cargo.toml")))
+            .find(|m| m.contains_key("workspace"));
+        if let Some(workspace) = workspace {
+            let lints = workspace.get("workspace").and_then(|w| w.get("lints"));
+            declared.extend(lints_check_cfg(lints));
+        }
+    } else {
+        declared.extend(lints_check_cfg(lints));
+    }
+    if let Ok(build) = std::fs::read_to_string(node.root.join("build.rs")) {
+        for (at, _) in build.match_indices("rustc-check-cfg=") {
+            let rest = &build[at + "rustc-check-cfg=".len()..];
+            let entry = rest.split('"').next().unwrap_or(rest);
+            declared.extend(check_cfg_names(&[entry]));
+        }
+    }
+    declared
+}
+
+/// Where a cfg or feature is used
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Site {
+    krate: String,
+    /// File relative to the meta root, with line
+    location: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct CustomCfg {
+    set_by: BTreeSet<String>,
+    uses: Vec<Site>,
+    /// Crates using it without declaring it
+    undeclared_in: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Report {
+    cfgs: BTreeMap<String, CustomCfg>,
+    /// Crate -> feature -> uses
+    features: BTreeMap<String, BTreeMap<String, Vec<Site>>>,
+    /// Crate -> features it uses but doesn't declare
+    undeclared_features: BTreeMap<String, BTreeSet<String>>,
+    failed: Vec<(String, String)>,
+}
+
+fn relative(path: &Path, cwd: &Path) -> String {
+    let cwd = cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf());
+    path.strip_prefix(&cwd)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+fn scan_crates(report: &mut Report, graph: &CrateGraph, cwd: &Path) {
+    let roots: Vec<&PathBuf> = graph.crates.iter().map(|c| &c.root).collect();
+    for node in &graph.crates {
+        let skip: Vec<PathBuf> = roots
+            .iter()
+            .filter(|r| **r != &node.root && r.starts_with(&node.root))
+            .map(|r| (*r).clone())
+            .collect();
+        let mut files = Vec::new();
+        rust_files(&node.root, &skip, &mut files);
+        let declared = declared_cfgs(node, cwd);
+        for file in files {
+            let Ok(src) = std::fs::read_to_string(&file) else {
+                continue;
+            };
+            for p in predicates(&src) {
+                let site = Site {
+                    krate: node.name.clone(),
+                    location: format!("{}:{}", relative(&file, cwd), p.line),
+                };
+                if p.name == "feature" {
+                    let Some(feature) = p.value else { continue };
+                    if !node.features.contains_key(&feature) {
+                        report
+                            .undeclared_features
+                            .entry(node.name.clone())
+                            .or_default()
+                            .insert(feature.clone());
+                    }
+                    report
+                        .features
+                        .entry(node.name.clone())
+                        .or_default()
+                        .entry(feature)
+                        .or_default()
+                        .push(site);
+                } else if !WELL_KNOWN.contains(&p.name.as_str()) {
+                    let entry = report.cfgs.entry(p.name.clone()).or_default();
+                    if !declared.contains(&p.name) {
+                        entry.undeclared_in.insert(node.name.clone());
+                    }
+                    entry.uses.push(site);
+                }
+            }
+        }
+        if let Ok(build) = std::fs::read_to_string(node.root.join("build.rs")) {
+            let source = format!("{} build script", node.name);
+            for name in directive_names(&build, "rustc-cfg=") {
+                report
+                    .cfgs
+                    .entry(name)
+                    .or_default()
+                    .set_by
+                    .insert(source.clone());
+            }
+        }
+    }
+}
+
+/// Cfgs set by cargo configs and meta env profiles
+fn scan_config(report: &mut Report, repos: &[String], cwd: &Path, config: &Config) {
+    let mut files = vec![(cwd.to_path_buf(), String::new())];
+    files.extend(
+        repos
+            .iter()
+            .map(|r| (project_path(cwd, r), format!("{r}/"))),
+    );
+    for (dir, label) in files {
+        for name in [".cargo/config.toml", ".cargo/config"] {
+            for cfg in config_file_cfgs(&dir.join(name)) {
+                let source = format!("{label}{name} rustflags");
+                report.cfgs.entry(cfg).or_default().set_by.insert(source);
+            }
+        }
+    }
+    for (profile, env) in &config.env {
+        if let Some(flags) = env.vars.get("RUSTFLAGS") {
+            for cfg in flag_cfgs(&flags.split_whitespace().collect::<Vec<_>>()) {
+                let source = format!("env profile {profile}");
+                report.cfgs.entry(cfg).or_default().set_by.insert(source);
+            }
+        }
+    }
+}
+
+fn render_text(report: &Report) -> String {
+    let warn = "⚠".yellow();
+    let mut out = String::new();
+    let mut undeclared = 0;
+    out.push_str(&format!("{}\n", "Custom cfgs".bold()));
+    if report.cfgs.is_empty() {
+        out.push_str("  none\n");
+    }
+    for (name, cfg) in &report.cfgs {
+        let crates: BTreeSet<&str> = cfg.uses.iter().map(|s| s.krate.as_str()).collect();
+        let set_by = if cfg.set_by.is_empty() {
+            "never set".to_string()
+        } else {
+            format!("set by {}", join(&cfg.set_by))
+        };
+        out.push_str(&format!(
+            "  {name}: {set_by}; {} uses in {}\n",
+            cfg.uses.len(),
+            if crates.is_empty() {
+                "no crate".to_string()
+            } else {
+                crates.into_iter().collect::<Vec<_>>().join(", ")
+            }
+        ));
+        for krate in &cfg.undeclared_in {
+            undeclared += 1;
+            let first = cfg.uses.iter().find(|s| &s.krate == krate);
+            let location = first
+                .map(|s| format!(" ({})", s.location))
+                .unwrap_or_default();
+            out.push_str(&format!("    {warn} undeclared in {krate}{location}\n"));
+        }
+    }
+    out.push_str(&format!("\n{}\n", "Feature gates".bold()));
+    if report.features.is_empty() {
+        out.push_str("  none\n");
+    }
+    for (krate, features) in &report.features {
+        let names: Vec<&str> = features.keys().map(String::as_str).collect();
+        out.push_str(&format!("  {krate}: {}\n", names.join(", ")));
+        for feature in report.undeclared_features.get(krate).into_iter().flatten() {
+            undeclared += 1;
+            let location = &features[feature][0].location;
+            out.push_str(&format!(
+                "    {warn} undeclared feature \"{feature}\" ({location})\n"
+            ));
+        }
+    }
+    for (repo, error) in &report.failed {
+        out.push_str(&format!("{} {repo}: {error}\n", "✗".red()));
+    }
+    out.push_str(&format!(
+        "\n{undeclared} undeclared cfgs would warn under check-cfg\n"
+    ));
+    out
+}
+
+fn join(items: &BTreeSet<String>) -> String {
+    items
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn render_json(report: &Report) -> serde_json::Value {
+    let sites = |sites: &[Site]| {
+        sites
+            .iter()
+            .map(|s| json!({ "crate": s.krate, "location": s.location }))
+            .collect::<Vec<_>>()
+    };
+    let cfgs: serde_json::Map<String, serde_json::Value> = report
+        .cfgs
+        .iter()
+        .map(|(name, cfg)| {
+            let value = json!({
+                "set_by": cfg.set_by,
+                "uses": sites(&cfg.uses),
+                "undeclared_in": cfg.undeclared_in,
+            });
+            (name.clone(), value)
+        })
+        .collect();
+    let features: serde_json::Map<String, serde_json::Value> = report
+        .features
+        .iter()
+        .map(|(krate, features)| {
+            let undeclared = report.undeclared_features.get(krate);
+            let value: serde_json::Map<String, serde_json::Value> = features
+                .iter()
+                .map(|(feature, uses)| {
+                    let declared = !undeclared.is_some_and(|u| u.contains(feature));
+                    (
+                        feature.clone(),
+                        json!({ "declared": declared, "uses": sites(uses) }),
+                    )
+                })
+                .collect();
+            (krate.clone(), serde_json::Value::Object(value))
+        })
+        .collect();
+    json!({ "cfgs": cfgs, "features": features })
+}
+
+/// Handle `meta cargo cfg-report [--format text|json]`
+pub(crate) fn execute(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    config: &Config,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let format = args::take_value(&mut args, "--format").unwrap_or_else(|| "text".to_string());
+    if format != "text" && format != "json" {
+        return CommandResult::Error(format!(
+            "unsupported format '{format}' (expected text or json)"
+        ));
+    }
+    let (graph, failed) = CrateGraph::load_partial(repos, cwd);
+    let mut report = Report {
+        failed,
+        ..Report::default()
+    };
+    scan_crates(&mut report, &graph, cwd);
+    scan_config(&mut report, repos, cwd, config);
+    if format == "json" {
+        match serde_json::to_string_pretty(&render_json(&report)) {
+            Ok(text) => CommandResult::Message(text),
+            Err(e) => CommandResult::Error(format!("Failed to serialize report: {e}")),
+        }
+    } else {
+        CommandResult::Message(render_text(&report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Package;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_predicates() {
+        let src = r#"
+#[cfg(all(unix, not(tokio_unstable)))]
+fn a() {}
+// #[cfg(commented)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+struct B;
+const S: &str = "cfg(in_string)";
+fn c() -> bool { cfg!(any(feature = "tls", loom)) }
+"#;
+        let found: Vec<(String, Option<String>, u32)> = predicates(src)
+            .into_iter()
+            .map(|p| (p.name, p.value, p.line))
+            .collect();
+        let some = |v: &str| Some(v.to_string());
+        assert_eq!(
+            found,
+            vec![
+                ("unix".to_string(), None, 2),
+                ("tokio_unstable".to_string(), None, 2),
+                ("feature".to_string(), some("serde"), 5),
+                ("feature".to_string(), some("tls"), 8),
+                ("loom".to_string(), None, 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flags_and_declarations() {
+        assert_eq!(
+            flag_cfgs(&["--cfg", "tokio_unstable", "--cfg=loom", "-Cdebuginfo=1"]),
+            vec!["tokio_unstable", "loom"]
+        );
+        assert_eq!(
+            check_cfg_names(&["cfg(loom, coverage)", "cfg(backend, values(\"a\"))"]),
+            vec!["loom", "coverage", "backend"]
+        );
+        let build = r#"println!("cargo::rustc-check-cfg=cfg(has_simd)"); println!("cargo:rustc-cfg=has_simd");"#;
+        assert_eq!(directive_names(build, "rustc-cfg="), vec!["has_simd"]);
+    }
+
+    #[test]
+    fn test_report_flags_undeclared_cfgs() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("app/src")).unwrap();
+        std::fs::create_dir_all(root.join(".cargo")).unwrap();
+        std::fs::write(
+            root.join("app/Cargo.toml"),
+            "[package]\nname = \"app\"\n\n[lints.rust]\nunexpected_cfgs = { level = \"warn\", check-cfg = [\"cfg(loom)\"] }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("app/src/lib.rs"),
+            "#[cfg(loom)]\nmod a;\n#[cfg(tokio_unstable)]\nmod b;\n#[cfg(feature = \"tls\")]\nmod c;\n#[cfg(feature = \"tsl\")]\nmod d;\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join(".cargo/config.toml"),
+            "[build]\nrustflags = [\"--cfg\", \"tokio_unstable\"]\n",
+        )
+        .unwrap();
+        let mut features = BTreeMap::new();
+        features.insert("tls".to_string(), Vec::new());
+        let graph = CrateGraph::from_packages(vec![(
+            "app".to_string(),
+            vec![Package {
+                name: "app".to_string(),
+                version: "0.1.0".to_string(),
+                source: None,
+                links: None,
+                publish: None,
+                manifest_path: root.join("app/Cargo.toml"),
+                edition: "2021".to_string(),
+                rust_version: None,
+                dependencies: Vec::new(),
+                targets: Vec::new(),
+                features,
+            }],
+        )]);
+
+        let mut report = Report::default();
+        scan_crates(&mut report, &graph, &root);
+        scan_config(&mut report, &["app".to_string()], &root, &Config::default());
+        assert!(report.cfgs["loom"].undeclared_in.is_empty());
+        let tokio = &report.cfgs["tokio_unstable"];
+        assert_eq!(join(&tokio.set_by), ".cargo/config.toml rustflags");
+        assert_eq!(join(&tokio.undeclared_in), "app");
+        assert_eq!(tokio.uses[0].location, "app/src/lib.rs:3");
+        assert_eq!(join(&report.undeclared_features["app"]), "tsl");
+
+        let text = render_text(&report);
+        assert!(text.contains("  app: tls, tsl\n"), "{text}");
+        assert!(text.contains("2 undeclared cfgs would warn"), "{text}");
+        assert_eq!(
+            render_json(&report)["features"]["app"]["tsl"]["declared"],
+            false
+        );
+    }
+}
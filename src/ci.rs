@@ -0,0 +1,177 @@
+//! Collapsible per-repo log sections for CI providers
+//!
+//! Each repo's full output is wrapped in the provider's group markers so long
+//! multi-repo logs can be navigated. On GitHub Actions, diagnostics are also
+//! emitted as workflow commands so they show up as file annotations.
+
+use crate::diagnostics::{self, Level};
+use crate::quickfix;
+use crate::runner::RunOutcome;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// CI system whose log markers are emitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Provider {
+    GitHub,
+    GitLab,
+    Buildkite,
+}
+
+impl Provider {
+    pub(crate) fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "github" => Ok(Provider::GitHub),
+            "gitlab" => Ok(Provider::GitLab),
+            "buildkite" => Ok(Provider::Buildkite),
+            other => Err(format!(
+                "unsupported CI provider '{other}' (expected github, gitlab or buildkite)"
+            )),
+        }
+    }
+
+    /// Detect the provider from the variables each CI system sets
+    pub(crate) fn detect() -> Result<Self, String> {
+        let set = |name: &str| std::env::var_os(name).is_some_and(|v| !v.is_empty());
+        if set("GITHUB_ACTIONS") {
+            Ok(Provider::GitHub)
+        } else if set("GITLAB_CI") {
+            Ok(Provider::GitLab)
+        } else if set("BUILDKITE") {
+            Ok(Provider::Buildkite)
+        } else {
+            Err(
+                "could not detect the CI provider; use --ci-log-groups=github|gitlab|buildkite"
+                    .to_string(),
+            )
+        }
+    }
+}
+
+/// Escape data for a GitHub workflow command
+fn escape_github(value: &str, property: bool) -> String {
+    let mut out = value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A");
+    if property {
+        out = out.replace(':', "%3A").replace(',', "%2C");
+    }
+    out
+}
+
+/// GitLab section names may only contain letters, digits, `_`, `.` and `-`
+fn gitlab_section(repo: &str) -> String {
+    let name: String = repo
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("meta_{name}")
+}
+
+fn header(outcome: &RunOutcome) -> String {
+    let status = if outcome.success { "✓" } else { "✗" };
+    format!("{status} {}: {}", outcome.dir, outcome.cmd)
+}
+
+fn annotations(cwd: &Path, outcome: &RunOutcome, out: &mut String) {
+    for d in diagnostics::parse(&outcome.output()) {
+        let command = match d.level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        };
+        let file = quickfix::root_relative(cwd, &outcome.dir, &d.file);
+        let title = d.code.as_deref().unwrap_or(command);
+        out.push_str(&format!(
+            "::{command} file={},line={},col={},title={}::{}\n",
+            escape_github(&file, true),
+            d.line,
+            d.column,
+            escape_github(title, true),
+            escape_github(&d.message, false)
+        ));
+    }
+}
+
+/// Render every outcome's output inside a collapsible section
+pub(crate) fn render(provider: Provider, cwd: &Path, outcomes: &[RunOutcome]) -> String {
+    let mut out = String::new();
+    for outcome in outcomes {
+        let mut log = outcome.output();
+        if !log.is_empty() && !log.ends_with('\n') {
+            log.push('\n');
+        }
+        match provider {
+            Provider::GitHub => {
+                out.push_str(&format!(
+                    "::group::{}\n{log}::endgroup::\n",
+                    header(outcome)
+                ));
+                annotations(cwd, outcome, &mut out);
+            }
+            Provider::GitLab => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                let section = gitlab_section(&outcome.dir);
+                let end = now + outcome.duration.as_secs();
+                out.push_str(&format!(
+                    "\x1b[0Ksection_start:{now}:{section}[collapsed=true]\r\x1b[0K{}\n{log}\x1b[0Ksection_end:{end}:{section}\r\x1b[0K\n",
+                    header(outcome)
+                ));
+            }
+            Provider::Buildkite => {
+                // `+++` starts an expanded group, so failures are open by default
+                let marker = if outcome.success { "---" } else { "+++" };
+                out.push_str(&format!("{marker} {}\n{log}", header(outcome)));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_groups_and_annotations() {
+        let out = render(
+            Provider::GitHub,
+            Path::new("/meta"),
+            &[RunOutcome::fixture("core", false)
+                .with_stderr("error[E0308]: mismatched types\n --> src/lib.rs:5:13")],
+        );
+        assert!(out.starts_with("::group::✗ core: cargo build\nerror[E0308]"));
+        assert!(out.contains("::endgroup::\n"));
+        assert!(out.contains(
+            "::error file=core/src/lib.rs,line=5,col=13,title=E0308::mismatched types\n"
+        ));
+    }
+
+    #[test]
+    fn test_gitlab_and_buildkite_markers() {
+        let outcomes = [
+            RunOutcome::fixture("libs/core", true).with_stderr("ok\n"),
+            RunOutcome::fixture("app", false),
+        ];
+        let gitlab = render(Provider::GitLab, Path::new("/meta"), &outcomes);
+        assert!(gitlab
+            .contains(":meta_libs_core[collapsed=true]\r\x1b[0K✓ libs/core: cargo build\nok\n"));
+        assert!(gitlab.contains("section_end:"));
+
+        let buildkite = render(Provider::Buildkite, Path::new("/meta"), &outcomes);
+        assert_eq!(
+            buildkite,
+            "--- ✓ libs/core: cargo build\nok\n+++ ✗ app: cargo build\n"
+        );
+        assert!(Provider::parse("jenkins").is_err());
+    }
+}
@@ -0,0 +1,404 @@
+//! In-process clippy runs: `--diff <ref>` and `--aggregate`
+//!
+//! With `--diff`, clippy runs in every repo as usual, but only diagnostics on
+//! lines added or modified since `<ref>` are reported, so pre-existing
+//! warnings in a file do not fail the gate for whoever touches it next.
+//!
+//! With `--aggregate`, clippy runs with `--message-format=json` and the
+//! diagnostics of all repos are merged into one report grouped by lint. A
+//! crate reached from several repos through path dependencies is linted in
+//! each of them; its warnings are counted once, for the repo that owns the
+//! file.
+
+use crate::diagnostics::{self, Diagnostic, Level};
+use crate::git::{self, ChangedLines};
+use crate::quickfix;
+use crate::runner::{self, RunOutcome};
+use crate::{project_path, CommandResult, PlannedCommand};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Lines of output shown when clippy fails without a located diagnostic
+const FAILURE_TAIL_LINES: usize = 10;
+
+/// Whether `d` falls on a line touched according to `changed`
+fn is_touched(d: &Diagnostic, changed: &ChangedLines) -> bool {
+    let file = d
+        .file
+        .strip_prefix("./")
+        .unwrap_or(&d.file)
+        .replace('\\', "/");
+    changed
+        .get(&file)
+        .is_some_and(|ranges| ranges.iter().any(|&(s, e)| (s..=e).contains(&d.line)))
+}
+
+/// Per-repo result of a diff-aware run
+struct RepoReport {
+    repo: String,
+    reported: Vec<Diagnostic>,
+    hidden: usize,
+    /// Clippy failed without any located diagnostic (e.g. a build script error)
+    failure: Option<String>,
+    git_error: Option<String>,
+}
+
+fn report_repo(cwd: &Path, outcome: &RunOutcome, since: &str) -> RepoReport {
+    let all = diagnostics::parse(&outcome.output());
+    let mut report = RepoReport {
+        repo: outcome.dir.clone(),
+        reported: Vec::new(),
+        hidden: 0,
+        failure: None,
+        git_error: None,
+    };
+    if !outcome.success && all.is_empty() {
+        let output = outcome.output();
+        let lines: Vec<&str> = output.lines().collect();
+        report.failure = Some(lines[lines.len().saturating_sub(FAILURE_TAIL_LINES)..].join("\n"));
+    }
+    match git::changed_lines(&project_path(cwd, &outcome.dir), since) {
+        Ok(changed) => {
+            let (touched, untouched): (Vec<_>, Vec<_>) =
+                all.into_iter().partition(|d| is_touched(d, &changed));
+            report.reported = touched;
+            report.hidden = untouched.len();
+        }
+        Err(e) => report.git_error = Some(format!("{e:#}")),
+    }
+    report
+}
+
+fn render(cwd: &Path, since: &str, reports: &[RepoReport]) -> String {
+    let mut out = String::new();
+    let (mut reported, mut hidden) = (0, 0);
+    for r in reports {
+        reported += r.reported.len();
+        hidden += r.hidden;
+        let failed = !r.reported.is_empty() || r.failure.is_some() || r.git_error.is_some();
+        let mark = if failed { "✗".red() } else { "✓".green() };
+        out.push_str(&format!("{mark} {}\n", r.repo.as_str().bold()));
+        for d in &r.reported {
+            out.push_str(&format!("  {}\n", quickfix::format_entry(cwd, &r.repo, d)));
+        }
+        if let Some(e) = &r.git_error {
+            out.push_str(&format!("  could not diff against {since}: {e}\n"));
+        }
+        if let Some(tail) = &r.failure {
+            out.push_str("  clippy failed:\n");
+            for line in tail.lines() {
+                out.push_str(&format!("      | {line}\n"));
+            }
+        }
+    }
+    out.push_str(&format!(
+        "\n{reported} diagnostics on lines changed since {since} ({hidden} pre-existing hidden)\n"
+    ));
+    out
+}
+
+/// `<cargo> clippy <args>` in every repo
+fn clippy_commands(cargo: &str, args: &[String], repos: &[String]) -> Vec<PlannedCommand> {
+    let mut cmd = format!("{cargo} clippy");
+    for arg in args {
+        cmd.push(' ');
+        cmd.push_str(arg);
+    }
+    repos
+        .iter()
+        .map(|dir| PlannedCommand {
+            dir: dir.clone(),
+            cmd: cmd.clone(),
+            env: None,
+        })
+        .collect()
+}
+
+/// Run `<cargo> clippy <args>` everywhere and report diagnostics on touched lines
+pub(crate) fn execute_diff(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    since: &str,
+) -> CommandResult {
+    let commands = clippy_commands(cargo, args, repos);
+
+    let reports: Vec<RepoReport> = runner::run_all(cwd, &commands, parallel)
+        .iter()
+        .map(|o| report_repo(cwd, o, since))
+        .collect();
+    let text = render(cwd, since, &reports);
+    if reports
+        .iter()
+        .any(|r| !r.reported.is_empty() || r.failure.is_some() || r.git_error.is_some())
+    {
+        CommandResult::Error(text)
+    } else {
+        CommandResult::Message(text)
+    }
+}
+
+/// Resolve `.` and `..` without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A diagnostic after deduplication, with its file relative to the meta root
+struct Finding {
+    repo: String,
+    diagnostic: Diagnostic,
+}
+
+/// Merge the diagnostics of all repos, counting each one once
+///
+/// Returns the findings, the number of duplicates dropped and the repos that
+/// failed without any diagnostic, with the tail of their output.
+fn aggregate(cwd: &Path, outcomes: &[RunOutcome]) -> (Vec<Finding>, usize, Vec<(String, String)>) {
+    let roots: Vec<(&str, PathBuf)> = outcomes
+        .iter()
+        .map(|o| (o.dir.as_str(), normalize(&project_path(cwd, &o.dir))))
+        .collect();
+    let mut findings: Vec<Finding> = Vec::new();
+    let mut duplicates = 0;
+    let mut failures = Vec::new();
+    for outcome in outcomes {
+        let found = diagnostics::parse_json(&outcome.stdout);
+        if !outcome.success && found.is_empty() {
+            let output = outcome.output();
+            let lines: Vec<&str> = output.lines().collect();
+            let tail = lines[lines.len().saturating_sub(FAILURE_TAIL_LINES)..].join("\n");
+            failures.push((outcome.dir.clone(), tail));
+        }
+        let repo_root = normalize(&project_path(cwd, &outcome.dir));
+        for mut d in found {
+            let file = normalize(&repo_root.join(&d.file));
+            // The most specific repo containing the file owns it
+            let owner = roots
+                .iter()
+                .filter(|(_, root)| file.starts_with(root))
+                .max_by_key(|(_, root)| root.components().count())
+                .map_or(outcome.dir.as_str(), |(dir, _)| dir);
+            d.file = file
+                .strip_prefix(normalize(cwd))
+                .unwrap_or(&file)
+                .to_string_lossy()
+                .replace('\\', "/");
+            if findings.iter().any(|f| f.diagnostic == d) {
+                duplicates += 1;
+                continue;
+            }
+            findings.push(Finding {
+                repo: owner.to_string(),
+                diagnostic: d,
+            });
+        }
+    }
+    (findings, duplicates, failures)
+}
+
+fn render_aggregate(
+    repos: &[String],
+    findings: &[Finding],
+    duplicates: usize,
+    failures: &[(String, String)],
+) -> String {
+    let mut groups: BTreeMap<(Level, &str), Vec<&Finding>> = BTreeMap::new();
+    for f in findings {
+        let code = f.diagnostic.code.as_deref().unwrap_or("uncategorized");
+        groups
+            .entry((f.diagnostic.level, code))
+            .or_default()
+            .push(f);
+    }
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    groups.sort_by(|((la, _), a), ((lb, _), b)| la.cmp(lb).then(b.len().cmp(&a.len())));
+
+    let mut out = String::new();
+    for ((level, code), group) in &groups {
+        let per_repo: Vec<String> = repos
+            .iter()
+            .filter_map(|repo| {
+                let n = group.iter().filter(|f| &f.repo == repo).count();
+                (n > 0).then(|| format!("{repo} {n}"))
+            })
+            .collect();
+        let header = format!("{}[{code}]", level.as_str());
+        let header = match level {
+            Level::Error => header.red().bold(),
+            Level::Warning => header.yellow().bold(),
+        };
+        out.push_str(&format!(
+            "{header}: {} ({})\n",
+            group.len(),
+            per_repo.join(", ")
+        ));
+        for f in group {
+            let d = &f.diagnostic;
+            out.push_str(&format!(
+                "    {}:{}:{}: {}\n",
+                d.file, d.line, d.column, d.message
+            ));
+        }
+    }
+    for (repo, tail) in failures {
+        out.push_str(&format!(
+            "{} {}: clippy failed:\n",
+            "✗".red(),
+            repo.as_str().bold()
+        ));
+        for line in tail.lines() {
+            out.push_str(&format!("      | {line}\n"));
+        }
+    }
+    let diagnostics: Vec<Diagnostic> = findings.iter().map(|f| f.diagnostic.clone()).collect();
+    let (errors, warnings) = diagnostics::counts(&diagnostics);
+    out.push_str(&format!(
+        "\n{warnings} warnings, {errors} errors across {} repos ({duplicates} duplicates removed)\n",
+        repos.len()
+    ));
+    out
+}
+
+/// Run clippy everywhere and print one report grouped by lint
+///
+/// Fails on errors, on repos where clippy failed, and when there are more
+/// than `max_warnings` warnings.
+pub(crate) fn execute_aggregate(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    max_warnings: Option<usize>,
+) -> CommandResult {
+    let args = [vec!["--message-format=json".to_string()], args.to_vec()].concat();
+    let outcomes = runner::run_all(cwd, &clippy_commands(cargo, &args, repos), parallel);
+    let (findings, duplicates, failures) = aggregate(cwd, &outcomes);
+    let mut text = render_aggregate(repos, &findings, duplicates, &failures);
+    let warnings = findings
+        .iter()
+        .filter(|f| f.diagnostic.level == Level::Warning)
+        .count();
+    let over = max_warnings.filter(|&max| warnings > max);
+    if let Some(max) = over {
+        text.push_str(&format!(
+            "{warnings} warnings exceed --max-warnings {max}\n"
+        ));
+    }
+    let failed = over.is_some()
+        || !failures.is_empty()
+        || outcomes.iter().any(|o| !o.success)
+        || warnings < findings.len();
+    if failed {
+        CommandResult::Error(text)
+    } else {
+        CommandResult::Message(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(file: &str, line: u32) -> Diagnostic {
+        Diagnostic {
+            level: Level::Warning,
+            code: Some("clippy::needless_return".to_string()),
+            message: "unneeded `return` statement".to_string(),
+            file: file.to_string(),
+            line,
+            column: 5,
+        }
+    }
+
+    #[test]
+    fn test_only_touched_lines_are_reported() {
+        let mut changed = ChangedLines::new();
+        changed.insert("src/lib.rs".to_string(), vec![(3, 5)]);
+        changed.insert("src/new.rs".to_string(), vec![(1, u32::MAX)]);
+
+        assert!(is_touched(&diagnostic("src/lib.rs", 4), &changed));
+        assert!(is_touched(&diagnostic("./src/lib.rs", 5), &changed));
+        assert!(!is_touched(&diagnostic("src/lib.rs", 6), &changed));
+        assert!(is_touched(&diagnostic("src/new.rs", 120), &changed));
+        assert!(!is_touched(&diagnostic("src/other.rs", 1), &changed));
+    }
+
+    #[test]
+    fn test_render_counts_hidden() {
+        let reports = vec![RepoReport {
+            repo: "core".to_string(),
+            reported: vec![diagnostic("src/lib.rs", 4)],
+            hidden: 2,
+            failure: None,
+            git_error: None,
+        }];
+        let text = render(Path::new("/meta"), "main", &reports);
+        assert!(text.contains("core/src/lib.rs:4:5: warning[clippy::needless_return]"));
+        assert!(text.contains("1 diagnostics on lines changed since main (2 pre-existing hidden)"));
+    }
+
+    fn message(file: &str, line: u32, code: &str) -> String {
+        format!(
+            r#"{{"reason":"compiler-message","message":{{"level":"warning","message":"lint","code":{{"code":"{code}"}},"spans":[{{"file_name":"{file}","line_start":{line},"column_start":5,"is_primary":true}}]}}}}"#
+        )
+    }
+
+    #[test]
+    fn test_aggregate_dedupes_path_dependencies() {
+        let cwd = Path::new("/meta");
+        let core = [
+            message("src/lib.rs", 4, "clippy::needless_return"),
+            message("src/lib.rs", 9, "clippy::needless_return"),
+        ]
+        .join("\n");
+        // `app` lints `core` through a path dependency
+        let app = [
+            message("src/main.rs", 2, "clippy::needless_return"),
+            message("../core/src/lib.rs", 4, "clippy::needless_return"),
+            message("src/main.rs", 7, "clippy::redundant_clone"),
+        ]
+        .join("\n");
+        let outcomes = vec![
+            RunOutcome::fixture("core", true).with_stdout(&core),
+            RunOutcome::fixture("app", true).with_stdout(&app),
+            RunOutcome::fixture("broken", false).with_stderr("error: could not compile `app`"),
+        ];
+        let (findings, duplicates, failures) = aggregate(cwd, &outcomes);
+        assert_eq!(findings.len(), 4);
+        assert_eq!(duplicates, 1);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(findings[0].diagnostic.file, "core/src/lib.rs");
+
+        let repos: Vec<String> = ["core", "app", "broken"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let text = render_aggregate(&repos, &findings, duplicates, &failures);
+        assert!(
+            text.contains("warning[clippy::needless_return]: 3 (core 2, app 1)"),
+            "{text}"
+        );
+        assert!(text.contains("    app/src/main.rs:7:5: lint"), "{text}");
+        assert!(text.contains("broken: clippy failed:"), "{text}");
+        assert!(
+            text.contains("4 warnings, 0 errors across 3 repos (1 duplicates removed)"),
+            "{text}"
+        );
+        let lint = text.find("needless_return").unwrap();
+        assert!(lint < text.find("redundant_clone").unwrap());
+    }
+}
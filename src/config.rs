@@ -0,0 +1,668 @@
+//! `.meta-rust.toml` configuration
+//!
+//! Read from the meta root. Every section is optional; a missing file is the
+//! same as an empty one. Unknown keys are errors naming the closest known key,
+//! and so are typos in the plugin request meta sends (see
+//! [`parse_plugin_request`]), though those only warn.
+
+use anyhow::Context;
+use meta_plugin_protocol::PluginRequest;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Name of the config file in the meta root
+pub const CONFIG_FILE: &str = ".meta-rust.toml";
+
+/// Top-level configuration
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub changes: ChangesConfig,
+    pub maintain: MaintainConfig,
+    pub coverage: CoverageConfig,
+    pub limits: LimitsConfig,
+    pub order: OrderConfig,
+    pub concurrency: ConcurrencyConfig,
+    pub cargo: CargoConfig,
+    pub sysdeps: SysdepsConfig,
+    pub integration: IntegrationConfig,
+    pub publish: PublishConfig,
+    pub docs: DocsConfig,
+    pub xtask: XtaskConfig,
+    pub features: FeaturesConfig,
+    pub platforms: PlatformsConfig,
+    pub dist: DistConfig,
+    pub policy: PolicyConfig,
+    pub notify: NotifyConfig,
+    pub debuginfo: DebugInfoConfig,
+    /// Named environment profiles for `--env-profile`, e.g. `[env.staging]`
+    pub env: BTreeMap<String, EnvProfile>,
+    /// Parameterized meta commands, e.g. `[tasks.release]`
+    pub tasks: BTreeMap<String, TaskConfig>,
+    /// Per-repo settings, keyed by repo path
+    pub repos: BTreeMap<String, RepoConfig>,
+}
+
+/// Checks repos must pass before merge, for `meta cargo gate`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyConfig {
+    /// Repo tag (or `*` for every repo) -> required cargo subcommands
+    pub required: BTreeMap<String, Vec<String>>,
+}
+
+/// Where `--notify-delta` sends notifications
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// URL that receives a JSON POST when repos newly fail or are fixed
+    pub webhook: Option<String>,
+}
+
+/// Environment variables and cargo flags selected with `--env-profile`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnvProfile {
+    pub vars: BTreeMap<String, String>,
+    /// Flags added to the cargo arguments, e.g. `["--features", "staging"]`
+    pub args: Vec<String>,
+}
+
+/// A meta command run as `meta cargo <task>`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TaskConfig {
+    /// Meta cargo command line with `{param}` placeholders,
+    /// e.g. `version {level} --commit --tag`
+    pub run: String,
+    pub params: Vec<TaskParam>,
+}
+
+/// A task parameter, given as `--<name> <value>` or asked for
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskParam {
+    pub name: String,
+    /// Question shown when asking; the name when unset
+    pub prompt: Option<String>,
+    /// Used for an empty answer, and when not running interactively
+    pub default: Option<String>,
+}
+
+/// Debug info handling, for every command or per cargo subcommand
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DebugInfoConfig {
+    /// `off`, `packed` or `unpacked` (`-C split-debuginfo`)
+    pub split: Option<String>,
+    /// `none`, `debuginfo` or `symbols`
+    pub strip: Option<String>,
+    /// Overrides per subcommand, e.g. `[debuginfo.commands.dist]`
+    pub commands: BTreeMap<String, DebugInfoSettings>,
+}
+
+/// Debug info settings of one subcommand
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DebugInfoSettings {
+    pub split: Option<String>,
+    pub strip: Option<String>,
+}
+
+impl DebugInfoConfig {
+    /// Settings for subcommand `sub`, its own entry taking precedence
+    pub fn settings(&self, sub: &str) -> DebugInfoSettings {
+        let own = self.commands.get(sub).cloned().unwrap_or_default();
+        DebugInfoSettings {
+            split: own.split.or_else(|| self.split.clone()),
+            strip: own.strip.or_else(|| self.strip.clone()),
+        }
+    }
+}
+
+/// Settings for a single repo
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepoConfig {
+    /// Failures are reported as warnings and do not fail the run
+    pub allow_failure: bool,
+    /// Overrides `[cargo] rustc_wrapper`; an empty string disables it
+    pub rustc_wrapper: Option<String>,
+}
+
+/// Settings for change detection (`affected`)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChangesConfig {
+    /// Globs, relative to each repo, for files that never count as changes
+    pub ignore: Vec<String>,
+    /// Fast-path command per cargo subcommand for repos where only Cargo.lock
+    /// changed, e.g. `test = "cargo check --locked"`
+    pub lockfile_only: BTreeMap<String, String>,
+}
+
+/// Settings for `meta cargo maintain`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaintainConfig {
+    /// Checks to run, in order (built-in names or keys of `commands`)
+    pub checks: Vec<String>,
+    /// Custom checks, or overrides of built-in ones, by name
+    pub commands: BTreeMap<String, String>,
+}
+
+/// Settings for `meta cargo coverage`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CoverageConfig {
+    /// Command printing llvm-cov JSON, instead of `cargo llvm-cov --summary-only --json`
+    pub command: Option<String>,
+    /// Where baselines are stored, relative to the meta root
+    pub baseline_dir: Option<String>,
+    /// Smallest allowed change in total coverage, in percentage points
+    /// (e.g. `-0.5` tolerates a half-point drop)
+    pub min_delta: Option<f64>,
+}
+
+/// Resource limits applied to each planned cargo command
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Memory cap, e.g. `"4G"` or `"512M"`
+    pub memory: Option<String>,
+    /// CPU cap in cores, e.g. `1.5`
+    pub cpus: Option<f64>,
+    /// Run every command at idle CPU and IO priority
+    pub nice: bool,
+    /// Per-repo overrides, keyed by repo path
+    pub repos: BTreeMap<String, RepoLimits>,
+}
+
+/// Resource limits for a single repo
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepoLimits {
+    pub memory: Option<String>,
+    pub cpus: Option<f64>,
+}
+
+/// Settings for repo execution order
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OrderConfig {
+    /// Order used when `--order` is not given (`alpha` if unset)
+    pub default: Option<String>,
+    /// Explicit sequence for `--order config`; unlisted repos follow in
+    /// meta project file order
+    pub repos: Vec<String>,
+}
+
+/// Per-repo concurrency classes for parallel runs
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConcurrencyConfig {
+    /// Maximum number of repos of each class running at once, e.g. `heavy = 1`
+    pub classes: BTreeMap<String, usize>,
+    /// Class of each repo, keyed by repo path; `exclusive` runs a repo alone
+    pub repos: BTreeMap<String, String>,
+}
+
+/// How cargo is invoked in each repo
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CargoConfig {
+    /// Pass the meta root's `.cargo/config.toml` to every repo with `--config`
+    pub root_config: bool,
+    /// Give each profile and target triple its own subdirectory of a shared
+    /// target dir
+    pub split_target_dir: bool,
+    /// `RUSTC_WRAPPER` for every repo, e.g. a script recording compile
+    /// times; relative paths are resolved against the meta root
+    pub rustc_wrapper: Option<String>,
+    /// Remap absolute paths in panics and debug info to meta-relative ones
+    pub remap_path_prefix: bool,
+    /// Reuse resolved plans while manifests, config and flags are unchanged
+    pub plan_cache: bool,
+}
+
+impl Default for CargoConfig {
+    fn default() -> Self {
+        CargoConfig {
+            root_config: true,
+            split_target_dir: false,
+            rustc_wrapper: None,
+            remap_path_prefix: false,
+            plan_cache: false,
+        }
+    }
+}
+
+/// System libraries and tools required to build, checked by `meta cargo sysdeps`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SysdepsConfig {
+    /// pkg-config modules every repo needs, optionally with a version
+    /// constraint, e.g. `"openssl >= 3"`
+    pub pkg_config: Vec<String>,
+    /// Executables every repo needs on PATH, e.g. `"protoc"`
+    pub tools: Vec<String>,
+    /// Additional requirements per repo, keyed by repo path
+    pub repos: BTreeMap<String, RepoSysdeps>,
+}
+
+/// System requirements of a single repo
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepoSysdeps {
+    pub pkg_config: Vec<String>,
+    pub tools: Vec<String>,
+}
+
+/// Repos wired together by `meta cargo integration`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IntegrationConfig {
+    /// Repos whose binaries are built first and exposed as `META_BIN_<NAME>`
+    pub binaries: Vec<String>,
+    /// Repos whose tests run against those binaries
+    pub tests: Vec<String>,
+    /// Services started before the tests and stopped after, in order
+    pub fixtures: Vec<FixtureConfig>,
+}
+
+/// A service the integration tests need, e.g. a database
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FixtureConfig {
+    pub name: String,
+    /// Command starting the service; may stay in the foreground
+    pub start: String,
+    /// Command stopping the service; without one the start process is killed
+    pub stop: Option<String>,
+    /// Command that succeeds once the service accepts requests
+    pub ready: Option<String>,
+    /// `host:port` that accepts TCP connections once the service is up
+    pub ready_tcp: Option<String>,
+    /// Seconds to wait for readiness (default 30)
+    pub ready_timeout: Option<u64>,
+    /// Repo to run the commands in (default: the meta root)
+    pub dir: Option<String>,
+    /// Extra environment for the tests, e.g. `DATABASE_URL`
+    pub env: BTreeMap<String, String>,
+}
+
+/// Settings for `meta cargo publish`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PublishConfig {
+    pub staging: StagingConfig,
+    /// Seconds to wait for each published version to reach the crates.io
+    /// index before publishing its dependents
+    pub wait_timeout: Option<u64>,
+}
+
+/// The local registry used by `meta cargo publish --staging`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StagingConfig {
+    /// Registry name from the cargo config's `[registries]`
+    pub registry: Option<String>,
+    /// Service that runs the registry for the duration of the publish
+    pub fixture: Option<FixtureConfig>,
+}
+
+/// Settings for `meta cargo doc-coverage`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DocsConfig {
+    /// Lowest documented-item percentage a crate may have
+    pub min_coverage: Option<f64>,
+}
+
+/// meta commands delegated to a repo's xtask (`cargo run -p xtask -- <task>`)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct XtaskConfig {
+    /// Package that runs the tasks; `xtask` when unset
+    pub package: Option<String>,
+    /// meta command -> task, for every repo with an `xtask/Cargo.toml`
+    pub tasks: BTreeMap<String, String>,
+    /// meta command -> task for specific repos, keyed by repo path
+    pub repos: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Settings for `meta cargo feature-report`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeaturesConfig {
+    /// Features to flag as heavy, as `dep/feature` (e.g. `"tokio/full"`);
+    /// every dependency's `full` feature is always flagged
+    pub heavy: Vec<String>,
+}
+
+/// Targets the organization ships, for `meta cargo platform-deps`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlatformsConfig {
+    /// Target triples, e.g. `"x86_64-unknown-linux-gnu"`
+    pub ship: Vec<String>,
+}
+
+/// Settings for `meta cargo dist`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DistConfig {
+    pub packaging: PackagingConfig,
+}
+
+/// Where `meta cargo dist packaging` writes package manager manifests
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PackagingConfig {
+    /// Packaging repo inside the meta workspace, e.g. `"homebrew-tap"`
+    pub repo: Option<String>,
+    /// Download URL of an artifact, with `{repo}`, `{app}`, `{tag}`,
+    /// `{version}` and `{artifact}` placeholders
+    pub url: Option<String>,
+    /// Formula directory in the packaging repo; `Formula` when unset
+    pub formula_dir: Option<String>,
+    /// Scoop bucket directory in the packaging repo; `bucket` when unset
+    pub bucket_dir: Option<String>,
+}
+
+impl Config {
+    /// Repos whose failures do not fail the run
+    pub fn allowed_failures(&self) -> Vec<String> {
+        self.repos
+            .iter()
+            .filter(|(_, r)| r.allow_failure)
+            .map(|(repo, _)| repo.clone())
+            .collect()
+    }
+
+    /// `RUSTC_WRAPPER` for `repo`, if any
+    pub fn rustc_wrapper(&self, repo: &str) -> Option<&str> {
+        let wrapper = self
+            .repos
+            .get(repo)
+            .and_then(|r| r.rustc_wrapper.as_deref())
+            .or(self.cargo.rustc_wrapper.as_deref())?;
+        (!wrapper.is_empty()).then_some(wrapper)
+    }
+
+    /// Load the config from `cwd`, falling back to defaults when absent
+    pub fn load(cwd: &Path) -> anyhow::Result<Self> {
+        let path = cwd.join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("invalid {CONFIG_FILE}"))
+    }
+
+    /// Parse config from TOML text
+    ///
+    /// Unknown keys are errors rather than silently ignored, reported with
+    /// their line and the closest known key.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        toml::from_str(text).map_err(|e| {
+            let message = e.to_string();
+            match diagnose_unknown_key(text, &message) {
+                Some(diagnosis) => anyhow::anyhow!("{diagnosis}\n{message}"),
+                None => anyhow::Error::msg(message),
+            }
+        })
+    }
+}
+
+/// Fields of a plugin request
+const REQUEST_FIELDS: &[&str] = &["command", "args", "projects", "cwd", "options"];
+
+/// Fields of a plugin request's `options`
+const REQUEST_OPTIONS: &[&str] = &[
+    "json_output",
+    "verbose",
+    "parallel",
+    "dry_run",
+    "silent",
+    "recursive",
+    "depth",
+    "include_filters",
+    "exclude_filters",
+    "strict",
+];
+
+/// Parse the plugin request meta sends on stdin, with a warning for each
+/// unknown field
+///
+/// Unknown fields may come from a newer meta, so they are ignored rather than
+/// rejected; the warning points out the field that was likely meant.
+pub fn parse_plugin_request(text: &str) -> Result<(PluginRequest, Vec<String>), String> {
+    let invalid = |e: serde_json::Error| format!("invalid plugin request: {e}");
+    let value: serde_json::Value = serde_json::from_str(text).map_err(invalid)?;
+    let mut warnings = unknown_fields(&value, "", REQUEST_FIELDS);
+    warnings.extend(unknown_fields(
+        &value["options"],
+        "options.",
+        REQUEST_OPTIONS,
+    ));
+    let request = serde_json::from_str(text).map_err(invalid)?;
+    Ok((request, warnings))
+}
+
+/// `unknown plugin request field ...` for each key of `value` not in `known`
+fn unknown_fields(value: &serde_json::Value, prefix: &str, known: &[&str]) -> Vec<String> {
+    value
+        .as_object()
+        .into_iter()
+        .flat_map(|o| o.keys())
+        .filter(|key| !known.contains(&key.as_str()))
+        .map(|key| {
+            let mut warning = format!("unknown plugin request field `{prefix}{key}`");
+            if let Some(suggestion) = closest(key, known) {
+                warning.push_str(&format!(", did you mean `{prefix}{suggestion}`?"));
+            }
+            warning
+        })
+        .collect()
+}
+
+/// Edit distance between `a` and `b`
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// The known key closest to `key`, if it is plausibly a typo
+fn closest<'a>(key: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|k| (levenshtein(key, k), *k))
+        .filter(|(d, k)| *d <= (k.len().max(key.len()) / 3).max(1))
+        .min()
+        .map(|(_, k)| k)
+}
+
+/// `unknown key `x` at line N, did you mean `y`?` for serde's unknown-field
+/// errors
+fn diagnose_unknown_key(text: &str, message: &str) -> Option<String> {
+    let rest = message.split("unknown field `").nth(1)?;
+    let (key, rest) = rest.split_once('`')?;
+    let rest = rest.lines().next().unwrap_or("");
+    let rest = rest.split(" for key ").next().unwrap_or(rest);
+    let known: Vec<&str> = rest
+        .split_once("expected")
+        .map(|(_, list)| list.split('`').skip(1).step_by(2).collect())
+        .unwrap_or_default();
+    let line = text.lines().position(|l| {
+        let l = l.trim_start().trim_start_matches('[');
+        l.strip_prefix(key)
+            .is_some_and(|after| after.trim_start().starts_with(['=', '.', ']']))
+    });
+    let mut diagnosis = format!("unknown key `{key}`");
+    if let Some(line) = line {
+        diagnosis.push_str(&format!(" at line {}", line + 1));
+    }
+    match closest(key, &known) {
+        Some(suggestion) => diagnosis.push_str(&format!(", did you mean `{suggestion}`?")),
+        None if !known.is_empty() => {
+            diagnosis.push_str(&format!(" (expected one of {})", known.join(", ")))
+        }
+        None => {}
+    }
+    Some(diagnosis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_missing_file_is_default() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::load(temp_dir.path()).unwrap();
+        assert!(config.changes.ignore.is_empty());
+    }
+
+    #[test]
+    fn test_parse_ignore_globs() {
+        let config = Config::parse("[changes]\nignore = [\"**/*.md\", \"docs/**\"]\n").unwrap();
+        assert_eq!(config.changes.ignore, vec!["**/*.md", "docs/**"]);
+    }
+
+    #[test]
+    fn test_parse_coverage_gate() {
+        let config = Config::parse("[coverage]\nmin_delta = -0.5\n").unwrap();
+        assert_eq!(config.coverage.min_delta, Some(-0.5));
+        assert!(config.coverage.command.is_none());
+    }
+
+    #[test]
+    fn test_parse_fixtures() {
+        let config = Config::parse(
+            "[[integration.fixtures]]\nname = \"db\"\nstart = \"pg_ctl start\"\nready_tcp = \"127.0.0.1:5432\"\nenv = { DATABASE_URL = \"postgres://localhost\" }\n",
+        )
+        .unwrap();
+        let fixture = &config.integration.fixtures[0];
+        assert_eq!(fixture.name, "db");
+        assert_eq!(fixture.ready_tcp.as_deref(), Some("127.0.0.1:5432"));
+        assert_eq!(fixture.env["DATABASE_URL"], "postgres://localhost");
+    }
+
+    #[test]
+    fn test_plugin_request_typos_are_diagnosed() {
+        let (request, warnings) = parse_plugin_request(
+            r#"{"command": "cargo build", "cwd": "/ws", "options": {"parallell": true}}"#,
+        )
+        .unwrap();
+        assert_eq!(request.command, "cargo build");
+        assert!(!request.options.parallel);
+        assert_eq!(
+            warnings,
+            vec!["unknown plugin request field `options.parallell`, did you mean `options.parallel`?"]
+        );
+        let (_, warnings) = parse_plugin_request(r#"{"comand": "cargo build"}"#).unwrap();
+        assert!(
+            warnings[0].ends_with("did you mean `command`?"),
+            "{warnings:?}"
+        );
+
+        let err = parse_plugin_request(r#"{"options": {"parallel": "yes"}}"#).unwrap_err();
+        assert!(
+            err.starts_with("invalid plugin request: invalid type"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_unknown_key_suggests_closest() {
+        let err = Config::parse("[limits]\nnice = true\nmemroy = \"4G\"\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("unknown key `memroy` at line 3, did you mean `memory`?"),
+            "{err}"
+        );
+        let err = Config::parse("[lmits]\n").unwrap_err().to_string();
+        assert!(err.contains("did you mean `limits`?"), "{err}");
+        let err = Config::parse("[limits]\nbananas = 1\n")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("(expected one of memory, cpus, nice, repos)"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("parallell", "parallel"), 1);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(closest("xyz", &["memory"]), None);
+    }
+
+    #[test]
+    fn test_parse_allow_failure() {
+        let config = Config::parse(
+            "[repos.\"labs/experimental\"]\nallow_failure = true\n\n[repos.core]\nallow_failure = false\n",
+        )
+        .unwrap();
+        assert_eq!(config.allowed_failures(), vec!["labs/experimental"]);
+    }
+
+    #[test]
+    fn test_parse_debuginfo() {
+        let config = Config::parse(
+            "[debuginfo]\nsplit = \"unpacked\"\n\n[debuginfo.commands.dist]\nsplit = \"packed\"\nstrip = \"debuginfo\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.debuginfo.settings("build").split.as_deref(),
+            Some("unpacked")
+        );
+        assert_eq!(
+            config.debuginfo.settings("dist"),
+            DebugInfoSettings {
+                split: Some("packed".to_string()),
+                strip: Some("debuginfo".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_xtask() {
+        let config =
+            Config::parse("[xtask.tasks]\nci = \"ci\"\n\n[xtask.repos.api]\ntest = \"test-all\"\n")
+                .unwrap();
+        assert_eq!(config.xtask.tasks["ci"], "ci");
+        assert_eq!(config.xtask.repos["api"]["test"], "test-all");
+        assert_eq!(config.xtask.package, None);
+    }
+
+    #[test]
+    fn test_parse_sysdeps() {
+        let config = Config::parse(
+            "[sysdeps]\ntools = [\"protoc\"]\n[sysdeps.repos.api]\npkg_config = [\"libpq >= 14\"]\n",
+        )
+        .unwrap();
+        assert_eq!(config.sysdeps.tools, vec!["protoc"]);
+        assert_eq!(config.sysdeps.repos["api"].pkg_config, vec!["libpq >= 14"]);
+    }
+}
@@ -0,0 +1,317 @@
+//! `meta cargo coverage`: line coverage per repo and merged, with baselines
+//!
+//! Each repo runs `cargo llvm-cov --summary-only --json` (or the configured
+//! command). `--save-baseline <ref>` stores the results under the meta root;
+//! `--diff-base <ref>` compares against a stored baseline and reports deltas,
+//! failing when the total drops by more than the allowed minimum delta.
+
+use crate::args;
+use crate::config::Config;
+use crate::runner::{self, RunOutcome};
+use crate::{CommandResult, PlannedCommand};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Command run in each repo when `[coverage] command` is not set
+const DEFAULT_COMMAND: &str = "cargo llvm-cov --summary-only --json";
+
+/// Directory, relative to the meta root, holding stored baselines
+const DEFAULT_BASELINE_DIR: &str = ".meta-rust/coverage";
+
+/// Covered and total line counts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LineCoverage {
+    pub covered: u64,
+    pub count: u64,
+}
+
+impl LineCoverage {
+    /// Percentage of lines covered (100 when there are no lines)
+    pub fn percent(self) -> f64 {
+        if self.count == 0 {
+            100.0
+        } else {
+            self.covered as f64 * 100.0 / self.count as f64
+        }
+    }
+
+    fn add(self, other: LineCoverage) -> LineCoverage {
+        LineCoverage {
+            covered: self.covered + other.covered,
+            count: self.count + other.count,
+        }
+    }
+}
+
+/// Stored per-repo coverage for one ref
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    pub repos: BTreeMap<String, LineCoverage>,
+}
+
+impl Baseline {
+    /// Merged coverage of every repo in the baseline
+    pub fn total(&self) -> LineCoverage {
+        self.repos
+            .values()
+            .fold(LineCoverage::default(), |a, &b| a.add(b))
+    }
+}
+
+/// Extract total line coverage from `cargo llvm-cov --json` output
+pub fn parse_llvm_cov(output: &str) -> Result<LineCoverage> {
+    // llvm-cov prints the JSON export on a single line; cargo noise may surround it
+    let json = output
+        .lines()
+        .find(|l| l.trim_start().starts_with('{'))
+        .context("no JSON coverage export in output")?;
+    let value: serde_json::Value = serde_json::from_str(json)?;
+    let lines = &value["data"][0]["totals"]["lines"];
+    let covered = lines["covered"]
+        .as_u64()
+        .context("missing totals.lines.covered")?;
+    let count = lines["count"]
+        .as_u64()
+        .context("missing totals.lines.count")?;
+    Ok(LineCoverage { covered, count })
+}
+
+/// Baseline file for `git_ref`, e.g. `origin/main` -> `origin_main.json`
+fn baseline_path(cwd: &Path, config: &Config, git_ref: &str) -> PathBuf {
+    let dir = config
+        .coverage
+        .baseline_dir
+        .as_deref()
+        .unwrap_or(DEFAULT_BASELINE_DIR);
+    let name: String = git_ref
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    cwd.join(dir).join(format!("{name}.json"))
+}
+
+fn load_baseline(path: &Path) -> Result<Baseline> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("no baseline at {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("invalid baseline {}", path.display()))
+}
+
+fn save_baseline(path: &Path, baseline: &Baseline) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(baseline)?)
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn delta(current: LineCoverage, base: Option<LineCoverage>) -> Option<f64> {
+    base.map(|b| current.percent() - b.percent())
+}
+
+fn format_delta(delta: Option<f64>) -> String {
+    match delta {
+        Some(d) if d < 0.0 => format!("{d:+.2}").red().to_string(),
+        Some(d) => format!("{d:+.2}").green().to_string(),
+        None => "new".to_string(),
+    }
+}
+
+/// Render per-repo and total coverage, with deltas when a baseline is given
+fn render(
+    current: &Baseline,
+    failures: &[(String, String)],
+    base: Option<(&str, &Baseline)>,
+) -> String {
+    let width = current
+        .repos
+        .keys()
+        .chain(failures.iter().map(|(r, _)| r))
+        .map(|r| r.len())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    let mut out = String::from("Line coverage:\n");
+    for (repo, cov) in &current.repos {
+        out.push_str(&format!(
+            "  {repo:<width$}  {:>6.2}%  ({}/{} lines)",
+            cov.percent(),
+            cov.covered,
+            cov.count
+        ));
+        if let Some((_, b)) = base {
+            out.push_str(&format!(
+                "  {}",
+                format_delta(delta(*cov, b.repos.get(repo).copied()))
+            ));
+        }
+        out.push('\n');
+    }
+    for (repo, error) in failures {
+        out.push_str(&format!("  {repo:<width$}  {}  {error}\n", "failed".red()));
+    }
+    let total = current.total();
+    out.push_str(&format!("  {:<width$}  {:>6.2}%", "Total", total.percent()));
+    if let Some((name, b)) = base {
+        out.push_str(&format!(
+            "  {} vs {name}",
+            format_delta(delta(total, Some(b.total())))
+        ));
+    }
+    out.push('\n');
+    out
+}
+
+/// Handle `meta cargo coverage [--diff-base <ref>] [--save-baseline <ref>] [--min-delta <pct>]`
+pub(crate) fn execute(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let diff_base = args::take_value(&mut args, "--diff-base");
+    let save = args::take_value(&mut args, "--save-baseline");
+    let min_delta = match args::take_value(&mut args, "--min-delta") {
+        Some(v) => match v.parse::<f64>() {
+            Ok(d) => Some(d),
+            Err(_) => return CommandResult::Error(format!("invalid --min-delta '{v}'")),
+        },
+        None => config.coverage.min_delta,
+    };
+
+    let base = match &diff_base {
+        Some(r) => match load_baseline(&baseline_path(cwd, config, r)) {
+            Ok(b) => Some(b),
+            Err(e) => return CommandResult::Error(format!("{e:#}")),
+        },
+        None => None,
+    };
+
+    let mut cmd = config
+        .coverage
+        .command
+        .clone()
+        .unwrap_or_else(|| DEFAULT_COMMAND.to_string());
+    for arg in &args {
+        cmd.push(' ');
+        cmd.push_str(arg);
+    }
+    let commands: Vec<PlannedCommand> = repos
+        .iter()
+        .map(|dir| PlannedCommand {
+            dir: dir.clone(),
+            cmd: cmd.clone(),
+            env: None,
+        })
+        .collect();
+
+    let mut current = Baseline::default();
+    let mut failures = Vec::new();
+    for outcome in runner::run_all(cwd, &commands, parallel) {
+        match outcome_coverage(&outcome) {
+            Ok(cov) => {
+                current.repos.insert(outcome.dir, cov);
+            }
+            Err(e) => failures.push((outcome.dir, format!("{e:#}"))),
+        }
+    }
+
+    let mut text = render(&current, &failures, diff_base.as_deref().zip(base.as_ref()));
+    if let Some(r) = &save {
+        let path = baseline_path(cwd, config, r);
+        if let Err(e) = save_baseline(&path, &current) {
+            return CommandResult::Error(format!("{e:#}"));
+        }
+        text.push_str(&format!("Baseline saved to {}\n", path.display()));
+    }
+
+    let mut failed = !failures.is_empty();
+    if let (Some(min), Some(b)) = (min_delta, &base) {
+        let d = current.total().percent() - b.total().percent();
+        if d < min {
+            text.push_str(&format!(
+                "{} total coverage changed by {d:+.2} points (minimum {min:+.2})\n",
+                "✗".red()
+            ));
+            failed = true;
+        }
+    }
+    if failed {
+        CommandResult::Error(text)
+    } else {
+        CommandResult::Message(text)
+    }
+}
+
+fn outcome_coverage(outcome: &RunOutcome) -> Result<LineCoverage> {
+    if !outcome.success {
+        let last = outcome
+            .stderr
+            .lines()
+            .last()
+            .unwrap_or("")
+            .trim()
+            .to_string();
+        anyhow::bail!("`{}` failed: {last}", outcome.cmd);
+    }
+    parse_llvm_cov(&outcome.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn cov(covered: u64, count: u64) -> LineCoverage {
+        LineCoverage { covered, count }
+    }
+
+    #[test]
+    fn test_parse_llvm_cov() {
+        let output = r#"{"data":[{"totals":{"lines":{"count":200,"covered":150,"percent":75.0}}}],"type":"llvm.coverage.json.export"}"#;
+        assert_eq!(parse_llvm_cov(output).unwrap(), cov(150, 200));
+        assert!(parse_llvm_cov("error: no tests").is_err());
+    }
+
+    #[test]
+    fn test_total_is_merged_by_lines() {
+        let mut b = Baseline::default();
+        b.repos.insert("core".to_string(), cov(90, 100));
+        b.repos.insert("app".to_string(), cov(10, 100));
+        assert_eq!(b.total(), cov(100, 200));
+        assert_eq!(b.total().percent(), 50.0);
+    }
+
+    #[test]
+    fn test_baseline_round_trip_and_delta() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = Config::default();
+        let path = baseline_path(temp_dir.path(), &config, "origin/main");
+        assert!(path.ends_with(".meta-rust/coverage/origin_main.json"));
+
+        let mut base = Baseline::default();
+        base.repos.insert("core".to_string(), cov(50, 100));
+        save_baseline(&path, &base).unwrap();
+        let loaded = load_baseline(&path).unwrap();
+        assert_eq!(loaded.repos["core"], cov(50, 100));
+
+        let mut current = Baseline::default();
+        current.repos.insert("core".to_string(), cov(60, 100));
+        current.repos.insert("app".to_string(), cov(1, 2));
+        let text = render(&current, &[], Some(("origin/main", &loaded)));
+        assert!(text.contains("+10.00"));
+        assert!(text.contains("new"));
+        assert!(text.contains("vs origin/main"));
+    }
+}
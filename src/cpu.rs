@@ -0,0 +1,110 @@
+//! CPU-specific build variants (`--cpu <name>`, `--target-feature <list>`)
+//!
+//! The flags become `-C target-cpu` / `-C target-feature` in RUSTFLAGS for
+//! every planned command, appended to any RUSTFLAGS already set. Artifacts
+//! collected by `meta cargo dist` carry the CPU name, so optimized builds can
+//! ship next to the baseline ones.
+
+use crate::{args, PlannedCommand};
+
+/// Archive and installer extensions kept at the end of variant names
+const EXTENSIONS: &[&str] = &[
+    ".tar.xz", ".tar.gz", ".tar.zst", ".tar.bz2", ".zip", ".msi", ".pkg", ".dmg", ".sh", ".ps1",
+];
+
+/// A CPU build variant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// `-C target-cpu` value, e.g. `native` or `x86-64-v3`
+    pub cpu: String,
+    /// `-C target-feature` value, e.g. `+avx2,+fma`
+    pub features: Option<String>,
+}
+
+impl Variant {
+    /// Take `--cpu` and `--target-feature` from `args`
+    pub(crate) fn take(args: &mut Vec<String>) -> Result<Option<Self>, String> {
+        let features = args::take_value(args, "--target-feature");
+        let Some(cpu) = args::take_value(args, "--cpu") else {
+            return match features {
+                Some(_) => Err("--target-feature requires --cpu".to_string()),
+                None => Ok(None),
+            };
+        };
+        let valid = |s: &str| {
+            !s.is_empty()
+                && s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+' | ','))
+        };
+        if !valid(&cpu) || features.as_deref().is_some_and(|f| !valid(f)) {
+            return Err(format!("invalid CPU variant '{cpu}'"));
+        }
+        Ok(Some(Variant { cpu, features }))
+    }
+
+    pub fn rustflags(&self) -> String {
+        let mut flags = format!("-C target-cpu={}", self.cpu);
+        if let Some(features) = &self.features {
+            flags.push_str(&format!(" -C target-feature={features}"));
+        }
+        flags
+    }
+
+    /// `name` with the CPU inserted before its archive extension, e.g.
+    /// `app-x86_64-unknown-linux-gnu-x86-64-v3.tar.xz`
+    pub fn artifact_name(&self, name: &str) -> String {
+        let at = EXTENSIONS
+            .iter()
+            .filter_map(|ext| name.find(ext))
+            .min()
+            .unwrap_or(name.len());
+        format!("{}-{}{}", &name[..at], self.cpu, &name[at..])
+    }
+
+    /// Add the variant's flags to RUSTFLAGS of every command
+    pub(crate) fn apply(&self, commands: &mut [PlannedCommand]) {
+        let inherited = std::env::var("RUSTFLAGS").unwrap_or_default();
+        for planned in commands {
+            let env = planned.env.get_or_insert_with(Default::default);
+            let base = env.get("RUSTFLAGS").unwrap_or(&inherited);
+            let flags = format!("{base} {}", self.rustflags()).trim().to_string();
+            env.insert("RUSTFLAGS".to_string(), flags);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_and_flags() {
+        let mut args: Vec<String> = ["--release", "--cpu", "x86-64-v3", "--target-feature=+avx2"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let variant = Variant::take(&mut args).unwrap().unwrap();
+        assert_eq!(args, vec!["--release"]);
+        assert_eq!(
+            variant.rustflags(),
+            "-C target-cpu=x86-64-v3 -C target-feature=+avx2"
+        );
+        assert_eq!(Variant::take(&mut args).unwrap(), None);
+
+        let mut args = vec!["--cpu".to_string(), "native; rm -rf /".to_string()];
+        assert!(Variant::take(&mut args).is_err());
+    }
+
+    #[test]
+    fn test_artifact_name() {
+        let variant = Variant {
+            cpu: "x86-64-v3".to_string(),
+            features: None,
+        };
+        assert_eq!(
+            variant.artifact_name("app-x86_64-unknown-linux-gnu.tar.xz.sha256"),
+            "app-x86_64-unknown-linux-gnu-x86-64-v3.tar.xz.sha256"
+        );
+        assert_eq!(variant.artifact_name("app"), "app-x86-64-v3");
+    }
+}
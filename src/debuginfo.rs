@@ -0,0 +1,216 @@
+//! Split debug info and symbol stripping (`--split-debuginfo`, `--strip`)
+//!
+//! The settings come from `[debuginfo]` in `.meta-rust.toml`, per subcommand
+//! under `[debuginfo.commands.<sub>]`, and the flags override both. They are
+//! passed to cargo as `CARGO_PROFILE_<PROFILE>_SPLIT_DEBUGINFO` /
+//! `_STRIP` for the profile the command builds, so every repo gets the same
+//! behaviour without editing its manifest. `meta cargo dist` also gathers the
+//! separated symbol files (`.dwp`, `.dSYM`, `.pdb`) into one directory.
+
+use crate::config::DebugInfoSettings;
+use crate::PlannedCommand;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+const SPLIT_VALUES: &[&str] = &["off", "packed", "unpacked"];
+const STRIP_VALUES: &[&str] = &["none", "debuginfo", "symbols"];
+/// Extensions of separated debug info next to the final artifacts
+const SYMBOL_EXTENSIONS: &[&str] = &["dwp", "dSYM", "pdb"];
+
+/// Cargo profile built by `sub` with `args`
+pub(crate) fn profile(sub: &str, args: &[String]) -> String {
+    let mut profile = None;
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--release" || arg == "-r" {
+            profile = Some("release".to_string());
+        } else if arg == "--profile" {
+            profile = args.get(i + 1).cloned();
+        } else if let Some(p) = arg.strip_prefix("--profile=") {
+            profile = Some(p.to_string());
+        }
+    }
+    profile.unwrap_or_else(|| {
+        match sub {
+            "bench" => "release",
+            "dist" => "dist",
+            _ => "dev",
+        }
+        .to_string()
+    })
+}
+
+/// Name of the cargo environment variable for `key` of `profile`
+fn profile_var(profile: &str, key: &str) -> String {
+    format!(
+        "CARGO_PROFILE_{}_{key}",
+        profile.to_uppercase().replace('-', "_")
+    )
+}
+
+/// Set the debug info variables of `profile` on every command
+pub(crate) fn apply(
+    commands: &mut [PlannedCommand],
+    settings: &DebugInfoSettings,
+    profile: &str,
+) -> Result<(), String> {
+    let mut vars = Vec::new();
+    for (value, allowed, flag, key) in [
+        (
+            &settings.split,
+            SPLIT_VALUES,
+            "--split-debuginfo",
+            "SPLIT_DEBUGINFO",
+        ),
+        (&settings.strip, STRIP_VALUES, "--strip", "STRIP"),
+    ] {
+        let Some(value) = value else { continue };
+        if !allowed.contains(&value.as_str()) {
+            return Err(format!(
+                "invalid {flag} '{value}' (expected {})",
+                allowed.join(", ")
+            ));
+        }
+        vars.push((profile_var(profile, key), value.clone()));
+    }
+    if vars.is_empty() {
+        return Ok(());
+    }
+    for planned in commands {
+        let env = planned.env.get_or_insert_with(Default::default);
+        for (name, value) in &vars {
+            env.insert(name.clone(), value.clone());
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn copy_recursive(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if from.is_dir() {
+        std::fs::create_dir_all(to)
+            .with_context(|| format!("failed to create {}", to.display()))?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        return Ok(());
+    }
+    std::fs::copy(from, to).with_context(|| format!("failed to copy {}", from.display()))?;
+    Ok(())
+}
+
+/// Output directories of `profile` in `target`, each paired with its
+/// counterpart under `dest`
+///
+/// `<target>/<profile>` maps to `dest` and, for cross builds,
+/// `<target>/<triple>/<profile>` to `<dest>/<triple>`. The dev profile lives
+/// in `debug`.
+pub(crate) fn profile_dirs(target: &Path, profile: &str, dest: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let dir_name = if profile == "dev" { "debug" } else { profile };
+    let mut dirs = vec![(target.join(dir_name), dest.to_path_buf())];
+    if let Ok(entries) = std::fs::read_dir(target) {
+        for entry in entries.flatten() {
+            let triple_dir = entry.path().join(dir_name);
+            if triple_dir.is_dir() && entry.file_name() != dir_name {
+                dirs.push((triple_dir, dest.join(entry.file_name())));
+            }
+        }
+    }
+    dirs
+}
+
+/// Copy the symbol files of `profile` in `target` into `dest`, returning how many
+pub(crate) fn collect_symbols(target: &Path, profile: &str, dest: &Path) -> anyhow::Result<usize> {
+    let mut copied = 0;
+    for (dir, dest) in profile_dirs(target, profile, dest) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_symbols = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| SYMBOL_EXTENSIONS.contains(&e));
+            if !is_symbols {
+                continue;
+            }
+            if !dest.is_dir() {
+                std::fs::create_dir_all(&dest)
+                    .with_context(|| format!("failed to create {}", dest.display()))?;
+            }
+            copy_recursive(&path, &dest.join(entry.file_name()))?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::strings;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_profile_env() {
+        assert_eq!(profile("build", &strings(&["--release"])), "release");
+        assert_eq!(profile("test", &[]), "dev");
+        assert_eq!(profile("dist", &[]), "dist");
+        assert_eq!(
+            profile("build", &strings(&["--profile=fast-ci"])),
+            "fast-ci"
+        );
+
+        let mut commands = vec![PlannedCommand {
+            dir: "core".to_string(),
+            cmd: "cargo build".to_string(),
+            env: None,
+        }];
+        let settings = DebugInfoSettings {
+            split: Some("packed".to_string()),
+            strip: None,
+        };
+        apply(&mut commands, &settings, "fast-ci").unwrap();
+        let env = commands[0].env.as_ref().unwrap();
+        assert_eq!(env["CARGO_PROFILE_FAST_CI_SPLIT_DEBUGINFO"], "packed");
+        assert!(!env.contains_key("CARGO_PROFILE_FAST_CI_STRIP"));
+
+        let settings = DebugInfoSettings {
+            split: None,
+            strip: Some("all".to_string()),
+        };
+        let err = apply(&mut commands, &settings, "dev").unwrap_err();
+        assert_eq!(
+            err,
+            "invalid --strip 'all' (expected none, debuginfo, symbols)"
+        );
+    }
+
+    #[test]
+    fn test_collect_symbols() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("target");
+        let native = target.join("dist");
+        let cross = target.join("aarch64-apple-darwin").join("dist");
+        std::fs::create_dir_all(&native).unwrap();
+        std::fs::create_dir_all(cross.join("app.dSYM").join("Contents")).unwrap();
+        std::fs::write(native.join("app"), "bin").unwrap();
+        std::fs::write(native.join("app.dwp"), "dwarf").unwrap();
+        std::fs::write(
+            cross.join("app.dSYM").join("Contents").join("Info.plist"),
+            "",
+        )
+        .unwrap();
+
+        let dest = temp_dir.path().join("symbols");
+        assert_eq!(collect_symbols(&target, "dist", &dest).unwrap(), 2);
+        assert!(dest.join("app.dwp").is_file());
+        assert!(dest
+            .join("aarch64-apple-darwin")
+            .join("app.dSYM")
+            .join("Contents")
+            .join("Info.plist")
+            .is_file());
+        assert!(!dest.join("app").exists());
+    }
+}
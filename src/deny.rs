@@ -0,0 +1,172 @@
+//! `meta cargo deny`: cargo-deny with a shared meta-level policy
+//!
+//! A `deny.toml` in the meta root is the policy every repo is checked
+//! against. A repo's own `deny.toml` (or `.deny.toml`) is layered over it:
+//! tables merge key by key with the repo's values winning, and lists are
+//! combined, so a repo can allow an extra license or skip a duplicate crate
+//! while the shared bans and advisories still apply. The merged config goes
+//! to `.meta-rust/deny/<repo>.toml` and is passed with `--config`. Without a
+//! meta-level `deny.toml`, each repo is checked with its own config.
+
+use crate::limits::shell_quote;
+use crate::{project_path, CommandResult, PlannedCommand};
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Policy file, in the meta root and in repos
+const CONFIG_FILE: &str = "deny.toml";
+
+/// Merged configs, relative to the meta root
+const MERGED_DIR: &str = ".meta-rust/deny";
+
+/// `overlay` layered over `base`
+fn layer(base: &mut toml::Table, overlay: &toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(b)), toml::Value::Table(o)) => layer(b, o),
+            (Some(toml::Value::Array(b)), toml::Value::Array(o)) => {
+                for item in o {
+                    if !b.contains(item) {
+                        b.push(item.clone());
+                    }
+                }
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+fn read_table(path: &Path) -> Result<toml::Table> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    toml::from_str(&text).with_context(|| format!("invalid {}", path.display()))
+}
+
+/// The repo's own config, if it has one
+fn repo_config(dir: &Path) -> Option<PathBuf> {
+    [CONFIG_FILE, ".deny.toml"]
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Config `repo` is checked with: the shared one, layered with the repo's
+fn config_for(shared: &toml::Table, repo: &str, cwd: &Path) -> Result<PathBuf> {
+    let mut merged = shared.clone();
+    if let Some(own) = repo_config(&project_path(cwd, repo)) {
+        layer(&mut merged, &read_table(&own)?);
+    }
+    let path = cwd
+        .join(MERGED_DIR)
+        .join(format!("{}.toml", repo.replace(['/', '\\'], "_")));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let text = toml::to_string(&merged).context("failed to serialize deny config")?;
+    std::fs::write(&path, text).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+fn plan(cargo: &str, args: &[String], repos: &[String], cwd: &Path) -> Result<Vec<PlannedCommand>> {
+    // `meta cargo deny check licenses` and `meta cargo deny licenses` alike
+    let args = match args.first().map(String::as_str) {
+        Some("check") => &args[1..],
+        _ => args,
+    };
+    let shared_path = cwd.join(CONFIG_FILE);
+    let shared = if shared_path.is_file() {
+        Some(read_table(&shared_path)?)
+    } else {
+        None
+    };
+    let mut commands = Vec::new();
+    for repo in repos {
+        let mut cmd = format!("{cargo} deny check");
+        if let Some(shared) = &shared {
+            let path = config_for(shared, repo, cwd)?;
+            cmd.push_str(&format!(
+                " --config {}",
+                shell_quote(&path.display().to_string())
+            ));
+        }
+        for arg in args {
+            cmd.push(' ');
+            cmd.push_str(arg);
+        }
+        commands.push(PlannedCommand {
+            dir: repo.clone(),
+            cmd,
+            env: None,
+        });
+    }
+    Ok(commands)
+}
+
+/// Handle `meta cargo deny [check] [checks and cargo-deny args]`
+pub(crate) fn execute(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+) -> std::result::Result<Vec<PlannedCommand>, CommandResult> {
+    plan(cargo, args, repos, cwd).map_err(|e| CommandResult::Error(format!("{e:#}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_repo_config_layers_over_shared() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(
+            root.join("deny.toml"),
+            "[licenses]\nallow = [\"MIT\", \"Apache-2.0\"]\nconfidence-threshold = 0.9\n\n[bans]\nmultiple-versions = \"deny\"\n",
+        )
+        .unwrap();
+        for repo in ["core", "app"] {
+            std::fs::create_dir_all(root.join(repo)).unwrap();
+        }
+        std::fs::write(
+            root.join("app/deny.toml"),
+            "[licenses]\nallow = [\"MIT\", \"ISC\"]\n\n[bans]\nmultiple-versions = \"warn\"\n",
+        )
+        .unwrap();
+
+        let repos = vec!["core".to_string(), "app".to_string()];
+        let args = vec!["check".to_string(), "licenses".to_string()];
+        let commands = plan("cargo", &args, &repos, root).unwrap();
+        assert!(commands[1].cmd.starts_with("cargo deny check --config "));
+        assert!(
+            commands[1].cmd.ends_with("app.toml' licenses"),
+            "{}",
+            commands[1].cmd
+        );
+
+        let merged = read_table(&root.join(".meta-rust/deny/app.toml")).unwrap();
+        let allow = merged["licenses"]["allow"].as_array().unwrap();
+        let allow: Vec<&str> = allow.iter().filter_map(|v| v.as_str()).collect();
+        assert_eq!(allow, vec!["MIT", "Apache-2.0", "ISC"]);
+        assert_eq!(merged["bans"]["multiple-versions"].as_str(), Some("warn"));
+        assert_eq!(
+            merged["licenses"]["confidence-threshold"].as_float(),
+            Some(0.9)
+        );
+
+        let core = read_table(&root.join(".meta-rust/deny/core.toml")).unwrap();
+        assert_eq!(core["bans"]["multiple-versions"].as_str(), Some("deny"));
+    }
+
+    #[test]
+    fn test_without_shared_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let repos = vec!["core".to_string()];
+        let commands = plan("cargo", &["bans".to_string()], &repos, temp_dir.path()).unwrap();
+        assert_eq!(commands[0].cmd, "cargo deny check bans");
+    }
+}
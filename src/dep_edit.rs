@@ -0,0 +1,259 @@
+//! `meta cargo add` / `meta cargo remove` in every repo
+//!
+//! The command runs once per repo with its arguments intact. Virtual
+//! workspaces, which `cargo add` and `cargo remove` reject without `-p`, get
+//! one invocation per member. With `--only-if-present`, only packages that
+//! already depend on one of the named crates are touched, so a dependency can
+//! be dropped (or its version or features changed) everywhere it is used
+//! without failing in the repos that never had it.
+
+use crate::metadata::{self, Package};
+use crate::{args, project_path, CommandResult, PlannedCommand};
+use std::path::Path;
+
+/// Flags of `cargo add` / `cargo remove` that take a value
+const VALUE_FLAGS: &[&str] = &[
+    "-F",
+    "--features",
+    "--rename",
+    "--registry",
+    "--path",
+    "--git",
+    "--branch",
+    "--tag",
+    "--rev",
+    "--target",
+    "-p",
+    "--package",
+    "--manifest-path",
+    "--lockfile-path",
+    "--config",
+    "-Z",
+    "--color",
+];
+
+/// Crate names given on the command line, without `@<version>`
+fn dependency_names(args: &[String]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut iter = args.iter().take_while(|a| a.as_str() != "--");
+    while let Some(arg) = iter.next() {
+        if VALUE_FLAGS.contains(&arg.as_str()) {
+            iter.next();
+        } else if !arg.starts_with('-') {
+            let name = arg.split('@').next().unwrap_or(arg);
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// `-p <name>` given by the user, if any
+fn selected_package(args: &[String]) -> Option<String> {
+    let mut iter = args.iter().take_while(|a| a.as_str() != "--");
+    while let Some(arg) = iter.next() {
+        if arg == "-p" || arg == "--package" {
+            return iter.next().cloned();
+        }
+        if let Some(name) = arg.strip_prefix("--package=") {
+            return Some(name.to_string());
+        }
+    }
+    None
+}
+
+/// Command line for one repo, `None` when nothing in it is affected
+fn command_for(
+    cargo: &str,
+    sub: &str,
+    args: &[String],
+    repo_dir: &Path,
+    packages: &[Package],
+    only_if_present: bool,
+) -> Option<String> {
+    let mut cmd = format!("{cargo} {sub}");
+    for arg in args {
+        cmd.push(' ');
+        cmd.push_str(arg);
+    }
+    if args.iter().any(|a| a.starts_with("--manifest-path")) {
+        return Some(cmd);
+    }
+
+    let canon = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+    let manifest = canon(&repo_dir.join("Cargo.toml"));
+    let root = packages
+        .iter()
+        .find(|p| canon(&p.manifest_path) == manifest);
+    let selected = selected_package(args);
+    let targets: Vec<&Package> = match (&selected, root) {
+        (Some(name), _) => packages.iter().filter(|p| &p.name == name).collect(),
+        (None, Some(root)) => vec![root],
+        (None, None) => packages.iter().collect(),
+    };
+    let names = dependency_names(args);
+    let targets: Vec<&Package> = targets
+        .into_iter()
+        .filter(|p| !only_if_present || p.dependencies.iter().any(|d| names.contains(&d.name)))
+        .collect();
+    if targets.is_empty() {
+        return None;
+    }
+    if selected.is_some() || root.is_some() {
+        return Some(cmd);
+    }
+    let per_member: Vec<String> = targets
+        .iter()
+        .map(|p| {
+            let mut member = format!("{cargo} {sub} -p {}", p.name);
+            for arg in args {
+                member.push(' ');
+                member.push_str(arg);
+            }
+            member
+        })
+        .collect();
+    Some(per_member.join(" && "))
+}
+
+/// Plan `meta cargo add|remove [--only-if-present] <args>`
+pub(crate) fn execute(
+    cargo: &str,
+    sub: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+) -> Result<Vec<PlannedCommand>, CommandResult> {
+    let mut args = args.to_vec();
+    let only_if_present = args::take_flag(&mut args, "--only-if-present");
+    if only_if_present && dependency_names(&args).is_empty() {
+        return Err(CommandResult::Error(format!(
+            "--only-if-present needs the names of the crates to {sub}"
+        )));
+    }
+    let mut commands = Vec::new();
+    for repo in repos {
+        let dir = project_path(cwd, repo);
+        let packages = match metadata::load_packages(&dir) {
+            Ok(packages) => packages,
+            Err(e) => {
+                let error = format!("cargo metadata failed: {e:#}");
+                commands.push(crate::errored_command(repo, &error));
+                continue;
+            }
+        };
+        if let Some(cmd) = command_for(cargo, sub, &args, &dir, &packages, only_if_present) {
+            commands.push(PlannedCommand {
+                dir: repo.clone(),
+                cmd,
+                env: None,
+            });
+        }
+    }
+    if commands.is_empty() {
+        return Err(CommandResult::Message(format!(
+            "No repo depends on {}",
+            dependency_names(&args).join(", ")
+        )));
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::strings;
+
+    #[test]
+    fn test_dependency_names() {
+        assert_eq!(
+            dependency_names(&strings(&[
+                "serde@1.0",
+                "--features",
+                "derive",
+                "-p",
+                "core",
+                "log"
+            ])),
+            vec!["serde", "log"]
+        );
+        assert!(dependency_names(&strings(&["--path", "../core"])).is_empty());
+    }
+
+    #[test]
+    fn test_virtual_workspace_runs_per_member() {
+        let packages = vec![
+            Package::fixture("cli")
+                .with_root("/ws/app/cli")
+                .with_dependencies(&["serde"]),
+            Package::fixture("server").with_root("/ws/app/server"),
+        ];
+        let dir = Path::new("/ws/app");
+        assert_eq!(
+            command_for(
+                "cargo",
+                "remove",
+                &strings(&["serde"]),
+                dir,
+                &packages,
+                false
+            )
+            .unwrap(),
+            "cargo remove -p cli serde && cargo remove -p server serde"
+        );
+        assert_eq!(
+            command_for(
+                "cargo",
+                "remove",
+                &strings(&["serde"]),
+                dir,
+                &packages,
+                true
+            )
+            .unwrap(),
+            "cargo remove -p cli serde"
+        );
+        assert!(command_for("cargo", "remove", &strings(&["log"]), dir, &packages, true).is_none());
+    }
+
+    #[test]
+    fn test_root_package_and_selected_package() {
+        let packages = vec![
+            Package::fixture("core").with_dependencies(&["serde"]),
+            Package::fixture("macros").with_root("/ws/core/macros"),
+        ];
+        let dir = Path::new("/ws/core");
+        assert_eq!(
+            command_for(
+                "cargo",
+                "add",
+                &strings(&["serde@1.0.200"]),
+                dir,
+                &packages,
+                true
+            )
+            .unwrap(),
+            "cargo add serde@1.0.200"
+        );
+        assert_eq!(
+            command_for(
+                "cargo",
+                "add",
+                &strings(&["-p", "macros", "syn"]),
+                dir,
+                &packages,
+                false
+            )
+            .unwrap(),
+            "cargo add -p macros syn"
+        );
+        assert!(command_for(
+            "cargo",
+            "add",
+            &strings(&["-p", "other", "syn"]),
+            dir,
+            &packages,
+            false
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,194 @@
+//! Compiler diagnostics parsed from cargo output
+//!
+//! Both the human-readable format and `--message-format=json` are supported.
+
+/// Severity of a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error,
+    Warning,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        }
+    }
+}
+
+/// A located warning or error, e.g. `warning[unused_variables]: ...` followed by
+/// ` --> src/lib.rs:3:9`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub level: Level,
+    /// Lint or error code, e.g. `E0308` or `clippy::needless_return`
+    pub code: Option<String>,
+    pub message: String,
+    /// Path as printed by cargo, relative to the repo
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// Parse a `level[code]: message` header line
+fn parse_header(line: &str) -> Option<(Level, Option<String>, String)> {
+    let (level, rest) = if let Some(rest) = line.strip_prefix("warning") {
+        (Level::Warning, rest)
+    } else if let Some(rest) = line.strip_prefix("error") {
+        (Level::Error, rest)
+    } else {
+        return None;
+    };
+    let (code, rest) = match rest.strip_prefix('[') {
+        Some(r) => {
+            let end = r.find(']')?;
+            (Some(r[..end].to_string()), &r[end + 1..])
+        }
+        None => (None, rest),
+    };
+    let message = rest.strip_prefix(": ")?;
+    Some((level, code, message.to_string()))
+}
+
+/// Parse a ` --> file:line:col` location line
+fn parse_location(line: &str) -> Option<(String, u32, u32)> {
+    let loc = line.trim_start().strip_prefix("--> ")?;
+    let mut parts = loc.rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let file = parts.next()?.to_string();
+    Some((file, line, column))
+}
+
+/// Extract all located diagnostics from cargo output
+///
+/// Summary lines such as "`x` (lib) generated 3 warnings" carry no location
+/// and are skipped.
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut diagnostics = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let Some((level, code, message)) = parse_header(line) else {
+            continue;
+        };
+        let location = lines.get(i + 1).and_then(|l| parse_location(l));
+        if let Some((file, line, column)) = location {
+            diagnostics.push(Diagnostic {
+                level,
+                code,
+                message,
+                file,
+                line,
+                column,
+            });
+        }
+    }
+    diagnostics
+}
+
+/// Extract all located diagnostics from `--message-format=json` output
+///
+/// Only `compiler-message` records with a primary span are kept; notes and
+/// summaries such as "3 warnings emitted" are skipped.
+pub fn parse_json(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for line in output.lines() {
+        let line = line.trim_start();
+        if !line.starts_with('{') {
+            continue;
+        }
+        let Ok(record) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if record["reason"] != "compiler-message" {
+            continue;
+        }
+        let message = &record["message"];
+        let level = match message["level"].as_str() {
+            Some("warning") => Level::Warning,
+            Some(l) if l.starts_with("error") => Level::Error,
+            _ => continue,
+        };
+        let spans = message["spans"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+        let Some(span) = spans.iter().find(|s| s["is_primary"] == true) else {
+            continue;
+        };
+        diagnostics.push(Diagnostic {
+            level,
+            code: message["code"]["code"].as_str().map(str::to_string),
+            message: message["message"].as_str().unwrap_or_default().to_string(),
+            file: span["file_name"].as_str().unwrap_or_default().to_string(),
+            line: span["line_start"].as_u64().unwrap_or(0) as u32,
+            column: span["column_start"].as_u64().unwrap_or(0) as u32,
+        });
+    }
+    diagnostics
+}
+
+/// Number of (errors, warnings) in `diagnostics`
+pub fn counts(diagnostics: &[Diagnostic]) -> (usize, usize) {
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.level == Level::Error)
+        .count();
+    (errors, diagnostics.len() - errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = r#"   Compiling demo v0.1.0 (/ws/demo)
+warning: unused variable: `x`
+ --> src/lib.rs:3:9
+  |
+3 |     let x = 1;
+  |         ^ help: if this is intentional, prefix it with an underscore: `_x`
+  |
+error[E0308]: mismatched types
+  --> src/main.rs:10:5
+   |
+warning: `demo` (lib) generated 1 warning
+error: could not compile `demo` (bin "demo") due to 1 previous error
+"#;
+
+    #[test]
+    fn test_parse_located_diagnostics() {
+        let diagnostics = parse(OUTPUT);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].level, Level::Warning);
+        assert_eq!(diagnostics[0].file, "src/lib.rs");
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (3, 9));
+        assert_eq!(diagnostics[1].code.as_deref(), Some("E0308"));
+        assert_eq!(counts(&diagnostics), (1, 1));
+    }
+
+    #[test]
+    fn test_parse_json_messages() {
+        let out = r#"{"reason":"compiler-artifact","fresh":true}
+{"reason":"compiler-message","message":{"level":"warning","message":"unneeded `return` statement","code":{"code":"clippy::needless_return"},"spans":[{"file_name":"src/lib.rs","line_start":4,"column_start":5,"is_primary":true}]}}
+{"reason":"compiler-message","message":{"level":"warning","message":"1 warning emitted","code":null,"spans":[]}}
+{"reason":"compiler-message","message":{"level":"error","message":"mismatched types","code":{"code":"E0308"},"spans":[{"file_name":"src/a.rs","line_start":1,"column_start":1,"is_primary":false},{"file_name":"src/main.rs","line_start":10,"column_start":5,"is_primary":true}]}}
+"#;
+        let diagnostics = parse_json(out);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].code.as_deref(),
+            Some("clippy::needless_return")
+        );
+        assert_eq!(diagnostics[1].file, "src/main.rs");
+        assert_eq!((diagnostics[1].line, diagnostics[1].column), (10, 5));
+        assert_eq!(counts(&diagnostics), (1, 1));
+    }
+
+    #[test]
+    fn test_windows_paths_keep_drive_letter() {
+        let out = "error: boom\n --> C:\\ws\\src\\lib.rs:1:2\n";
+        assert_eq!(parse(out)[0].file, "C:\\ws\\src\\lib.rs");
+    }
+}
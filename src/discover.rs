@@ -0,0 +1,231 @@
+//! Finding the Cargo projects of a repo
+//!
+//! Most repos have their manifest at the root. Repos that keep their Rust code
+//! in a subdirectory (`rust/Cargo.toml`, `crates/*/Cargo.toml`) are searched
+//! a few levels deep instead. A manifest with a `[workspace]` table, virtual
+//! or not, covers every manifest below it, so a workspace runs once from its
+//! root rather than once per member.
+//!
+//! The same holds across meta projects: when a workspace root and some of its
+//! members are all projects of the meta repo, commands run from the root only
+//! (see [`covered_members`]). Membership comes from `cargo metadata`, so
+//! `exclude`d directories keep their own run.
+
+use crate::metadata;
+use std::path::{Path, PathBuf};
+
+/// How many directory levels below a repo are searched
+const MAX_DEPTH: usize = 3;
+/// Directories that never contain project manifests
+const SKIP_DIRS: &[&str] = &["target", "node_modules", "vendor"];
+
+/// Whether the manifest in `dir` declares a workspace
+fn is_workspace(dir: &Path) -> bool {
+    std::fs::read_to_string(dir.join("Cargo.toml"))
+        .ok()
+        .and_then(|text| toml::from_str::<toml::Table>(&text).ok())
+        .is_some_and(|manifest| manifest.contains_key("workspace"))
+}
+
+fn walk(dir: &Path, depth: usize, exclude: &[PathBuf], found: &mut Vec<PathBuf>) {
+    if dir.join("Cargo.toml").is_file() {
+        found.push(dir.to_path_buf());
+        if is_workspace(dir) {
+            return;
+        }
+    }
+    if depth == MAX_DEPTH {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut children: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && !SKIP_DIRS.contains(&name.as_ref())
+        })
+        .map(|e| e.path())
+        .filter(|p| !exclude.contains(p))
+        .collect();
+    children.sort();
+    for child in children {
+        walk(&child, depth + 1, exclude, found);
+    }
+}
+
+/// Directories below `repo_dir` to run cargo in, `repo_dir` itself when it
+/// has a manifest
+///
+/// Directories in `exclude` (other repos nested inside this one) are not
+/// searched.
+pub(crate) fn project_dirs(repo_dir: &Path, exclude: &[PathBuf]) -> Vec<PathBuf> {
+    if repo_dir.join("Cargo.toml").is_file() {
+        return vec![repo_dir.to_path_buf()];
+    }
+    let mut found = Vec::new();
+    walk(repo_dir, 0, exclude, &mut found);
+    found
+}
+
+/// Cargo project dirs of the meta projects `dirs`, relative to `cwd`
+pub(crate) fn rust_projects(dirs: &[String], cwd: &Path) -> Vec<String> {
+    let roots: Vec<PathBuf> = dirs.iter().map(|d| crate::project_path(cwd, d)).collect();
+    let mut projects = Vec::new();
+    for (dir, root) in dirs.iter().zip(&roots) {
+        let nested: Vec<PathBuf> = roots
+            .iter()
+            .filter(|r| *r != root && r.starts_with(root))
+            .cloned()
+            .collect();
+        for found in project_dirs(root, &nested) {
+            let rel = found.strip_prefix(root).unwrap_or(&found);
+            let project = if rel.as_os_str().is_empty() {
+                dir.clone()
+            } else if dir == "." {
+                rel.to_string_lossy().replace('\\', "/")
+            } else {
+                format!("{dir}/{}", rel.to_string_lossy().replace('\\', "/"))
+            };
+            if !projects.contains(&project) {
+                projects.push(project);
+            }
+        }
+    }
+    projects
+}
+
+/// Projects that are members of the workspace of another project in
+/// `projects`, each with that workspace project
+pub(crate) fn covered_members(projects: &[String], cwd: &Path) -> Vec<(String, String)> {
+    let canon = |p: PathBuf| p.canonicalize().unwrap_or(p);
+    let nested_in = |root: &String| -> Vec<&String> {
+        let root_dir = crate::project_path(cwd, root);
+        projects
+            .iter()
+            .filter(|p| *p != root && crate::project_path(cwd, p).starts_with(&root_dir))
+            .collect()
+    };
+    let roots: Vec<&String> = projects
+        .iter()
+        .filter(|root| !nested_in(root).is_empty() && is_workspace(&crate::project_path(cwd, root)))
+        .collect();
+    let dirs: Vec<PathBuf> = roots.iter().map(|r| crate::project_path(cwd, r)).collect();
+    let loaded = metadata::load_each(&dirs, metadata::load_packages);
+    let mut covered: Vec<(String, String)> = Vec::new();
+    for (root, packages) in roots.into_iter().zip(loaded) {
+        let nested = nested_in(root);
+        let Ok(packages) = packages else {
+            continue;
+        };
+        let members: Vec<PathBuf> = packages
+            .iter()
+            .map(|p| canon(p.root().to_path_buf()))
+            .collect();
+        for project in nested {
+            let dir = canon(crate::project_path(cwd, project));
+            if members.contains(&dir) && !covered.iter().any(|(p, _)| p == project) {
+                covered.push((project.clone(), root.clone()));
+            }
+        }
+    }
+    covered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, path: &str, text: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn test_nested_crates_and_virtual_workspaces() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "core/Cargo.toml", "[package]\nname = \"core\"\n");
+        write(root, "core/fuzz/Cargo.toml", "[package]\nname = \"fuzz\"\n");
+        // Virtual workspace in a subdirectory: only its root is a project
+        write(
+            root,
+            "app/rust/Cargo.toml",
+            "[workspace]\nmembers = [\"crates/*\"]\n",
+        );
+        write(
+            root,
+            "app/rust/crates/cli/Cargo.toml",
+            "[package]\nname = \"cli\"\n",
+        );
+        // Loose crates without a workspace each count
+        write(
+            root,
+            "tools/crates/a/Cargo.toml",
+            "[package]\nname = \"a\"\n",
+        );
+        write(
+            root,
+            "tools/crates/b/Cargo.toml",
+            "[package]\nname = \"b\"\n",
+        );
+        write(root, "tools/target/debug/build/x/Cargo.toml", "[package]\n");
+        write(root, "docs/README.md", "");
+
+        let dirs: Vec<String> = [".", "core", "app", "tools", "docs"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            rust_projects(&dirs, root),
+            vec!["core", "app/rust", "tools/crates/a", "tools/crates/b"]
+        );
+    }
+
+    #[test]
+    fn test_root_does_not_search_other_repos() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(root, "core/Cargo.toml", "[package]\nname = \"core\"\n");
+        write(root, "xtask/Cargo.toml", "[package]\nname = \"xtask\"\n");
+        let dirs = vec![".".to_string(), "core".to_string()];
+        assert_eq!(rust_projects(&dirs, root), vec!["xtask", "core"]);
+    }
+
+    #[test]
+    fn test_members_covered_by_root_workspace() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write(
+            root,
+            "Cargo.toml",
+            "[workspace]\nmembers = [\"core\", \"app\"]\nexclude = [\"labs\"]\n",
+        );
+        for name in ["core", "app", "labs"] {
+            write(
+                root,
+                &format!("{name}/Cargo.toml"),
+                &format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"),
+            );
+            write(root, &format!("{name}/src/lib.rs"), "");
+        }
+        let projects: Vec<String> = [".", "core", "app", "labs"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            covered_members(&projects, root),
+            vec![
+                ("core".to_string(), ".".to_string()),
+                ("app".to_string(), ".".to_string())
+            ]
+        );
+        // Without the root in the plan, members run on their own
+        assert!(covered_members(&projects[1..], root).is_empty());
+    }
+}
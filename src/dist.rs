@@ -0,0 +1,514 @@
+//! `meta cargo dist`: shipping binary repos
+//!
+//! `meta cargo dist` runs `cargo dist build` in every repo configured for
+//! cargo-dist (`dist-workspace.toml`, or `[workspace.metadata.dist]` /
+//! `[package.metadata.dist]` in the root manifest) and copies the archives and
+//! installers it lists into one directory, `.meta-rust/dist/<repo>/` unless
+//! `--out` says otherwise. The binaries, their separated debug info (see
+//! [`crate::debuginfo`]) and build metadata are kept as a symbolication
+//! bundle in `.meta-rust/symbols/<repo>/<tag>/`, or under `--symbols-dir`
+//! (see [`crate::symbolicate`]).
+//!
+//! `meta cargo dist upload --github` publishes what cargo-dist built: the
+//! dist manifest (`<target>/distrib/dist-manifest.json`) of every repo lists
+//! its release tag and artifacts, and each repo gets a GitHub release with its
+//! archives, installers and checksums attached (via `gh`). `--umbrella <tag>`
+//! puts every repo's artifacts on a single release of the meta repo instead.
+//! `meta cargo dist packaging` then regenerates package manager manifests
+//! from the same dist manifests (see [`crate::packaging`]).
+
+use crate::config::Config;
+use crate::cpu::Variant;
+use crate::limits::shell_quote;
+use crate::{
+    args, cargo_config, debuginfo, project_path, target_dir, CommandResult, PlannedCommand,
+};
+use crate::{packaging, runner, symbolicate};
+use anyhow::{bail, Context};
+use colored::Colorize;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Where collected artifacts go unless `--out` says otherwise
+const DEFAULT_OUT: &str = ".meta-rust/dist";
+/// Artifact kinds that must ship with a checksum
+const CHECKSUMMED_KINDS: &[&str] = &["executable-zip"];
+
+/// A file produced by cargo-dist
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistArtifact {
+    pub name: String,
+    /// cargo-dist artifact kind, e.g. `executable-zip` or `checksum`
+    pub kind: String,
+    pub path: PathBuf,
+    /// Name of the checksum artifact covering this one
+    pub checksum: Option<String>,
+    /// Targets the artifact is built for
+    pub target_triples: Vec<String>,
+}
+
+/// What a repo's dist manifest says to release
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistManifest {
+    pub tag: String,
+    /// `(app name, version)` of every release in the manifest
+    pub releases: Vec<(String, String)>,
+    pub artifacts: Vec<DistArtifact>,
+}
+
+/// Whether the repo at `dir` is configured for cargo-dist
+pub fn is_configured(dir: &Path) -> bool {
+    if dir.join("dist-workspace.toml").is_file() {
+        return true;
+    }
+    let Ok(text) = std::fs::read_to_string(dir.join("Cargo.toml")) else {
+        return false;
+    };
+    let Ok(table) = toml::from_str::<toml::Table>(&text) else {
+        return false;
+    };
+    ["workspace", "package"].iter().any(|section| {
+        table
+            .get(*section)
+            .and_then(|s| s.get("metadata"))
+            .and_then(|m| m.get("dist"))
+            .is_some()
+    })
+}
+
+/// Directory name for `repo` under the collection directory
+fn collect_dir_name(repo: &str) -> String {
+    if repo == "." {
+        "root".to_string()
+    } else {
+        repo.replace(['/', '\\'], "-")
+    }
+}
+
+/// Read and parse the dist manifest of the repo at `dir`
+pub fn load_manifest(dir: &Path, root_config: Option<&Path>) -> anyhow::Result<DistManifest> {
+    let path = manifest_path(dir, root_config)?;
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    parse_manifest(&text, path.parent().unwrap_or(Path::new(".")))
+}
+
+/// Dist manifest of the repo at `dir`, under its target directory
+pub fn manifest_path(dir: &Path, root_config: Option<&Path>) -> anyhow::Result<PathBuf> {
+    let target = target_dir::resolve(dir, root_config)?;
+    Ok(target.path.join("distrib").join("dist-manifest.json"))
+}
+
+/// Parse a dist manifest; artifacts without a path live in `distrib`
+pub fn parse_manifest(json: &str, distrib: &Path) -> anyhow::Result<DistManifest> {
+    let value: serde_json::Value = serde_json::from_str(json).context("invalid dist manifest")?;
+    let Some(tag) = value["announcement_tag"].as_str() else {
+        bail!("dist manifest has no announcement_tag");
+    };
+    let mut artifacts: Vec<DistArtifact> = value["artifacts"]
+        .as_object()
+        .map(|artifacts| {
+            artifacts
+                .iter()
+                .map(|(name, a)| DistArtifact {
+                    name: name.clone(),
+                    kind: a["kind"].as_str().unwrap_or("").to_string(),
+                    path: a["path"]
+                        .as_str()
+                        .map(PathBuf::from)
+                        .unwrap_or_else(|| distrib.join(name)),
+                    checksum: a["checksum"].as_str().map(str::to_string),
+                    target_triples: a["target_triples"]
+                        .as_array()
+                        .map(|t| {
+                            t.iter()
+                                .filter_map(|t| t.as_str().map(str::to_string))
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    artifacts.sort_by(|a, b| a.name.cmp(&b.name));
+    let releases = value["releases"]
+        .as_array()
+        .map(|releases| {
+            releases
+                .iter()
+                .filter_map(|r| {
+                    Some((
+                        r["app_name"].as_str()?.to_string(),
+                        r["app_version"].as_str()?.to_string(),
+                    ))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(DistManifest {
+        tag: tag.to_string(),
+        releases,
+        artifacts,
+    })
+}
+
+/// Files to upload, after checking every archive is built and checksummed
+pub fn upload_files(manifest: &DistManifest) -> Result<Vec<PathBuf>, Vec<String>> {
+    let names: BTreeSet<&str> = manifest.artifacts.iter().map(|a| a.name.as_str()).collect();
+    let mut problems = Vec::new();
+    for artifact in &manifest.artifacts {
+        if !artifact.path.is_file() {
+            problems.push(format!("{} was not built", artifact.name));
+        }
+        let needs_checksum = CHECKSUMMED_KINDS.contains(&artifact.kind.as_str());
+        let checksummed = artifact
+            .checksum
+            .as_deref()
+            .is_some_and(|c| names.contains(c));
+        if needs_checksum && !checksummed {
+            problems.push(format!("{} has no checksum artifact", artifact.name));
+        }
+    }
+    if problems.is_empty() {
+        Ok(manifest.artifacts.iter().map(|a| a.path.clone()).collect())
+    } else {
+        Err(problems)
+    }
+}
+
+/// `gh release create` for `tag` with `files` attached
+fn release_command(tag: &str, files: &[PathBuf]) -> String {
+    let tag = shell_quote(tag);
+    let mut cmd = format!("gh release create {tag} --title {tag} --notes ''");
+    for file in files {
+        cmd.push(' ');
+        cmd.push_str(&shell_quote(&file.display().to_string()));
+    }
+    cmd
+}
+
+/// Handle `meta cargo dist upload --github [--umbrella <tag>] [--dry-run]`
+fn upload(args: &[String], repos: &[String], cwd: &Path, config: &Config) -> CommandResult {
+    let mut args = args.to_vec();
+    if !args::take_flag(&mut args, "--github") {
+        return CommandResult::Error(
+            "meta cargo dist upload needs a destination; only --github is supported".to_string(),
+        );
+    }
+    let umbrella = args::take_value(&mut args, "--umbrella");
+    let dry_run = args::take_flag(&mut args, "--dry-run");
+    let root_config = cargo_config::root_config(cwd, config);
+
+    let mut releases: Vec<(String, DistManifest, Vec<PathBuf>)> = Vec::new();
+    let mut problems = Vec::new();
+    for repo in repos {
+        let loaded =
+            manifest_path(&project_path(cwd, repo), root_config.as_deref()).and_then(|path| {
+                match std::fs::read_to_string(&path) {
+                    Ok(text) => {
+                        let distrib = path.parent().unwrap_or(Path::new("."));
+                        parse_manifest(&text, distrib).map(Some)
+                    }
+                    // Repos cargo-dist never built have nothing to release
+                    Err(_) => Ok(None),
+                }
+            });
+        match loaded {
+            Ok(Some(manifest)) => match upload_files(&manifest) {
+                Ok(files) => releases.push((repo.clone(), manifest, files)),
+                Err(p) => problems.extend(p.into_iter().map(|p| format!("{repo}: {p}"))),
+            },
+            Ok(None) => {}
+            Err(e) => problems.push(format!("{repo}: {e:#}")),
+        }
+    }
+    if !problems.is_empty() {
+        let mut out = String::new();
+        for problem in &problems {
+            out.push_str(&format!("{} {problem}\n", "error:".red()));
+        }
+        return CommandResult::Error(out);
+    }
+    if releases.is_empty() {
+        return CommandResult::Error(
+            "no dist manifests found; run `meta cargo dist` first".to_string(),
+        );
+    }
+
+    let commands: Vec<PlannedCommand> = match &umbrella {
+        Some(tag) => {
+            let files: Vec<PathBuf> = releases.iter().flat_map(|(_, _, f)| f.clone()).collect();
+            let mut seen = BTreeSet::new();
+            if let Some(dup) = files
+                .iter()
+                .filter_map(|f| f.file_name())
+                .find(|name| !seen.insert(*name))
+            {
+                return CommandResult::Error(format!(
+                    "artifact {} is produced by several repos; upload per repo instead",
+                    dup.to_string_lossy()
+                ));
+            }
+            vec![PlannedCommand {
+                dir: ".".to_string(),
+                cmd: release_command(tag, &files),
+                env: None,
+            }]
+        }
+        None => releases
+            .iter()
+            .map(|(repo, manifest, files)| PlannedCommand {
+                dir: repo.clone(),
+                cmd: release_command(&manifest.tag, files),
+                env: None,
+            })
+            .collect(),
+    };
+    if dry_run {
+        let mut out = String::from("Would run:\n");
+        for c in &commands {
+            out.push_str(&format!("  [{}] {}\n", c.dir, c.cmd));
+        }
+        return CommandResult::Message(out);
+    }
+
+    let mut out = String::from("GitHub releases:\n");
+    let mut failed = false;
+    for (planned, outcome) in commands.iter().zip(runner::run_all(cwd, &commands, false)) {
+        if outcome.success {
+            out.push_str(&format!("  {} {}\n", "ok".green(), planned.dir));
+        } else {
+            failed = true;
+            let reason = outcome.stderr.trim().lines().last().unwrap_or("");
+            out.push_str(&format!("  {} {}: {reason}\n", "FAIL".red(), planned.dir));
+        }
+    }
+    if failed {
+        CommandResult::Error(out)
+    } else {
+        CommandResult::Message(out)
+    }
+}
+
+/// Copy the artifacts of `manifest` into `dir`, returning how many
+fn collect(
+    manifest: &DistManifest,
+    dir: &Path,
+    variant: Option<&Variant>,
+) -> anyhow::Result<usize> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let mut copied = 0;
+    for artifact in manifest.artifacts.iter().filter(|a| a.path.is_file()) {
+        let name = match variant {
+            Some(v) => v.artifact_name(&artifact.name),
+            None => artifact.name.clone(),
+        };
+        let dest = dir.join(name);
+        std::fs::copy(&artifact.path, &dest)
+            .with_context(|| format!("failed to copy {}", artifact.path.display()))?;
+        copied += 1;
+    }
+    Ok(copied)
+}
+
+/// Handle `meta cargo dist [--out <dir>] [--symbols-dir <dir>] [--cpu <name>] [cargo dist build args]`
+fn build(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let out_dir = args::take_value(&mut args, "--out")
+        .map(|d| cwd.join(d))
+        .unwrap_or_else(|| cwd.join(DEFAULT_OUT));
+    let symbols_dir = args::take_value(&mut args, "--symbols-dir")
+        .map(|d| cwd.join(d))
+        .unwrap_or_else(|| cwd.join(symbolicate::DEFAULT_DIR));
+    let variant = match Variant::take(&mut args) {
+        Ok(v) => v,
+        Err(e) => return CommandResult::Error(e),
+    };
+    let dist_repos: Vec<&String> = repos
+        .iter()
+        .filter(|r| is_configured(&project_path(cwd, r)))
+        .collect();
+    if dist_repos.is_empty() {
+        return CommandResult::Message(
+            "No repos are configured for cargo-dist (run `cargo dist init` in a binary repo)"
+                .to_string(),
+        );
+    }
+    let extra = args.iter().map(|a| format!(" {a}")).collect::<String>();
+    let mut commands: Vec<PlannedCommand> = dist_repos
+        .iter()
+        .map(|repo| PlannedCommand {
+            dir: repo.to_string(),
+            cmd: format!("{cargo} dist build{extra}"),
+            env: None,
+        })
+        .collect();
+    if let Some(variant) = &variant {
+        variant.apply(&mut commands);
+    }
+    let profile = debuginfo::profile("dist", &args);
+    if let Err(e) = debuginfo::apply(&mut commands, &config.debuginfo.settings("dist"), &profile) {
+        return CommandResult::Error(e);
+    }
+    let outcomes = runner::run_all(cwd, &commands, parallel);
+
+    let root_config = cargo_config::root_config(cwd, config);
+    let mut out = String::from("cargo-dist builds:\n");
+    let mut failed = false;
+    for (repo, outcome) in dist_repos.iter().zip(&outcomes) {
+        if !outcome.success {
+            failed = true;
+            let reason = outcome.stderr.trim().lines().last().unwrap_or("");
+            out.push_str(&format!("  {} {repo}: {reason}\n", "FAIL".red()));
+            continue;
+        }
+        let dest = out_dir.join(collect_dir_name(repo));
+        let repo_dir = project_path(cwd, repo);
+        let bundled = load_manifest(&repo_dir, root_config.as_deref()).and_then(|manifest| {
+            let n = collect(&manifest, &dest, variant.as_ref())?;
+            let release = match &variant {
+                Some(v) => v.artifact_name(&manifest.tag),
+                None => manifest.tag.clone(),
+            };
+            let bundle = symbols_dir.join(collect_dir_name(repo)).join(release);
+            let target = target_dir::resolve(&repo_dir, root_config.as_deref())?;
+            let info = symbolicate::BuildInfo {
+                repo: repo.to_string(),
+                tag: manifest.tag.clone(),
+                ..Default::default()
+            };
+            let info = symbolicate::write_bundle(&repo_dir, &target.path, &profile, &bundle, info)?;
+            Ok((n, info, bundle))
+        });
+        match bundled {
+            Ok((n, info, bundle)) => out.push_str(&format!(
+                "  {} {repo}: {n} artifacts in {}; {} binaries, {} symbol files in {}\n",
+                "ok".green(),
+                dest.display(),
+                info.binaries.len(),
+                info.symbols,
+                bundle.display()
+            )),
+            Err(e) => {
+                failed = true;
+                out.push_str(&format!("  {} {repo}: {e:#}\n", "FAIL".red()));
+            }
+        }
+    }
+    if failed {
+        CommandResult::Error(out)
+    } else {
+        CommandResult::Message(out)
+    }
+}
+
+/// Handle `meta cargo dist [upload ...]`
+pub(crate) fn execute(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+) -> CommandResult {
+    match args.split_first() {
+        Some((sub, rest)) if sub == "upload" => upload(rest, repos, cwd, config),
+        Some((sub, rest)) if sub == "packaging" => packaging::execute(rest, repos, cwd, config),
+        _ => build(cargo, args, repos, cwd, parallel, config),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MANIFEST: &str = r#"{
+        "announcement_tag": "v1.2.0",
+        "releases": [{"app_name": "app", "app_version": "1.2.0"}],
+        "artifacts": {
+            "app-x86_64-unknown-linux-gnu.tar.xz": {
+                "kind": "executable-zip",
+                "target_triples": ["x86_64-unknown-linux-gnu"],
+                "checksum": "app-x86_64-unknown-linux-gnu.tar.xz.sha256"
+            },
+            "app-x86_64-unknown-linux-gnu.tar.xz.sha256": {"kind": "checksum"},
+            "app-installer.sh": {"kind": "installer"}
+        }
+    }"#;
+
+    #[test]
+    fn test_upload_files_require_checksums() {
+        let temp_dir = TempDir::new().unwrap();
+        let manifest = parse_manifest(MANIFEST, temp_dir.path()).unwrap();
+        assert_eq!(manifest.tag, "v1.2.0");
+        assert_eq!(
+            manifest.releases,
+            vec![("app".to_string(), "1.2.0".to_string())]
+        );
+        assert_eq!(
+            manifest.artifacts[1].target_triples,
+            vec!["x86_64-unknown-linux-gnu"]
+        );
+        assert_eq!(manifest.artifacts.len(), 3);
+        let problems = upload_files(&manifest).unwrap_err();
+        assert_eq!(problems.len(), 3);
+
+        for artifact in &manifest.artifacts {
+            std::fs::write(&artifact.path, "").unwrap();
+        }
+        assert_eq!(upload_files(&manifest).unwrap().len(), 3);
+
+        let mut unchecked = manifest.clone();
+        unchecked.artifacts.retain(|a| a.kind != "checksum");
+        let problems = upload_files(&unchecked).unwrap_err();
+        assert!(problems[0].contains("has no checksum artifact"));
+    }
+
+    #[test]
+    fn test_is_configured_and_collect() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("cli");
+        std::fs::create_dir(&repo).unwrap();
+        std::fs::write(repo.join("Cargo.toml"), "[package]\nname = \"cli\"\n").unwrap();
+        assert!(!is_configured(&repo));
+        std::fs::write(
+            repo.join("Cargo.toml"),
+            "[workspace]\nmembers = []\n\n[workspace.metadata.dist]\ncargo-dist-version = \"0.22.1\"\n",
+        )
+        .unwrap();
+        assert!(is_configured(&repo));
+
+        let manifest = parse_manifest(MANIFEST, temp_dir.path()).unwrap();
+        std::fs::write(&manifest.artifacts[0].path, "installer").unwrap();
+        let dest = temp_dir
+            .path()
+            .join("out")
+            .join(collect_dir_name("tools/cli"));
+        assert_eq!(collect(&manifest, &dest, None).unwrap(), 1);
+        assert!(dest.join("app-installer.sh").is_file());
+        assert!(dest.ends_with("tools-cli"));
+
+        let variant = Variant {
+            cpu: "x86-64-v3".to_string(),
+            features: None,
+        };
+        assert_eq!(collect(&manifest, &dest, Some(&variant)).unwrap(), 1);
+        assert!(dest.join("app-installer-x86-64-v3.sh").is_file());
+    }
+
+    #[test]
+    fn test_release_command() {
+        assert_eq!(
+            release_command("v1.2.0", &[PathBuf::from("/t/app it.tar.xz")]),
+            "gh release create 'v1.2.0' --title 'v1.2.0' --notes '' '/t/app it.tar.xz'"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! `meta cargo doc-coverage`: documentation coverage across repos
+//!
+//! Runs `rustdoc -Z unstable-options --show-coverage --output-format json`
+//! (nightly) on the library of every crate and sums the per-file counts into
+//! per-crate, per-repo and overall percentages. A crate below `--min` (or
+//! `[docs] min_coverage`) fails the run.
+
+use crate::config::Config;
+use crate::doc_index::library_crates;
+use crate::runner;
+use crate::{args, cargo_config, CommandResult, PlannedCommand};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Documented items out of all items
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DocCount {
+    pub total: u64,
+    pub with_docs: u64,
+}
+
+impl DocCount {
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            self.with_docs as f64 * 100.0 / self.total as f64
+        }
+    }
+
+    fn add(&mut self, other: DocCount) {
+        self.total += other.total;
+        self.with_docs += other.with_docs;
+    }
+}
+
+/// Sum the per-file counts of `--show-coverage --output-format json`
+pub fn parse_coverage(stdout: &str) -> Result<DocCount, String> {
+    let json = stdout
+        .lines()
+        .find(|l| l.trim_start().starts_with('{'))
+        .ok_or("no coverage JSON in rustdoc output")?;
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("invalid coverage JSON: {e}"))?;
+    let files = value.as_object().ok_or("coverage JSON is not an object")?;
+    let mut count = DocCount::default();
+    for file in files.values() {
+        count.add(DocCount {
+            total: file["total"].as_u64().unwrap_or(0),
+            with_docs: file["with_docs"].as_u64().unwrap_or(0),
+        });
+    }
+    Ok(count)
+}
+
+/// Handle `meta cargo doc-coverage [--min <pct>] [--toolchain <name>]`
+pub(crate) fn execute(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let toolchain =
+        args::take_value(&mut args, "--toolchain").unwrap_or_else(|| "nightly".to_string());
+    let min = match args::take_value(&mut args, "--min").map(|m| m.parse::<f64>()) {
+        None => config.docs.min_coverage,
+        Some(Ok(m)) => Some(m),
+        Some(Err(_)) => return CommandResult::Error("--min expects a percentage".to_string()),
+    };
+    let crates = match library_crates(repos, cwd) {
+        Ok(c) => c,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    let cargo =
+        cargo_config::cargo(cwd, config).replacen("cargo", &format!("cargo +{toolchain}"), 1);
+    let commands: Vec<PlannedCommand> = crates
+        .iter()
+        .map(|(repo, name)| PlannedCommand {
+            dir: repo.clone(),
+            cmd: format!(
+                "{cargo} rustdoc -p {name} --lib -- -Z unstable-options --show-coverage --output-format json"
+            ),
+            env: None,
+        })
+        .collect();
+    let outcomes = runner::run_all(cwd, &commands, parallel);
+
+    let mut out = String::from("Documentation coverage:\n");
+    let mut by_repo: BTreeMap<&str, DocCount> = BTreeMap::new();
+    let mut total = DocCount::default();
+    let mut failures = Vec::new();
+    for ((repo, name), outcome) in crates.iter().zip(&outcomes) {
+        let parsed = if outcome.success {
+            parse_coverage(&outcome.stdout)
+        } else {
+            Err(format!(
+                "rustdoc failed: {}",
+                outcome.stderr.trim().lines().last().unwrap_or("")
+            ))
+        };
+        let count = match parsed {
+            Ok(c) => c,
+            Err(e) => {
+                failures.push(format!("{name}: {e}"));
+                continue;
+            }
+        };
+        let below = min.is_some_and(|m| count.percent() < m);
+        let line = format!(
+            "  {name} ({repo}): {:.1}% ({}/{})",
+            count.percent(),
+            count.with_docs,
+            count.total
+        );
+        if below {
+            out.push_str(&format!("{} {}\n", line, "below minimum".red()));
+            failures.push(format!(
+                "{name}: {:.1}% is below the minimum of {:.1}%",
+                count.percent(),
+                min.unwrap_or_default()
+            ));
+        } else {
+            out.push_str(&format!("{line}\n"));
+        }
+        by_repo.entry(repo).or_default().add(count);
+        total.add(count);
+    }
+
+    if by_repo.len() > 1 {
+        out.push_str("By repo:\n");
+        for (repo, count) in &by_repo {
+            out.push_str(&format!("  {repo}: {:.1}%\n", count.percent()));
+        }
+    }
+    out.push_str(&format!(
+        "Total: {:.1}% ({}/{} items documented)\n",
+        total.percent(),
+        total.with_docs,
+        total.total
+    ));
+    if failures.is_empty() {
+        return CommandResult::Message(out);
+    }
+    for failure in &failures {
+        out.push_str(&format!("{} {failure}\n", "error:".red()));
+    }
+    CommandResult::Error(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_coverage_sums_files() {
+        let stdout = r#"{"src/lib.rs":{"total":10,"with_docs":7,"total_examples":10,"with_examples":1},"src/net.rs":{"total":6,"with_docs":1,"total_examples":6,"with_examples":0}}"#;
+        let count = parse_coverage(stdout).unwrap();
+        assert_eq!(
+            count,
+            DocCount {
+                total: 16,
+                with_docs: 8
+            }
+        );
+        assert_eq!(count.percent(), 50.0);
+        assert_eq!(DocCount::default().percent(), 100.0);
+        assert!(parse_coverage("warning: nothing").is_err());
+    }
+}
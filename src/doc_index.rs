@@ -0,0 +1,328 @@
+//! `meta cargo doc-index`: one searchable item index across all repos
+//!
+//! Builds rustdoc JSON (nightly-only, `-Z unstable-options --output-format
+//! json`) for the library of every crate, then merges the local items of each
+//! crate's `paths` table into `.meta-rust/doc-index.json`.
+//! `meta cargo doc-index search <query>` searches that file.
+
+use crate::config::Config;
+use crate::runner;
+use crate::{
+    args, cargo_config, metadata, project_path, target_dir, CommandResult, PlannedCommand,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Index file, relative to the meta root
+const INDEX_FILE: &str = ".meta-rust/doc-index.json";
+/// Target kinds rustdoc can document as a library
+const LIB_KINDS: &[&str] = &["lib", "rlib", "proc-macro"];
+/// Results shown by `search` unless `--limit` says otherwise
+const DEFAULT_LIMIT: usize = 20;
+
+/// A documented item
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexItem {
+    pub repo: String,
+    pub krate: String,
+    /// Full path, e.g. `core::net::Client`
+    pub path: String,
+    /// rustdoc item kind, e.g. `struct` or `function`
+    pub kind: String,
+    /// First paragraph line of the docs
+    pub summary: String,
+}
+
+/// The merged index
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DocIndex {
+    pub items: Vec<IndexItem>,
+}
+
+impl DocIndex {
+    fn path(cwd: &Path) -> PathBuf {
+        cwd.join(INDEX_FILE)
+    }
+
+    /// Load the index written by the last `doc-index` run
+    pub fn load(cwd: &Path) -> Result<Self> {
+        let path = Self::path(cwd);
+        let text = std::fs::read_to_string(&path).with_context(|| {
+            format!(
+                "failed to read {} (run `meta cargo doc-index` first)",
+                path.display()
+            )
+        })?;
+        serde_json::from_str(&text).with_context(|| format!("invalid {}", path.display()))
+    }
+
+    fn save(&self, cwd: &Path) -> Result<PathBuf> {
+        let path = Self::path(cwd);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Items matching `query`, best matches first
+    ///
+    /// Exact names beat name prefixes, which beat path and then docs matches.
+    pub fn search(&self, query: &str, kind: Option<&str>) -> Vec<&IndexItem> {
+        let query = query.to_lowercase();
+        let mut hits: Vec<(u8, &IndexItem)> = self
+            .items
+            .iter()
+            .filter(|item| kind.is_none_or(|k| item.kind == k))
+            .filter_map(|item| {
+                let path = item.path.to_lowercase();
+                let name = path.rsplit("::").next().unwrap_or(&path);
+                let rank = if name == query {
+                    0
+                } else if name.starts_with(&query) {
+                    1
+                } else if path.contains(&query) {
+                    2
+                } else if item.summary.to_lowercase().contains(&query) {
+                    3
+                } else {
+                    return None;
+                };
+                Some((rank, item))
+            })
+            .collect();
+        hits.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.path.cmp(&b.1.path)));
+        hits.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+/// First line of the first paragraph of `docs`
+fn summary(docs: &str) -> String {
+    docs.lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or("")
+        .to_string()
+}
+
+/// Local items of one crate's rustdoc JSON
+pub fn parse_rustdoc(json: &str, repo: &str, krate: &str) -> Result<Vec<IndexItem>> {
+    let value: serde_json::Value = serde_json::from_str(json).context("invalid rustdoc JSON")?;
+    let Some(paths) = value["paths"].as_object() else {
+        anyhow::bail!("rustdoc JSON has no paths table");
+    };
+    let mut items: Vec<IndexItem> = paths
+        .iter()
+        .filter(|(_, p)| p["crate_id"].as_u64() == Some(0))
+        .filter_map(|(id, p)| {
+            let path: Vec<&str> = p["path"]
+                .as_array()?
+                .iter()
+                .filter_map(|s| s.as_str())
+                .collect();
+            Some(IndexItem {
+                repo: repo.to_string(),
+                krate: krate.to_string(),
+                path: path.join("::"),
+                kind: p["kind"].as_str().unwrap_or("").to_string(),
+                summary: summary(value["index"][id.as_str()]["docs"].as_str().unwrap_or("")),
+            })
+        })
+        .collect();
+    items.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(items)
+}
+
+/// `(repo, package)` of every package with a library target
+///
+/// Binaries have no public API to document.
+pub(crate) fn library_crates(repos: &[String], cwd: &Path) -> Result<Vec<(String, String)>> {
+    let mut crates = Vec::new();
+    for repo in repos {
+        let packages = metadata::load_packages(&project_path(cwd, repo))
+            .with_context(|| format!("{repo}: failed to load cargo metadata"))?;
+        crates.extend(
+            packages
+                .into_iter()
+                .filter(|p| LIB_KINDS.iter().any(|k| p.has_target(k)))
+                .map(|p| (repo.clone(), p.name)),
+        );
+    }
+    Ok(crates)
+}
+
+/// Handle `meta cargo doc-index [--toolchain <name>]`
+fn build(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let toolchain =
+        args::take_value(&mut args, "--toolchain").unwrap_or_else(|| "nightly".to_string());
+    let crates = match library_crates(repos, cwd) {
+        Ok(c) => c,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    let cargo =
+        cargo_config::cargo(cwd, config).replacen("cargo", &format!("cargo +{toolchain}"), 1);
+    let root_config = cargo_config::root_config(cwd, config);
+    let commands: Vec<PlannedCommand> = crates
+        .iter()
+        .map(|(repo, name)| PlannedCommand {
+            dir: repo.clone(),
+            cmd: format!(
+                "{cargo} rustdoc -p {name} --lib -- -Z unstable-options --output-format json"
+            ),
+            env: None,
+        })
+        .collect();
+    let outcomes = runner::run_all(cwd, &commands, parallel);
+
+    let mut index = DocIndex::default();
+    let mut problems = Vec::new();
+    for ((repo, name), outcome) in crates.iter().zip(&outcomes) {
+        if !outcome.success {
+            let reason = outcome.stderr.trim().lines().last().unwrap_or("");
+            problems.push(format!("{name}: rustdoc failed: {reason}"));
+            continue;
+        }
+        let krate = name.replace('-', "_");
+        let json = target_dir::resolve(&project_path(cwd, repo), root_config.as_deref())
+            .map(|t| t.path.join("doc").join(format!("{krate}.json")));
+        let parsed = json.and_then(|file| {
+            let text = std::fs::read_to_string(&file)
+                .with_context(|| format!("failed to read {}", file.display()))?;
+            parse_rustdoc(&text, repo, &krate)
+        });
+        match parsed {
+            Ok(items) => index.items.extend(items),
+            Err(e) => problems.push(format!("{name}: {e:#}")),
+        }
+    }
+    let path = match index.save(cwd) {
+        Ok(p) => p,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    let mut out = format!(
+        "Indexed {} items from {} crates into {}\n",
+        index.items.len(),
+        crates.len() - problems.len(),
+        path.display()
+    );
+    for problem in &problems {
+        out.push_str(&format!("error: {problem}\n"));
+    }
+    if problems.is_empty() {
+        CommandResult::Message(out)
+    } else {
+        CommandResult::Error(out)
+    }
+}
+
+/// Handle `meta cargo doc-index search <query> [--kind <kind>] [--limit <n>]`
+fn search(args: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let kind = args::take_value(&mut args, "--kind");
+    let limit = match args::take_value(&mut args, "--limit").map(|l| l.parse::<usize>()) {
+        None => DEFAULT_LIMIT,
+        Some(Ok(n)) => n,
+        Some(Err(_)) => return CommandResult::Error("--limit expects a number".to_string()),
+    };
+    let Some(query) = args.first() else {
+        return CommandResult::Error("usage: meta cargo doc-index search <query>".to_string());
+    };
+    let index = match DocIndex::load(cwd) {
+        Ok(i) => i,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    let hits = index.search(query, kind.as_deref());
+    if hits.is_empty() {
+        return CommandResult::Message(format!("No items match '{query}'"));
+    }
+    let mut out = String::new();
+    for item in hits.iter().take(limit) {
+        out.push_str(&format!("{} {} ({})\n", item.kind, item.path, item.repo));
+        if !item.summary.is_empty() {
+            out.push_str(&format!("    {}\n", item.summary));
+        }
+    }
+    if hits.len() > limit {
+        out.push_str(&format!("... {} more (use --limit)\n", hits.len() - limit));
+    }
+    CommandResult::Message(out)
+}
+
+/// Handle `meta cargo doc-index [search ...]`
+pub(crate) fn execute(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+) -> CommandResult {
+    match args.split_first() {
+        Some((sub, rest)) if sub == "search" => search(rest, cwd),
+        _ => build(args, repos, cwd, parallel, config),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUSTDOC: &str = r#"{
+        "index": {
+            "1": {"name": "Client", "docs": "A connection.\n\nMore text."},
+            "2": {"name": "connect", "docs": null}
+        },
+        "paths": {
+            "1": {"crate_id": 0, "path": ["core", "net", "Client"], "kind": "struct"},
+            "2": {"crate_id": 0, "path": ["core", "net", "connect"], "kind": "function"},
+            "9": {"crate_id": 3, "path": ["std", "string", "String"], "kind": "struct"}
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_rustdoc_local_items() {
+        let items = parse_rustdoc(RUSTDOC, "libs/core", "core").unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].path, "core::net::Client");
+        assert_eq!(items[0].summary, "A connection.");
+        assert_eq!(items[1].kind, "function");
+    }
+
+    #[test]
+    fn test_search_ranking_and_persistence() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut index = DocIndex {
+            items: parse_rustdoc(RUSTDOC, "libs/core", "core").unwrap(),
+        };
+        index.items.push(IndexItem {
+            repo: "app".to_string(),
+            krate: "app".to_string(),
+            path: "app::ClientPool".to_string(),
+            kind: "struct".to_string(),
+            summary: String::new(),
+        });
+        index.save(temp_dir.path()).unwrap();
+        let index = DocIndex::load(temp_dir.path()).unwrap();
+
+        let paths: Vec<&str> = index
+            .search("client", None)
+            .iter()
+            .map(|i| i.path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["core::net::Client", "app::ClientPool"]);
+        assert_eq!(
+            index.search("connection", None)[0].path,
+            "core::net::Client"
+        );
+        assert_eq!(index.search("c", Some("function")).len(), 1);
+    }
+}
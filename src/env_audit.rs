@@ -0,0 +1,316 @@
+//! `meta cargo env-audit`: inventory of build-affecting environment variables
+//!
+//! Sources are scanned for `env!`/`option_env!`, build scripts additionally
+//! for `env::var`/`env::var_os`, and existing build-script output in the
+//! target directory for `rerun-if-env-changed` directives.
+
+use crate::args;
+use crate::cargo_config;
+use crate::config::Config;
+use crate::target_dir;
+use crate::{project_path, CommandResult};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// How a variable is read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Usage {
+    /// `env!("X")`, required at compile time
+    Env,
+    /// `option_env!("X")`
+    OptionEnv,
+    /// `std::env::var("X")` in a build script
+    BuildScript,
+    /// `cargo:rerun-if-env-changed=X` emitted by a build script
+    RerunIfChanged,
+}
+
+impl Usage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Usage::Env => "env!",
+            Usage::OptionEnv => "option_env!",
+            Usage::BuildScript => "build script env::var",
+            Usage::RerunIfChanged => "rerun-if-env-changed",
+        }
+    }
+}
+
+/// One place a variable is read
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EnvUse {
+    pub var: String,
+    pub usage: Usage,
+    pub repo: String,
+    /// File relative to the repo, with a line number for source matches
+    pub location: String,
+}
+
+/// String literal argument right after `open` at `text[at..]`, e.g. `("X"`
+fn literal_after(text: &str, at: usize) -> Option<&str> {
+    let rest = text[at..].trim_start().strip_prefix('"')?;
+    let end = rest.find('"')?;
+    let name = &rest[..end];
+    (!name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_'))
+        .then_some(name)
+}
+
+/// Variables read by one source line
+fn scan_line(line: &str, build_script: bool) -> Vec<(String, Usage)> {
+    let mut found = Vec::new();
+    let mut patterns = vec![("option_env!(", Usage::OptionEnv), ("env!(", Usage::Env)];
+    if build_script {
+        patterns.push(("env::var(", Usage::BuildScript));
+        patterns.push(("env::var_os(", Usage::BuildScript));
+    }
+    for (pattern, usage) in patterns {
+        for (at, _) in line.match_indices(pattern) {
+            if usage == Usage::Env && line[..at].ends_with("option_") {
+                continue;
+            }
+            if let Some(name) = literal_after(line, at + pattern.len()) {
+                found.push((name.to_string(), usage));
+            }
+        }
+    }
+    found
+}
+
+/// `.rs` files under `dir`, skipping hidden dirs, `target` and `skip`
+pub(crate) fn rust_files(dir: &Path, skip: &[PathBuf], out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    entries.sort();
+    for path in entries {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" && !skip.contains(&path) {
+                rust_files(&path, skip, out);
+            }
+        } else if name.ends_with(".rs") {
+            out.push(path);
+        }
+    }
+}
+
+/// `rerun-if-env-changed` variables from build-script output under `target`
+fn rerun_vars(target: &Path) -> Vec<(String, PathBuf)> {
+    let mut found = Vec::new();
+    let Ok(profiles) = std::fs::read_dir(target) else {
+        return found;
+    };
+    for profile in profiles.filter_map(|e| e.ok()) {
+        let Ok(builds) = std::fs::read_dir(profile.path().join("build")) else {
+            continue;
+        };
+        for build in builds.filter_map(|e| e.ok()) {
+            let output = build.path().join("output");
+            let Ok(text) = std::fs::read_to_string(&output) else {
+                continue;
+            };
+            for line in text.lines() {
+                let var = line
+                    .strip_prefix("cargo::rerun-if-env-changed=")
+                    .or_else(|| line.strip_prefix("cargo:rerun-if-env-changed="));
+                if let Some(var) = var {
+                    found.push((var.trim().to_string(), output.clone()));
+                }
+            }
+        }
+    }
+    found
+}
+
+/// Collect every variable use in `repo`
+fn audit_repo(repo: &str, repos: &[String], cwd: &Path, config: &Config) -> Vec<EnvUse> {
+    let dir = project_path(cwd, repo);
+    let nested: Vec<PathBuf> = repos
+        .iter()
+        .filter(|r| r.as_str() != repo && r.as_str() != ".")
+        .map(|r| project_path(cwd, r))
+        .collect();
+    let mut files = Vec::new();
+    rust_files(&dir, &nested, &mut files);
+
+    let relative = |p: &Path| {
+        p.strip_prefix(&dir)
+            .unwrap_or(p)
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    let mut uses = Vec::new();
+    for file in &files {
+        let Ok(text) = std::fs::read_to_string(file) else {
+            continue;
+        };
+        let build_script = file.file_name().is_some_and(|n| n == "build.rs");
+        for (i, line) in text.lines().enumerate() {
+            for (var, usage) in scan_line(line, build_script) {
+                uses.push(EnvUse {
+                    var,
+                    usage,
+                    repo: repo.to_string(),
+                    location: format!("{}:{}", relative(file), i + 1),
+                });
+            }
+        }
+    }
+
+    let root_config = cargo_config::root_config(cwd, config);
+    if let Ok(target) = target_dir::resolve(&dir, root_config.as_deref()) {
+        for (var, output) in rerun_vars(&target.path) {
+            uses.push(EnvUse {
+                var,
+                usage: Usage::RerunIfChanged,
+                repo: repo.to_string(),
+                location: relative(&output),
+            });
+        }
+    }
+    uses.sort();
+    uses.dedup();
+    uses
+}
+
+/// Uses grouped by variable name
+fn by_var(uses: &[EnvUse]) -> BTreeMap<&str, Vec<&EnvUse>> {
+    let mut grouped: BTreeMap<&str, Vec<&EnvUse>> = BTreeMap::new();
+    for u in uses {
+        grouped.entry(u.var.as_str()).or_default().push(u);
+    }
+    grouped
+}
+
+fn render_text(uses: &[EnvUse]) -> String {
+    if uses.is_empty() {
+        return "No build-affecting environment variables found".to_string();
+    }
+    let grouped = by_var(uses);
+    let mut out = format!("{} environment variables:\n", grouped.len());
+    for (var, uses) in grouped {
+        out.push_str(&format!("  {var}\n"));
+        for u in uses {
+            out.push_str(&format!(
+                "    {}: {} ({})\n",
+                u.repo,
+                u.location,
+                u.usage.as_str()
+            ));
+        }
+    }
+    out
+}
+
+fn render_json(uses: &[EnvUse]) -> serde_json::Value {
+    let vars: serde_json::Map<String, serde_json::Value> = by_var(uses)
+        .into_iter()
+        .map(|(var, uses)| {
+            let entries: Vec<serde_json::Value> = uses
+                .iter()
+                .map(|u| {
+                    json!({
+                        "repo": u.repo,
+                        "location": u.location,
+                        "usage": u.usage.as_str(),
+                    })
+                })
+                .collect();
+            (var.to_string(), json!(entries))
+        })
+        .collect();
+    json!({ "variables": vars })
+}
+
+/// Handle `meta cargo env-audit [--format text|json]`
+pub(crate) fn execute(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    config: &Config,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let format = args::take_value(&mut args, "--format").unwrap_or_else(|| "text".to_string());
+    let uses: Vec<EnvUse> = repos
+        .iter()
+        .flat_map(|repo| audit_repo(repo, repos, cwd, config))
+        .collect();
+    match format.as_str() {
+        "text" => CommandResult::Message(render_text(&uses)),
+        "json" => match serde_json::to_string_pretty(&render_json(&uses)) {
+            Ok(text) => CommandResult::Message(text),
+            Err(e) => CommandResult::Error(format!("Failed to serialize audit: {e}")),
+        },
+        other => CommandResult::Error(format!(
+            "unsupported format '{other}' (expected text or json)"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scan_line() {
+        assert_eq!(
+            scan_line(
+                r#"const V: &str = env!("CARGO_PKG_VERSION"); let s = option_env!("GIT_SHA");"#,
+                false
+            ),
+            vec![
+                ("GIT_SHA".to_string(), Usage::OptionEnv),
+                ("CARGO_PKG_VERSION".to_string(), Usage::Env),
+            ]
+        );
+        assert!(scan_line(r#"std::env::var("TARGET")"#, false).is_empty());
+        assert_eq!(
+            scan_line(r#"let t = std::env::var( "TARGET").unwrap();"#, true),
+            vec![("TARGET".to_string(), Usage::BuildScript)]
+        );
+        assert!(scan_line(r#"env!(concat!("A", "B"))"#, false).is_empty());
+    }
+
+    #[test]
+    fn test_audit_repo_sources_and_build_output() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(
+            root.join("src/lib.rs"),
+            "\npub const V: &str = env!(\"APP_VERSION\");\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("build.rs"),
+            "fn main() { std::env::var_os(\"PROTOC\"); }\n",
+        )
+        .unwrap();
+        let build = root.join("target/debug/build/app-0123/");
+        std::fs::create_dir_all(&build).unwrap();
+        std::fs::write(build.join("output"), "cargo:rerun-if-env-changed=PROTOC\n").unwrap();
+        // Vendored sources under target/ are not scanned
+        std::fs::write(root.join("target/debug/ignored.rs"), "env!(\"NOPE\")").unwrap();
+
+        let uses = audit_repo(".", &[".".to_string()], root, &Config::default());
+        let summary: Vec<(&str, Usage, &str)> = uses
+            .iter()
+            .map(|u| (u.var.as_str(), u.usage, u.location.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("APP_VERSION", Usage::Env, "src/lib.rs:2"),
+                ("PROTOC", Usage::BuildScript, "build.rs:1"),
+                (
+                    "PROTOC",
+                    Usage::RerunIfChanged,
+                    "target/debug/build/app-0123/output"
+                ),
+            ]
+        );
+    }
+}
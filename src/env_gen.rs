@@ -0,0 +1,331 @@
+//! `meta cargo env-gen`: development environment descriptors
+//!
+//! Collects the union of what the repos need — rust toolchain channels,
+//! components and targets from their `rust-toolchain(.toml)` files, plus the
+//! `[sysdeps]` tools and libraries — and renders it as a Nix flake or a
+//! devcontainer.json, so onboarding environments follow the meta config.
+
+use crate::config::SysdepsConfig;
+use crate::toolchain::{read_toolchain, Toolchain};
+use crate::{args, project_path, CommandResult};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Everything the generated environment must provide
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    /// Every channel some repo pins
+    pub channels: BTreeSet<String>,
+    pub components: BTreeSet<String>,
+    pub targets: BTreeSet<String>,
+    pub tools: BTreeSet<String>,
+    /// pkg-config module names, without version constraints
+    pub libraries: BTreeSet<String>,
+}
+
+impl Environment {
+    /// Union of the toolchains and `[sysdeps]` of all repos
+    pub fn collect(toolchains: &[Toolchain], sysdeps: &SysdepsConfig) -> Self {
+        let mut env = Environment::default();
+        for toolchain in toolchains {
+            env.channels.extend(toolchain.channel.clone());
+            env.components.extend(toolchain.components.iter().cloned());
+            env.targets.extend(toolchain.targets.iter().cloned());
+        }
+        let repos = sysdeps.repos.values();
+        env.tools.extend(
+            sysdeps
+                .tools
+                .iter()
+                .chain(repos.clone().flat_map(|r| &r.tools))
+                .cloned(),
+        );
+        env.libraries.extend(
+            sysdeps
+                .pkg_config
+                .iter()
+                .chain(repos.flat_map(|r| &r.pkg_config))
+                .filter_map(|spec| spec.split_whitespace().next())
+                .map(str::to_string),
+        );
+        env
+    }
+
+    /// The single channel the environment installs: the newest one pinned
+    ///
+    /// Nightly outranks beta, which outranks stable; numbered releases rank
+    /// below a plain `stable`.
+    pub fn channel(&self) -> String {
+        let rank = |c: &String| {
+            let class = if c.starts_with("nightly") {
+                3
+            } else if c.starts_with("beta") {
+                2
+            } else if c == "stable" {
+                1
+            } else {
+                0
+            };
+            let numbers: Vec<u64> = c
+                .split(|ch: char| !ch.is_ascii_digit())
+                .filter_map(|n| n.parse().ok())
+                .collect();
+            (class, numbers)
+        };
+        self.channels
+            .iter()
+            .max_by_key(|c| rank(c))
+            .cloned()
+            .unwrap_or_else(|| "stable".to_string())
+    }
+}
+
+/// nixpkgs attribute for a tool or pkg-config module
+fn nix_package(name: &str) -> &str {
+    match name {
+        "protoc" => "protobuf",
+        "libpq" => "postgresql",
+        "sqlite3" => "sqlite",
+        "libssl" | "libcrypto" => "openssl",
+        "libzstd" => "zstd",
+        other => other,
+    }
+}
+
+/// Debian package for a tool, or for a pkg-config module's headers
+fn apt_package(name: &str, library: bool) -> String {
+    match name {
+        "protoc" => "protobuf-compiler".to_string(),
+        "openssl" | "libssl" | "libcrypto" => "libssl-dev".to_string(),
+        "zlib" => "zlib1g-dev".to_string(),
+        "sqlite3" => "libsqlite3-dev".to_string(),
+        other if library && other.starts_with("lib") => format!("{other}-dev"),
+        other if library => format!("lib{other}-dev"),
+        other => other.to_string(),
+    }
+}
+
+fn nix_list(items: &BTreeSet<String>) -> String {
+    items
+        .iter()
+        .map(|i| format!("\"{i}\""))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// rust-overlay toolchain expression for `channel`
+fn nix_toolchain(channel: &str) -> String {
+    match channel.split_once('-') {
+        _ if channel == "stable" || channel == "beta" || channel == "nightly" => {
+            format!("pkgs.rust-bin.{channel}.latest.default")
+        }
+        Some((kind @ ("stable" | "beta" | "nightly"), date)) => {
+            format!("pkgs.rust-bin.{kind}.\"{date}\".default")
+        }
+        _ => format!("pkgs.rust-bin.stable.\"{channel}\".default"),
+    }
+}
+
+/// Render a `flake.nix` using rust-overlay
+pub fn render_nix(env: &Environment) -> String {
+    let mut native = vec!["rust".to_string()];
+    if !env.libraries.is_empty() {
+        native.push("pkgs.pkg-config".to_string());
+    }
+    native.extend(env.tools.iter().map(|t| format!("pkgs.{}", nix_package(t))));
+    let build: Vec<String> = env
+        .libraries
+        .iter()
+        .map(|l| format!("pkgs.{}", nix_package(l)))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let mut out = String::from("# Generated by `meta cargo env-gen --format nix`\n");
+    if env.channels.len() > 1 {
+        out.push_str(&format!(
+            "# Repos pin several toolchains ({}); the newest is used here.\n",
+            env.channels.iter().cloned().collect::<Vec<_>>().join(", ")
+        ));
+    }
+    out.push_str(&format!(
+        r#"{{
+  description = "Development environment for the meta workspace";
+
+  inputs = {{
+    nixpkgs.url = "github:NixOS/nixpkgs/nixos-unstable";
+    rust-overlay.url = "github:oxalica/rust-overlay";
+    flake-utils.url = "github:numtide/flake-utils";
+  }};
+
+  outputs = {{ nixpkgs, rust-overlay, flake-utils, ... }}:
+    flake-utils.lib.eachDefaultSystem (system:
+      let
+        pkgs = import nixpkgs {{
+          inherit system;
+          overlays = [ (import rust-overlay) ];
+        }};
+        rust = {toolchain}.override {{
+          extensions = [ {extensions} ];
+          targets = [ {targets} ];
+        }};
+      in
+      {{
+        devShells.default = pkgs.mkShell {{
+          nativeBuildInputs = [ {native} ];
+          buildInputs = [ {build} ];
+        }};
+      }});
+}}
+"#,
+        toolchain = nix_toolchain(&env.channel()),
+        extensions = nix_list(&env.components),
+        targets = nix_list(&env.targets),
+        native = native.join(" "),
+        build = build.join(" "),
+    ));
+    out
+}
+
+/// Render a `devcontainer.json` using the official rust feature
+pub fn render_devcontainer(env: &Environment) -> String {
+    let join = |items: &BTreeSet<String>| items.iter().cloned().collect::<Vec<_>>().join(",");
+    let mut rust = serde_json::Map::new();
+    rust.insert("version".to_string(), serde_json::json!(env.channel()));
+    rust.insert("profile".to_string(), serde_json::json!("minimal"));
+    if !env.components.is_empty() {
+        rust.insert(
+            "components".to_string(),
+            serde_json::json!(join(&env.components)),
+        );
+    }
+    if !env.targets.is_empty() {
+        rust.insert("targets".to_string(), serde_json::json!(join(&env.targets)));
+    }
+
+    let mut apt: BTreeSet<String> = env.tools.iter().map(|t| apt_package(t, false)).collect();
+    apt.extend(env.libraries.iter().map(|l| apt_package(l, true)));
+    if !env.libraries.is_empty() {
+        apt.insert("pkg-config".to_string());
+    }
+
+    let mut container = serde_json::Map::new();
+    container.insert("name".to_string(), serde_json::json!("meta workspace"));
+    container.insert(
+        "image".to_string(),
+        serde_json::json!("mcr.microsoft.com/devcontainers/base:bookworm"),
+    );
+    container.insert(
+        "features".to_string(),
+        serde_json::json!({ "ghcr.io/devcontainers/features/rust:1": rust }),
+    );
+    if !apt.is_empty() {
+        let packages = apt.into_iter().collect::<Vec<_>>().join(" ");
+        container.insert(
+            "postCreateCommand".to_string(),
+            serde_json::json!(format!(
+                "sudo apt-get update && sudo apt-get install -y {packages}"
+            )),
+        );
+    }
+    let mut out =
+        serde_json::to_string_pretty(&serde_json::Value::Object(container)).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+/// Handle `meta cargo env-gen --format nix|devcontainer [--write <path>]`
+pub(crate) fn execute(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    sysdeps: &SysdepsConfig,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let Some(format) = args::take_value(&mut args, "--format") else {
+        return CommandResult::Error("--format nix|devcontainer is required".to_string());
+    };
+    let write = args::take_value(&mut args, "--write");
+
+    let toolchains: Vec<Toolchain> = repos
+        .iter()
+        .filter_map(|repo| read_toolchain(&project_path(cwd, repo)))
+        .chain(read_toolchain(cwd))
+        .collect();
+    let env = Environment::collect(&toolchains, sysdeps);
+    let text = match format.as_str() {
+        "nix" => render_nix(&env),
+        "devcontainer" => render_devcontainer(&env),
+        other => {
+            return CommandResult::Error(format!(
+                "unsupported format '{other}' (expected nix or devcontainer)"
+            ))
+        }
+    };
+    match write {
+        None => CommandResult::Message(text),
+        Some(path) => {
+            let path = cwd.join(path);
+            if let Some(parent) = path.parent() {
+                if let Err(e) = std::fs::create_dir_all(parent) {
+                    return CommandResult::Error(format!(
+                        "Failed to create {}: {e}",
+                        parent.display()
+                    ));
+                }
+            }
+            match std::fs::write(&path, text) {
+                Ok(()) => CommandResult::Message(format!("Wrote {}", path.display())),
+                Err(e) => CommandResult::Error(format!("Failed to write {}: {e}", path.display())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_newest_channel_wins() {
+        let env = Environment {
+            channels: ["1.70.0", "1.76.0", "1.9.0"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            ..Environment::default()
+        };
+        assert_eq!(env.channel(), "1.76.0");
+        assert_eq!(Environment::default().channel(), "stable");
+    }
+
+    #[test]
+    fn test_render_includes_sysdeps() {
+        let config = Config::parse(
+            "[sysdeps]\ntools = [\"protoc\"]\n[sysdeps.repos.api]\npkg_config = [\"openssl >= 3\"]\n",
+        )
+        .unwrap();
+        let toolchain = Toolchain {
+            channel: Some("1.76.0".to_string()),
+            components: vec!["clippy".to_string()],
+            targets: Vec::new(),
+        };
+        let env = Environment::collect(&[toolchain], &config.sysdeps);
+
+        let nix = render_nix(&env);
+        assert!(nix.contains("pkgs.rust-bin.stable.\"1.76.0\".default"));
+        assert!(nix.contains("nativeBuildInputs = [ rust pkgs.pkg-config pkgs.protobuf ];"));
+        assert!(nix.contains("buildInputs = [ pkgs.openssl ];"));
+
+        let devcontainer: serde_json::Value =
+            serde_json::from_str(&render_devcontainer(&env)).unwrap();
+        let rust = &devcontainer["features"]["ghcr.io/devcontainers/features/rust:1"];
+        assert_eq!(rust["version"], "1.76.0");
+        assert_eq!(rust["components"], "clippy");
+        assert_eq!(
+            devcontainer["postCreateCommand"],
+            "sudo apt-get update && sudo apt-get install -y libssl-dev pkg-config protobuf-compiler"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Named environment profiles (`--env-profile <name>[,<name>...]`)
+//!
+//! A profile in `.meta-rust.toml` bundles environment variables and cargo
+//! flags:
+//!
+//! ```toml
+//! [env.staging]
+//! vars = { API_URL = "https://staging.example.com", RUST_LOG = "info" }
+//! args = ["--features", "staging"]
+//! ```
+//!
+//! The flags go before any `--` of the command's arguments and the variables
+//! into the environment of every planned command. Several profiles apply in
+//! order, later ones winning for the same variable.
+
+use crate::config::EnvProfile;
+use crate::PlannedCommand;
+use std::collections::BTreeMap;
+
+/// The profiles named in `names` (comma-separated), merged in order
+pub(crate) fn select(
+    names: &str,
+    profiles: &BTreeMap<String, EnvProfile>,
+) -> Result<EnvProfile, String> {
+    let mut merged = EnvProfile::default();
+    for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let Some(profile) = profiles.get(name) else {
+            let known: Vec<&str> = profiles.keys().map(String::as_str).collect();
+            return Err(if known.is_empty() {
+                format!("unknown env profile '{name}': no [env.<name>] profiles are configured")
+            } else {
+                format!(
+                    "unknown env profile '{name}' (configured: {})",
+                    known.join(", ")
+                )
+            });
+        };
+        merged.vars.extend(profile.vars.clone());
+        merged.args.extend(profile.args.iter().cloned());
+    }
+    Ok(merged)
+}
+
+/// Add the profile's cargo flags to `args`, ahead of any `--`
+pub(crate) fn insert_args(args: &mut Vec<String>, profile: &EnvProfile) {
+    let at = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    args.splice(at..at, profile.args.iter().cloned());
+}
+
+/// Set the profile's variables for every command
+pub(crate) fn apply_vars(commands: &mut [PlannedCommand], profile: &EnvProfile) {
+    if profile.vars.is_empty() {
+        return;
+    }
+    for planned in commands {
+        let env = planned.env.get_or_insert_with(Default::default);
+        for (key, value) in &profile.vars {
+            env.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_profiles_merge_in_order() {
+        let config = Config::parse(
+            "[env.staging]\nvars = { API_URL = \"https://staging\", RUST_LOG = \"info\" }\nargs = [\"--features\", \"staging\"]\n\n[env.debug]\nvars = { RUST_LOG = \"debug\" }\n",
+        )
+        .unwrap();
+        let profile = select("staging,debug", &config.env).unwrap();
+        assert_eq!(profile.vars["RUST_LOG"], "debug");
+        assert_eq!(profile.vars["API_URL"], "https://staging");
+
+        let mut args: Vec<String> = ["--release", "--", "--nocapture"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        insert_args(&mut args, &profile);
+        assert_eq!(
+            args,
+            vec!["--release", "--features", "staging", "--", "--nocapture"]
+        );
+
+        let mut commands = vec![PlannedCommand {
+            dir: "core".to_string(),
+            cmd: "cargo test".to_string(),
+            env: None,
+        }];
+        apply_vars(&mut commands, &profile);
+        assert_eq!(
+            commands[0].env.as_ref().unwrap()["API_URL"],
+            "https://staging"
+        );
+
+        let err = select("prod", &config.env).unwrap_err();
+        assert!(err.contains("configured: debug, staging"), "{err}");
+    }
+}
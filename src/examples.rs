@@ -0,0 +1,254 @@
+//! `meta cargo examples`: build (and smoke-run) every example
+//!
+//! Examples are discovered from the `example` targets `cargo metadata`
+//! reports in each repo and built one by one with their `required-features`
+//! enabled. With `--smoke`, each built example is also run with its output
+//! going to `.meta-rust/examples/<package>-<example>.log`: an example fails
+//! if it exits unsuccessfully, and one still running after `--timeout`
+//! seconds (e.g. a server) is stopped and counts as passing.
+
+use crate::config::Config;
+use crate::integration::profile_dir;
+use crate::runner;
+use crate::{cargo_config, metadata, project_path, target_dir, CommandResult, PlannedCommand};
+use anyhow::Context;
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+/// Seconds a smoke run may take unless `--timeout` says otherwise
+const DEFAULT_TIMEOUT: u64 = 10;
+/// Pause between checks on a running example
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// An example target
+#[derive(Debug, Clone, PartialEq)]
+pub struct Example {
+    pub repo: String,
+    pub package: String,
+    pub name: String,
+    pub required_features: Vec<String>,
+}
+
+impl Example {
+    fn build_command(&self, cargo: &str, extra: &str) -> String {
+        let mut cmd = format!("{cargo} build -p {} --example {}", self.package, self.name);
+        if !self.required_features.is_empty() {
+            cmd.push_str(&format!(" --features {}", self.required_features.join(",")));
+        }
+        cmd + extra
+    }
+}
+
+/// Example targets of the local packages in `repos`
+pub fn discover(repos: &[String], cwd: &Path) -> anyhow::Result<Vec<Example>> {
+    let mut examples = Vec::new();
+    for repo in repos {
+        let packages = metadata::load_packages(&project_path(cwd, repo))
+            .with_context(|| format!("{repo}: failed to load cargo metadata"))?;
+        for package in packages {
+            for target in package
+                .targets
+                .iter()
+                .filter(|t| t.kinds.iter().any(|k| k == "example"))
+            {
+                examples.push(Example {
+                    repo: repo.clone(),
+                    package: package.name.clone(),
+                    name: target.name.clone(),
+                    required_features: target.required_features.clone(),
+                });
+            }
+        }
+    }
+    Ok(examples)
+}
+
+/// Result of a smoke run
+#[derive(Debug, Clone, PartialEq)]
+pub enum Smoke {
+    /// Exited successfully
+    Exited,
+    /// Still running when the timeout hit, then stopped
+    TimedOut,
+    /// Exited unsuccessfully or could not start
+    Failed(String),
+}
+
+/// Run `cmd` in `dir` for at most `timeout`, writing its output to `log`
+pub fn smoke(cmd: &str, dir: &Path, log: &Path, timeout: Duration) -> Smoke {
+    let started = (|| {
+        if let Some(parent) = log.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let out = std::fs::File::create(log)?;
+        let err = out.try_clone()?;
+        runner::shell(cmd)
+            .current_dir(dir)
+            .stdin(Stdio::null())
+            .stdout(out)
+            .stderr(err)
+            .spawn()
+    })();
+    let mut child = match started {
+        Ok(c) => c,
+        Err(e) => return Smoke::Failed(format!("failed to start: {e}")),
+    };
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Smoke::Exited,
+            Ok(Some(status)) => return Smoke::Failed(status.to_string()),
+            Ok(None) if start.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Smoke::TimedOut;
+            }
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => return Smoke::Failed(e.to_string()),
+        }
+    }
+}
+
+/// Where the smoke output of `example` is written
+fn log_path(cwd: &Path, example: &Example) -> PathBuf {
+    cwd.join(".meta-rust")
+        .join("examples")
+        .join(format!("{}-{}.log", example.package, example.name))
+}
+
+/// Handle `meta cargo examples [--smoke] [--timeout <secs>] [cargo build args]`
+pub(crate) fn execute(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let smoke_run = crate::args::take_flag(&mut args, "--smoke");
+    let timeout = match crate::args::take_value(&mut args, "--timeout").map(|t| t.parse::<u64>()) {
+        None => DEFAULT_TIMEOUT,
+        Some(Ok(t)) => t,
+        Some(Err(_)) => return CommandResult::Error("--timeout expects seconds".to_string()),
+    };
+    let examples = match discover(repos, cwd) {
+        Ok(e) => e,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    if examples.is_empty() {
+        return CommandResult::Message(format!("No examples in {} repos", repos.len()));
+    }
+
+    let cargo = cargo_config::cargo(cwd, config);
+    let extra = args.iter().map(|a| format!(" {a}")).collect::<String>();
+    let commands: Vec<PlannedCommand> = examples
+        .iter()
+        .map(|e| PlannedCommand {
+            dir: e.repo.clone(),
+            cmd: e.build_command(&cargo, &extra),
+            env: None,
+        })
+        .collect();
+    let outcomes = runner::run_all(cwd, &commands, parallel);
+
+    let root_config = cargo_config::root_config(cwd, config);
+    let profile = profile_dir(&args);
+    let mut out = String::new();
+    let mut failed = 0;
+    for (example, outcome) in examples.iter().zip(&outcomes) {
+        let label = format!("{} {} ({})", example.package, example.name, example.repo);
+        if !outcome.success {
+            failed += 1;
+            out.push_str(&format!("{} {label}: build failed\n", "FAIL".red()));
+            let reason = outcome.stderr.trim().lines().last().unwrap_or("");
+            out.push_str(&format!("    {reason}\n"));
+            continue;
+        }
+        if !smoke_run {
+            out.push_str(&format!("{} {label}\n", "ok".green()));
+            continue;
+        }
+        let dir = project_path(cwd, &example.repo);
+        let binary = match target_dir::resolve(&dir, root_config.as_deref()) {
+            Ok(t) => t.path.join(&profile).join("examples").join(format!(
+                "{}{}",
+                example.name,
+                std::env::consts::EXE_SUFFIX
+            )),
+            Err(e) => {
+                failed += 1;
+                out.push_str(&format!("{} {label}: {e:#}\n", "FAIL".red()));
+                continue;
+            }
+        };
+        let log = log_path(cwd, example);
+        let cmd = format!("\"{}\"", binary.display());
+        match smoke(&cmd, &dir, &log, Duration::from_secs(timeout)) {
+            Smoke::Exited => out.push_str(&format!("{} {label}\n", "ok".green())),
+            Smoke::TimedOut => out.push_str(&format!(
+                "{} {label} (still running after {timeout}s)\n",
+                "ok".green()
+            )),
+            Smoke::Failed(reason) => {
+                failed += 1;
+                out.push_str(&format!(
+                    "{} {label}: {reason}, see {}\n",
+                    "FAIL".red(),
+                    log.display()
+                ));
+            }
+        }
+    }
+    let verb = if smoke_run { "ran" } else { "built" };
+    out.push_str(&format!(
+        "{} of {} examples {verb} successfully\n",
+        examples.len() - failed,
+        examples.len()
+    ));
+    if failed == 0 {
+        CommandResult::Message(out)
+    } else {
+        CommandResult::Error(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_build_command_enables_required_features() {
+        let example = Example {
+            repo: "core".to_string(),
+            package: "core".to_string(),
+            name: "serve".to_string(),
+            required_features: vec!["net".to_string(), "tls".to_string()],
+        };
+        assert_eq!(
+            example.build_command("cargo", " --release"),
+            "cargo build -p core --example serve --features net,tls --release"
+        );
+    }
+
+    #[test]
+    fn test_smoke_reports_exit_status() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = temp_dir.path().join("logs").join("ok.log");
+        let timeout = Duration::from_secs(30);
+        assert_eq!(
+            smoke("cargo --version", temp_dir.path(), &log, timeout),
+            Smoke::Exited
+        );
+        assert!(std::fs::read_to_string(&log).unwrap().contains("cargo"));
+        let failed = smoke(
+            "cargo locate-project --manifest-path missing/Cargo.toml",
+            temp_dir.path(),
+            &log,
+            timeout,
+        );
+        assert!(matches!(failed, Smoke::Failed(_)));
+    }
+}
@@ -0,0 +1,91 @@
+//! `meta cargo expand`: cargo-expand for one crate, wherever it lives
+//!
+//! `meta cargo expand -p foo module::path` finds the repo hosting crate `foo`
+//! in the meta workspace and runs `cargo expand -p foo module::path` there,
+//! so shared proc macros can be debugged without knowing which repo defines
+//! the crate that uses them. Crate names match with `-` and `_` treated alike.
+
+use crate::graph::CrateGraph;
+use crate::{args, CommandResult, PlannedCommand};
+use std::path::Path;
+
+const USAGE: &str = "usage: meta cargo expand -p <crate> [item path] [cargo expand args]";
+
+fn same_crate(a: &str, b: &str) -> bool {
+    a.replace('-', "_") == b.replace('-', "_")
+}
+
+/// The command expanding `package` in the repo that hosts it
+fn plan(
+    cargo: &str,
+    package: &str,
+    args: &[String],
+    graph: &CrateGraph,
+    failed: &[(String, String)],
+) -> Result<PlannedCommand, String> {
+    let Some(node) = graph.crates.iter().find(|c| same_crate(&c.name, package)) else {
+        let mut message = format!("no repo in the meta workspace hosts crate '{package}'");
+        for (repo, error) in failed {
+            message.push_str(&format!("\n  {repo} was not searched: {error}"));
+        }
+        return Err(message);
+    };
+    let mut cmd = format!("{cargo} expand -p {}", node.name);
+    for arg in args {
+        cmd.push(' ');
+        cmd.push_str(arg);
+    }
+    Ok(PlannedCommand {
+        dir: node.repo.clone(),
+        cmd,
+        env: None,
+    })
+}
+
+/// Handle `meta cargo expand -p <crate> [item path] [cargo expand args]`
+pub(crate) fn execute(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+) -> Result<Vec<PlannedCommand>, CommandResult> {
+    let mut args = args.to_vec();
+    let Some(package) =
+        args::take_value(&mut args, "-p").or_else(|| args::take_value(&mut args, "--package"))
+    else {
+        return Err(CommandResult::Error(USAGE.to_string()));
+    };
+    let (graph, failed) = CrateGraph::load_partial(repos, cwd);
+    plan(cargo, &package, &args, &graph, &failed)
+        .map(|command| vec![command])
+        .map_err(CommandResult::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Package;
+
+    #[test]
+    fn test_expand_runs_in_hosting_repo() {
+        let graph = CrateGraph::from_packages(vec![
+            (
+                "macros".to_string(),
+                vec![Package::fixture("shared-derive").with_root("/ws/macros")],
+            ),
+            (
+                "app".to_string(),
+                vec![Package::fixture("app-models").with_root("/ws/app/models")],
+            ),
+        ]);
+        let args = vec!["models::User".to_string(), "--lib".to_string()];
+        let command = plan("cargo", "app_models", &args, &graph, &[]).unwrap();
+        assert_eq!(command.dir, "app");
+        assert_eq!(command.cmd, "cargo expand -p app-models models::User --lib");
+
+        let failed = vec![("cli".to_string(), "cargo metadata failed".to_string())];
+        let err = plan("cargo", "missing", &[], &graph, &failed).unwrap_err();
+        assert!(err.contains("hosts crate 'missing'"), "{err}");
+        assert!(err.contains("cli was not searched"), "{err}");
+    }
+}
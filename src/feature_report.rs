@@ -0,0 +1,158 @@
+//! `meta cargo feature-report <dep>`: enabled features of a dependency per repo
+//!
+//! Cargo unifies the features of a dependency over everything that is built
+//! together, so one crate asking for `tokio/full` compiles the full runtime
+//! for the whole repo. The report reads the resolved graph of `cargo
+//! metadata` in every repo and shows which features of `<dep>` end up
+//! enabled, flagging heavy ones (`full` and those in `[features] heavy`).
+
+use crate::config::FeaturesConfig;
+use crate::metadata::{self, ResolvedFeatures};
+use crate::{project_path, CommandResult};
+use colored::Colorize;
+use std::path::Path;
+
+/// Feature flagged for every dependency
+const ALWAYS_HEAVY: &str = "full";
+
+/// Whether `feature` of `dep` is a heavy feature
+fn is_heavy(config: &FeaturesConfig, dep: &str, feature: &str) -> bool {
+    feature == ALWAYS_HEAVY
+        || config
+            .heavy
+            .iter()
+            .any(|h| *h == format!("{dep}/{feature}"))
+}
+
+/// Versions of `dep` in a resolved graph, with their non-`default` features
+pub fn enabled_features(resolved: &[ResolvedFeatures], dep: &str) -> Vec<ResolvedFeatures> {
+    let mut found: Vec<ResolvedFeatures> = resolved
+        .iter()
+        .filter(|r| r.name == dep)
+        .map(|r| {
+            let mut features: Vec<String> = r
+                .features
+                .iter()
+                .filter(|f| *f != "default")
+                .cloned()
+                .collect();
+            features.sort();
+            ResolvedFeatures {
+                features,
+                ..r.clone()
+            }
+        })
+        .collect();
+    found.sort_by(|a, b| a.version.cmp(&b.version));
+    found
+}
+
+/// Handle `meta cargo feature-report <dep>`
+pub(crate) fn execute(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    config: &FeaturesConfig,
+) -> CommandResult {
+    let Some(dep) = args.first() else {
+        return CommandResult::Error("usage: meta cargo feature-report <dep>".to_string());
+    };
+    let mut out = format!("Enabled features of {dep}:\n");
+    let mut heavy_repos = Vec::new();
+    let mut failures = Vec::new();
+    let mut users = 0;
+    for repo in repos {
+        let resolved = match metadata::load_resolved_features(&project_path(cwd, repo)) {
+            Ok(r) => r,
+            Err(e) => {
+                failures.push(format!("{repo}: {e:#}"));
+                continue;
+            }
+        };
+        let found = enabled_features(&resolved, dep);
+        if found.is_empty() {
+            continue;
+        }
+        users += 1;
+        for version in &found {
+            let mut heavy = false;
+            let features: Vec<String> = version
+                .features
+                .iter()
+                .map(|f| {
+                    if is_heavy(config, dep, f) {
+                        heavy = true;
+                        f.red().bold().to_string()
+                    } else {
+                        f.clone()
+                    }
+                })
+                .collect();
+            let features = if features.is_empty() {
+                "(default only)".to_string()
+            } else {
+                features.join(", ")
+            };
+            out.push_str(&format!(
+                "  {repo}: {dep} {}: {features}\n",
+                version.version
+            ));
+            if heavy && !heavy_repos.contains(repo) {
+                heavy_repos.push(repo.clone());
+            }
+        }
+    }
+    if users == 0 && failures.is_empty() {
+        return CommandResult::Message(format!("No repo depends on {dep}"));
+    }
+    for repo in &heavy_repos {
+        out.push_str(&format!(
+            "{} {repo} enables heavy features of {dep}; \
+             `cargo tree -e features -i {dep}` there shows who asks for them\n",
+            "warning:".yellow()
+        ));
+    }
+    for failure in &failures {
+        out.push_str(&format!("{} {failure}\n", "error:".red()));
+    }
+    if failures.is_empty() {
+        CommandResult::Message(out)
+    } else {
+        CommandResult::Error(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolved(name: &str, version: &str, features: &[&str]) -> ResolvedFeatures {
+        ResolvedFeatures {
+            name: name.to_string(),
+            version: version.to_string(),
+            features: features.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_enabled_features_drops_default() {
+        let graph = vec![
+            resolved("tokio", "1.38.0", &["rt", "default", "full"]),
+            resolved("serde", "1.0.200", &["derive"]),
+        ];
+        let found = enabled_features(&graph, "tokio");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].features, vec!["full", "rt"]);
+        assert!(enabled_features(&graph, "mio").is_empty());
+    }
+
+    #[test]
+    fn test_heavy_features() {
+        let config = FeaturesConfig {
+            heavy: vec!["reqwest/blocking".to_string()],
+        };
+        assert!(is_heavy(&config, "tokio", "full"));
+        assert!(is_heavy(&config, "reqwest", "blocking"));
+        assert!(!is_heavy(&config, "ureq", "blocking"));
+    }
+}
@@ -0,0 +1,105 @@
+//! Include/exclude project filters
+//!
+//! meta passes `--include-only` and `--exclude` patterns in the request
+//! options. A pattern is a path glob (see [`crate::glob`]) matched against
+//! the project directory, or against the name of the package in its
+//! Cargo.toml.
+
+use crate::{glob, project_path};
+use std::path::Path;
+
+/// Patterns selecting the projects a command runs in
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProjectFilters {
+    /// When non-empty, only matching projects run
+    pub include: Vec<String>,
+    /// Matching projects never run
+    pub exclude: Vec<String>,
+}
+
+impl ProjectFilters {
+    pub fn new(include: Option<Vec<String>>, exclude: Option<Vec<String>>) -> Self {
+        ProjectFilters {
+            include: include.unwrap_or_default(),
+            exclude: exclude.unwrap_or_default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// `dirs` narrowed to the projects the filters select, in order
+    pub(crate) fn apply(&self, dirs: Vec<String>, cwd: &Path) -> Vec<String> {
+        if self.is_empty() {
+            return dirs;
+        }
+        dirs.into_iter()
+            .filter(|dir| {
+                let package = package_name(&project_path(cwd, dir));
+                let hit = |patterns: &[String]| {
+                    glob::matches_any(patterns, dir)
+                        || package
+                            .as_deref()
+                            .is_some_and(|name| glob::matches_any(patterns, name))
+                };
+                (self.include.is_empty() || hit(&self.include)) && !hit(&self.exclude)
+            })
+            .collect()
+    }
+}
+
+/// `[package] name` of the manifest in `dir`
+fn package_name(dir: &Path) -> Option<String> {
+    let text = std::fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    let manifest: toml::Value = toml::from_str(&text).ok()?;
+    manifest
+        .get("package")?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_filters_match_paths_and_package_names() {
+        let temp_dir = TempDir::new().unwrap();
+        for (dir, name) in [
+            ("libs/core", "acme-core"),
+            ("libs/net", "acme-net"),
+            ("app", "app"),
+        ] {
+            std::fs::create_dir_all(temp_dir.path().join(dir)).unwrap();
+            std::fs::write(
+                temp_dir.path().join(dir).join("Cargo.toml"),
+                format!("[package]\nname = \"{name}\"\n"),
+            )
+            .unwrap();
+        }
+        let dirs: Vec<String> = ["libs/core", "libs/net", "app"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        let filters = ProjectFilters::new(Some(vec!["libs/*".to_string()]), None);
+        assert_eq!(
+            filters.apply(dirs.clone(), temp_dir.path()),
+            vec!["libs/core", "libs/net"]
+        );
+
+        let filters = ProjectFilters::new(None, Some(vec!["acme-n*".to_string()]));
+        assert_eq!(
+            filters.apply(dirs.clone(), temp_dir.path()),
+            vec!["libs/core", "app"]
+        );
+
+        assert_eq!(
+            ProjectFilters::default().apply(dirs.clone(), temp_dir.path()),
+            dirs
+        );
+    }
+}
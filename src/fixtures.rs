@@ -0,0 +1,279 @@
+//! Service fixtures for `meta cargo integration`
+//!
+//! Each fixture's `start` command is spawned in the background with its
+//! output going to `.meta-rust/fixtures/<name>.log`, then polled with its
+//! readiness probe. Fixtures are torn down in reverse order: `stop` runs if
+//! configured, and a start process that is still alive is killed. On Unix
+//! each fixture runs in its own process group and the whole group is
+//! signalled, so a `start` like `cd db && postgres ...` doesn't leave the
+//! service behind once its `sh -c` wrapper is gone.
+
+use crate::config::FixtureConfig;
+use crate::{project_path, runner};
+use anyhow::{bail, Context};
+use std::collections::HashMap;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::time::{Duration, Instant};
+
+/// Default seconds to wait for a fixture to become ready
+const DEFAULT_READY_TIMEOUT: u64 = 30;
+/// Pause between readiness probes
+const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+/// How long a fixture gets to exit after SIGTERM before it is killed
+const TERM_GRACE: Duration = Duration::from_secs(5);
+
+/// A started fixture
+pub struct Running {
+    config: FixtureConfig,
+    child: Child,
+    dir: PathBuf,
+    env: HashMap<String, String>,
+}
+
+/// Where a fixture's output is written
+pub fn log_path(cwd: &Path, name: &str) -> PathBuf {
+    cwd.join(".meta-rust")
+        .join("fixtures")
+        .join(format!("{name}.log"))
+}
+
+/// Whether `addr` (`host:port`) accepts a TCP connection
+fn tcp_ready(addr: &str) -> bool {
+    let Ok(addrs) = addr.to_socket_addrs() else {
+        return false;
+    };
+    addrs
+        .into_iter()
+        .any(|a| TcpStream::connect_timeout(&a, PROBE_INTERVAL).is_ok())
+}
+
+/// Stop a start process and, on Unix, everything in its process group
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        let group = format!("-{}", child.id());
+        let signal = |name: &str| {
+            std::process::Command::new("kill")
+                .args([name, "--", &group])
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success())
+        };
+        if signal("-TERM") {
+            let started = Instant::now();
+            while started.elapsed() < TERM_GRACE && signal("-0") {
+                let _ = child.try_wait();
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            signal("-KILL");
+        }
+    }
+    let _ = child.kill();
+}
+
+/// Run every configured probe once
+fn probe(fixture: &FixtureConfig, dir: &Path, env: &HashMap<String, String>) -> bool {
+    let command_ok = fixture.ready.as_ref().is_none_or(|cmd| {
+        runner::shell(cmd)
+            .current_dir(dir)
+            .envs(env)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    });
+    command_ok && fixture.ready_tcp.as_deref().is_none_or(tcp_ready)
+}
+
+/// Start `fixture` and wait until it is ready
+///
+/// `env` (the `META_BIN_*` variables) is passed to its commands, so a fixture
+/// can run a binary the integration lane just built.
+pub fn start(
+    fixture: &FixtureConfig,
+    cwd: &Path,
+    env: &HashMap<String, String>,
+) -> anyhow::Result<Running> {
+    let dir = project_path(cwd, fixture.dir.as_deref().unwrap_or("."));
+    let log = log_path(cwd, &fixture.name);
+    if let Some(parent) = log.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let out = std::fs::File::create(&log)
+        .with_context(|| format!("failed to create {}", log.display()))?;
+    let err = out.try_clone()?;
+    let mut command = runner::shell(&fixture.start);
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = command
+        .current_dir(&dir)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(out)
+        .stderr(err)
+        .spawn()
+        .with_context(|| format!("fixture {}: failed to start", fixture.name))?;
+
+    let timeout = Duration::from_secs(fixture.ready_timeout.unwrap_or(DEFAULT_READY_TIMEOUT));
+    let started = Instant::now();
+    loop {
+        // A start command that exits successfully daemonized the service
+        if let Some(status) = child.try_wait()? {
+            if !status.success() {
+                bail!(
+                    "fixture {}: start command failed ({status}), see {}",
+                    fixture.name,
+                    log.display()
+                );
+            }
+        }
+        if probe(fixture, &dir, env) {
+            break;
+        }
+        if started.elapsed() >= timeout {
+            kill(&mut child);
+            let _ = child.wait();
+            bail!(
+                "fixture {}: not ready after {}s, see {}",
+                fixture.name,
+                timeout.as_secs(),
+                log.display()
+            );
+        }
+        std::thread::sleep(PROBE_INTERVAL);
+    }
+    Ok(Running {
+        config: fixture.clone(),
+        child,
+        dir,
+        env: env.clone(),
+    })
+}
+
+/// Start all fixtures in order; on failure, stop the ones already running
+pub fn start_all(
+    fixtures: &[FixtureConfig],
+    cwd: &Path,
+    env: &HashMap<String, String>,
+) -> anyhow::Result<Vec<Running>> {
+    let mut running = Vec::new();
+    for fixture in fixtures {
+        match start(fixture, cwd, env) {
+            Ok(r) => running.push(r),
+            Err(e) => {
+                stop_all(running);
+                return Err(e);
+            }
+        }
+    }
+    Ok(running)
+}
+
+/// Stop fixtures in reverse start order, returning problems as warnings
+pub fn stop_all(running: Vec<Running>) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for mut fixture in running.into_iter().rev() {
+        if let Some(stop) = &fixture.config.stop {
+            let ok = runner::shell(stop)
+                .current_dir(&fixture.dir)
+                .envs(&fixture.env)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success());
+            if !ok {
+                warnings.push(format!(
+                    "fixture {}: stop command failed",
+                    fixture.config.name
+                ));
+            }
+        }
+        if let Ok(None) = fixture.child.try_wait() {
+            kill(&mut fixture.child);
+        }
+        let _ = fixture.child.wait();
+    }
+    warnings
+}
+
+/// Test environment contributed by the fixtures
+pub fn test_env(fixtures: &[FixtureConfig]) -> HashMap<String, String> {
+    fixtures
+        .iter()
+        .flat_map(|f| f.env.iter().map(|(k, v)| (k.clone(), v.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn fixture(name: &str, start: &str, ready: Option<&str>) -> FixtureConfig {
+        FixtureConfig {
+            name: name.to_string(),
+            start: start.to_string(),
+            ready: ready.map(str::to_string),
+            ready_timeout: Some(5),
+            ..FixtureConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_start_waits_for_ready_and_stops() {
+        let temp_dir = TempDir::new().unwrap();
+        let running = start_all(
+            &[fixture("tool", "cargo --version", Some("cargo --version"))],
+            temp_dir.path(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(running.len(), 1);
+        assert!(stop_all(running).is_empty());
+        assert!(log_path(temp_dir.path(), "tool").is_file());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stop_kills_the_whole_process_group() {
+        let temp_dir = TempDir::new().unwrap();
+        let running = start_all(
+            &[fixture(
+                "service",
+                "sleep 30 & echo $! > service.pid; wait",
+                Some("test -s service.pid"),
+            )],
+            temp_dir.path(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        let pid = std::fs::read_to_string(temp_dir.path().join("service.pid")).unwrap();
+        assert!(stop_all(running).is_empty());
+        let alive = std::process::Command::new("kill")
+            .args(["-0", pid.trim()])
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+            .success();
+        assert!(!alive, "sleep {} survived teardown", pid.trim());
+    }
+
+    #[test]
+    fn test_failed_start_is_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let result = start_all(
+            &[fixture(
+                "broken",
+                "cargo locate-project --manifest-path missing/Cargo.toml",
+                Some("cargo locate-project --manifest-path missing/Cargo.toml"),
+            )],
+            temp_dir.path(),
+            &HashMap::new(),
+        );
+        let err = result.err().expect("start should fail").to_string();
+        assert!(err.contains("fixture broken"));
+    }
+}
@@ -0,0 +1,195 @@
+//! `meta cargo fmt --check`: one formatting gate for the whole workspace
+//!
+//! `cargo fmt --check` runs in every repo and the files rustfmt would change
+//! are collected from its `Diff in <file>` headers, so the report lists every
+//! mis-formatted file of every repo instead of a wall of diffs.
+
+use crate::runner::{self, RunOutcome};
+use crate::{project_path, CommandResult, PlannedCommand};
+use colored::Colorize;
+use std::path::Path;
+
+/// Lines of output shown when rustfmt fails without reporting a diff
+const FAILURE_TAIL_LINES: usize = 10;
+
+/// Files named in rustfmt's `Diff in <file>:<line>:` (or, from older
+/// versions, `Diff in <file> at line <n>:`) headers, in order without
+/// duplicates
+pub fn misformatted_files(output: &str) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    for line in output.lines() {
+        let Some(rest) = line.trim_start().strip_prefix("Diff in ") else {
+            continue;
+        };
+        let rest = rest.trim_end().trim_end_matches(':');
+        let file = match rest.rsplit_once(" at line ") {
+            Some((file, _)) => file,
+            None => match rest.rsplit_once(':') {
+                Some((file, n)) if n.bytes().all(|b| b.is_ascii_digit()) => file,
+                _ => rest,
+            },
+        };
+        if !files.iter().any(|f| f == file) {
+            files.push(file.to_string());
+        }
+    }
+    files
+}
+
+/// Per-repo result of a check run
+struct RepoReport {
+    repo: String,
+    /// Paths relative to the repo where possible
+    files: Vec<String>,
+    /// rustfmt failed without reporting a diff (e.g. a parse error)
+    failure: Option<String>,
+}
+
+fn report_repo(cwd: &Path, outcome: &RunOutcome) -> RepoReport {
+    let output = outcome.output();
+    let root = project_path(cwd, &outcome.dir);
+    let files: Vec<String> = misformatted_files(&output)
+        .into_iter()
+        .map(|f| match Path::new(&f).strip_prefix(&root) {
+            Ok(rel) => rel.to_string_lossy().replace('\\', "/"),
+            Err(_) => f,
+        })
+        .collect();
+    let failure = (!outcome.success && files.is_empty()).then(|| {
+        let lines: Vec<&str> = output.lines().collect();
+        lines[lines.len().saturating_sub(FAILURE_TAIL_LINES)..].join("\n")
+    });
+    RepoReport {
+        repo: outcome.dir.clone(),
+        files,
+        failure,
+    }
+}
+
+fn render(reports: &[RepoReport]) -> String {
+    let mut out = String::new();
+    let mut bad_repos = 0;
+    let mut bad_files = 0;
+    for r in reports {
+        if r.files.is_empty() && r.failure.is_none() {
+            out.push_str(&format!("{} {}\n", "✓".green(), r.repo.as_str().bold()));
+            continue;
+        }
+        bad_repos += 1;
+        bad_files += r.files.len();
+        match &r.failure {
+            Some(tail) => {
+                out.push_str(&format!(
+                    "{} {}: rustfmt failed:\n",
+                    "✗".red(),
+                    r.repo.as_str().bold()
+                ));
+                for line in tail.lines() {
+                    out.push_str(&format!("      | {line}\n"));
+                }
+            }
+            None => {
+                out.push_str(&format!(
+                    "{} {}: {} files\n",
+                    "✗".red(),
+                    r.repo.as_str().bold(),
+                    r.files.len()
+                ));
+                for file in &r.files {
+                    out.push_str(&format!("    {file}\n"));
+                }
+            }
+        }
+    }
+    out.push_str(&format!(
+        "\n{bad_repos}/{} repos not formatted ({bad_files} files)\n",
+        reports.len()
+    ));
+    if bad_repos > 0 {
+        out.push_str("fix: meta cargo fmt\n");
+    }
+    out
+}
+
+/// Run `<cargo> fmt --check <args>` everywhere and list mis-formatted files
+pub(crate) fn execute_check(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+) -> CommandResult {
+    let mut cmd = format!("{cargo} fmt --check");
+    for arg in args {
+        cmd.push(' ');
+        cmd.push_str(arg);
+    }
+    let commands: Vec<PlannedCommand> = repos
+        .iter()
+        .map(|dir| PlannedCommand {
+            dir: dir.clone(),
+            cmd: cmd.clone(),
+            env: None,
+        })
+        .collect();
+    let reports: Vec<RepoReport> = runner::run_all(cwd, &commands, parallel)
+        .iter()
+        .map(|o| report_repo(cwd, o))
+        .collect();
+    let text = render(&reports);
+    if reports
+        .iter()
+        .any(|r| !r.files.is_empty() || r.failure.is_some())
+    {
+        CommandResult::Error(text)
+    } else {
+        CommandResult::Message(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "Diff in /meta/core/src/lib.rs:3:
+ fn main() {
+-    let x=1;
++    let x = 1;
+ }
+Diff in /meta/core/src/lib.rs:40:
+Diff in /meta/core/src/util.rs at line 7:
+";
+
+    #[test]
+    fn test_misformatted_files() {
+        assert_eq!(
+            misformatted_files(OUTPUT),
+            vec!["/meta/core/src/lib.rs", "/meta/core/src/util.rs"]
+        );
+        assert!(misformatted_files("").is_empty());
+    }
+
+    #[test]
+    fn test_render_lists_files_per_repo() {
+        let cwd = Path::new("/meta");
+        let reports: Vec<RepoReport> = [
+            RunOutcome::fixture("core", false).with_stdout(OUTPUT),
+            RunOutcome::fixture("app", true),
+            RunOutcome::fixture("labs", false).with_stderr("error: expected item, found `}`"),
+        ]
+        .iter()
+        .map(|o| report_repo(cwd, o))
+        .collect();
+        let text = render(&reports);
+        assert!(
+            text.contains("core: 2 files\n    src/lib.rs\n    src/util.rs\n"),
+            "{text}"
+        );
+        assert!(text.contains("app"), "{text}");
+        assert!(
+            text.contains("labs: rustfmt failed:\n      | error: expected item"),
+            "{text}"
+        );
+        assert!(text.contains("2/3 repos not formatted (2 files)"), "{text}");
+    }
+}
@@ -0,0 +1,174 @@
+//! `meta cargo gate`: run the checks a repo must pass before merge
+//!
+//! `[policy.required]` in `.meta-rust.toml` maps repo tags from the meta
+//! project file to cargo subcommands, e.g. `libs = ["clippy", "test"]`. The
+//! tag `*` applies to every repo. Each repo runs the union of the checks of
+//! its tags, and the report says which repos comply.
+
+use crate::config::Config;
+use crate::runner;
+use crate::{cargo_config, CommandResult, PlannedCommand};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Tag that applies to every repo
+const ANY_TAG: &str = "*";
+
+/// Tags of every project in the meta project file, keyed by path
+fn project_tags(cwd: &Path) -> BTreeMap<String, Vec<String>> {
+    match meta_core::config::walk_meta_tree(cwd, Some(0)) {
+        Ok(tree) => tree
+            .into_iter()
+            .map(|n| (n.info.path, n.info.tags))
+            .collect(),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+/// Checks required for a repo with `tags`, in policy order without duplicates
+pub(crate) fn required_checks(
+    tags: &[String],
+    policy: &BTreeMap<String, Vec<String>>,
+) -> Vec<String> {
+    let mut checks: Vec<String> = Vec::new();
+    for (tag, required) in policy {
+        if tag == ANY_TAG || tags.contains(tag) {
+            for check in required {
+                if !checks.contains(check) {
+                    checks.push(check.clone());
+                }
+            }
+        }
+    }
+    checks
+}
+
+/// Handle `meta cargo gate`
+pub(crate) fn execute(
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+) -> CommandResult {
+    let policy = &config.policy.required;
+    if policy.is_empty() {
+        return CommandResult::Error(
+            "no required checks configured (add [policy.required] to .meta-rust.toml)".to_string(),
+        );
+    }
+    let tags = project_tags(cwd);
+    let cargo = cargo_config::cargo(cwd, config);
+    let mut commands = Vec::new();
+    let mut checks = Vec::new();
+    for repo in repos {
+        let repo_tags = tags.get(repo).cloned().unwrap_or_default();
+        for check in required_checks(&repo_tags, policy) {
+            commands.push(PlannedCommand {
+                dir: repo.clone(),
+                cmd: format!("{cargo} {check}"),
+                env: None,
+            });
+            checks.push((repo.clone(), check));
+        }
+    }
+    let outcomes = runner::run_all(cwd, &commands, parallel);
+
+    let mut failures: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for ((repo, check), outcome) in checks.iter().zip(&outcomes) {
+        let entry = failures.entry(repo.as_str()).or_default();
+        if !outcome.success {
+            entry.push(check.as_str());
+        }
+    }
+    let mut out = String::new();
+    let mut compliant = 0;
+    for repo in repos {
+        let required: Vec<&str> = checks
+            .iter()
+            .filter(|(r, _)| r == repo)
+            .map(|(_, c)| c.as_str())
+            .collect();
+        match failures.get(repo.as_str()) {
+            None => out.push_str(&format!("- {repo}: no required checks\n")),
+            Some(fails) if fails.is_empty() => {
+                compliant += 1;
+                out.push_str(&format!(
+                    "{} {repo}: {}\n",
+                    "✓".green(),
+                    required.join(", ")
+                ));
+            }
+            Some(fails) => {
+                let passed: Vec<&str> = required
+                    .iter()
+                    .copied()
+                    .filter(|c| !fails.contains(c))
+                    .collect();
+                out.push_str(&format!(
+                    "{} {repo}: failed {}",
+                    "✗".red(),
+                    fails.join(", ")
+                ));
+                if !passed.is_empty() {
+                    out.push_str(&format!(" (passed {})", passed.join(", ")));
+                }
+                out.push('\n');
+            }
+        }
+    }
+    let gated = failures.len();
+    out.push_str(&format!("Policy: {compliant}/{gated} repos compliant\n"));
+    if compliant == gated {
+        CommandResult::Message(out)
+    } else {
+        CommandResult::Error(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::strings;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_required_checks_by_tag() {
+        let policy = BTreeMap::from([
+            ("*".to_string(), strings(&["build"])),
+            ("libs".to_string(), strings(&["clippy", "build"])),
+        ]);
+        assert_eq!(
+            required_checks(&strings(&["libs"]), &policy),
+            vec!["build", "clippy"]
+        );
+        assert_eq!(required_checks(&[], &policy), vec!["build"]);
+    }
+
+    #[test]
+    fn test_gate_reports_compliance() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join(".meta"),
+            r#"{"projects": {"core": {"repo": "x", "tags": ["libs"]}, "app": "y"}}"#,
+        )
+        .unwrap();
+        let config = Config::parse(
+            "[policy.required]\nlibs = [\"--version\", \"locate-project --manifest-path missing/Cargo.toml\"]\n",
+        )
+        .unwrap();
+        for repo in ["core", "app"] {
+            std::fs::create_dir(temp_dir.path().join(repo)).unwrap();
+        }
+        let repos = strings(&["core", "app"]);
+        match execute(&repos, temp_dir.path(), false, &config) {
+            CommandResult::Error(out) => {
+                assert!(out.contains("core: failed locate-project"), "{out}");
+                assert!(out.contains("(passed --version)"), "{out}");
+                assert!(out.contains("app: no required checks"), "{out}");
+                assert!(out.contains("Policy: 0/1 repos compliant"), "{out}");
+            }
+            _ => panic!("Expected Error result"),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Thin wrappers around the git CLI
+
+use anyhow::{bail, Context};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+/// Run git in `dir` and return its stdout
+pub(crate) fn run(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Files changed in `dir` since `since`, relative to `dir`
+///
+/// Includes uncommitted and untracked files so local runs see work in progress.
+pub(crate) fn changed_files(dir: &Path, since: &str) -> anyhow::Result<Vec<String>> {
+    let mut files: Vec<String> = run(dir, &["diff", "--name-only", "--relative", since])?
+        .lines()
+        .map(str::to_string)
+        .collect();
+    let untracked = run(dir, &["ls-files", "--others", "--exclude-standard"])?;
+    files.extend(untracked.lines().map(str::to_string));
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Inclusive line ranges added or modified per file, relative to the diffed dir
+pub(crate) type ChangedLines = BTreeMap<String, Vec<(u32, u32)>>;
+
+/// Parse `git diff -U0` output into the new-side line ranges of each file
+fn parse_changed_lines(diff: &str) -> ChangedLines {
+    let mut changed = ChangedLines::new();
+    let mut current: Option<String> = None;
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            current = path.strip_prefix("b/").map(str::to_string);
+            continue;
+        }
+        let (Some(file), Some(hunk)) = (&current, line.strip_prefix("@@ ")) else {
+            continue;
+        };
+        // @@ -a,b +c,d @@: `d` lines starting at `c` (d defaults to 1)
+        let Some(new) = hunk.split_whitespace().find_map(|p| p.strip_prefix('+')) else {
+            continue;
+        };
+        let (start, count) = match new.split_once(',') {
+            Some((s, c)) => (s.parse().unwrap_or(0), c.parse().unwrap_or(0)),
+            None => (new.parse().unwrap_or(0), 1),
+        };
+        if count > 0 {
+            changed
+                .entry(file.clone())
+                .or_default()
+                .push((start, start + count - 1));
+        }
+    }
+    changed
+}
+
+/// Lines touched in `dir` since `since`; untracked files count as entirely new
+pub(crate) fn changed_lines(dir: &Path, since: &str) -> anyhow::Result<ChangedLines> {
+    let diff = run(
+        dir,
+        &[
+            "diff",
+            "-U0",
+            "--no-color",
+            "--no-ext-diff",
+            "--relative",
+            since,
+        ],
+    )?;
+    let mut changed = parse_changed_lines(&diff);
+    for file in run(dir, &["ls-files", "--others", "--exclude-standard"])?.lines() {
+        changed.insert(file.to_string(), vec![(1, u32::MAX)]);
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_changed_lines() {
+        let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -3 +3 @@ fn a() {
+-old
++new
+@@ -10,0 +11,2 @@
++one
++two
+@@ -20,3 +21,0 @@
+diff --git a/gone.rs b/gone.rs
+--- a/gone.rs
++++ /dev/null
+@@ -1,2 +0,0 @@
+";
+        let changed = parse_changed_lines(diff);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed["src/lib.rs"], vec![(3, 3), (11, 12)]);
+    }
+}
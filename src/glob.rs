@@ -0,0 +1,63 @@
+//! Minimal path glob matching
+//!
+//! Patterns are matched against `/`-separated relative paths, segment by
+//! segment: `*` matches within a segment, `?` matches one character, and a
+//! `**` segment matches any number of segments (including none). Patterns are
+//! anchored, so `*.md` only matches top-level files; use `**/*.md` to match
+//! at any depth.
+
+/// Whether `path` matches `pattern`
+pub(crate) fn matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.trim_matches('/').split('/').collect();
+    let path: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match_segments(&pattern, &path)
+}
+
+/// Whether `path` matches any of `patterns`
+pub(crate) fn matches_any(patterns: &[String], path: &str) -> bool {
+    patterns.iter().any(|p| matches(p, path))
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some(&"**") => (0..=path.len()).any(|i| match_segments(&pattern[1..], &path[i..])),
+        Some(seg) => {
+            !path.is_empty()
+                && match_segment(seg.as_bytes(), path[0].as_bytes())
+                && match_segments(&pattern[1..], &path[1..])
+        }
+    }
+}
+
+fn match_segment(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') => (0..=text.len()).any(|i| match_segment(&pattern[1..], &text[i..])),
+        Some(b'?') => !text.is_empty() && match_segment(&pattern[1..], &text[1..]),
+        Some(c) => text.first() == Some(c) && match_segment(&pattern[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_star() {
+        assert!(matches("**/*.md", "README.md"));
+        assert!(matches("**/*.md", "crates/a/docs/guide.md"));
+        assert!(matches("docs/**", "docs/book/intro.md"));
+        assert!(matches(".github/**", ".github/workflows/ci.yml"));
+        assert!(!matches("docs/**", "src/docs.rs"));
+    }
+
+    #[test]
+    fn test_single_segment_wildcards() {
+        assert!(matches("*.md", "CHANGELOG.md"));
+        assert!(!matches("*.md", "docs/CHANGELOG.md"));
+        assert!(matches("libs/*", "libs/core"));
+        assert!(!matches("libs/*", "libs/core/nested"));
+        assert!(matches("v?.txt", "v1.txt"));
+    }
+}
@@ -0,0 +1,695 @@
+//! Cross-repo crate dependency graph
+//!
+//! Built from `cargo metadata` in every Rust repo of the meta workspace. An edge
+//! exists whenever a crate depends on a crate that lives in the workspace,
+//! whether the dependency is declared by path, git, or registry version.
+//!
+//! `meta cargo graph` exports the graph as DOT or Mermaid, with the crates of
+//! each repo grouped into a cluster, or as JSON for dashboards. The JSON
+//! schema is versioned by its `schema` field: fields are only ever added
+//! within a version, and crates are referenced by their index in `crates`.
+
+use crate::metadata::{self, DependencyKind, Package};
+use crate::{args, CommandResult};
+use anyhow::Context;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// A crate defined somewhere in the meta workspace
+#[derive(Debug, Clone)]
+pub struct CrateNode {
+    pub name: String,
+    pub version: String,
+    /// Meta project directory the crate belongs to
+    pub repo: String,
+    /// Directory containing the crate's Cargo.toml
+    pub root: PathBuf,
+    pub manifest_path: PathBuf,
+    /// False for `publish = false` crates
+    pub publishable: bool,
+    /// Registries the crate may be published to, `None` for any
+    pub publish: Option<Vec<String>>,
+    /// Declared features and what each enables
+    pub features: BTreeMap<String, Vec<String>>,
+    /// Whether the crate is a procedural macro library
+    pub proc_macro: bool,
+    /// Every declared dependency, inside the workspace or not
+    pub dependencies: Vec<metadata::Dependency>,
+}
+
+/// `from` depends on `to`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: DependencyKind,
+}
+
+/// Crates of the meta workspace and the dependency edges between them
+#[derive(Debug, Clone, Default)]
+pub struct CrateGraph {
+    pub crates: Vec<CrateNode>,
+    pub edges: Vec<Edge>,
+}
+
+/// Number of path components in a repo dir ("." has none)
+fn depth(repo: &str) -> usize {
+    if repo == "." {
+        0
+    } else {
+        Path::new(repo).components().count()
+    }
+}
+
+impl CrateGraph {
+    /// Load the graph by running `cargo metadata` in every repo
+    pub fn load(repos: &[String], cwd: &Path) -> anyhow::Result<Self> {
+        let dirs: Vec<_> = repos.iter().map(|r| crate::project_path(cwd, r)).collect();
+        let mut packages = Vec::new();
+        for (repo, pkgs) in repos
+            .iter()
+            .zip(metadata::load_each(&dirs, metadata::load_packages))
+        {
+            let pkgs = pkgs.with_context(|| format!("{repo}: failed to load cargo metadata"))?;
+            packages.push((repo.clone(), pkgs));
+        }
+        Ok(Self::from_packages(packages))
+    }
+
+    /// [`CrateGraph::load`] without the repos whose metadata fails to load,
+    /// returned alongside with their errors
+    pub fn load_partial(repos: &[String], cwd: &Path) -> (Self, Vec<(String, String)>) {
+        let dirs: Vec<_> = repos.iter().map(|r| crate::project_path(cwd, r)).collect();
+        let mut packages = Vec::new();
+        let mut failed = Vec::new();
+        for (repo, pkgs) in repos
+            .iter()
+            .zip(metadata::load_each(&dirs, metadata::load_packages))
+        {
+            match pkgs {
+                Ok(pkgs) => packages.push((repo.clone(), pkgs)),
+                Err(e) => failed.push((repo.clone(), format!("cargo metadata failed: {e:#}"))),
+            }
+        }
+        (Self::from_packages(packages), failed)
+    }
+
+    /// Build the graph from per-repo package lists
+    ///
+    /// A crate reported by several repos (e.g. a root workspace that also
+    /// covers a member repo) is attributed to the most specific repo.
+    pub fn from_packages(repos: Vec<(String, Vec<Package>)>) -> Self {
+        let mut graph = CrateGraph::default();
+        let mut deps: Vec<Vec<metadata::Dependency>> = Vec::new();
+        for (repo, packages) in repos {
+            for pkg in packages {
+                if let Some(i) = graph
+                    .crates
+                    .iter()
+                    .position(|c| c.manifest_path == pkg.manifest_path)
+                {
+                    if depth(&repo) > depth(&graph.crates[i].repo) {
+                        graph.crates[i].repo = repo.clone();
+                    }
+                    continue;
+                }
+                graph.crates.push(CrateNode {
+                    name: pkg.name.clone(),
+                    version: pkg.version.clone(),
+                    repo: repo.clone(),
+                    root: pkg.root().to_path_buf(),
+                    manifest_path: pkg.manifest_path.clone(),
+                    publishable: pkg.is_publishable(),
+                    publish: pkg.publish.clone(),
+                    features: pkg.features.clone(),
+                    proc_macro: pkg.has_target("proc-macro"),
+                    dependencies: pkg.dependencies.clone(),
+                });
+                deps.push(pkg.dependencies);
+            }
+        }
+
+        for (from, crate_deps) in deps.iter().enumerate() {
+            for dep in crate_deps {
+                let target = dep
+                    .path
+                    .as_ref()
+                    .and_then(|p| graph.crates.iter().position(|c| &c.root == p))
+                    .or_else(|| graph.crates.iter().position(|c| c.name == dep.name));
+                if let Some(to) = target {
+                    let edge = Edge {
+                        from,
+                        to,
+                        kind: dep.kind,
+                    };
+                    if to != from && !graph.edges.contains(&edge) {
+                        graph.edges.push(edge);
+                    }
+                }
+            }
+        }
+        graph
+    }
+
+    /// Indices of crates belonging to `repo`
+    pub fn crates_in_repo<'a>(&'a self, repo: &'a str) -> impl Iterator<Item = usize> + 'a {
+        self.crates
+            .iter()
+            .enumerate()
+            .filter(move |(_, c)| c.repo == repo)
+            .map(|(i, _)| i)
+    }
+
+    /// `repos` ordered so that every repo comes after the repos it depends on
+    ///
+    /// Ties keep the order of `repos`. Repos in a dependency cycle are
+    /// appended in their original order.
+    pub fn repo_order(&self, repos: &[String]) -> Vec<String> {
+        let mut deps: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); repos.len()];
+        for edge in &self.edges {
+            let from = repos.iter().position(|r| *r == self.crates[edge.from].repo);
+            let to = repos.iter().position(|r| *r == self.crates[edge.to].repo);
+            if let (Some(from), Some(to)) = (from, to) {
+                if from != to {
+                    deps[from].insert(to);
+                }
+            }
+        }
+
+        let mut placed = vec![false; repos.len()];
+        let mut order = Vec::new();
+        while let Some(next) =
+            (0..repos.len()).find(|&i| !placed[i] && deps[i].iter().all(|&d| placed[d]))
+        {
+            placed[next] = true;
+            order.push(repos[next].clone());
+        }
+        order.extend(
+            (0..repos.len())
+                .filter(|&i| !placed[i])
+                .map(|i| repos[i].clone()),
+        );
+        order
+    }
+
+    /// `repos` grouped into levels whose repos only depend on earlier levels
+    ///
+    /// Repos of one level can build in parallel. Repos in a dependency cycle
+    /// form a final level.
+    pub fn repo_levels(&self, repos: &[String]) -> Vec<Vec<String>> {
+        let index = |repo: &str| repos.iter().position(|r| r == repo);
+        let mut deps: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); repos.len()];
+        for edge in &self.edges {
+            let from = index(&self.crates[edge.from].repo);
+            let to = index(&self.crates[edge.to].repo);
+            if let (Some(from), Some(to)) = (from, to) {
+                if from != to {
+                    deps[from].insert(to);
+                }
+            }
+        }
+
+        let mut placed = vec![false; repos.len()];
+        let mut levels = Vec::new();
+        loop {
+            let level: Vec<usize> = (0..repos.len())
+                .filter(|&i| !placed[i] && deps[i].iter().all(|&d| placed[d]))
+                .collect();
+            if level.is_empty() {
+                break;
+            }
+            for &i in &level {
+                placed[i] = true;
+            }
+            levels.push(level.into_iter().map(|i| repos[i].clone()).collect());
+        }
+        let rest: Vec<String> = (0..repos.len())
+            .filter(|&i| !placed[i])
+            .map(|i| repos[i].clone())
+            .collect();
+        if !rest.is_empty() {
+            levels.push(rest);
+        }
+        levels
+    }
+
+    /// Crate indices ordered so every crate follows its normal and build
+    /// dependencies, e.g. for publishing
+    ///
+    /// Ties keep graph order; crates in a cycle are appended.
+    pub fn crate_order(&self) -> Vec<usize> {
+        let n = self.crates.len();
+        let mut deps: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); n];
+        for edge in self.edges.iter().filter(|e| e.kind != DependencyKind::Dev) {
+            deps[edge.from].insert(edge.to);
+        }
+        let mut placed = vec![false; n];
+        let mut order = Vec::new();
+        while let Some(next) = (0..n).find(|&i| !placed[i] && deps[i].iter().all(|&d| placed[d])) {
+            placed[next] = true;
+            order.push(next);
+        }
+        order.extend((0..n).filter(|&i| !placed[i]));
+        order
+    }
+
+    /// `seeds` plus every crate that transitively depends on one of them
+    pub fn dependents(&self, seeds: &BTreeSet<usize>) -> BTreeSet<usize> {
+        let mut result = seeds.clone();
+        let mut queue: Vec<usize> = seeds.iter().copied().collect();
+        while let Some(node) = queue.pop() {
+            for edge in self.edges.iter().filter(|e| e.to == node) {
+                if result.insert(edge.from) {
+                    queue.push(edge.from);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// DOT string literal
+fn dot_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Mermaid node label, which cannot contain a raw `"`
+fn mermaid_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "#quot;"))
+}
+
+impl CrateGraph {
+    /// Repos of the graph in first-seen order
+    fn repos(&self) -> Vec<&str> {
+        let mut repos: Vec<&str> = Vec::new();
+        for c in &self.crates {
+            if !repos.contains(&c.repo.as_str()) {
+                repos.push(&c.repo);
+            }
+        }
+        repos
+    }
+
+    /// Graphviz DOT; dev edges are dashed and build edges dotted
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph meta {\n    rankdir=LR;\n    node [shape=box];\n");
+        for (r, repo) in self.repos().into_iter().enumerate() {
+            out.push_str(&format!(
+                "    subgraph cluster_{r} {{\n        label={};\n",
+                dot_quote(repo)
+            ));
+            for i in self.crates_in_repo(repo) {
+                out.push_str(&format!(
+                    "        c{i} [label={}];\n",
+                    dot_quote(&self.crates[i].name)
+                ));
+            }
+            out.push_str("    }\n");
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                DependencyKind::Normal => "",
+                DependencyKind::Dev => " [style=dashed]",
+                DependencyKind::Build => " [style=dotted]",
+            };
+            out.push_str(&format!("    c{} -> c{}{style};\n", edge.from, edge.to));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Mermaid flowchart; dev edges are dotted and build edges labelled
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("graph LR\n");
+        for (r, repo) in self.repos().into_iter().enumerate() {
+            out.push_str(&format!("    subgraph r{r}[{}]\n", mermaid_quote(repo)));
+            for i in self.crates_in_repo(repo) {
+                out.push_str(&format!(
+                    "        c{i}[{}]\n",
+                    mermaid_quote(&self.crates[i].name)
+                ));
+            }
+            out.push_str("    end\n");
+        }
+        for edge in &self.edges {
+            let arrow = match edge.kind {
+                DependencyKind::Normal => "-->",
+                DependencyKind::Dev => "-.->",
+                DependencyKind::Build => "-- build -->",
+            };
+            out.push_str(&format!("    c{} {arrow} c{}\n", edge.from, edge.to));
+        }
+        out
+    }
+}
+
+/// Version of the JSON export schema
+const SCHEMA_VERSION: u32 = 1;
+
+fn kind_name(kind: DependencyKind) -> &'static str {
+    match kind {
+        DependencyKind::Normal => "normal",
+        DependencyKind::Dev => "dev",
+        DependencyKind::Build => "build",
+    }
+}
+
+/// How a dependency is declared: `path`, `git` or `registry`
+fn source_name(dep: &metadata::Dependency) -> &'static str {
+    match &dep.source {
+        _ if dep.path.is_some() => "path",
+        Some(source) if source.starts_with("git+") => "git",
+        _ => "registry",
+    }
+}
+
+/// JSON options beyond the always-present fields
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonDetail {
+    /// Crate versions and edge version requirements
+    pub versions: bool,
+    /// Declared crate features and the features requested per edge
+    pub features: bool,
+}
+
+impl CrateGraph {
+    /// The declaration behind `edge`
+    fn declaration(&self, edge: &Edge) -> Option<&metadata::Dependency> {
+        let to = &self.crates[edge.to];
+        let deps = &self.crates[edge.from].dependencies;
+        let of_kind = || deps.iter().filter(|d| d.kind == edge.kind);
+        of_kind()
+            .find(|d| d.path.as_deref() == Some(to.root.as_path()))
+            .or_else(|| of_kind().find(|d| d.name == to.name))
+    }
+
+    /// JSON export; manifest paths are relative to `cwd` where possible
+    pub fn to_json(&self, cwd: &Path, detail: JsonDetail) -> serde_json::Value {
+        let cwd = cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf());
+        let relative = |p: &Path| {
+            p.strip_prefix(&cwd)
+                .unwrap_or(p)
+                .to_string_lossy()
+                .replace('\\', "/")
+        };
+        let repos: Vec<serde_json::Value> = self
+            .repos()
+            .into_iter()
+            .map(|repo| json!({ "path": repo, "crates": self.crates_in_repo(repo).collect::<Vec<_>>() }))
+            .collect();
+        let crates: Vec<serde_json::Value> = self
+            .crates
+            .iter()
+            .enumerate()
+            .map(|(id, c)| {
+                let mut node = serde_json::Map::new();
+                node.insert("id".to_string(), json!(id));
+                node.insert("name".to_string(), json!(c.name));
+                node.insert("repo".to_string(), json!(c.repo));
+                node.insert(
+                    "manifest_path".to_string(),
+                    json!(relative(&c.manifest_path)),
+                );
+                node.insert(
+                    "publish".to_string(),
+                    json!({ "publishable": c.publishable, "registries": c.publish }),
+                );
+                if detail.versions {
+                    node.insert("version".to_string(), json!(c.version));
+                }
+                if detail.features {
+                    node.insert("features".to_string(), json!(c.features));
+                }
+                serde_json::Value::Object(node)
+            })
+            .collect();
+        let edges: Vec<serde_json::Value> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                let dep = self.declaration(edge);
+                let mut value = serde_json::Map::new();
+                value.insert("from".to_string(), json!(edge.from));
+                value.insert("to".to_string(), json!(edge.to));
+                value.insert("kind".to_string(), json!(kind_name(edge.kind)));
+                value.insert(
+                    "source".to_string(),
+                    json!(dep.map_or("registry", source_name)),
+                );
+                if detail.versions {
+                    value.insert("req".to_string(), json!(dep.map(|d| d.req.as_str())));
+                }
+                if detail.features {
+                    let features = dep.map(|d| d.features.clone()).unwrap_or_default();
+                    value.insert("features".to_string(), json!(features));
+                }
+                serde_json::Value::Object(value)
+            })
+            .collect();
+        json!({
+            "schema": SCHEMA_VERSION,
+            "repos": repos,
+            "crates": crates,
+            "edges": edges,
+        })
+    }
+}
+
+/// Handle `meta cargo graph [--format dot|mermaid|json] [--no-dev]
+/// [--include-versions] [--include-features]`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let format = args::take_value(&mut args, "--format").unwrap_or_else(|| "dot".to_string());
+    let no_dev = args::take_flag(&mut args, "--no-dev");
+    let detail = JsonDetail {
+        versions: args::take_flag(&mut args, "--include-versions"),
+        features: args::take_flag(&mut args, "--include-features"),
+    };
+    let mut graph = match CrateGraph::load(repos, cwd) {
+        Ok(graph) => graph,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    if no_dev {
+        graph.edges.retain(|e| e.kind != DependencyKind::Dev);
+    }
+    match format.as_str() {
+        "dot" => CommandResult::Message(graph.to_dot()),
+        "mermaid" => CommandResult::Message(graph.to_mermaid()),
+        "json" => match serde_json::to_string_pretty(&graph.to_json(cwd, detail)) {
+            Ok(json) => CommandResult::Message(json),
+            Err(e) => CommandResult::Error(format!("failed to serialize graph: {e}")),
+        },
+        other => CommandResult::Error(format!(
+            "unsupported format '{other}' (expected dot, mermaid or json)"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Dependency;
+
+    #[test]
+    fn test_edges_between_repos() {
+        let graph = CrateGraph::from_packages(vec![
+            (
+                "core".to_string(),
+                vec![Package::fixture("core").with_dependencies(&["serde"])],
+            ),
+            (
+                "app".to_string(),
+                vec![Package::fixture("app").with_dependencies(&["core"])],
+            ),
+        ]);
+        assert_eq!(graph.crates.len(), 2);
+        assert_eq!(
+            graph.edges,
+            vec![Edge {
+                from: 1,
+                to: 0,
+                kind: DependencyKind::Normal
+            }]
+        );
+    }
+
+    #[test]
+    fn test_transitive_dependents() {
+        let graph = CrateGraph::from_packages(vec![
+            ("a".to_string(), vec![Package::fixture("a")]),
+            (
+                "b".to_string(),
+                vec![Package::fixture("b").with_dependencies(&["a"])],
+            ),
+            (
+                "c".to_string(),
+                vec![Package::fixture("c").with_dependencies(&["b"])],
+            ),
+            ("d".to_string(), vec![Package::fixture("d")]),
+        ]);
+        let affected = graph.dependents(&BTreeSet::from([0]));
+        assert_eq!(affected, BTreeSet::from([0, 1, 2]));
+    }
+
+    #[test]
+    fn test_shared_manifest_goes_to_most_specific_repo() {
+        let graph = CrateGraph::from_packages(vec![
+            (
+                ".".to_string(),
+                vec![Package::fixture("core").with_root("/ws/libs/core")],
+            ),
+            (
+                "libs/core".to_string(),
+                vec![Package::fixture("core").with_root("/ws/libs/core")],
+            ),
+        ]);
+        assert_eq!(graph.crates.len(), 1);
+        assert_eq!(graph.crates[0].repo, "libs/core");
+    }
+
+    #[test]
+    fn test_crate_order_ignores_dev_cycles() {
+        let base = Package::fixture("base")
+            .with_dependency(Dependency::fixture("app").with_kind(DependencyKind::Dev));
+        let graph = CrateGraph::from_packages(vec![
+            (
+                "app".to_string(),
+                vec![Package::fixture("app").with_dependencies(&["base"])],
+            ),
+            ("base".to_string(), vec![base]),
+        ]);
+        assert_eq!(graph.crate_order(), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_repo_order_puts_dependencies_first() {
+        let graph = CrateGraph::from_packages(vec![
+            (
+                "app".to_string(),
+                vec![Package::fixture("app").with_dependencies(&["mid"])],
+            ),
+            (
+                "mid".to_string(),
+                vec![Package::fixture("mid").with_dependencies(&["base"])],
+            ),
+            ("base".to_string(), vec![Package::fixture("base")]),
+            ("tool".to_string(), vec![Package::fixture("tool")]),
+        ]);
+        let repos: Vec<String> = ["app", "mid", "base", "tool"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(graph.repo_order(&repos), vec!["base", "mid", "app", "tool"]);
+    }
+
+    #[test]
+    fn test_repo_levels() {
+        let graph = CrateGraph::from_packages(vec![
+            (
+                "app".to_string(),
+                vec![Package::fixture("app").with_dependencies(&["mid"])],
+            ),
+            (
+                "mid".to_string(),
+                vec![Package::fixture("mid").with_dependencies(&["base"])],
+            ),
+            ("base".to_string(), vec![Package::fixture("base")]),
+            ("tool".to_string(), vec![Package::fixture("tool")]),
+        ]);
+        let repos: Vec<String> = ["app", "mid", "base", "tool"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            graph.repo_levels(&repos),
+            vec![vec!["base", "tool"], vec!["mid"], vec!["app"]]
+        );
+    }
+
+    fn export_graph() -> CrateGraph {
+        let mut graph = CrateGraph::from_packages(vec![
+            (
+                "core".to_string(),
+                vec![
+                    Package::fixture("core"),
+                    Package::fixture("core-\"x\"").with_root("/ws/core/x"),
+                ],
+            ),
+            (
+                "app".to_string(),
+                vec![Package::fixture("app").with_dependencies(&["core"])],
+            ),
+        ]);
+        graph.edges.push(Edge {
+            from: 2,
+            to: 1,
+            kind: DependencyKind::Dev,
+        });
+        graph
+    }
+
+    #[test]
+    fn test_dot_export() {
+        let dot = export_graph().to_dot();
+        assert!(dot.starts_with("digraph meta {"), "{dot}");
+        assert!(
+            dot.contains("subgraph cluster_0 {\n        label=\"core\";"),
+            "{dot}"
+        );
+        assert!(dot.contains("c1 [label=\"core-\\\"x\\\"\"];"), "{dot}");
+        assert!(dot.contains("c2 -> c0;\n"), "{dot}");
+        assert!(dot.contains("c2 -> c1 [style=dashed];"), "{dot}");
+    }
+
+    #[test]
+    fn test_mermaid_export() {
+        let mermaid = export_graph().to_mermaid();
+        assert!(mermaid.starts_with("graph LR\n"), "{mermaid}");
+        assert!(
+            mermaid.contains("subgraph r1[\"app\"]\n        c2[\"app\"]\n    end"),
+            "{mermaid}"
+        );
+        assert!(mermaid.contains("c1[\"core-#quot;x#quot;\"]"), "{mermaid}");
+        assert!(mermaid.contains("c2 --> c0\n"), "{mermaid}");
+        assert!(mermaid.contains("c2 -.-> c1\n"), "{mermaid}");
+    }
+
+    #[test]
+    fn test_json_export() {
+        let mut graph = export_graph();
+        graph.crates[0].features = BTreeMap::from([("std".to_string(), Vec::new())]);
+        graph.crates[2].publishable = false;
+        graph.crates[2].publish = Some(Vec::new());
+        graph.crates[2].dependencies[0].path = Some(PathBuf::from("/ws/core"));
+        graph.crates[2].dependencies[0].features = vec!["std".to_string()];
+
+        let plain = graph.to_json(Path::new("/ws"), JsonDetail::default());
+        assert_eq!(plain["schema"], 1);
+        assert_eq!(plain["repos"][1], json!({ "path": "app", "crates": [2] }));
+        assert_eq!(plain["crates"][2]["manifest_path"], "app/Cargo.toml");
+        assert_eq!(
+            plain["crates"][2]["publish"],
+            json!({ "publishable": false, "registries": [] })
+        );
+        assert!(plain["crates"][0].get("version").is_none());
+        assert_eq!(
+            plain["edges"][0],
+            json!({ "from": 2, "to": 0, "kind": "normal", "source": "path" })
+        );
+        // Dev edge without a matching declaration
+        assert_eq!(plain["edges"][1]["source"], "registry");
+
+        let full = graph.to_json(
+            Path::new("/ws"),
+            JsonDetail {
+                versions: true,
+                features: true,
+            },
+        );
+        assert_eq!(full["crates"][0]["version"], "0.1.0");
+        assert_eq!(full["crates"][0]["features"], json!({ "std": [] }));
+        assert_eq!(full["edges"][0]["req"], "*");
+        assert_eq!(full["edges"][0]["features"], json!(["std"]));
+    }
+}
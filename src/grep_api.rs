@@ -0,0 +1,571 @@
+//! `meta cargo grep-api`: find cross-repo uses of an item before changing it
+//!
+//! Sources are tokenized rather than grepped: comments, string and char
+//! literals never match, `use` declarations (groups, renames, globs) are
+//! resolved, so `use core::Client as C; C::new()` counts as a use of
+//! `core::Client`. Method calls cannot be resolved without type information
+//! and are only found by single-segment patterns.
+
+use crate::env_audit::rust_files;
+use crate::{args, project_path, CommandResult};
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Tok {
+    Ident(String),
+    Punct(char),
+    Literal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Token {
+    pub(crate) tok: Tok,
+    pub(crate) line: u32,
+    /// Offset, in chars, of the token (for raw identifiers, of the name)
+    pub(crate) start: usize,
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Tokens of Rust source, without comments and with literals collapsed
+pub(crate) fn tokenize(src: &str) -> Vec<Token> {
+    let chars: Vec<char> = src.chars().collect();
+    let at = |i: usize| chars.get(i).copied().unwrap_or('\0');
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    // Skip a quoted literal starting at the opening quote, escapes included
+    let skip_quoted = |i: &mut usize, line: &mut u32, quote: char| {
+        *i += 1;
+        while *i < chars.len() && chars[*i] != quote {
+            if chars[*i] == '\\' {
+                *i += 1;
+            }
+            if at(*i) == '\n' {
+                *line += 1;
+            }
+            *i += 1;
+        }
+        *i += 1;
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start_line = line;
+        let begin = i;
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && at(i + 1) == '/' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && at(i + 1) == '*' {
+            let mut depth = 0;
+            while i < chars.len() {
+                if chars[i] == '/' && at(i + 1) == '*' {
+                    depth += 1;
+                    i += 2;
+                } else if chars[i] == '*' && at(i + 1) == '/' {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+            }
+        } else if (c == 'r' || (c == 'b' && at(i + 1) == 'r'))
+            && matches!(at(i + if c == 'b' { 2 } else { 1 }), '"' | '#')
+            && {
+                let mut j = i + if c == 'b' { 2 } else { 1 };
+                while at(j) == '#' {
+                    j += 1;
+                }
+                at(j) == '"'
+            }
+        {
+            // Raw string: r"..", r#".."#, br".."
+            let mut j = i + if c == 'b' { 2 } else { 1 };
+            let mut hashes = 0;
+            while at(j) == '#' {
+                hashes += 1;
+                j += 1;
+            }
+            j += 1;
+            loop {
+                if j >= chars.len() {
+                    break;
+                }
+                if chars[j] == '\n' {
+                    line += 1;
+                }
+                if chars[j] == '"' && (1..=hashes).all(|h| at(j + h) == '#') {
+                    j += 1 + hashes;
+                    break;
+                }
+                j += 1;
+            }
+            i = j;
+            tokens.push(Token {
+                tok: Tok::Literal,
+                line: start_line,
+                start: begin,
+            });
+        } else if c == 'r' && at(i + 1) == '#' && is_ident_start(at(i + 2)) {
+            // Raw identifier
+            let start = i + 2;
+            i = start;
+            while is_ident_char(at(i)) {
+                i += 1;
+            }
+            tokens.push(Token {
+                tok: Tok::Ident(chars[start..i].iter().collect()),
+                line,
+                start,
+            });
+        } else if c == '"' || (c == 'b' && matches!(at(i + 1), '"' | '\'')) {
+            if c == 'b' {
+                i += 1;
+            }
+            let quote = chars[i];
+            skip_quoted(&mut i, &mut line, quote);
+            tokens.push(Token {
+                tok: Tok::Literal,
+                line: start_line,
+                start: begin,
+            });
+        } else if c == '\'' {
+            if at(i + 1) == '\\' || at(i + 2) == '\'' {
+                skip_quoted(&mut i, &mut line, '\'');
+                tokens.push(Token {
+                    tok: Tok::Literal,
+                    line,
+                    start: begin,
+                });
+            } else {
+                // Lifetime or label
+                i += 1;
+                while is_ident_char(at(i)) {
+                    i += 1;
+                }
+            }
+        } else if is_ident_start(c) {
+            let start = i;
+            while is_ident_char(at(i)) {
+                i += 1;
+            }
+            tokens.push(Token {
+                tok: Tok::Ident(chars[start..i].iter().collect()),
+                line,
+                start,
+            });
+        } else if c.is_ascii_digit() {
+            while is_ident_char(at(i)) || (at(i) == '.' && at(i + 1).is_ascii_digit()) {
+                i += 1;
+            }
+            tokens.push(Token {
+                tok: Tok::Literal,
+                line,
+                start: begin,
+            });
+        } else {
+            tokens.push(Token {
+                tok: Tok::Punct(c),
+                line,
+                start: begin,
+            });
+            i += 1;
+        }
+    }
+    tokens
+}
+
+/// A name brought into scope by a `use` declaration
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Import {
+    /// `use a::b as alias;` (alias is `b` without `as`)
+    Name { alias: String, path: Vec<String> },
+    /// `use a::b::*;`
+    Glob(Vec<String>),
+}
+
+fn is_punct(tokens: &[Token], i: usize, c: char) -> bool {
+    tokens.get(i).is_some_and(|t| t.tok == Tok::Punct(c))
+}
+
+fn is_path_sep(tokens: &[Token], i: usize) -> bool {
+    is_punct(tokens, i, ':') && is_punct(tokens, i + 1, ':')
+}
+
+fn ident(tokens: &[Token], i: usize) -> Option<&str> {
+    match tokens.get(i).map(|t| &t.tok) {
+        Some(Tok::Ident(name)) => Some(name),
+        _ => None,
+    }
+}
+
+/// Parse one use tree at `*i`, appending what it imports to `out`
+fn parse_use_tree(
+    tokens: &[Token],
+    i: &mut usize,
+    prefix: &[String],
+    out: &mut Vec<(Import, u32)>,
+) {
+    let mut path = prefix.to_vec();
+    if is_path_sep(tokens, *i) {
+        *i += 2;
+    }
+    loop {
+        let line = tokens.get(*i).map_or(0, |t| t.line);
+        if is_punct(tokens, *i, '{') {
+            *i += 1;
+            while *i < tokens.len() && !is_punct(tokens, *i, '}') {
+                let start = *i;
+                parse_use_tree(tokens, i, &path, out);
+                if is_punct(tokens, *i, ',') {
+                    *i += 1;
+                } else if *i == start {
+                    // Not a use tree, e.g. `$x` in a macro body
+                    *i += 1;
+                }
+            }
+            *i += 1;
+            return;
+        }
+        if is_punct(tokens, *i, '*') {
+            *i += 1;
+            out.push((Import::Glob(path), line));
+            return;
+        }
+        let Some(name) = ident(tokens, *i) else {
+            return;
+        };
+        *i += 1;
+        // `use a::b::{self}` imports `b` itself
+        if name != "self" {
+            path.push(name.to_string());
+        }
+        if is_path_sep(tokens, *i) {
+            *i += 2;
+            continue;
+        }
+        let mut alias = path.last().cloned().unwrap_or_default();
+        if ident(tokens, *i) == Some("as") {
+            alias = ident(tokens, *i + 1).unwrap_or("_").to_string();
+            *i += 2;
+        }
+        out.push((Import::Name { alias, path }, line));
+        return;
+    }
+}
+
+/// A `::`-separated path written in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PathUse {
+    segments: Vec<String>,
+    line: u32,
+}
+
+/// Imports and (non-`use`) paths of a token stream
+fn scan(tokens: &[Token]) -> (Vec<(Import, u32)>, Vec<PathUse>) {
+    let mut imports = Vec::new();
+    let mut paths = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if ident(tokens, i) == Some("use") {
+            i += 1;
+            parse_use_tree(tokens, &mut i, &[], &mut imports);
+            while i < tokens.len() && !is_punct(tokens, i, ';') {
+                i += 1;
+            }
+            continue;
+        }
+        let continues_path = i >= 2 && is_path_sep(tokens, i - 2);
+        if let (Some(first), false) = (ident(tokens, i), continues_path) {
+            let line = tokens[i].line;
+            let mut segments = vec![first.to_string()];
+            i += 1;
+            while is_path_sep(tokens, i) {
+                let Some(next) = ident(tokens, i + 2) else {
+                    break;
+                };
+                segments.push(next.to_string());
+                i += 3;
+            }
+            paths.push(PathUse { segments, line });
+            continue;
+        }
+        i += 1;
+    }
+    (imports, paths)
+}
+
+/// Full paths `segments` may refer to, given the file's imports
+fn resolve(segments: &[String], imports: &[(Import, u32)]) -> Vec<Vec<String>> {
+    let named = imports.iter().find_map(|(import, _)| match import {
+        Import::Name { alias, path } if *alias == segments[0] => Some(path),
+        _ => None,
+    });
+    if let Some(path) = named {
+        return vec![path.iter().chain(&segments[1..]).cloned().collect()];
+    }
+    let mut candidates = vec![segments.to_vec()];
+    for (import, _) in imports {
+        if let Import::Glob(prefix) = import {
+            candidates.push(prefix.iter().chain(segments).cloned().collect());
+        }
+    }
+    candidates
+}
+
+fn starts_with(path: &[String], pattern: &[String]) -> bool {
+    path.len() >= pattern.len() && path[..pattern.len()] == *pattern
+}
+
+/// Lines of `src` that use `pattern` (`Item` or `crate::path::Item`)
+pub fn find_uses(src: &str, pattern: &str) -> BTreeSet<u32> {
+    let pattern: Vec<String> = pattern
+        .trim_start_matches("::")
+        .split("::")
+        .map(str::to_string)
+        .collect();
+    let tokens = tokenize(src);
+    if pattern.len() == 1 {
+        return tokens
+            .iter()
+            .filter(|t| t.tok == Tok::Ident(pattern[0].clone()))
+            .map(|t| t.line)
+            .collect();
+    }
+
+    let (imports, paths) = scan(&tokens);
+    let mut lines = BTreeSet::new();
+    for (import, line) in &imports {
+        if let Import::Name { path, .. } = import {
+            if starts_with(path, &pattern) {
+                lines.insert(*line);
+            }
+        }
+    }
+    for path in &paths {
+        if resolve(&path.segments, &imports)
+            .iter()
+            .any(|c| starts_with(c, &pattern))
+        {
+            lines.insert(path.line);
+        }
+    }
+    lines
+}
+
+/// Lines of `src` with a path into crate `krate` that names `item`
+///
+/// Used when only the item's name is known, not its module path.
+pub(crate) fn find_item_uses(src: &str, krate: &str, item: &str) -> BTreeSet<u32> {
+    let (imports, paths) = scan(&tokenize(src));
+    let names =
+        |p: &[String]| p.first().is_some_and(|f| f == krate) && p[1..].iter().any(|s| s == item);
+    let mut lines: BTreeSet<u32> = imports
+        .iter()
+        .filter(|(import, _)| matches!(import, Import::Name { path, .. } if names(path)))
+        .map(|(_, line)| *line)
+        .collect();
+    for path in &paths {
+        if resolve(&path.segments, &imports).iter().any(|c| names(c)) {
+            lines.insert(path.line);
+        }
+    }
+    lines
+}
+
+/// One use of the searched item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub repo: String,
+    pub file: String,
+    pub line: u32,
+    pub text: String,
+}
+
+/// Uses of `pattern` in the Rust sources of `repo`
+fn search_repo(repo: &str, repos: &[String], cwd: &Path, pattern: &str) -> Vec<Hit> {
+    let dir = project_path(cwd, repo);
+    let nested: Vec<PathBuf> = repos
+        .iter()
+        .filter(|r| r.as_str() != repo && r.as_str() != ".")
+        .map(|r| project_path(cwd, r))
+        .collect();
+    let mut files = Vec::new();
+    rust_files(&dir, &nested, &mut files);
+
+    let mut hits = Vec::new();
+    for file in &files {
+        let Ok(src) = std::fs::read_to_string(file) else {
+            continue;
+        };
+        let lines: Vec<&str> = src.lines().collect();
+        let relative = file
+            .strip_prefix(&dir)
+            .unwrap_or(file)
+            .to_string_lossy()
+            .replace('\\', "/");
+        for line in find_uses(&src, pattern) {
+            hits.push(Hit {
+                repo: repo.to_string(),
+                file: relative.clone(),
+                line,
+                text: lines
+                    .get(line as usize - 1)
+                    .map_or("", |l| l.trim())
+                    .to_string(),
+            });
+        }
+    }
+    hits
+}
+
+/// Handle `meta cargo grep-api <pattern> [--format text|json]`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let format = args::take_value(&mut args, "--format").unwrap_or_else(|| "text".to_string());
+    let Some(pattern) = args.iter().find(|a| !a.starts_with('-')).cloned() else {
+        return CommandResult::Error(
+            "usage: meta cargo grep-api <Item|crate::path::Item>".to_string(),
+        );
+    };
+    let hits: Vec<Hit> = repos
+        .iter()
+        .flat_map(|repo| search_repo(repo, repos, cwd, &pattern))
+        .collect();
+
+    match format.as_str() {
+        "text" => {
+            if hits.is_empty() {
+                return CommandResult::Message(format!("No uses of {pattern} found"));
+            }
+            let mut out = String::new();
+            for hit in &hits {
+                let file = if hit.repo == "." {
+                    hit.file.clone()
+                } else {
+                    format!("{}/{}", hit.repo, hit.file)
+                };
+                out.push_str(&format!("{file}:{}: {}\n", hit.line, hit.text));
+            }
+            let files: BTreeSet<(&str, &str)> = hits
+                .iter()
+                .map(|h| (h.repo.as_str(), h.file.as_str()))
+                .collect();
+            let repos: BTreeSet<&str> = hits.iter().map(|h| h.repo.as_str()).collect();
+            out.push_str(&format!(
+                "{} uses of {pattern} in {} files across {} repos\n",
+                hits.len(),
+                files.len(),
+                repos.len()
+            ));
+            CommandResult::Message(out)
+        }
+        "json" => {
+            let value = json!({
+                "pattern": pattern,
+                "uses": hits.iter().map(|h| json!({
+                    "repo": h.repo,
+                    "file": h.file,
+                    "line": h.line,
+                    "text": h.text,
+                })).collect::<Vec<_>>(),
+            });
+            match serde_json::to_string_pretty(&value) {
+                Ok(text) => CommandResult::Message(text),
+                Err(e) => CommandResult::Error(format!("Failed to serialize uses: {e}")),
+            }
+        }
+        other => CommandResult::Error(format!(
+            "unsupported format '{other}' (expected text or json)"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SRC: &str = r##"use core::net::{self, Client as Conn};
+use core::prelude::*;
+
+// core::net::Client in a comment
+fn main() {
+    let s = "core::net::Client";
+    let r = r#"Client"#;
+    let c = Conn::new();
+    let d = core::net::Client::default();
+    let e = net::Client::with('x', 'static_label);
+    helper::<'_>();
+}
+"##;
+
+    #[test]
+    fn test_tokenizer_skips_comments_and_literals() {
+        let idents: Vec<String> = tokenize(SRC)
+            .into_iter()
+            .filter_map(|t| match t.tok {
+                Tok::Ident(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(idents.iter().filter(|i| *i == "Client").count(), 3);
+    }
+
+    #[test]
+    fn test_resolves_imports() {
+        assert_eq!(
+            find_uses(SRC, "core::net::Client"),
+            BTreeSet::from([1, 8, 9, 10])
+        );
+        assert_eq!(
+            find_uses(SRC, "core::prelude::helper"),
+            BTreeSet::from([11])
+        );
+        assert!(find_uses(SRC, "other::Client").is_empty());
+        assert_eq!(find_uses(SRC, "default"), BTreeSet::from([9]));
+    }
+
+    #[test]
+    fn test_use_group_with_macro_tokens() {
+        let src = "macro_rules! m {\n    ($x:ident) => { use super::{$x, Client}; };\n}\nfn f() { Client::new(); }\n";
+        assert_eq!(find_uses(src, "super::Client"), BTreeSet::from([2, 4]));
+        assert!(find_item_uses(src, "core", "Client").is_empty());
+    }
+
+    #[test]
+    fn test_execute_lists_hits_per_repo() {
+        let temp_dir = TempDir::new().unwrap();
+        let src = temp_dir.path().join("app/src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::write(src.join("main.rs"), SRC).unwrap();
+        let args = vec!["core::net::Client".to_string()];
+        match execute(&args, &["app".to_string()], temp_dir.path()) {
+            CommandResult::Message(msg) => {
+                assert!(msg.contains("app/src/main.rs:8: let c = Conn::new();"));
+                assert!(msg.contains("4 uses of core::net::Client in 1 files across 1 repos"));
+            }
+            _ => panic!("Expected Message result"),
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! `meta cargo hakari`: workspace-hack crates across repos
+//!
+//! cargo-hakari keeps one `workspace-hack` crate per workspace that depends on
+//! every third-party crate with the union of the features the workspace uses,
+//! so feature unification no longer depends on which packages are built.
+//! `generate` and `verify` run the corresponding `cargo hakari` subcommand in
+//! every repo with a hakari config. The default report compares the managed
+//! dependencies across repos: a crate unified with different features in
+//! different repos is built once per feature set, which defeats a shared
+//! target dir or cache.
+
+use crate::{metadata, project_path, CommandResult, PlannedCommand};
+use anyhow::Context;
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Where cargo-hakari looks for its config, newest location first
+const HAKARI_CONFIGS: &[&str] = &[".config/hakari.toml", ".guppy/hakari.toml"];
+/// Workspace-hack package name when the config doesn't set `hakari-package`
+const DEFAULT_PACKAGE: &str = "workspace-hack";
+
+/// Dependency name -> unified features
+pub type Unified = BTreeMap<String, BTreeSet<String>>;
+
+/// Name of the workspace-hack package of the repo at `dir`, if it uses hakari
+pub fn hakari_package(dir: &Path) -> Option<String> {
+    let text = HAKARI_CONFIGS
+        .iter()
+        .find_map(|c| std::fs::read_to_string(dir.join(c)).ok())?;
+    let table = toml::from_str::<toml::Table>(&text).ok()?;
+    Some(
+        table
+            .get("hakari-package")
+            .and_then(|p| p.as_str())
+            .unwrap_or(DEFAULT_PACKAGE)
+            .to_string(),
+    )
+}
+
+/// Features per dependency in a workspace-hack manifest
+pub fn unified_features(manifest: &str) -> anyhow::Result<Unified> {
+    let table: toml::Table = toml::from_str(manifest).context("invalid Cargo.toml")?;
+    let mut unified = Unified::new();
+    for section in ["dependencies", "build-dependencies"] {
+        let Some(deps) = table.get(section).and_then(|d| d.as_table()) else {
+            continue;
+        };
+        for (key, spec) in deps {
+            let name = spec.get("package").and_then(|p| p.as_str()).unwrap_or(key);
+            let features = unified.entry(name.to_string()).or_default();
+            for feature in spec
+                .get("features")
+                .and_then(|f| f.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|f| f.as_str())
+            {
+                features.insert(feature.to_string());
+            }
+        }
+    }
+    Ok(unified)
+}
+
+/// A distinct feature set and the repos unifying to it
+pub type Variant = (BTreeSet<String>, Vec<String>);
+
+/// A dependency unified with different features in different repos
+#[derive(Debug, Clone, PartialEq)]
+pub struct Churn {
+    pub dependency: String,
+    pub variants: Vec<Variant>,
+}
+
+/// Dependencies whose unified features differ between repos
+pub fn churn(repos: &[(String, Unified)]) -> Vec<Churn> {
+    let mut by_dep: BTreeMap<&str, Vec<Variant>> = BTreeMap::new();
+    for (repo, unified) in repos {
+        for (dep, features) in unified {
+            let variants = by_dep.entry(dep).or_default();
+            match variants.iter_mut().find(|(f, _)| f == features) {
+                Some((_, users)) => users.push(repo.clone()),
+                None => variants.push((features.clone(), vec![repo.clone()])),
+            }
+        }
+    }
+    by_dep
+        .into_iter()
+        .filter(|(_, variants)| variants.len() > 1)
+        .map(|(dep, variants)| Churn {
+            dependency: dep.to_string(),
+            variants,
+        })
+        .collect()
+}
+
+/// Manifest of the workspace-hack package of the repo at `dir`
+fn hack_manifest(dir: &Path, package: &str) -> anyhow::Result<PathBuf> {
+    let packages = metadata::load_packages(dir).context("failed to load cargo metadata")?;
+    packages
+        .into_iter()
+        .find(|p| p.name == package)
+        .map(|p| p.manifest_path)
+        .with_context(|| format!("no '{package}' package (run `meta cargo hakari generate`)"))
+}
+
+/// Handle `meta cargo hakari` (the cross-repo report)
+fn report(hakari_repos: &[(String, String)], cwd: &Path) -> CommandResult {
+    let mut loaded = Vec::new();
+    let mut failures = Vec::new();
+    for (repo, package) in hakari_repos {
+        let unified = hack_manifest(&project_path(cwd, repo), package).and_then(|manifest| {
+            let text = std::fs::read_to_string(&manifest)
+                .with_context(|| format!("failed to read {}", manifest.display()))?;
+            unified_features(&text)
+        });
+        match unified {
+            Ok(u) => loaded.push((repo.clone(), u)),
+            Err(e) => failures.push(format!("{repo}: {e:#}")),
+        }
+    }
+
+    let churn = churn(&loaded);
+    let mut out = format!(
+        "{} repos with a workspace-hack crate: {}\n",
+        hakari_repos.len(),
+        hakari_repos
+            .iter()
+            .map(|(r, _)| r.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if churn.is_empty() {
+        out.push_str("Unified features are consistent across repos\n");
+    }
+    for c in &churn {
+        out.push_str(&format!(
+            "{} {} is unified with {} different feature sets\n",
+            "warning:".yellow(),
+            c.dependency,
+            c.variants.len()
+        ));
+        for (features, users) in &c.variants {
+            let features = if features.is_empty() {
+                "(default)".to_string()
+            } else {
+                features.iter().cloned().collect::<Vec<_>>().join(", ")
+            };
+            out.push_str(&format!("    {}: {features}\n", users.join(", ")));
+        }
+    }
+    for failure in &failures {
+        out.push_str(&format!("{} {failure}\n", "error:".red()));
+    }
+    if failures.is_empty() {
+        CommandResult::Message(out)
+    } else {
+        CommandResult::Error(out)
+    }
+}
+
+/// Handle `meta cargo hakari [generate|verify]`
+pub(crate) fn execute(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+) -> CommandResult {
+    let hakari_repos: Vec<(String, String)> = repos
+        .iter()
+        .filter_map(|r| hakari_package(&project_path(cwd, r)).map(|p| (r.clone(), p)))
+        .collect();
+    if hakari_repos.is_empty() {
+        return CommandResult::Message(format!(
+            "No repos use cargo-hakari (no {})",
+            HAKARI_CONFIGS[0]
+        ));
+    }
+    match args.split_first() {
+        None => report(&hakari_repos, cwd),
+        Some((sub, rest)) if sub == "generate" || sub == "verify" => {
+            let extra = rest.iter().map(|a| format!(" {a}")).collect::<String>();
+            let commands = hakari_repos
+                .iter()
+                .map(|(repo, _)| PlannedCommand {
+                    dir: repo.clone(),
+                    cmd: format!("{cargo} hakari {sub}{extra}"),
+                    env: None,
+                })
+                .collect();
+            CommandResult::Plan(commands, Some(parallel))
+        }
+        Some((sub, _)) => CommandResult::Error(format!(
+            "unknown hakari subcommand '{sub}' (expected generate or verify)"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const HACK: &str = r#"
+[package]
+name = "workspace-hack"
+version = "0.1.0"
+
+# BEGIN HAKARI SECTION
+[dependencies]
+tokio = { version = "1", features = ["net", "rt", "macros"] }
+serde = { version = "1", features = ["derive"] }
+log = "0.4"
+
+[build-dependencies]
+cc = { version = "1", default-features = false }
+# END HAKARI SECTION
+"#;
+
+    #[test]
+    fn test_unified_features() {
+        let unified = unified_features(HACK).unwrap();
+        assert_eq!(unified.len(), 4);
+        assert!(unified["tokio"].contains("macros"));
+        assert!(unified["log"].is_empty());
+    }
+
+    #[test]
+    fn test_churn_groups_feature_sets() {
+        let api = unified_features(HACK).unwrap();
+        let mut web = api.clone();
+        web.get_mut("tokio").unwrap().insert("full".to_string());
+        let worker = web.clone();
+        let churn = churn(&[
+            ("api".to_string(), api),
+            ("web".to_string(), web),
+            ("worker".to_string(), worker),
+        ]);
+        assert_eq!(churn.len(), 1);
+        assert_eq!(churn[0].dependency, "tokio");
+        assert_eq!(churn[0].variants[0].1, vec!["api"]);
+        assert_eq!(churn[0].variants[1].1, vec!["web", "worker"]);
+    }
+
+    #[test]
+    fn test_generate_plans_hakari_repos_only() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("api/.config")).unwrap();
+        std::fs::write(
+            temp_dir.path().join("api/.config/hakari.toml"),
+            "hakari-package = \"api-hack\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir(temp_dir.path().join("web")).unwrap();
+        assert_eq!(
+            hakari_package(&temp_dir.path().join("api")).as_deref(),
+            Some("api-hack")
+        );
+        let repos = vec!["api".to_string(), "web".to_string()];
+        let result = execute(
+            "cargo",
+            &["generate".to_string()],
+            &repos,
+            temp_dir.path(),
+            false,
+        );
+        match result {
+            CommandResult::Plan(commands, _) => {
+                assert_eq!(commands.len(), 1);
+                assert_eq!(commands[0].dir, "api");
+                assert_eq!(commands[0].cmd, "cargo hakari generate");
+            }
+            _ => panic!("Expected Plan result"),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Invocation history: `meta cargo history` and `meta cargo last [--rerun]`
+//!
+//! Every command is recorded in `.meta-rust/history.json` with its
+//! arguments, project selection and outcome. `meta cargo last --rerun` runs
+//! the previous invocation again exactly, which helps when narrowing down
+//! failures that only show up across repos now and then. Plans are run by
+//! meta, so their outcome is recorded as the number of planned commands.
+
+use crate::filters::ProjectFilters;
+use crate::state;
+use crate::{args, CommandResult};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// History file, relative to the meta root
+const HISTORY_FILE: &str = ".meta-rust/history.json";
+
+/// Invocations kept; older ones are dropped
+const MAX_INVOCATIONS: usize = 100;
+
+/// One recorded invocation
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Invocation {
+    /// Unix seconds
+    pub time: u64,
+    pub command: String,
+    pub args: Vec<String>,
+    pub parallel: bool,
+    pub projects: Vec<String>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub outcome: String,
+}
+
+impl Invocation {
+    pub fn filters(&self) -> ProjectFilters {
+        ProjectFilters {
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+        }
+    }
+
+    /// The invocation as a command line
+    fn command_line(&self) -> String {
+        let mut line = format!("meta {}", self.command);
+        for arg in &self.args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        if self.parallel {
+            line.push_str(" --parallel");
+        }
+        for include in &self.include {
+            line.push_str(&format!(" --include {include}"));
+        }
+        for exclude in &self.exclude {
+            line.push_str(&format!(" --exclude {exclude}"));
+        }
+        if !self.projects.is_empty() {
+            line.push_str(&format!(" (projects: {})", self.projects.join(", ")));
+        }
+        line
+    }
+}
+
+/// Recorded invocations, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    pub invocations: Vec<Invocation>,
+}
+
+impl History {
+    /// Load the history, starting empty when there is none
+    pub fn load(cwd: &Path) -> Result<Self> {
+        state::load(cwd, HISTORY_FILE)
+    }
+
+    /// Append `invocation` and write the history back, keeping the newest
+    pub fn record(&mut self, cwd: &Path, invocation: Invocation) -> Result<()> {
+        self.invocations.push(invocation);
+        let excess = self.invocations.len().saturating_sub(MAX_INVOCATIONS);
+        self.invocations.drain(..excess);
+        state::save(cwd, HISTORY_FILE, self)
+    }
+}
+
+/// Short description of a result for the history
+fn outcome(result: &CommandResult) -> String {
+    match result {
+        CommandResult::Plan(commands, _) => format!("planned {} commands", commands.len()),
+        CommandResult::Message(_) => "ok".to_string(),
+        CommandResult::Error(_) => "failed".to_string(),
+        CommandResult::ShowHelp(_) => "help".to_string(),
+    }
+}
+
+/// Record an invocation and its result, warning when the history can't be
+/// written
+pub(crate) fn record(
+    cwd: &Path,
+    command: &str,
+    args: &[String],
+    parallel: bool,
+    projects: &[String],
+    filters: &ProjectFilters,
+    result: &CommandResult,
+) {
+    let invocation = Invocation {
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        command: command.to_string(),
+        args: args.to_vec(),
+        parallel,
+        projects: projects.to_vec(),
+        include: filters.include.clone(),
+        exclude: filters.exclude.clone(),
+        outcome: outcome(result),
+    };
+    let recorded = History::load(cwd).and_then(|mut h| h.record(cwd, invocation));
+    if let Err(e) = recorded {
+        eprintln!("warning: failed to record history: {e:#}");
+    }
+}
+
+/// Handle `meta cargo history [--limit <n>]`
+pub(crate) fn execute_history(args: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let limit = match args::take_value(&mut args, "--limit").map(|l| l.parse::<usize>()) {
+        None => 20,
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => return CommandResult::Error("--limit expects a number".to_string()),
+    };
+    let history = match History::load(cwd) {
+        Ok(h) => h,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    if history.invocations.is_empty() {
+        return CommandResult::Message("No recorded invocations".to_string());
+    }
+    let skip = history.invocations.len().saturating_sub(limit);
+    let mut out = String::new();
+    for (i, invocation) in history.invocations.iter().enumerate().skip(skip) {
+        out.push_str(&format!(
+            "{:>4}  {}  [{}]\n",
+            i + 1,
+            invocation.command_line(),
+            invocation.outcome
+        ));
+    }
+    CommandResult::Message(out)
+}
+
+/// The previous invocation for `meta cargo last`, or the message to show
+///
+/// Returns the invocation to run again with `--rerun`.
+pub(crate) fn execute_last(args: &[String], cwd: &Path) -> Result<Invocation, CommandResult> {
+    let mut args = args.to_vec();
+    let rerun = args::take_flag(&mut args, "--rerun");
+    let history = History::load(cwd).map_err(|e| CommandResult::Error(format!("{e:#}")))?;
+    let Some(last) = history.invocations.last() else {
+        return Err(CommandResult::Error(
+            "No recorded invocation to repeat".to_string(),
+        ));
+    };
+    if !rerun {
+        return Err(CommandResult::Message(format!(
+            "{}  [{}]",
+            last.command_line(),
+            last.outcome
+        )));
+    }
+    eprintln!("rerunning: {}", last.command_line());
+    Ok(last.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_record_and_repeat_last() {
+        let temp_dir = TempDir::new().unwrap();
+        let cwd = temp_dir.path();
+        let filters = ProjectFilters::new(Some(vec!["libs/*".to_string()]), None);
+        let args = vec!["--release".to_string()];
+        for command in ["cargo build", "cargo test"] {
+            record(
+                cwd,
+                command,
+                &args,
+                true,
+                &[],
+                &filters,
+                &CommandResult::Message(String::new()),
+            );
+        }
+
+        match execute_history(&["--limit".to_string(), "1".to_string()], cwd) {
+            CommandResult::Message(out) => {
+                assert_eq!(
+                    out,
+                    "   2  meta cargo test --release --parallel --include libs/*  [ok]\n"
+                );
+            }
+            _ => panic!("Expected Message result"),
+        }
+        match execute_last(&[], cwd) {
+            Err(CommandResult::Message(out)) => assert!(out.starts_with("meta cargo test")),
+            _ => panic!("Expected Message result"),
+        }
+        let last = execute_last(&["--rerun".to_string()], cwd).unwrap();
+        assert_eq!(last.command, "cargo test");
+        assert_eq!(last.filters().include, vec!["libs/*"]);
+        assert!(last.parallel);
+    }
+
+    #[test]
+    fn test_last_without_history() {
+        let temp_dir = TempDir::new().unwrap();
+        match execute_last(&["--rerun".to_string()], temp_dir.path()) {
+            Err(CommandResult::Error(e)) => assert!(e.contains("No recorded invocation")),
+            _ => panic!("Expected Error result"),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Static HTML report for run results
+//!
+//! Writes an `index.html` overview plus one page per repo with its command,
+//! diagnostics and full log. The site has no external assets, so it can be
+//! published as a CI artifact as-is.
+
+use crate::diagnostics::{self, Level};
+use crate::ndjson;
+use crate::runner::RunOutcome;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em}\
+table{border-collapse:collapse}td,th{padding:4px 12px;border-bottom:1px solid #ddd;text-align:left}\
+.ok{color:#1a7f37}.fail{color:#cf222e}.warning{color:#9a6700}\
+pre{background:#f6f8fa;padding:1em;overflow-x:auto}";
+
+/// Escape text for inclusion in HTML
+pub(crate) fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// File name of the page for a repo dir, distinct for every dir
+fn page_name(dir: &str) -> String {
+    format!("{}.html", ndjson::file_stem(dir))
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title><style>{STYLE}</style></head>\n<body>\n{body}</body></html>\n",
+        escape(title)
+    )
+}
+
+fn status_cell(outcome: &RunOutcome) -> &'static str {
+    if outcome.success {
+        "<span class=\"ok\">passed</span>"
+    } else {
+        "<span class=\"fail\">failed</span>"
+    }
+}
+
+fn render_index(outcomes: &[RunOutcome]) -> String {
+    let failed = outcomes.iter().filter(|o| !o.success).count();
+    let mut body = format!(
+        "<h1>meta cargo report</h1>\n<p>{} repo(s): {} passed, {failed} failed</p>\n<table>\n<tr><th>Repo</th><th>Status</th><th>Command</th><th>Time</th><th>Errors</th><th>Warnings</th></tr>\n",
+        outcomes.len(),
+        outcomes.len() - failed
+    );
+    for outcome in outcomes {
+        let (errors, warnings) = diagnostics::counts(&diagnostics::parse(&outcome.output()));
+        body.push_str(&format!(
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td><code>{}</code></td><td>{:.1}s</td><td>{errors}</td><td>{warnings}</td></tr>\n",
+            page_name(&outcome.dir),
+            escape(&outcome.dir),
+            status_cell(outcome),
+            escape(&outcome.cmd),
+            outcome.duration.as_secs_f64(),
+        ));
+    }
+    body.push_str("</table>\n");
+    page("meta cargo report", &body)
+}
+
+fn render_repo_page(outcome: &RunOutcome) -> String {
+    let output = outcome.output();
+    let mut body = format!(
+        "<p><a href=\"index.html\">&larr; all repos</a></p>\n<h1>{}</h1>\n<p>{} &middot; <code>{}</code> &middot; {:.1}s",
+        escape(&outcome.dir),
+        status_cell(outcome),
+        escape(&outcome.cmd),
+        outcome.duration.as_secs_f64()
+    );
+    if let Some(code) = outcome.exit_code {
+        body.push_str(&format!(" &middot; exit code {code}"));
+    }
+    body.push_str("</p>\n");
+
+    let diags = diagnostics::parse(&output);
+    if !diags.is_empty() {
+        body.push_str("<h2>Diagnostics</h2>\n<ul>\n");
+        for d in &diags {
+            let class = match d.level {
+                Level::Error => "fail",
+                Level::Warning => "warning",
+            };
+            body.push_str(&format!(
+                "<li><span class=\"{class}\">{}</span> <code>{}:{}:{}</code> {}</li>\n",
+                d.level.as_str(),
+                escape(&d.file),
+                d.line,
+                d.column,
+                escape(&d.message)
+            ));
+        }
+        body.push_str("</ul>\n");
+    }
+    body.push_str(&format!("<h2>Log</h2>\n<pre>{}</pre>\n", escape(&output)));
+    page(&outcome.dir, &body)
+}
+
+/// Write the report site into `dir`, returning the path of `index.html`
+pub(crate) fn write_report(dir: &Path, outcomes: &[RunOutcome]) -> anyhow::Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    for outcome in outcomes {
+        let path = dir.join(page_name(&outcome.dir));
+        std::fs::write(&path, render_repo_page(outcome))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    let index = dir.join("index.html");
+    std::fs::write(&index, render_index(outcomes))
+        .with_context(|| format!("failed to write {}", index.display()))?;
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_write_report() {
+        let temp_dir = TempDir::new().unwrap();
+        let outcomes = vec![
+            RunOutcome::fixture(".", true),
+            RunOutcome::fixture("libs/core", false)
+                .with_stderr("error[E0308]: mismatched types\n --> src/lib.rs:1:1\n"),
+        ];
+        let index = write_report(&temp_dir.path().join("report"), &outcomes).unwrap();
+
+        let html = std::fs::read_to_string(index).unwrap();
+        assert!(html.contains("1 passed, 1 failed"));
+        assert!(html.contains("href=\"libs~2Fcore.html\""));
+
+        let page =
+            std::fs::read_to_string(temp_dir.path().join("report/libs~2Fcore.html")).unwrap();
+        assert!(page.contains("mismatched types"));
+        assert!(page.contains("exit code 101"));
+        assert_ne!(page_name("libs-core"), page_name("libs/core"));
+    }
+}
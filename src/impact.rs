@@ -0,0 +1,333 @@
+//! `meta cargo impact <crate>`: blast radius of a pending change
+//!
+//! Public items of the crate touched since a ref (the working tree against
+//! `HEAD` by default) are taken from the git diff: items whose span covers a
+//! changed line, plus declarations removed outright. Every repo depending on
+//! the crate, per the cross-repo graph, is then searched for paths naming
+//! those items (see [`crate::grep_api`]).
+
+use crate::env_audit::rust_files;
+use crate::graph::CrateGraph;
+use crate::grep_api::{find_item_uses, tokenize, Tok, Token};
+use crate::{args, git, project_path, CommandResult};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Item keywords whose `pub` declarations form the public surface
+const ITEM_KEYWORDS: &[&str] = &[
+    "fn", "struct", "enum", "trait", "type", "const", "static", "union",
+];
+
+/// A `pub` item declaration
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PubItem {
+    pub kind: String,
+    pub name: String,
+    /// First and last line of the declaration, body included
+    pub lines: (u32, u32),
+}
+
+fn ident(tokens: &[Token], i: usize) -> Option<&str> {
+    match tokens.get(i).map(|t| &t.tok) {
+        Some(Tok::Ident(name)) => Some(name),
+        _ => None,
+    }
+}
+
+/// Line on which the item whose name is at `start` ends
+fn item_end(tokens: &[Token], start: usize) -> u32 {
+    let mut depth = 0i32;
+    for (k, token) in tokens.iter().enumerate().skip(start) {
+        match token.tok {
+            Tok::Punct('(' | '[') => depth += 1,
+            Tok::Punct(')' | ']') => depth -= 1,
+            Tok::Punct(';') if depth == 0 => return token.line,
+            Tok::Punct('{') if depth == 0 => {
+                let mut braces = 0;
+                for t in &tokens[k..] {
+                    match t.tok {
+                        Tok::Punct('{') => braces += 1,
+                        Tok::Punct('}') => {
+                            braces -= 1;
+                            if braces == 0 {
+                                return t.line;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+    tokens.last().map_or(0, |t| t.line)
+}
+
+/// `pub` items declared in `src`, including methods in impl blocks
+///
+/// `pub(crate)` and narrower items are skipped, as are re-exports.
+pub fn pub_items(src: &str) -> Vec<PubItem> {
+    let tokens = tokenize(src);
+    let mut items = Vec::new();
+    for i in 0..tokens.len() {
+        if ident(&tokens, i) != Some("pub")
+            || tokens.get(i + 1).map(|t| &t.tok) == Some(&Tok::Punct('('))
+        {
+            continue;
+        }
+        let mut j = i + 1;
+        while matches!(ident(&tokens, j), Some("async" | "unsafe" | "extern"))
+            || tokens.get(j).is_some_and(|t| t.tok == Tok::Literal)
+            || (ident(&tokens, j) == Some("const") && ident(&tokens, j + 1) == Some("fn"))
+        {
+            j += 1;
+        }
+        let (Some(kind), Some(name)) = (ident(&tokens, j), ident(&tokens, j + 1)) else {
+            continue;
+        };
+        if !ITEM_KEYWORDS.contains(&kind) {
+            continue;
+        }
+        items.push(PubItem {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            lines: (tokens[i].line, item_end(&tokens, j + 1)),
+        });
+    }
+    items
+}
+
+/// `(kind, name, file)` of public items declared on lines removed by a
+/// `git diff -U0`
+fn removed_items(diff: &str) -> Vec<(String, String, String)> {
+    let mut removed = Vec::new();
+    let mut file = String::new();
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("--- ") {
+            file = path.strip_prefix("a/").unwrap_or(path).to_string();
+        } else if let Some(code) = line.strip_prefix('-') {
+            for item in pub_items(code) {
+                removed.push((item.kind, item.name, file.clone()));
+            }
+        }
+    }
+    removed
+}
+
+/// A changed public item and where it lives
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ChangedItem {
+    pub name: String,
+    pub kind: String,
+    /// `file:line`, or `file (removed)`
+    pub location: String,
+}
+
+/// Public items of the crate at `root` changed since `since`
+fn changed_items(root: &Path, since: &str) -> anyhow::Result<Vec<ChangedItem>> {
+    let mut items = BTreeSet::new();
+    for (file, ranges) in git::changed_lines(root, since)? {
+        if !file.ends_with(".rs") {
+            continue;
+        }
+        let Ok(src) = std::fs::read_to_string(root.join(&file)) else {
+            continue;
+        };
+        for item in pub_items(&src) {
+            let (start, end) = item.lines;
+            if ranges.iter().any(|&(a, b)| a <= end && start <= b) {
+                items.insert(ChangedItem {
+                    name: item.name,
+                    kind: item.kind,
+                    location: format!("{file}:{start}"),
+                });
+            }
+        }
+    }
+    let diff = git::run(
+        root,
+        &[
+            "diff",
+            "-U0",
+            "--no-color",
+            "--no-ext-diff",
+            "--relative",
+            since,
+        ],
+    )?;
+    for (kind, name, file) in removed_items(&diff) {
+        if !items.iter().any(|i| i.name == name && i.kind == kind) {
+            items.insert(ChangedItem {
+                name,
+                kind,
+                location: format!("{file} (removed)"),
+            });
+        }
+    }
+    Ok(items.into_iter().collect())
+}
+
+/// `(file, line, item)` uses of `items` from crate `krate` in `repo`
+fn uses_in_repo(
+    repo: &str,
+    repos: &[String],
+    cwd: &Path,
+    krate: &str,
+    items: &[ChangedItem],
+) -> Vec<(String, u32, String)> {
+    let dir = project_path(cwd, repo);
+    let nested: Vec<PathBuf> = repos
+        .iter()
+        .filter(|r| r.as_str() != repo && r.as_str() != ".")
+        .map(|r| project_path(cwd, r))
+        .collect();
+    let mut files = Vec::new();
+    rust_files(&dir, &nested, &mut files);
+
+    let mut uses = Vec::new();
+    for file in files {
+        let Ok(src) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        let relative = file
+            .strip_prefix(&dir)
+            .unwrap_or(&file)
+            .to_string_lossy()
+            .replace('\\', "/");
+        let names: BTreeSet<&str> = items.iter().map(|i| i.name.as_str()).collect();
+        for name in names {
+            for line in find_item_uses(&src, krate, name) {
+                uses.push((relative.clone(), line, name.to_string()));
+            }
+        }
+    }
+    uses.sort();
+    uses
+}
+
+/// Handle `meta cargo impact <crate> [--since <ref>]`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let since = args::take_value(&mut args, "--since").unwrap_or_else(|| "HEAD".to_string());
+    let Some(name) = args.iter().find(|a| !a.starts_with('-')).cloned() else {
+        return CommandResult::Error(
+            "usage: meta cargo impact <crate> [--since <ref>]".to_string(),
+        );
+    };
+
+    let graph = match CrateGraph::load(repos, cwd) {
+        Ok(g) => g,
+        Err(e) => return CommandResult::Error(format!("Failed to load crate graph: {e:#}")),
+    };
+    let Some(index) = graph.crates.iter().position(|c| c.name == name) else {
+        return CommandResult::Error(format!("crate '{name}' is not part of any repo"));
+    };
+    let node = &graph.crates[index];
+    let items = match changed_items(&node.root, &since) {
+        Ok(items) => items,
+        Err(e) => return CommandResult::Error(format!("{}: {e:#}", node.repo)),
+    };
+
+    let mut out = format!("Changed public items of {name} (since {since}):\n");
+    if items.is_empty() {
+        out.push_str("  none\n");
+        return CommandResult::Message(out);
+    }
+    for item in &items {
+        out.push_str(&format!(
+            "  {} {} ({})\n",
+            item.kind, item.name, item.location
+        ));
+    }
+
+    let dependents = graph.dependents(&BTreeSet::from([index]));
+    let mut downstream: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for i in dependents.iter().filter(|&&i| i != index) {
+        let c = &graph.crates[*i];
+        downstream.entry(&c.repo).or_default().push(&c.name);
+    }
+    if downstream.is_empty() {
+        out.push_str("\nNo repo depends on this crate\n");
+        return CommandResult::Message(out);
+    }
+
+    let krate = name.replace('-', "_");
+    let mut using = 0;
+    out.push_str("\nDownstream repos:\n");
+    for (repo, crates) in &downstream {
+        let uses = uses_in_repo(repo, repos, cwd, &krate, &items);
+        if uses.is_empty() {
+            out.push_str(&format!(
+                "  {repo} ({}): no uses of changed items\n",
+                crates.join(", ")
+            ));
+            continue;
+        }
+        using += 1;
+        out.push_str(&format!(
+            "  {repo} ({}): {} uses\n",
+            crates.join(", "),
+            uses.len()
+        ));
+        for (file, line, item) in uses {
+            out.push_str(&format!("      {file}:{line} {item}\n"));
+        }
+    }
+    out.push_str(&format!(
+        "\nBlast radius: {using} of {} dependent repos use changed items\n",
+        downstream.len()
+    ));
+    CommandResult::Message(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pub_items_spans() {
+        let src = "pub struct Client {\n    addr: String,\n}\n\nimpl Client {\n    pub async fn connect(&self) {\n        todo!()\n    }\n    pub(crate) fn internal() {}\n}\n\npub const fn limit() -> usize { 4 }\npub use other::Thing;\npub type Alias = Vec<u8>;\n";
+        let items: Vec<(String, (u32, u32))> = pub_items(src)
+            .into_iter()
+            .map(|i| (i.name, i.lines))
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                ("Client".to_string(), (1, 3)),
+                ("connect".to_string(), (6, 8)),
+                ("limit".to_string(), (12, 12)),
+                ("Alias".to_string(), (14, 14)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_removed_items_from_diff() {
+        let diff = "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -4 +3,0 @@\n-pub fn gone(x: u8) -> u8 {\n-    // pub fn not_code\n";
+        assert_eq!(
+            removed_items(diff),
+            vec![(
+                "fn".to_string(),
+                "gone".to_string(),
+                "src/lib.rs".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_item_uses_need_crate_path() {
+        let src =
+            "use core_net::{Client, other};\nfn f() { core_net::io::connect(); connect(); }\n";
+        assert_eq!(
+            find_item_uses(src, "core_net", "Client"),
+            BTreeSet::from([1])
+        );
+        assert_eq!(
+            find_item_uses(src, "core_net", "connect"),
+            BTreeSet::from([2])
+        );
+        assert!(find_item_uses(src, "other_crate", "Client").is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! `meta cargo info`: toolchain and manifest facts of every repo as one JSON
+//!
+//! rustc runs inside each repo so rustup honors its `rust-toolchain` file.
+//! Repos sharing a toolchain share one entry under `toolchains`, which makes
+//! it easy to spot the repo that builds with something different.
+
+use crate::metadata;
+use crate::runner::{self, RunOutcome};
+use crate::{project_path, CommandResult, PlannedCommand};
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+/// rustc queries run in each repo, keyed by their name in the JSON
+const QUERIES: &[(&str, &str)] = &[
+    ("version", "rustc -vV"),
+    ("sysroot", "rustc --print sysroot"),
+    ("cfg", "rustc --print cfg"),
+    ("target_list", "rustc --print target-list"),
+];
+
+/// `key: value` lines of `rustc -vV` as a JSON object
+fn parse_version(output: &str) -> Value {
+    let mut fields = Map::new();
+    for line in output.lines() {
+        if let Some((key, value)) = line.split_once(": ") {
+            fields.insert(key.trim().replace(' ', "_"), json!(value.trim()));
+        } else if line.starts_with("rustc ") {
+            fields.insert("release_line".to_string(), json!(line.trim()));
+        }
+    }
+    Value::Object(fields)
+}
+
+/// Toolchain facts from the outcomes of [`QUERIES`], in order
+fn toolchain_json(outcomes: &[&RunOutcome]) -> Result<Value, String> {
+    if let Some(failed) = outcomes.iter().find(|o| !o.success) {
+        return Err(format!("`{}` failed: {}", failed.cmd, failed.stderr.trim()));
+    }
+    let lines = |o: &RunOutcome| -> Vec<String> {
+        o.stdout
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    Ok(json!({
+        "version": parse_version(&outcomes[0].stdout),
+        "sysroot": outcomes[1].stdout.trim(),
+        "cfg": lines(outcomes[2]),
+        "target_list": lines(outcomes[3]),
+    }))
+}
+
+/// Manifest facts of a repo's packages
+fn packages_json(repo_dir: &Path) -> Value {
+    match metadata::load_packages(repo_dir) {
+        Ok(packages) => json!(packages
+            .iter()
+            .map(|p| json!({
+                "name": p.name,
+                "version": p.version,
+                "edition": p.edition,
+                "rust_version": p.rust_version,
+                "has_build_script": p.has_target("custom-build"),
+            }))
+            .collect::<Vec<_>>()),
+        Err(e) => json!({ "error": format!("{e:#}") }),
+    }
+}
+
+/// Handle `meta cargo info`
+pub(crate) fn execute(repos: &[String], cwd: &Path, parallel: bool) -> CommandResult {
+    let commands: Vec<PlannedCommand> = repos
+        .iter()
+        .flat_map(|repo| {
+            QUERIES.iter().map(move |(_, cmd)| PlannedCommand {
+                dir: repo.clone(),
+                cmd: cmd.to_string(),
+                env: None,
+            })
+        })
+        .collect();
+    let outcomes = runner::run_all(cwd, &commands, parallel);
+
+    // Toolchains are keyed by their `rustc -vV` release line plus host
+    let mut toolchains = Map::new();
+    let mut repo_entries = Map::new();
+    for (repo, chunk) in repos.iter().zip(outcomes.chunks(QUERIES.len())) {
+        let chunk: Vec<&RunOutcome> = chunk.iter().collect();
+        let mut entry = Map::new();
+        match toolchain_json(&chunk) {
+            Ok(toolchain) => {
+                let version = &toolchain["version"];
+                let key = format!(
+                    "{} ({})",
+                    version["release_line"].as_str().unwrap_or("unknown"),
+                    version["host"].as_str().unwrap_or("unknown host")
+                );
+                entry.insert("toolchain".to_string(), json!(key));
+                toolchains.entry(key).or_insert(toolchain);
+            }
+            Err(e) => {
+                entry.insert("toolchain_error".to_string(), json!(e));
+            }
+        }
+        entry.insert(
+            "packages".to_string(),
+            packages_json(&project_path(cwd, repo)),
+        );
+        repo_entries.insert(repo.clone(), Value::Object(entry));
+    }
+
+    let info = json!({ "toolchains": toolchains, "repos": repo_entries });
+    match serde_json::to_string_pretty(&info) {
+        Ok(text) => CommandResult::Message(text),
+        Err(e) => CommandResult::Error(format!("Failed to serialize info: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        let v = parse_version(
+            "rustc 1.80.0 (051478957 2024-07-21)\nbinary: rustc\nhost: x86_64-unknown-linux-gnu\nLLVM version: 18.1.7\n",
+        );
+        assert_eq!(v["host"], "x86_64-unknown-linux-gnu");
+        assert_eq!(v["LLVM_version"], "18.1.7");
+        assert_eq!(v["release_line"], "rustc 1.80.0 (051478957 2024-07-21)");
+    }
+
+    #[test]
+    fn test_info_for_repo() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir(temp_dir.path().join("src")).unwrap();
+        std::fs::write(temp_dir.path().join("src/lib.rs"), "").unwrap();
+
+        let result = execute(&[".".to_string()], temp_dir.path(), false);
+        let CommandResult::Message(text) = result else {
+            panic!("Expected Message result");
+        };
+        let info: Value = serde_json::from_str(&text).unwrap();
+        let repo = &info["repos"]["."];
+        assert_eq!(repo["packages"][0]["name"], "demo");
+        let toolchain = repo["toolchain"].as_str().unwrap();
+        assert!(info["toolchains"][toolchain]["cfg"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|c| c.as_str().unwrap().starts_with("target_os=")));
+    }
+}
@@ -0,0 +1,243 @@
+//! `meta cargo integration`: build binaries, then test against them
+//!
+//! The `[integration]` binary repos are built first. Every `bin` target they
+//! define is exposed to the test repos as `META_BIN_<NAME>` (upper-cased,
+//! `-` replaced by `_`), pointing into the binary repo's target directory.
+//! Tests only start once every build succeeded and every configured
+//! fixture is ready (see [`crate::fixtures`]); fixtures are stopped afterwards
+//! whatever the outcome.
+
+use crate::config::{Config, IntegrationConfig};
+use crate::output::OutputOptions;
+use crate::runner::{self, RunOutcome};
+use crate::{
+    cargo_config, fixtures, limits, metadata, project_path, target_dir, CommandResult,
+    PlannedCommand,
+};
+use anyhow::{bail, Context};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Environment variable exposing binary `name`
+pub fn bin_var(name: &str) -> String {
+    format!("META_BIN_{}", name.to_uppercase().replace('-', "_"))
+}
+
+/// Profile directory under the target dir for the cargo `args`
+pub(crate) fn profile_dir(args: &[String]) -> String {
+    let mut profile = None;
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--release" || arg == "-r" {
+            profile = Some("release".to_string());
+        } else if arg == "--profile" {
+            profile = args.get(i + 1).cloned();
+        } else if let Some(p) = arg.strip_prefix("--profile=") {
+            profile = Some(p.to_string());
+        }
+    }
+    match profile.as_deref() {
+        None | Some("dev") | Some("test") => "debug".to_string(),
+        Some("bench") => "release".to_string(),
+        Some(p) => p.to_string(),
+    }
+}
+
+/// `META_BIN_*` variables for the bin targets of the binary repos
+fn binary_env(
+    binaries: &[String],
+    cwd: &Path,
+    root_config: Option<&Path>,
+    profile: &str,
+) -> anyhow::Result<BTreeMap<String, PathBuf>> {
+    let mut env = BTreeMap::new();
+    let mut owners: HashMap<String, &str> = HashMap::new();
+    for repo in binaries {
+        let dir = project_path(cwd, repo);
+        let target = target_dir::resolve(&dir, root_config)
+            .with_context(|| format!("{repo}: failed to resolve target dir"))?;
+        let packages = metadata::load_packages(&dir)
+            .with_context(|| format!("{repo}: failed to load cargo metadata"))?;
+        for bin in packages.iter().flat_map(|p| &p.targets) {
+            if !bin.kinds.iter().any(|k| k == "bin") {
+                continue;
+            }
+            let var = bin_var(&bin.name);
+            if let Some(other) = owners.insert(var.clone(), repo) {
+                bail!(
+                    "binary '{}' is defined by both {other} and {repo}",
+                    bin.name
+                );
+            }
+            let file = format!("{}{}", bin.name, std::env::consts::EXE_SUFFIX);
+            env.insert(var, target.path.join(profile).join(file));
+        }
+    }
+    Ok(env)
+}
+
+/// Every configured integration repo must be a Rust project of the meta repo
+fn check_repos(config: &IntegrationConfig, repos: &[String]) -> Result<(), String> {
+    if config.binaries.is_empty() || config.tests.is_empty() {
+        return Err(
+            "meta cargo integration needs [integration] binaries and tests in .meta-rust.toml"
+                .to_string(),
+        );
+    }
+    for repo in config.binaries.iter().chain(&config.tests) {
+        if !repos.contains(repo) {
+            return Err(format!(
+                "[integration] repo '{repo}' is not a Rust project of this meta repo"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Run `commands` with the configured limits and concurrency classes
+fn run_stage(
+    cwd: &Path,
+    mut commands: Vec<PlannedCommand>,
+    parallel: bool,
+    config: &Config,
+) -> Result<Vec<RunOutcome>, String> {
+    limits::apply(&mut commands, &config.limits)?;
+    let limits = runner::concurrency_for(&commands, &config.concurrency)?;
+    Ok(runner::run_all_limited(cwd, &commands, parallel, &limits))
+}
+
+/// Handle `meta cargo integration [cargo args]`
+///
+/// `args` go to both `cargo build --bins` and `cargo test`, so `--release`
+/// tests against release binaries.
+pub(crate) fn execute(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+    output: &OutputOptions,
+) -> CommandResult {
+    if let Err(e) = check_repos(&config.integration, repos) {
+        return CommandResult::Error(e);
+    }
+    let root_config = cargo_config::root_config(cwd, config);
+    let env = match binary_env(
+        &config.integration.binaries,
+        cwd,
+        root_config.as_deref(),
+        &profile_dir(args),
+    ) {
+        Ok(env) => env,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    let extra = args.iter().map(|a| format!(" {a}")).collect::<String>();
+    // Without a chosen format, show full logs: a failed build explains why
+    // the tests never ran
+    let mut output = output.clone();
+    if !output.is_active() {
+        output.ordered_output = true;
+    }
+
+    let builds = config
+        .integration
+        .binaries
+        .iter()
+        .map(|repo| PlannedCommand {
+            dir: repo.clone(),
+            cmd: format!("{cargo} build --bins{extra}"),
+            env: None,
+        })
+        .collect();
+    let mut outcomes = match run_stage(cwd, builds, parallel, config) {
+        Ok(o) => o,
+        Err(e) => return CommandResult::Error(e),
+    };
+    if outcomes.iter().any(|o| !o.success) {
+        return output.deliver(cwd, &outcomes);
+    }
+
+    let bin_env: HashMap<String, String> = env
+        .iter()
+        .map(|(var, path)| (var.clone(), path.display().to_string()))
+        .collect();
+    let running = match fixtures::start_all(&config.integration.fixtures, cwd, &bin_env) {
+        Ok(r) => r,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    let mut test_env = bin_env;
+    test_env.extend(fixtures::test_env(&config.integration.fixtures));
+    let tests = config
+        .integration
+        .tests
+        .iter()
+        .map(|repo| PlannedCommand {
+            dir: repo.clone(),
+            cmd: format!("{cargo} test{extra}"),
+            env: Some(test_env.clone()),
+        })
+        .collect();
+    let stage = run_stage(cwd, tests, parallel, config);
+    let warnings = fixtures::stop_all(running);
+    match stage {
+        Ok(o) => outcomes.extend(o),
+        Err(e) => return CommandResult::Error(e),
+    }
+    let warnings: String = warnings.iter().map(|w| format!("warning: {w}\n")).collect();
+    match output.deliver(cwd, &outcomes) {
+        CommandResult::Message(text) => CommandResult::Message(text + &warnings),
+        CommandResult::Error(text) => CommandResult::Error(text + &warnings),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::strings;
+
+    #[test]
+    fn test_bin_var_and_profile() {
+        assert_eq!(bin_var("api-server"), "META_BIN_API_SERVER");
+        assert_eq!(profile_dir(&[]), "debug");
+        assert_eq!(profile_dir(&strings(&["--release"])), "release");
+        assert_eq!(profile_dir(&strings(&["--profile", "ci"])), "ci");
+        assert_eq!(profile_dir(&strings(&["--profile=dev"])), "debug");
+    }
+
+    #[test]
+    fn test_check_repos() {
+        let config =
+            Config::parse("[integration]\nbinaries = [\"server\"]\ntests = [\"e2e\"]\n").unwrap();
+        assert!(check_repos(&config.integration, &strings(&["server", "e2e"])).is_ok());
+        let err = check_repos(&config.integration, &strings(&["server"])).unwrap_err();
+        assert!(err.contains("'e2e'"));
+        assert!(check_repos(&IntegrationConfig::default(), &[]).is_err());
+    }
+
+    #[test]
+    fn test_metadata_failure_is_error() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for repo in ["server", "e2e"] {
+            std::fs::create_dir(temp_dir.path().join(repo)).unwrap();
+        }
+        let config =
+            Config::parse("[integration]\nbinaries = [\"server\"]\ntests = [\"e2e\"]\n").unwrap();
+        // `server` has no manifest, so metadata fails before anything runs
+        let result = execute(
+            "cargo",
+            &[],
+            &strings(&["server", "e2e"]),
+            temp_dir.path(),
+            false,
+            &config,
+            &OutputOptions::default(),
+        );
+        match result {
+            CommandResult::Error(msg) => {
+                assert!(msg.contains("server: failed to load cargo metadata"))
+            }
+            _ => panic!("Expected Error result"),
+        }
+    }
+}
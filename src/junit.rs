@@ -0,0 +1,121 @@
+//! JUnit XML report for cross-repo runs (`--report junit=<file>`)
+//!
+//! Every repo becomes a `<testsuite>` with one `<testcase>` per libtest case.
+//! Repos without parsed tests (build failures, non-test commands) get a
+//! single test case named after the command, so CI still shows them.
+
+use crate::html;
+use crate::libtest::{self, TestStatus};
+use crate::runner::RunOutcome;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Escape text for XML, dropping control characters XML 1.0 cannot hold
+/// (e.g. the ESC of ANSI colors)
+fn xml(text: &str) -> String {
+    let text: String = text
+        .chars()
+        .filter(|&c| c >= ' ' || matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    html::escape(&text)
+}
+
+fn render_suite(outcome: &RunOutcome, out: &mut String) -> usize {
+    let repo = xml(&outcome.dir);
+    let secs = outcome.duration.as_secs_f64();
+    let cases = libtest::parse(&outcome.output());
+    if cases.is_empty() {
+        let failures = usize::from(!outcome.success);
+        out.push_str(&format!(
+            "  <testsuite name=\"{repo}\" tests=\"1\" failures=\"{failures}\" skipped=\"0\" time=\"{secs:.3}\">\n"
+        ));
+        out.push_str(&format!(
+            "    <testcase classname=\"{repo}\" name=\"{}\" time=\"{secs:.3}\"",
+            xml(&outcome.cmd)
+        ));
+        if outcome.success {
+            out.push_str("/>\n");
+        } else {
+            let message = match outcome.exit_code {
+                Some(code) => format!("exit code {code}"),
+                None => "terminated by signal".to_string(),
+            };
+            out.push_str(&format!(
+                ">\n      <failure message=\"{message}\">{}</failure>\n    </testcase>\n",
+                xml(&outcome.output())
+            ));
+        }
+        out.push_str("  </testsuite>\n");
+        return 1;
+    }
+
+    let count = |status| cases.iter().filter(|c| c.status == status).count();
+    out.push_str(&format!(
+        "  <testsuite name=\"{repo}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{secs:.3}\">\n",
+        cases.len(),
+        count(TestStatus::Failed),
+        count(TestStatus::Ignored)
+    ));
+    for case in &cases {
+        out.push_str(&format!(
+            "    <testcase classname=\"{repo}\" name=\"{}\"",
+            xml(&case.name)
+        ));
+        match case.status {
+            TestStatus::Passed => out.push_str("/>\n"),
+            TestStatus::Ignored => out.push_str(">\n      <skipped/>\n    </testcase>\n"),
+            TestStatus::Failed => out.push_str(&format!(
+                ">\n      <failure message=\"test failed\">{}</failure>\n    </testcase>\n",
+                xml(&case.output)
+            )),
+        }
+    }
+    out.push_str("  </testsuite>\n");
+    cases.len()
+}
+
+/// JUnit XML for `outcomes` and the number of test cases in it
+pub fn render(outcomes: &[RunOutcome]) -> (String, usize) {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+    let mut cases = 0;
+    for outcome in outcomes {
+        cases += render_suite(outcome, &mut out);
+    }
+    out.push_str("</testsuites>\n");
+    (out, cases)
+}
+
+/// Write the report to `path`, returning the number of test cases
+pub fn write(path: &Path, outcomes: &[RunOutcome]) -> Result<usize> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let (xml, cases) = render(outcomes);
+    std::fs::write(path, xml).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(cases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_suites_per_repo() {
+        let tests = "test a ... ok\ntest b ... ignored\ntest c ... FAILED\n\nfailures:\n\n---- c stdout ----\nassert <x>\n\nfailures:\n";
+        let (xml, cases) = render(&[
+            RunOutcome::fixture("core", false)
+                .with_stdout(tests)
+                .with_duration(Duration::from_millis(250)),
+            RunOutcome::fixture("app", false).with_stdout("error[E0308]: \u{1b}[1mmismatched\n"),
+        ]);
+        assert_eq!(cases, 4);
+        assert!(xml.contains(
+            "<testsuite name=\"core\" tests=\"3\" failures=\"1\" skipped=\"1\" time=\"0.250\">"
+        ));
+        assert!(xml.contains("<testcase classname=\"core\" name=\"a\"/>"));
+        assert!(xml.contains("<failure message=\"test failed\">assert &lt;x&gt;</failure>"));
+        assert!(xml.contains("<failure message=\"exit code 101\">error[E0308]: [1mmismatched"));
+    }
+}
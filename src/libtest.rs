@@ -0,0 +1,124 @@
+//! Per-test results parsed from libtest's human-readable output
+//!
+//! Handles the `test <name> ... ok` lines printed by `cargo test` and the
+//! `---- <name> stdout ----` sections that carry the output of failed tests.
+
+/// Result of a single test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Ignored,
+}
+
+/// A single test from a libtest run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    pub status: TestStatus,
+    /// Captured output of a failed test
+    pub output: String,
+}
+
+/// Parse a `test <name> ... <status>` line
+fn parse_result_line(line: &str) -> Option<(String, TestStatus)> {
+    let rest = line.strip_prefix("test ")?;
+    let (name, status) = rest.rsplit_once(" ... ")?;
+    let status = match status.trim() {
+        "ok" => TestStatus::Passed,
+        "FAILED" => TestStatus::Failed,
+        s if s.starts_with("ignored") => TestStatus::Ignored,
+        _ => return None,
+    };
+    Some((name.to_string(), status))
+}
+
+/// Extract every test result from `output`, in the order printed
+pub fn parse(output: &str) -> Vec<TestCase> {
+    let mut cases: Vec<TestCase> = output
+        .lines()
+        .filter_map(parse_result_line)
+        .map(|(name, status)| TestCase {
+            name,
+            status,
+            output: String::new(),
+        })
+        .collect();
+
+    // Attach `---- name stdout ----` sections to their failed tests
+    let mut current: Option<usize> = None;
+    for line in output.lines() {
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|l| l.strip_suffix(" stdout ----"))
+        {
+            current = cases
+                .iter()
+                .position(|c| c.name == name && c.status == TestStatus::Failed);
+            continue;
+        }
+        if line == "failures:" || line.starts_with("test result:") {
+            current = None;
+            continue;
+        }
+        if let Some(i) = current {
+            cases[i].output.push_str(line);
+            cases[i].output.push('\n');
+        }
+    }
+    for case in &mut cases {
+        let trimmed = case.output.trim_end().len();
+        case.output.truncate(trimmed);
+    }
+    cases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "\
+running 3 tests
+test tests::adds ... ok
+test tests::slow ... ignored, takes too long
+test tests::breaks ... FAILED
+
+failures:
+
+---- tests::breaks stdout ----
+thread 'tests::breaks' panicked at src/lib.rs:9:9:
+assertion failed: false
+
+failures:
+    tests::breaks
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out
+
+running 1 test
+test src/lib.rs - add (line 3) ... ok
+";
+
+    #[test]
+    fn test_parse_statuses() {
+        let cases = parse(OUTPUT);
+        let summary: Vec<(&str, TestStatus)> =
+            cases.iter().map(|c| (c.name.as_str(), c.status)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("tests::adds", TestStatus::Passed),
+                ("tests::slow", TestStatus::Ignored),
+                ("tests::breaks", TestStatus::Failed),
+                ("src/lib.rs - add (line 3)", TestStatus::Passed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failure_output_is_attached() {
+        let cases = parse(OUTPUT);
+        assert!(cases[2].output.contains("assertion failed: false"));
+        assert!(!cases[2].output.contains("test result"));
+        assert!(cases[0].output.is_empty());
+    }
+}
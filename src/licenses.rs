@@ -0,0 +1,230 @@
+//! `meta cargo licenses`: the license of every third-party dependency
+//!
+//! The resolved graph of every repo is loaded with `cargo metadata`; each
+//! registry or git crate is listed once per version with its declared
+//! license and the repos that use it. Crates without a `license` expression
+//! (only a `license-file`, or nothing) show as `UNKNOWN`.
+
+use crate::metadata::{self, Component};
+use crate::{args, project_path, CommandResult};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// License shown for crates that declare no expression
+const UNKNOWN: &str = "UNKNOWN";
+
+/// One third-party crate version across all repos
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Row {
+    name: String,
+    version: String,
+    license: String,
+    repos: BTreeSet<String>,
+}
+
+/// Rows sorted by crate and version
+fn inventory(repos: &[(String, Vec<Component>)]) -> Vec<Row> {
+    let mut rows: BTreeMap<(String, String), Row> = BTreeMap::new();
+    for (repo, components) in repos {
+        for c in components.iter().filter(|c| c.source.is_some()) {
+            rows.entry((c.name.clone(), c.version.clone()))
+                .or_insert_with(|| Row {
+                    name: c.name.clone(),
+                    version: c.version.clone(),
+                    license: c.license.clone().unwrap_or_else(|| UNKNOWN.to_string()),
+                    repos: BTreeSet::new(),
+                })
+                .repos
+                .insert(repo.clone());
+        }
+    }
+    rows.into_values().collect()
+}
+
+fn joined(repos: &BTreeSet<String>, sep: &str) -> String {
+    repos
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+fn render_text(rows: &[Row]) -> String {
+    let width =
+        |f: fn(&Row) -> usize, title: &str| rows.iter().map(f).max().unwrap_or(0).max(title.len());
+    let name = width(|r| r.name.len(), "crate");
+    let version = width(|r| r.version.len(), "version");
+    let license = width(|r| r.license.len(), "license");
+    let mut out = format!(
+        "{:name$}  {:version$}  {:license$}  repos\n",
+        "crate", "version", "license"
+    );
+    for r in rows {
+        out.push_str(&format!(
+            "{:name$}  {:version$}  {:license$}  {}\n",
+            r.name,
+            r.version,
+            r.license,
+            joined(&r.repos, ", ")
+        ));
+    }
+    let mut by_license: BTreeMap<&str, usize> = BTreeMap::new();
+    for r in rows {
+        *by_license.entry(&r.license).or_default() += 1;
+    }
+    out.push('\n');
+    for (license, count) in by_license {
+        out.push_str(&format!("{count:>5}  {license}\n"));
+    }
+    out
+}
+
+/// A CSV field, quoted when needed
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn render_csv(rows: &[Row]) -> String {
+    let mut out = "crate,version,license,repos\n".to_string();
+    for r in rows {
+        let fields = [
+            r.name.clone(),
+            r.version.clone(),
+            r.license.clone(),
+            joined(&r.repos, ";"),
+        ];
+        let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn render_markdown(rows: &[Row]) -> String {
+    let mut out = "| Crate | Version | License | Repos |\n|---|---|---|---|\n".to_string();
+    for r in rows {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            r.name,
+            r.version,
+            r.license.replace('|', "\\|"),
+            joined(&r.repos, ", ")
+        ));
+    }
+    out
+}
+
+fn render_json(rows: &[Row]) -> serde_json::Value {
+    json!({
+        "crates": rows.iter().map(|r| json!({
+            "name": r.name,
+            "version": r.version,
+            "license": r.license,
+            "repos": r.repos,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// Handle `meta cargo licenses [--format text|json|csv|markdown]`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let format = args::take_value(&mut args, "--format").unwrap_or_else(|| "text".to_string());
+    if !["text", "json", "csv", "markdown"].contains(&format.as_str()) {
+        return CommandResult::Error(format!(
+            "unsupported format '{format}' (expected text, json, csv or markdown)"
+        ));
+    }
+    let mut loaded = Vec::new();
+    let dirs: Vec<_> = repos.iter().map(|r| project_path(cwd, r)).collect();
+    for (repo, result) in repos
+        .iter()
+        .zip(metadata::load_each(&dirs, metadata::load_components))
+    {
+        match result {
+            Ok(components) => loaded.push((repo.clone(), components)),
+            Err(e) => {
+                return CommandResult::Error(format!(
+                    "{repo}: failed to load cargo metadata: {e:#}"
+                ))
+            }
+        }
+    }
+    let rows = inventory(&loaded);
+    match format.as_str() {
+        "json" => match serde_json::to_string_pretty(&render_json(&rows)) {
+            Ok(text) => CommandResult::Message(text),
+            Err(e) => CommandResult::Error(format!("Failed to serialize licenses: {e}")),
+        },
+        "csv" => CommandResult::Message(render_csv(&rows)),
+        "markdown" => CommandResult::Message(render_markdown(&rows)),
+        _ => CommandResult::Message(render_text(&rows)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn component(name: &str, version: &str, license: Option<&str>, local: bool) -> Component {
+        Component {
+            id: format!("{name} {version}"),
+            name: name.to_string(),
+            version: version.to_string(),
+            source: (!local).then(|| "registry+x".to_string()),
+            license: license.map(str::to_string),
+            member: local,
+            dependencies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_inventory_across_repos() {
+        let repos = vec![
+            (
+                "core".to_string(),
+                vec![
+                    component("core", "0.1.0", Some("MIT"), true),
+                    component("serde", "1.0.200", Some("MIT OR Apache-2.0"), false),
+                ],
+            ),
+            (
+                "app".to_string(),
+                vec![
+                    component("serde", "1.0.200", Some("MIT OR Apache-2.0"), false),
+                    component("ring", "0.17.8", None, false),
+                ],
+            ),
+        ];
+        let rows = inventory(&repos);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].name, "ring");
+        assert_eq!(rows[0].license, UNKNOWN);
+        assert_eq!(joined(&rows[1].repos, ","), "app,core");
+
+        assert_eq!(
+            render_csv(&rows),
+            "crate,version,license,repos\nring,0.17.8,UNKNOWN,app\nserde,1.0.200,MIT OR Apache-2.0,app;core\n"
+        );
+        assert!(render_markdown(&rows)
+            .contains("| serde | 1.0.200 | MIT OR Apache-2.0 | app, core |\n"));
+        assert_eq!(render_json(&rows)["crates"][1]["repos"][1], "core");
+        let text = render_text(&rows);
+        assert!(
+            text.starts_with("crate  version  license            repos\n"),
+            "{text}"
+        );
+        assert!(text.contains("    1  UNKNOWN\n"), "{text}");
+    }
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("MIT"), "MIT");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
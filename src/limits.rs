@@ -0,0 +1,379 @@
+//! Memory, CPU and priority limits for planned commands
+//!
+//! On Linux each limited command is wrapped in a transient systemd scope,
+//! which puts it in its own cgroup with `MemoryMax` and `CPUQuota` set. On
+//! Windows it runs under a PowerShell helper script (written once to the temp
+//! directory) that first puts itself in a job object with a job memory limit and a hard CPU rate cap, so every process
+//! the command starts inherits them (the wrapper's own few dozen megabytes
+//! count towards the memory limit). Low-priority commands are wrapped in
+//! `nice`/`ionice`. Wrapping the command string works for plans handed back
+//! to meta as well as in-process runs.
+
+use crate::config::LimitsConfig;
+use crate::PlannedCommand;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Effective limits for one command
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Limits {
+    memory: Option<String>,
+    cpus: Option<f64>,
+}
+
+impl Limits {
+    fn for_repo(config: &LimitsConfig, repo: &str) -> Self {
+        let repo_limits = config.repos.get(repo);
+        Limits {
+            memory: repo_limits
+                .and_then(|r| r.memory.clone())
+                .or_else(|| config.memory.clone()),
+            cpus: repo_limits.and_then(|r| r.cpus).or(config.cpus),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.memory.is_none() && self.cpus.is_none()
+    }
+}
+
+/// Accept sizes like `512M`, `4G` or a plain byte count
+fn validate_memory(size: &str) -> Result<(), String> {
+    let digits = size.trim_end_matches(['K', 'M', 'G', 'T']);
+    let suffix_len = size.len() - digits.len();
+    if digits.is_empty() || suffix_len > 1 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!(
+            "invalid memory limit '{size}' (expected e.g. 512M or 4G)"
+        ));
+    }
+    Ok(())
+}
+
+/// Bytes in a size accepted by [`validate_memory`]
+fn memory_bytes(size: &str) -> u64 {
+    let (digits, unit) = match size.strip_suffix(['K', 'M', 'G', 'T']) {
+        Some(digits) => (digits, &size[digits.len()..]),
+        None => (size, ""),
+    };
+    let shift = match unit {
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => 0,
+    };
+    digits
+        .parse::<u64>()
+        .unwrap_or(0)
+        .saturating_mul(1 << shift)
+}
+
+/// Quote `s` for a POSIX shell
+pub(crate) fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Wrap `cmd` so it runs with `limits`: in a cgroup on Linux, in a job
+/// object on Windows
+fn wrap(cmd: &str, limits: &Limits) -> Result<String, String> {
+    if let Some(memory) = &limits.memory {
+        validate_memory(memory)?;
+    }
+    if let Some(cpus) = limits.cpus {
+        if cpus <= 0.0 {
+            return Err(format!("invalid CPU limit '{cpus}' (must be positive)"));
+        }
+    }
+    if cfg!(target_os = "linux") {
+        Ok(systemd_scope(cmd, limits))
+    } else if cfg!(windows) {
+        Ok(job_object_command(cmd, limits, &job_script_path()?))
+    } else {
+        Err(
+            "resource limits are only supported on Linux (via systemd-run) and Windows \
+             (via job objects)"
+                .to_string(),
+        )
+    }
+}
+
+fn systemd_scope(cmd: &str, limits: &Limits) -> String {
+    let mut wrapped = "systemd-run --user --scope --quiet --collect".to_string();
+    if let Some(memory) = &limits.memory {
+        wrapped.push_str(&format!(" -p MemoryMax={memory} -p MemorySwapMax=0"));
+    }
+    if let Some(cpus) = limits.cpus {
+        wrapped.push_str(&format!(" -p CPUQuota={}%", (cpus * 100.0).round() as u64));
+    }
+    wrapped.push_str(&format!(" -- sh -c {}", shell_quote(cmd)));
+    wrapped
+}
+
+/// Win32 job object setup, loaded into PowerShell with `Add-Type`
+const JOB_OBJECT_TYPE: &str = r#"using System;
+using System.ComponentModel;
+using System.Diagnostics;
+using System.Runtime.InteropServices;
+
+public static class MetaRustJob {
+    [StructLayout(LayoutKind.Sequential)]
+    struct BasicLimits {
+        public long PerProcessUserTimeLimit;
+        public long PerJobUserTimeLimit;
+        public uint LimitFlags;
+        public UIntPtr MinimumWorkingSetSize;
+        public UIntPtr MaximumWorkingSetSize;
+        public uint ActiveProcessLimit;
+        public UIntPtr Affinity;
+        public uint PriorityClass;
+        public uint SchedulingClass;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    struct IoCounters {
+        public ulong ReadOperationCount, WriteOperationCount, OtherOperationCount;
+        public ulong ReadTransferCount, WriteTransferCount, OtherTransferCount;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    struct ExtendedLimits {
+        public BasicLimits Basic;
+        public IoCounters Io;
+        public UIntPtr ProcessMemoryLimit;
+        public UIntPtr JobMemoryLimit;
+        public UIntPtr PeakProcessMemoryUsed;
+        public UIntPtr PeakJobMemoryUsed;
+    }
+
+    [StructLayout(LayoutKind.Sequential)]
+    struct CpuRate {
+        public uint ControlFlags;
+        public uint Rate;
+    }
+
+    [DllImport("kernel32.dll", SetLastError = true)]
+    static extern IntPtr CreateJobObject(IntPtr attributes, string name);
+
+    [DllImport("kernel32.dll", SetLastError = true)]
+    static extern bool SetInformationJobObject(IntPtr job, int infoClass, IntPtr info, uint length);
+
+    [DllImport("kernel32.dll", SetLastError = true)]
+    static extern bool AssignProcessToJobObject(IntPtr job, IntPtr process);
+
+    static void Set(IntPtr job, int infoClass, object info) {
+        int size = Marshal.SizeOf(info);
+        IntPtr ptr = Marshal.AllocHGlobal(size);
+        try {
+            Marshal.StructureToPtr(info, ptr, false);
+            if (!SetInformationJobObject(job, infoClass, ptr, (uint)size)) {
+                throw new Win32Exception();
+            }
+        } finally {
+            Marshal.FreeHGlobal(ptr);
+        }
+    }
+
+    // Put this process, and so everything it starts, in a job with the limits
+    public static void Enter(ulong memory, double cpus) {
+        IntPtr job = CreateJobObject(IntPtr.Zero, null);
+        if (job == IntPtr.Zero) {
+            throw new Win32Exception();
+        }
+        ExtendedLimits limits = new ExtendedLimits();
+        // JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE
+        limits.Basic.LimitFlags = 0x2000;
+        if (memory > 0) {
+            // JOB_OBJECT_LIMIT_JOB_MEMORY
+            limits.Basic.LimitFlags |= 0x200;
+            limits.JobMemoryLimit = new UIntPtr(memory);
+        }
+        // JobObjectExtendedLimitInformation
+        Set(job, 9, limits);
+        if (cpus > 0) {
+            CpuRate rate = new CpuRate();
+            // JOB_OBJECT_CPU_RATE_CONTROL_ENABLE | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP
+            rate.ControlFlags = 0x1 | 0x4;
+            double share = Math.Round(cpus * 10000 / Environment.ProcessorCount);
+            rate.Rate = (uint)Math.Max(1, Math.Min(10000, share));
+            // JobObjectCpuRateControlInformation
+            Set(job, 15, rate);
+        }
+        if (!AssignProcessToJobObject(job, Process.GetCurrentProcess().Handle)) {
+            throw new Win32Exception();
+        }
+    }
+}"#;
+
+/// Helper running a base64 (UTF-8) command line through `cmd.exe` inside a
+/// job object, exiting with its exit code
+fn job_script() -> String {
+    format!(
+        "param([UInt64]$Memory, [Double]$Cpus, [String]$Command)\n\
+         $ErrorActionPreference = 'Stop'\n\
+         Add-Type -TypeDefinition @'\n{JOB_OBJECT_TYPE}\n'@\n\
+         [MetaRustJob]::Enter($Memory, $Cpus)\n\
+         $line = [Text.Encoding]::UTF8.GetString([Convert]::FromBase64String($Command))\n\
+         $info = New-Object System.Diagnostics.ProcessStartInfo 'cmd.exe', ('/C ' + $line)\n\
+         $info.UseShellExecute = $false\n\
+         $process = [System.Diagnostics.Process]::Start($info)\n\
+         $process.WaitForExit()\n\
+         exit $process.ExitCode\n"
+    )
+}
+
+/// The helper script in the temp directory, written on first use
+///
+/// The file name carries a hash of the contents, so plans built by different
+/// versions never share a helper.
+fn job_script_path() -> Result<PathBuf, String> {
+    let script = job_script();
+    let mut hasher = DefaultHasher::new();
+    script.hash(&mut hasher);
+    let path = std::env::temp_dir().join(format!("meta-rust-job-{:016x}.ps1", hasher.finish()));
+    if !path.is_file() {
+        crate::state::write_atomic(&path, script.as_bytes())
+            .map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+    }
+    Ok(path)
+}
+
+/// `powershell` running `cmd` under the job object helper at `script`
+///
+/// The command travels base64-encoded, so no cmd.exe or PowerShell quoting
+/// applies and the line stays far below cmd.exe's 8191-character limit.
+fn job_object_command(cmd: &str, limits: &Limits, script: &Path) -> String {
+    let memory = limits.memory.as_deref().map_or(0, memory_bytes);
+    let cpus = limits.cpus.unwrap_or(0.0);
+    format!(
+        "powershell -NoProfile -NonInteractive -ExecutionPolicy Bypass -File \"{}\" {memory} {cpus} {}",
+        script.display(),
+        base64(cmd.as_bytes())
+    )
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Wrap `cmd` so it runs at idle CPU and IO priority
+fn lower_priority(cmd: &str) -> String {
+    if cfg!(windows) {
+        format!("start \"\" /LOW /B /WAIT cmd /C {cmd}")
+    } else if cfg!(target_os = "linux") {
+        format!("nice -n 19 ionice -c 3 sh -c {}", shell_quote(cmd))
+    } else {
+        format!("nice -n 19 sh -c {}", shell_quote(cmd))
+    }
+}
+
+/// Apply the configured limits to every command in place
+pub(crate) fn apply(commands: &mut [PlannedCommand], config: &LimitsConfig) -> Result<(), String> {
+    for command in commands {
+        let limits = Limits::for_repo(config, &command.dir);
+        if !limits.is_empty() {
+            command.cmd = wrap(&command.cmd, &limits)?;
+        }
+        if config.nice {
+            command.cmd = lower_priority(&command.cmd);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_repo_overrides_global_limits() {
+        let config = Config::parse(
+            "[limits]\nmemory = \"4G\"\ncpus = 2.0\n\n[limits.repos.app]\nmemory = \"8G\"\n",
+        )
+        .unwrap();
+        let app = Limits::for_repo(&config.limits, "app");
+        assert_eq!(app.memory.as_deref(), Some("8G"));
+        assert_eq!(app.cpus, Some(2.0));
+        assert!(Limits::for_repo(&LimitsConfig::default(), "app").is_empty());
+    }
+
+    #[test]
+    fn test_memory_validation() {
+        assert!(validate_memory("512M").is_ok());
+        assert!(validate_memory("1073741824").is_ok());
+        assert!(validate_memory("4GB").is_err());
+        assert!(validate_memory("G").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_nice_wraps_outermost() {
+        let mut commands = vec![PlannedCommand {
+            dir: ".".to_string(),
+            cmd: "cargo build".to_string(),
+            env: None,
+        }];
+        let config = LimitsConfig {
+            nice: true,
+            ..LimitsConfig::default()
+        };
+        apply(&mut commands, &config).unwrap();
+        assert_eq!(
+            commands[0].cmd,
+            "nice -n 19 ionice -c 3 sh -c 'cargo build'"
+        );
+    }
+
+    #[test]
+    fn test_job_object_wrapper() {
+        assert_eq!(memory_bytes("512M"), 512 << 20);
+        assert_eq!(memory_bytes("1024"), 1024);
+        let limits = Limits {
+            memory: Some("4G".to_string()),
+            cpus: Some(1.5),
+        };
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+
+        let script = job_script_path().unwrap();
+        assert!(std::fs::read_to_string(&script)
+            .unwrap()
+            .contains("[MetaRustJob]::Enter($Memory, $Cpus)\n"));
+        let cmd = "cargo build --release --features 'a b' -p app";
+        let wrapped = job_object_command(cmd, &limits, &script);
+        assert!(wrapped.ends_with(&format!(" 4294967296 1.5 {}", base64(cmd.as_bytes()))));
+        // Every Windows command runs through `cmd /C`
+        assert!(wrapped.len() < 8191, "{} chars", wrapped.len());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_wrap_in_systemd_scope() {
+        let limits = Limits {
+            memory: Some("4G".to_string()),
+            cpus: Some(1.5),
+        };
+        assert_eq!(
+            wrap("cargo build --features 'a b'", &limits).unwrap(),
+            "systemd-run --user --scope --quiet --collect -p MemoryMax=4G -p MemorySwapMax=0 \
+             -p CPUQuota=150% -- sh -c 'cargo build --features '\\''a b'\\'''"
+        );
+    }
+}
@@ -0,0 +1,363 @@
+//! `meta cargo link` and `meta cargo unlink`: local cross-repo development
+//!
+//! `link` finds every registry or git dependency that names a crate hosted in
+//! another repo of the meta workspace and patches it to that local checkout,
+//! so a change spanning several repos builds without editing manifests by
+//! hand. Dependencies of the linked checkouts are followed too: when A uses
+//! B and B uses C from the registry, A's workspace patches both. The
+//! `[patch]` tables go to each repo's `.cargo/config.toml`, or its
+//! Cargo.toml with `--manifest`, inside a marked block that `unlink` removes
+//! again (deleting the file if nothing else is left in it). Running `link`
+//! twice replaces the block. Cargo ignores, with a warning, a patch whose
+//! local version doesn't satisfy the dependency's requirement.
+
+use crate::graph::CrateGraph;
+use crate::metadata::DependencyKind;
+use crate::{args, project_path, CommandResult};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+
+const BEGIN: &str = "# BEGIN meta cargo link";
+const END: &str = "# END meta cargo link";
+
+/// Patches of one repo: `[patch.<source>]` key -> crate -> local root
+type Patches = BTreeMap<String, BTreeMap<String, PathBuf>>;
+
+/// The `[patch.<key>]` key for a dependency source
+fn patch_key(source: &str) -> String {
+    match source {
+        "registry+https://github.com/rust-lang/crates.io-index"
+        | "sparse+https://index.crates.io/" => "crates-io".to_string(),
+        _ => {
+            let url = source.strip_prefix("git+").unwrap_or(source);
+            let url = url.strip_prefix("registry+").unwrap_or(url);
+            let url = url.split(['?', '#']).next().unwrap_or(url);
+            format!("\"{url}\"")
+        }
+    }
+}
+
+/// Non-path dependencies on crates other repos host, of `repo` and of every
+/// local checkout its build then pulls in
+fn patches_for(repo: &str, graph: &CrateGraph) -> Patches {
+    let mut patches = Patches::new();
+    let mut seen: BTreeSet<usize> = graph.crates_in_repo(repo).collect();
+    let mut queue: Vec<usize> = seen.iter().copied().collect();
+    while let Some(i) = queue.pop() {
+        let node = &graph.crates[i];
+        for dep in &node.dependencies {
+            // Dev-dependencies only count for the repo's own crates
+            if dep.kind == DependencyKind::Dev && node.repo != repo {
+                continue;
+            }
+            let local = match (&dep.source, &dep.path) {
+                (Some(source), _) => {
+                    let Some(j) = graph
+                        .crates
+                        .iter()
+                        .position(|c| c.name == dep.name && c.repo != repo)
+                    else {
+                        continue;
+                    };
+                    patches
+                        .entry(patch_key(source))
+                        .or_default()
+                        .insert(dep.name.clone(), graph.crates[j].root.clone());
+                    j
+                }
+                (None, Some(path)) => {
+                    let Some(j) = graph.crates.iter().position(|c| &c.root == path) else {
+                        continue;
+                    };
+                    j
+                }
+                (None, None) => continue,
+            };
+            if seen.insert(local) {
+                queue.push(local);
+            }
+        }
+    }
+    patches
+}
+
+/// `path` relative to `base`, with `/` separators
+fn relative(path: &Path, base: &Path) -> String {
+    let canon = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+    let (path, base) = (canon(path), canon(base));
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<String> = vec!["..".to_string(); base.len() - common];
+    parts.extend(
+        path[common..]
+            .iter()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+    );
+    if parts.is_empty() {
+        ".".to_string()
+    } else {
+        parts.join("/")
+    }
+}
+
+fn render_block(patches: &Patches, repo_dir: &Path) -> String {
+    let mut out = format!("{BEGIN}\n");
+    for (key, crates) in patches {
+        out.push_str(&format!("[patch.{key}]\n"));
+        for (name, root) in crates {
+            out.push_str(&format!(
+                "{name} = {{ path = \"{}\" }}\n",
+                relative(root, repo_dir)
+            ));
+        }
+    }
+    out.push_str(END);
+    out.push('\n');
+    out
+}
+
+/// `text` without the link block, and whether there was one
+fn strip_block(text: &str) -> (String, bool) {
+    let mut out = String::new();
+    let mut inside = false;
+    let mut found = false;
+    for line in text.split_inclusive('\n') {
+        match line.trim_end() {
+            BEGIN => {
+                inside = true;
+                found = true;
+            }
+            END if inside => inside = false,
+            _ if !inside => out.push_str(line),
+            _ => {}
+        }
+    }
+    if found {
+        // Drop the blank line `link` put before the block
+        while out.ends_with("\n\n") {
+            out.pop();
+        }
+    }
+    (out, found)
+}
+
+/// `[patch.<key>]` tables the file already defines outside the block
+fn conflicts<'a>(text: &str, patches: &'a Patches) -> Vec<&'a str> {
+    patches
+        .keys()
+        .filter(|key| {
+            let header = format!("[patch.{key}]");
+            text.lines().any(|l| l.trim() == header)
+        })
+        .map(String::as_str)
+        .collect()
+}
+
+/// The file `repo`'s patches go to
+fn target_file(repo_dir: &Path, manifest: bool) -> PathBuf {
+    if manifest {
+        repo_dir.join("Cargo.toml")
+    } else {
+        repo_dir.join(".cargo/config.toml")
+    }
+}
+
+fn link_repo(repo_dir: &Path, patches: &Patches, manifest: bool) -> Result<PathBuf, String> {
+    let path = target_file(repo_dir, manifest);
+    let existing = std::fs::read_to_string(&path).unwrap_or_default();
+    let (mut text, _) = strip_block(&existing);
+    let conflicting = conflicts(&text, patches);
+    if !conflicting.is_empty() {
+        return Err(format!(
+            "{} already defines [patch.{}]",
+            path.display(),
+            conflicting.join("], [patch.")
+        ));
+    }
+    if !text.is_empty() {
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    text.push_str(&render_block(patches, repo_dir));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    std::fs::write(&path, text).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+    Ok(path)
+}
+
+/// Remove the link block from both possible files, returning those changed
+fn unlink_repo(repo_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut changed = Vec::new();
+    for manifest in [false, true] {
+        let path = target_file(repo_dir, manifest);
+        let Ok(existing) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let (text, found) = strip_block(&existing);
+        if !found {
+            continue;
+        }
+        let result = if text.trim().is_empty() && !manifest {
+            std::fs::remove_file(&path)
+        } else {
+            std::fs::write(&path, text)
+        };
+        result.map_err(|e| format!("failed to update {}: {e}", path.display()))?;
+        changed.push(path);
+    }
+    Ok(changed)
+}
+
+/// Handle `meta cargo link [--manifest]`
+pub(crate) fn execute_link(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let manifest = args::take_flag(&mut args, "--manifest");
+    let (graph, failed) = CrateGraph::load_partial(repos, cwd);
+    let mut out = String::new();
+    let mut errors: Vec<String> = failed.iter().map(|(r, e)| format!("{r}: {e}")).collect();
+    for repo in repos {
+        let patches = patches_for(repo, &graph);
+        if patches.is_empty() {
+            continue;
+        }
+        let dir = project_path(cwd, repo);
+        match link_repo(&dir, &patches, manifest) {
+            Ok(path) => {
+                let crates: Vec<&str> = patches
+                    .values()
+                    .flat_map(|c| c.keys())
+                    .map(String::as_str)
+                    .collect();
+                out.push_str(&format!(
+                    "{repo}: {} -> {}\n",
+                    crates.join(", "),
+                    relative(&path, cwd)
+                ));
+            }
+            Err(e) => errors.push(format!("{repo}: {e}")),
+        }
+    }
+    if out.is_empty() && errors.is_empty() {
+        out.push_str("No repo depends on another repo's crates through a registry or git\n");
+    }
+    if errors.is_empty() {
+        CommandResult::Message(out)
+    } else {
+        CommandResult::Error(format!("{out}{}", errors.join("\n")))
+    }
+}
+
+/// Handle `meta cargo unlink`
+pub(crate) fn execute_unlink(repos: &[String], cwd: &Path) -> CommandResult {
+    let mut out = String::new();
+    for repo in repos {
+        match unlink_repo(&project_path(cwd, repo)) {
+            Ok(paths) => {
+                for path in paths {
+                    out.push_str(&format!("{repo}: unlinked {}\n", relative(&path, cwd)));
+                }
+            }
+            Err(e) => return CommandResult::Error(format!("{out}{repo}: {e}")),
+        }
+    }
+    if out.is_empty() {
+        out.push_str("No linked repos\n");
+    }
+    CommandResult::Message(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::{Dependency, DependencyKind, Package};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_link_and_unlink() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["core/crates/util", "app/.cargo"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join("app/.cargo/config.toml"), "[build]\njobs = 4\n").unwrap();
+        let graph = CrateGraph::from_packages(vec![
+            (
+                "core".to_string(),
+                vec![Package::fixture("util").with_root(root.join("core/crates/util"))],
+            ),
+            (
+                "app".to_string(),
+                vec![Package::fixture("app")
+                    .with_root(root.join("app"))
+                    .with_dependency(Dependency::fixture("util").in_registry())
+                    .with_dependency(Dependency::fixture("serde").in_registry())],
+            ),
+        ]);
+        assert!(patches_for("core", &graph).is_empty());
+        let patches = patches_for("app", &graph);
+        let app = root.join("app");
+        link_repo(&app, &patches, false).unwrap();
+        // Linking again replaces the block
+        let path = link_repo(&app, &patches, false).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            text,
+            format!("[build]\njobs = 4\n\n{BEGIN}\n[patch.crates-io]\nutil = {{ path = \"../core/crates/util\" }}\n{END}\n")
+        );
+
+        assert_eq!(unlink_repo(&app).unwrap(), vec![path.clone()]);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[build]\njobs = 4\n"
+        );
+        assert!(unlink_repo(&app).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_link_follows_linked_dependencies() {
+        let core = Package::fixture("core").with_dependency(
+            Dependency::fixture("proto")
+                .in_registry()
+                .with_kind(DependencyKind::Dev),
+        );
+        let graph = CrateGraph::from_packages(vec![
+            ("proto".to_string(), vec![Package::fixture("proto")]),
+            (
+                "util".to_string(),
+                vec![Package::fixture("util")
+                    .with_dependency(Dependency::fixture("core").in_registry())],
+            ),
+            ("core".to_string(), vec![core]),
+            (
+                "app".to_string(),
+                vec![Package::fixture("app")
+                    .with_dependency(Dependency::fixture("util").in_registry())],
+            ),
+        ]);
+        let patches = patches_for("app", &graph);
+        let linked: Vec<&String> = patches["crates-io"].keys().collect();
+        // core's dev-dependency on proto isn't part of app's build
+        assert_eq!(linked, vec!["core", "util"]);
+        assert_eq!(patches_for("core", &graph)["crates-io"].len(), 1);
+    }
+
+    #[test]
+    fn test_patch_keys_and_conflicts() {
+        assert_eq!(patch_key("sparse+https://index.crates.io/"), "crates-io");
+        assert_eq!(
+            patch_key("git+https://github.com/acme/core?branch=main#0123abcd"),
+            "\"https://github.com/acme/core\""
+        );
+        let mut patches = Patches::new();
+        patches.entry("crates-io".to_string()).or_default();
+        assert_eq!(
+            conflicts("[patch.crates-io]\nfoo = { path = \"x\" }\n", &patches),
+            vec!["crates-io"]
+        );
+    }
+}
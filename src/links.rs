@@ -0,0 +1,257 @@
+//! `meta cargo links-check`: native library conflicts between repos
+//!
+//! Cargo refuses to put two packages declaring the same `links` key into one
+//! build graph, and two semver-incompatible versions of a `-sys` crate usually
+//! link the same library twice. Within a repo cargo catches this itself; across
+//! repos it only surfaces once they share a target dir or get merged into one
+//! workspace, as a confusing link error. This check reports it up front.
+
+use crate::config::Config;
+use crate::metadata::{self, Package};
+use crate::{cargo_config, project_path, target_dir, CommandResult};
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Semver compatibility class of a version: `1.2.3` -> `1`, `0.9.1` -> `0.9`
+fn compat_version(version: &str) -> String {
+    let parts: Vec<&str> = version.split(['.', '-', '+']).take(3).collect();
+    match parts.as_slice() {
+        ["0", "0", patch] => format!("0.0.{patch}"),
+        ["0", minor, ..] => format!("0.{minor}"),
+        [major, ..] => major.to_string(),
+        [] => version.to_string(),
+    }
+}
+
+/// What a group of packages conflicts on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Several packages declare the same `links` key
+    Links(String),
+    /// Semver-incompatible versions of the same `-sys` crate
+    SysVersions(String),
+}
+
+/// A conflict between repos
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    /// `(repo, package, version)` for every claimant
+    pub claims: Vec<(String, String, String)>,
+}
+
+impl Conflict {
+    /// Repos involved, in first-seen order
+    pub fn repos(&self) -> Vec<&str> {
+        let mut repos: Vec<&str> = Vec::new();
+        for (repo, _, _) in &self.claims {
+            if !repos.contains(&repo.as_str()) {
+                repos.push(repo);
+            }
+        }
+        repos
+    }
+}
+
+/// Group claims by `key`, keeping groups whose claimants are incompatible
+fn conflicting_groups<'a>(
+    repos: &'a [(String, Vec<Package>)],
+    key: impl Fn(&'a Package) -> Option<&'a str>,
+) -> BTreeMap<&'a str, Vec<(String, String, String)>> {
+    let mut groups: BTreeMap<&str, Vec<(String, String, String)>> = BTreeMap::new();
+    for (repo, packages) in repos {
+        for pkg in packages {
+            if let Some(k) = key(pkg) {
+                let claim = (repo.clone(), pkg.name.clone(), pkg.version.clone());
+                let group = groups.entry(k).or_default();
+                if !group.contains(&claim) {
+                    group.push(claim);
+                }
+            }
+        }
+    }
+    groups.retain(|_, claims| {
+        let first = (&claims[0].1, compat_version(&claims[0].2));
+        let differs = claims
+            .iter()
+            .any(|(_, name, version)| (name, compat_version(version)) != first);
+        let repos_differ = claims.iter().any(|(repo, _, _)| *repo != claims[0].0);
+        differs && repos_differ
+    });
+    groups
+}
+
+/// Find `links` and `-sys` version conflicts between the repos' dependency graphs
+pub fn find_conflicts(repos: &[(String, Vec<Package>)]) -> Vec<Conflict> {
+    let links = conflicting_groups(repos, |p| p.links.as_deref());
+    let mut conflicts: Vec<Conflict> = links
+        .into_iter()
+        .map(|(key, claims)| Conflict {
+            kind: ConflictKind::Links(key.to_string()),
+            claims,
+        })
+        .collect();
+    // A `-sys` crate with a `links` key is already covered above
+    let sys = conflicting_groups(repos, |p| {
+        (p.name.ends_with("-sys") && p.links.is_none()).then_some(p.name.as_str())
+    });
+    conflicts.extend(sys.into_iter().map(|(name, claims)| Conflict {
+        kind: ConflictKind::SysVersions(name.to_string()),
+        claims,
+    }));
+    conflicts
+}
+
+/// Handle `meta cargo links-check`
+///
+/// Conflicts between repos that resolve to the same target dir are errors;
+/// the rest are warnings about a future shared workspace.
+pub(crate) fn execute(repos: &[String], cwd: &Path, config: &Config) -> CommandResult {
+    let root_config = cargo_config::root_config(cwd, config);
+    let mut loaded = Vec::new();
+    let mut target_dirs: BTreeMap<String, PathBuf> = BTreeMap::new();
+    let mut failures = Vec::new();
+    for repo in repos {
+        let dir = project_path(cwd, repo);
+        match metadata::load_all_packages(&dir) {
+            Ok(packages) => loaded.push((repo.clone(), packages)),
+            Err(e) => {
+                failures.push(format!("{repo}: {e:#}"));
+                continue;
+            }
+        }
+        if let Ok(target) = target_dir::resolve(&dir, root_config.as_deref()) {
+            let path = target.path.canonicalize().unwrap_or(target.path);
+            target_dirs.insert(repo.clone(), path);
+        }
+    }
+
+    let conflicts = find_conflicts(&loaded);
+    let mut out = String::new();
+    let mut errors = 0;
+    for conflict in &conflicts {
+        let involved = conflict.repos();
+        let dirs: Vec<&PathBuf> = involved
+            .iter()
+            .filter_map(|r| target_dirs.get(*r))
+            .collect();
+        let shared = dirs.iter().collect::<BTreeSet<_>>().len() < dirs.len();
+        let label = if shared {
+            errors += 1;
+            "error:".red().to_string()
+        } else {
+            "warning:".yellow().to_string()
+        };
+        let what = match &conflict.kind {
+            ConflictKind::Links(key) => format!("native library '{key}' is linked by"),
+            ConflictKind::SysVersions(name) => format!("incompatible versions of {name} in"),
+        };
+        out.push_str(&format!("{label} {what} {}\n", involved.join(", ")));
+        for (repo, name, version) in &conflict.claims {
+            out.push_str(&format!("    {repo}: {name} {version}\n"));
+        }
+        if shared {
+            out.push_str("    these repos share a target directory\n");
+        }
+    }
+    for failure in &failures {
+        out.push_str(&format!("{} {failure}\n", "error:".red()));
+    }
+
+    if conflicts.is_empty() && failures.is_empty() {
+        return CommandResult::Message(format!(
+            "No native library conflicts across {} repos",
+            repos.len()
+        ));
+    }
+    if errors > 0 || !failures.is_empty() {
+        CommandResult::Error(out)
+    } else {
+        CommandResult::Message(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compat_version() {
+        assert_eq!(compat_version("1.2.3"), "1");
+        assert_eq!(compat_version("0.9.102"), "0.9");
+        assert_eq!(compat_version("0.0.4"), "0.0.4");
+        assert_eq!(compat_version("2.0.0-rc.1"), "2");
+    }
+
+    #[test]
+    fn test_links_conflict_across_repos() {
+        let conflicts = find_conflicts(&[
+            (
+                "api".to_string(),
+                vec![Package::fixture("openssl-sys")
+                    .with_version("0.9.99")
+                    .in_registry()
+                    .with_links("openssl")],
+            ),
+            (
+                "worker".to_string(),
+                vec![Package::fixture("boring-sys")
+                    .with_version("4.0.0")
+                    .in_registry()
+                    .with_links("openssl")],
+            ),
+            (
+                "web".to_string(),
+                vec![Package::fixture("openssl-sys")
+                    .with_version("0.9.80")
+                    .in_registry()
+                    .with_links("openssl")],
+            ),
+        ]);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].kind,
+            ConflictKind::Links("openssl".to_string())
+        );
+        assert_eq!(conflicts[0].repos(), vec!["api", "worker", "web"]);
+    }
+
+    #[test]
+    fn test_sys_major_versions() {
+        let compatible = find_conflicts(&[
+            (
+                "a".to_string(),
+                vec![Package::fixture("foo-sys")
+                    .with_version("1.2.0")
+                    .in_registry()],
+            ),
+            (
+                "b".to_string(),
+                vec![Package::fixture("foo-sys")
+                    .with_version("1.4.0")
+                    .in_registry()],
+            ),
+        ]);
+        assert!(compatible.is_empty());
+
+        let conflicts = find_conflicts(&[
+            (
+                "a".to_string(),
+                vec![Package::fixture("foo-sys")
+                    .with_version("1.2.0")
+                    .in_registry()],
+            ),
+            (
+                "b".to_string(),
+                vec![Package::fixture("foo-sys")
+                    .with_version("2.0.0")
+                    .in_registry()],
+            ),
+        ]);
+        assert_eq!(
+            conflicts[0].kind,
+            ConflictKind::SysVersions("foo-sys".to_string())
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! `meta cargo lock-check` and `meta cargo lock-sync`: lockfiles across repos
+//!
+//! `lock-check` runs `cargo update --workspace --locked` in every repo, which
+//! changes nothing but fails when a repo's Cargo.lock no longer matches its
+//! manifests (or is missing), and reports those repos.
+//!
+//! `lock-sync <crate>` pins a dependency to one resolved version per
+//! semver-compatible line (`1.x`, `2.x`, `0.3.x`, ...) in every lockfile that
+//! contains it: the version given with `--version` for its line, or else the
+//! highest one any repo already resolves on each line. Each repo that differs
+//! runs `cargo update -p <crate>@<current> --precise <version>`; a repo whose
+//! manifest doesn't allow that version fails like any other command.
+
+use crate::align::compat_key;
+use crate::output::is_stale_lockfile;
+use crate::runner::{self, RunOutcome};
+use crate::{args, project_path, CommandResult, PlannedCommand};
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Versions of `name` resolved in a Cargo.lock
+fn locked_versions(lockfile: &str, name: &str) -> Result<BTreeSet<String>, String> {
+    let lock: toml::Table =
+        toml::from_str(lockfile).map_err(|e| format!("invalid Cargo.lock: {e}"))?;
+    let packages = lock.get("package").and_then(|p| p.as_array());
+    Ok(packages
+        .into_iter()
+        .flatten()
+        .filter(|p| p.get("name").and_then(|n| n.as_str()) == Some(name))
+        .filter_map(|p| p.get("version").and_then(|v| v.as_str()))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Sort key of a version: numeric components, pre-releases before releases
+fn version_key(version: &str) -> (Vec<u64>, bool) {
+    let version = version.split('+').next().unwrap_or(version);
+    let (release, pre) = match version.split_once('-') {
+        Some((release, _)) => (release, true),
+        None => (version, false),
+    };
+    let parts = release.split('.').map(|p| p.parse().unwrap_or(0)).collect();
+    (parts, !pre)
+}
+
+/// `cargo update --precise` commands aligning `name` on one version per
+/// semver-compatible line, and those versions
+fn sync_plan(
+    cargo: &str,
+    name: &str,
+    version: Option<&str>,
+    locked: &[(String, BTreeSet<String>)],
+) -> Result<(Vec<String>, Vec<PlannedCommand>), String> {
+    let line = |v: &str| compat_key(&version_key(v).0);
+    let mut targets: BTreeMap<Vec<u64>, &str> = BTreeMap::new();
+    for v in locked.iter().flat_map(|(_, versions)| versions) {
+        let target = targets.entry(line(v)).or_insert(v);
+        if version_key(v) > version_key(target) {
+            *target = v;
+        }
+    }
+    if targets.is_empty() {
+        return Err(format!("no Cargo.lock contains {name}"));
+    }
+    if let Some(v) = version {
+        // Only the line of the requested version moves
+        if !targets.contains_key(&line(v)) {
+            return Err(format!(
+                "no Cargo.lock has a {name} version compatible with {v}"
+            ));
+        }
+        targets = BTreeMap::from([(line(v), v)]);
+    }
+    let mut commands = Vec::new();
+    for (repo, versions) in locked {
+        for current in versions {
+            let Some(target) = targets.get(&line(current)).filter(|t| **t != current) else {
+                continue;
+            };
+            commands.push(PlannedCommand {
+                dir: repo.clone(),
+                cmd: format!("{cargo} update -p {name}@{current} --precise {target}"),
+                env: None,
+            });
+        }
+    }
+    Ok((targets.values().map(|t| t.to_string()).collect(), commands))
+}
+
+/// Handle `meta cargo lock-sync <crate> [--version <version>]`
+pub(crate) fn execute_sync(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+) -> Result<Vec<PlannedCommand>, CommandResult> {
+    let mut args = args.to_vec();
+    let version = args::take_value(&mut args, "--version");
+    let [name] = args.as_slice() else {
+        return Err(CommandResult::Error(
+            "usage: meta cargo lock-sync <crate> [--version <version>]".to_string(),
+        ));
+    };
+    let mut locked = Vec::new();
+    for repo in repos {
+        let path = project_path(cwd, repo).join("Cargo.lock");
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let versions = locked_versions(&text, name)
+            .map_err(|e| CommandResult::Error(format!("{repo}: {e}")))?;
+        if !versions.is_empty() {
+            locked.push((repo.clone(), versions));
+        }
+    }
+    let (targets, commands) =
+        sync_plan(cargo, name, version.as_deref(), &locked).map_err(CommandResult::Error)?;
+    if commands.is_empty() {
+        return Err(CommandResult::Message(format!(
+            "{name} is already at {} in every lockfile",
+            targets.join(" and ")
+        )));
+    }
+    Ok(commands)
+}
+
+fn render_check(outcomes: &[RunOutcome], cwd: &Path) -> (String, bool) {
+    let mut out = String::new();
+    let mut stale = 0;
+    let mut failed = false;
+    for o in outcomes {
+        if o.success {
+            out.push_str(&format!("{} {}\n", "✓".green(), o.dir));
+        } else if is_stale_lockfile(o) {
+            stale += 1;
+            let what = if project_path(cwd, &o.dir).join("Cargo.lock").is_file() {
+                "lockfile out of date"
+            } else {
+                "no lockfile"
+            };
+            out.push_str(&format!(
+                "{} {} ({what})\n    fix: cd {} && cargo update --workspace\n",
+                "✗".red(),
+                o.dir,
+                o.dir
+            ));
+        } else {
+            failed = true;
+            let reason = o.stderr.trim().lines().last().unwrap_or("failed");
+            out.push_str(&format!("{} {}: {reason}\n", "✗".red(), o.dir));
+        }
+    }
+    out.push_str(&format!(
+        "\n{stale} of {} lockfiles out of date\n",
+        outcomes.len()
+    ));
+    (out, failed || stale > 0)
+}
+
+/// Handle `meta cargo lock-check [cargo update args]`
+pub(crate) fn execute_check(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+) -> CommandResult {
+    let mut cmd = format!("{cargo} update --workspace --locked");
+    for arg in args {
+        cmd.push(' ');
+        cmd.push_str(arg);
+    }
+    let commands: Vec<PlannedCommand> = repos
+        .iter()
+        .map(|repo| PlannedCommand {
+            dir: repo.clone(),
+            cmd: cmd.clone(),
+            env: None,
+        })
+        .collect();
+    let (text, failed) = render_check(&runner::run_all(cwd, &commands, parallel), cwd);
+    if failed {
+        CommandResult::Error(text)
+    } else {
+        CommandResult::Message(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const LOCK: &str = "version = 3\n\n[[package]]\nname = \"serde\"\nversion = \"1.0.190\"\n\n[[package]]\nname = \"syn\"\nversion = \"1.0.109\"\n\n[[package]]\nname = \"syn\"\nversion = \"2.0.60\"\n";
+
+    #[test]
+    fn test_lock_sync_aligns_on_highest_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for (repo, serde) in [("core", "1.0.190"), ("app", "1.0.200"), ("cli", "")] {
+            std::fs::create_dir_all(root.join(repo)).unwrap();
+            if !serde.is_empty() {
+                let lock = format!("[[package]]\nname = \"serde\"\nversion = \"{serde}\"\n");
+                std::fs::write(root.join(repo).join("Cargo.lock"), lock).unwrap();
+            }
+        }
+        let repos: Vec<String> = ["core", "app", "cli"].map(String::from).to_vec();
+        let commands = execute_sync("cargo", &["serde".to_string()], &repos, root).unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].dir, "core");
+        assert_eq!(
+            commands[0].cmd,
+            "cargo update -p serde@1.0.190 --precise 1.0.200"
+        );
+
+        let args = ["--version", "1.0.200", "serde"].map(String::from);
+        let commands = execute_sync("cargo", &args, &repos[1..], root);
+        assert!(matches!(commands, Err(CommandResult::Message(_))));
+    }
+
+    #[test]
+    fn test_lock_sync_keeps_incompatible_lines_apart() {
+        let locked = vec![
+            (
+                "core".to_string(),
+                BTreeSet::from(["1.0.109".to_string(), "2.0.60".to_string()]),
+            ),
+            ("app".to_string(), BTreeSet::from(["2.0.50".to_string()])),
+            ("cli".to_string(), BTreeSet::from(["1.0.100".to_string()])),
+        ];
+        let (targets, commands) = sync_plan("cargo", "syn", None, &locked).unwrap();
+        assert_eq!(targets, vec!["1.0.109", "2.0.60"]);
+        let plan: Vec<(&str, &str)> = commands
+            .iter()
+            .map(|c| (c.dir.as_str(), c.cmd.as_str()))
+            .collect();
+        assert_eq!(
+            plan,
+            vec![
+                ("app", "cargo update -p syn@2.0.50 --precise 2.0.60"),
+                ("cli", "cargo update -p syn@1.0.100 --precise 1.0.109"),
+            ]
+        );
+
+        let (_, commands) = sync_plan("cargo", "syn", Some("2.0.61"), &locked).unwrap();
+        assert_eq!(commands.len(), 2);
+        assert!(commands.iter().all(|c| c.cmd.ends_with("--precise 2.0.61")));
+        assert!(sync_plan("cargo", "syn", Some("3.0.0"), &locked).is_err());
+    }
+
+    #[test]
+    fn test_locked_versions_and_ordering() {
+        let versions = locked_versions(LOCK, "syn").unwrap();
+        assert_eq!(versions.len(), 2);
+        assert!(locked_versions(LOCK, "tokio").unwrap().is_empty());
+        assert!(version_key("1.0.10") > version_key("1.0.9"));
+        assert!(version_key("2.0.0") > version_key("2.0.0-rc.1"));
+    }
+
+    #[test]
+    fn test_check_reports_stale_lockfiles() {
+        let temp_dir = TempDir::new().unwrap();
+        let outcomes = vec![
+            RunOutcome::fixture("core", true),
+            RunOutcome::fixture("app", false).with_stderr(
+                "error: the lock file /ws/app/Cargo.lock needs to be updated but --locked was passed to prevent this\n",
+            ),
+        ];
+        let (text, failed) = render_check(&outcomes, temp_dir.path());
+        assert!(failed);
+        assert!(text.contains("app (no lockfile)"), "{text}");
+        assert!(text.contains("1 of 2 lockfiles out of date"), "{text}");
+    }
+}
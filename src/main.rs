@@ -2,11 +2,27 @@
 
 use indexmap::IndexMap;
 use meta_plugin_protocol::{
-    run_plugin, CommandResult, PluginDefinition, PluginHelp, PluginInfo, PluginRequest,
+    output_execution_plan, run_plugin, CommandResult, ExecutionPlan, PluginDefinition, PluginHelp,
+    PluginInfo, PluginRequest,
 };
+use std::io::Read;
 use std::path::PathBuf;
 
 fn main() {
+    // `meta-rust affected ...` is a standalone entry point for CI orchestrators
+    let cli_args: Vec<String> = std::env::args().skip(1).collect();
+    if cli_args.first().map(String::as_str) == Some("affected") {
+        std::process::exit(run_affected(&cli_args[1..]));
+    }
+    // `meta-rust --standalone <command> ...` runs without the meta CLI
+    if cli_args.first().map(String::as_str) == Some("--standalone") {
+        std::process::exit(run_standalone(&cli_args[1..]));
+    }
+    // Requests are parsed here rather than in `run_plugin` to report typos
+    if cli_args.iter().any(|a| a == "--meta-plugin-exec") {
+        std::process::exit(run_exec());
+    }
+
     let mut help_commands = IndexMap::new();
     help_commands.insert(
         "build".to_string(),
@@ -16,12 +32,307 @@ fn main() {
         "test".to_string(),
         "Run tests across all Rust projects".to_string(),
     );
+    help_commands.insert(
+        "bench".to_string(),
+        "Run benchmarks (--no-run to only compile them)".to_string(),
+    );
+    help_commands.insert(
+        "check".to_string(),
+        "Check all Rust projects without producing binaries".to_string(),
+    );
+    help_commands.insert(
+        "doc".to_string(),
+        "Build documentation for all Rust projects".to_string(),
+    );
+    help_commands.insert(
+        "clean".to_string(),
+        "Remove build artifacts in every Rust project".to_string(),
+    );
+    help_commands.insert(
+        "update".to_string(),
+        "Update Cargo.lock dependencies in every Rust project".to_string(),
+    );
+    help_commands.insert(
+        "clippy".to_string(),
+        "Run clippy across all Rust projects (--diff <ref>, --aggregate report)".to_string(),
+    );
+    help_commands.insert(
+        "fmt".to_string(),
+        "Format all Rust projects (--check lists mis-formatted files)".to_string(),
+    );
+    help_commands.insert(
+        "add".to_string(),
+        "Add a dependency in every repo (--only-if-present to update existing ones)".to_string(),
+    );
+    help_commands.insert(
+        "remove".to_string(),
+        "Remove a dependency from every repo that declares it (--only-if-present)".to_string(),
+    );
+    help_commands.insert(
+        "rustc".to_string(),
+        "Pass rustc flags to each repo's primary package".to_string(),
+    );
+    help_commands.insert(
+        "link".to_string(),
+        "Patch dependencies on sibling repos' crates to the local checkouts".to_string(),
+    );
+    help_commands.insert(
+        "unlink".to_string(),
+        "Remove the patches added by meta cargo link".to_string(),
+    );
+    help_commands.insert(
+        "expand".to_string(),
+        "Run cargo expand for a crate in the repo that hosts it".to_string(),
+    );
+    help_commands.insert(
+        "affected".to_string(),
+        "List repos/crates affected by changes since a git ref".to_string(),
+    );
+    help_commands.insert(
+        "lock-check".to_string(),
+        "Report repos whose Cargo.lock is out of date".to_string(),
+    );
+    help_commands.insert(
+        "lock-sync".to_string(),
+        "Pin a dependency to one version in every lockfile".to_string(),
+    );
+    help_commands.insert(
+        "outdated".to_string(),
+        "Report stale dependencies grouped by crate across repos".to_string(),
+    );
+    help_commands.insert(
+        "audit".to_string(),
+        "Merge cargo audit advisories of all repos into one report".to_string(),
+    );
+    help_commands.insert(
+        "deny".to_string(),
+        "Run cargo deny with the meta deny.toml layered under each repo's".to_string(),
+    );
+    help_commands.insert(
+        "history".to_string(),
+        "List recent invocations and their outcome".to_string(),
+    );
+    help_commands.insert(
+        "last".to_string(),
+        "Show the previous invocation, or repeat it with --rerun".to_string(),
+    );
+    help_commands.insert(
+        "compare-runs".to_string(),
+        "Diff two saved run results and report regressions per repo".to_string(),
+    );
+    help_commands.insert(
+        "coverage".to_string(),
+        "Report line coverage per repo and merged, with baseline deltas".to_string(),
+    );
+    help_commands.insert(
+        "maintain".to_string(),
+        "Run maintenance checks (audit, outdated, ...) with a combined report".to_string(),
+    );
+
+    help_commands.insert(
+        "info".to_string(),
+        "Print per-repo toolchain and manifest facts as JSON".to_string(),
+    );
+    help_commands.insert(
+        "build-scripts".to_string(),
+        "List dependencies with build scripts for supply-chain review".to_string(),
+    );
+    help_commands.insert(
+        "dist".to_string(),
+        "Build cargo-dist installers across repos and upload them to GitHub Releases".to_string(),
+    );
+    help_commands.insert(
+        "doc-coverage".to_string(),
+        "Report documentation coverage of every crate".to_string(),
+    );
+    help_commands.insert(
+        "doc-index".to_string(),
+        "Build and search a merged rustdoc item index".to_string(),
+    );
+    help_commands.insert(
+        "cfg-report".to_string(),
+        "List custom cfgs and feature gates, flagging undeclared ones".to_string(),
+    );
+    help_commands.insert(
+        "env-audit".to_string(),
+        "List environment variables that affect each repo's build".to_string(),
+    );
+    help_commands.insert(
+        "env-gen".to_string(),
+        "Generate a Nix or devcontainer development environment".to_string(),
+    );
+    help_commands.insert(
+        "examples".to_string(),
+        "Build and smoke-run every example across repos".to_string(),
+    );
+    help_commands.insert(
+        "feature-report".to_string(),
+        "Show the unified features of a dependency in every repo".to_string(),
+    );
+    help_commands.insert(
+        "gate".to_string(),
+        "Run the checks required by policy for each repo's tags".to_string(),
+    );
+    help_commands.insert(
+        "graph".to_string(),
+        "Export the cross-repo crate dependency graph as DOT, Mermaid or JSON".to_string(),
+    );
+    help_commands.insert(
+        "grep-api".to_string(),
+        "Find cross-repo uses of an item before a breaking change".to_string(),
+    );
+    help_commands.insert(
+        "hakari".to_string(),
+        "Manage workspace-hack crates and report feature unification churn".to_string(),
+    );
+    help_commands.insert(
+        "proc-macro-impact".to_string(),
+        "Show which crates and repos rebuild when a proc-macro crate changes".to_string(),
+    );
+    help_commands.insert(
+        "impact".to_string(),
+        "Show downstream uses of a crate's changed public items".to_string(),
+    );
+    help_commands.insert(
+        "integration".to_string(),
+        "Build binary repos, then run integration tests against them".to_string(),
+    );
+    help_commands.insert(
+        "links-check".to_string(),
+        "Detect native library conflicts between repos".to_string(),
+    );
+    help_commands.insert(
+        "msrv-impact".to_string(),
+        "Check whether a dependency bump would raise any package's MSRV".to_string(),
+    );
+    help_commands.insert(
+        "platform-deps".to_string(),
+        "Find platform-specific dependencies for platforms you don't ship".to_string(),
+    );
+    help_commands.insert(
+        "publish".to_string(),
+        "Publish all crates in dependency order (--dry-run, --staging rehearsal)".to_string(),
+    );
+    help_commands.insert(
+        "quarantine".to_string(),
+        "Temporarily skip broken repos, with a reason and expiry date".to_string(),
+    );
+    help_commands.insert(
+        "rename-dep".to_string(),
+        "Rename a dependency across manifests and sources of all repos".to_string(),
+    );
+    help_commands.insert(
+        "repro-check".to_string(),
+        "Build every repo twice and check that the artifacts are identical".to_string(),
+    );
+    help_commands.insert(
+        "unreleased".to_string(),
+        "List repos with commits since their last release tag".to_string(),
+    );
+    help_commands.insert(
+        "version".to_string(),
+        "Bump crate versions across repos and update dependents".to_string(),
+    );
+    help_commands.insert(
+        "versions".to_string(),
+        "Report and align dependency versions that differ across repos".to_string(),
+    );
+    help_commands.insert(
+        "symbolicate".to_string(),
+        "Resolve a production backtrace against dist symbol bundles".to_string(),
+    );
+    help_commands.insert(
+        "licenses".to_string(),
+        "List third-party crates with their licenses and the repos using them".to_string(),
+    );
+    help_commands.insert(
+        "sbom".to_string(),
+        "Write one CycloneDX or SPDX SBOM for every repo's dependencies".to_string(),
+    );
+    help_commands.insert(
+        "sysdeps".to_string(),
+        "Check that declared system dependencies are installed".to_string(),
+    );
+    help_commands.insert(
+        "toolchains".to_string(),
+        "List pinned toolchains and the repos using each".to_string(),
+    );
+    help_commands.insert(
+        "toolchain".to_string(),
+        "Bump the toolchain of every repo and check what breaks".to_string(),
+    );
+    help_commands.insert(
+        "target-dirs".to_string(),
+        "Show each repo's effective cargo target directory".to_string(),
+    );
 
     run_plugin(PluginDefinition {
         info: PluginInfo {
             name: "rust".to_string(),
             version: env!("CARGO_PKG_VERSION").to_string(),
-            commands: vec!["cargo build".to_string(), "cargo test".to_string()],
+            commands: vec![
+                "cargo build".to_string(),
+                "cargo test".to_string(),
+                "cargo bench".to_string(),
+                "cargo check".to_string(),
+                "cargo doc".to_string(),
+                "cargo clean".to_string(),
+                "cargo run".to_string(),
+                "cargo update".to_string(),
+                "cargo add".to_string(),
+                "cargo remove".to_string(),
+                "cargo clippy".to_string(),
+                "cargo fmt".to_string(),
+                "cargo rustc".to_string(),
+                "cargo expand".to_string(),
+                "cargo link".to_string(),
+                "cargo unlink".to_string(),
+                "cargo affected".to_string(),
+                "cargo audit".to_string(),
+                "cargo outdated".to_string(),
+                "cargo lock-check".to_string(),
+                "cargo lock-sync".to_string(),
+                "cargo deny".to_string(),
+                "cargo history".to_string(),
+                "cargo last".to_string(),
+                "cargo compare-runs".to_string(),
+                "cargo coverage".to_string(),
+                "cargo maintain".to_string(),
+                "cargo info".to_string(),
+                "cargo build-scripts".to_string(),
+                "cargo dist".to_string(),
+                "cargo doc-coverage".to_string(),
+                "cargo doc-index".to_string(),
+                "cargo env-audit".to_string(),
+                "cargo cfg-report".to_string(),
+                "cargo env-gen".to_string(),
+                "cargo examples".to_string(),
+                "cargo feature-report".to_string(),
+                "cargo gate".to_string(),
+                "cargo graph".to_string(),
+                "cargo grep-api".to_string(),
+                "cargo hakari".to_string(),
+                "cargo impact".to_string(),
+                "cargo proc-macro-impact".to_string(),
+                "cargo integration".to_string(),
+                "cargo links-check".to_string(),
+                "cargo msrv-impact".to_string(),
+                "cargo platform-deps".to_string(),
+                "cargo publish".to_string(),
+                "cargo quarantine".to_string(),
+                "cargo rename-dep".to_string(),
+                "cargo repro-check".to_string(),
+                "cargo symbolicate".to_string(),
+                "cargo sysdeps".to_string(),
+                "cargo toolchains".to_string(),
+                "cargo toolchain".to_string(),
+                "cargo target-dirs".to_string(),
+                "cargo unreleased".to_string(),
+                "cargo version".to_string(),
+                "cargo versions".to_string(),
+                "cargo sbom".to_string(),
+                "cargo licenses".to_string(),
+            ],
             description: Some("Rust/Cargo commands for meta repositories".to_string()),
             help: Some(PluginHelp {
                 usage: "meta cargo <command> [args...]".to_string(),
@@ -31,14 +342,69 @@ fn main() {
                     "meta cargo build".to_string(),
                     "meta cargo test".to_string(),
                     "meta cargo build --release".to_string(),
+                    "meta cargo clippy --diff origin/main -- -D warnings".to_string(),
+                    "meta cargo fmt --check".to_string(),
+                    "meta cargo affected --since origin/main --format json".to_string(),
+                    "meta cargo coverage --diff-base origin/main --min-delta -0.5".to_string(),
                 ],
-                note: Some("To run raw cargo commands: meta exec -- cargo <command>".to_string()),
+                note: Some(
+                    "Other cargo subcommands are passed through: meta cargo <command> [args...]"
+                        .to_string(),
+                ),
             }),
         },
         execute,
     });
 }
 
+/// Execute the plugin request on stdin, as `run_plugin` would
+fn run_exec() -> i32 {
+    let mut input = String::new();
+    if let Err(e) = std::io::stdin().read_to_string(&mut input) {
+        eprintln!("Failed to read plugin request: {e}");
+        return 1;
+    }
+    let request = match meta_rust_cli::config::parse_plugin_request(&input) {
+        Ok((request, warnings)) => {
+            for warning in warnings {
+                eprintln!("warning: {warning}");
+            }
+            request
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            return 1;
+        }
+    };
+    match execute(request) {
+        CommandResult::Plan(commands, parallel) => {
+            output_execution_plan(ExecutionPlan {
+                pre_commands: vec![],
+                commands,
+                post_commands: vec![],
+                parallel,
+                max_parallel: None,
+                spawn_stagger_ms: None,
+            });
+            0
+        }
+        CommandResult::Message(msg) => {
+            println!("{msg}");
+            0
+        }
+        CommandResult::Error(e) => {
+            eprintln!("{e}");
+            1
+        }
+        CommandResult::ShowHelp(reason) => {
+            if let Some(reason) = reason {
+                eprintln!("{reason}");
+            }
+            0
+        }
+    }
+}
+
 fn execute(request: PluginRequest) -> CommandResult {
     let cwd = if request.cwd.is_empty() {
         match std::env::current_dir() {
@@ -49,11 +415,90 @@ fn execute(request: PluginRequest) -> CommandResult {
         PathBuf::from(&request.cwd)
     };
 
-    meta_rust_cli::execute_command(
+    let filters = meta_rust_cli::ProjectFilters::new(
+        request.options.include_filters,
+        request.options.exclude_filters,
+    );
+    // CommandResult has no structured variant, so JSON runs print NDJSON
+    let mut args = request.args;
+    if request.options.json_output && !args.iter().any(|a| a.starts_with("--output")) {
+        // Before any `--`, where everything is passed through to cargo
+        let end = args.iter().position(|a| a == "--").unwrap_or(args.len());
+        args.insert(end, "--output=ndjson".to_string());
+    }
+    meta_rust_cli::execute_filtered(
         &request.command,
-        &request.args,
+        &args,
         request.options.parallel,
         &request.projects,
+        &filters,
         &cwd,
     )
 }
+
+fn run_affected(args: &[String]) -> i32 {
+    let cwd = match std::env::current_dir() {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Failed to get working directory: {e}");
+            return 1;
+        }
+    };
+    match meta_rust_cli::execute_command("cargo affected", args, false, &[], &cwd) {
+        CommandResult::Message(msg) => {
+            println!("{msg}");
+            0
+        }
+        CommandResult::Error(e) => {
+            eprintln!("{e}");
+            1
+        }
+        _ => {
+            eprintln!("usage: meta-rust affected --since <ref> [--format text|json]");
+            2
+        }
+    }
+}
+
+fn run_standalone(args: &[String]) -> i32 {
+    let Some((command, rest)) = args.split_first() else {
+        eprintln!("usage: meta-rust --standalone <command> [--parallel] [args...]");
+        return 2;
+    };
+    let cwd = match std::env::current_dir() {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Failed to get working directory: {e}");
+            return 1;
+        }
+    };
+    let parallel = rest.iter().any(|a| a == "--parallel");
+    let mut args: Vec<String> = rest
+        .iter()
+        .filter(|a| *a != "--parallel")
+        .cloned()
+        .collect();
+    args.push("--standalone".to_string());
+    let command = format!("cargo {command}");
+    match meta_rust_cli::execute_command(&command, &args, parallel, &[], &cwd) {
+        CommandResult::Message(msg) => {
+            println!("{msg}");
+            0
+        }
+        CommandResult::Error(e) => {
+            eprintln!("{e}");
+            1
+        }
+        CommandResult::ShowHelp(reason) => {
+            if let Some(reason) = reason {
+                eprintln!("{reason}");
+            }
+            eprintln!("{}", meta_rust_cli::get_help_text());
+            2
+        }
+        CommandResult::Plan(..) => {
+            eprintln!("{command} returned a plan that --standalone cannot run");
+            1
+        }
+    }
+}
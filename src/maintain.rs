@@ -0,0 +1,214 @@
+//! `meta cargo maintain`: scheduled maintenance bundle
+//!
+//! Runs a set of maintenance checks in every Rust repo and renders a single
+//! combined report. The command fails when any check fails, so it can be used
+//! directly as a cron job.
+
+use crate::args;
+use crate::config::Config;
+use crate::runner::{self, RunOutcome};
+use crate::{CommandResult, PlannedCommand};
+use colored::Colorize;
+use std::path::Path;
+
+/// Built-in checks and the commands they run
+const BUILTIN_CHECKS: &[(&str, &str)] = &[
+    ("audit", "cargo audit"),
+    ("outdated", "cargo outdated --root-deps-only --exit-code 1"),
+    ("machete", "cargo machete"),
+    ("udeps", "cargo +nightly udeps --all-targets"),
+    ("lockfile", "cargo metadata --locked --format-version 1"),
+    ("licenses", "cargo deny check licenses"),
+];
+
+/// Checks run when neither config nor `--checks` selects any
+/// (`udeps` needs a nightly toolchain, so it is opt-in)
+const DEFAULT_CHECKS: &[&str] = &["audit", "outdated", "machete", "lockfile", "licenses"];
+
+/// Lines of output shown for a failed check
+const FAILURE_TAIL_LINES: usize = 10;
+
+/// Outcome of one check in one repo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// The cargo subcommand backing the check is not installed
+    Skipped,
+}
+
+fn status_of(outcome: &RunOutcome) -> CheckStatus {
+    if outcome.success {
+        CheckStatus::Passed
+    } else if outcome.stderr.contains("no such command") {
+        CheckStatus::Skipped
+    } else {
+        CheckStatus::Failed
+    }
+}
+
+/// Resolve check names to `(name, command)` pairs
+fn resolve_checks(names: &[String], config: &Config) -> Result<Vec<(String, String)>, String> {
+    names
+        .iter()
+        .map(|name| {
+            let cmd = config.maintain.commands.get(name).cloned().or_else(|| {
+                BUILTIN_CHECKS
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, c)| c.to_string())
+            });
+            cmd.map(|c| (name.clone(), c))
+                .ok_or_else(|| format!("unknown maintenance check '{name}'"))
+        })
+        .collect()
+}
+
+/// Render the combined report; `results` holds `(repo, check, outcome)` in plan order
+fn render_report(
+    repos: &[String],
+    checks: usize,
+    results: &[(String, String, RunOutcome)],
+) -> String {
+    let mut out = format!(
+        "Maintenance report: {} repo(s), {checks} check(s)\n",
+        repos.len()
+    );
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    for repo in repos {
+        out.push_str(&format!("\n{}\n", repo.bold()));
+        for (_, check, outcome) in results.iter().filter(|(r, ..)| r == repo) {
+            let secs = outcome.duration.as_secs_f64();
+            match status_of(outcome) {
+                CheckStatus::Passed => {
+                    passed += 1;
+                    out.push_str(&format!("  {} {check} ({secs:.1}s)\n", "✓".green()));
+                }
+                CheckStatus::Skipped => {
+                    skipped += 1;
+                    out.push_str(&format!("  {} {check} (not installed)\n", "-".yellow()));
+                }
+                CheckStatus::Failed => {
+                    failed += 1;
+                    out.push_str(&format!("  {} {check} ({secs:.1}s)\n", "✗".red()));
+                    let output = outcome.output();
+                    let lines: Vec<&str> = output.lines().collect();
+                    for line in &lines[lines.len().saturating_sub(FAILURE_TAIL_LINES)..] {
+                        out.push_str(&format!("      | {line}\n"));
+                    }
+                }
+            }
+        }
+    }
+    out.push_str(&format!(
+        "\nSummary: {passed} passed, {failed} failed, {skipped} skipped\n"
+    ));
+    out
+}
+
+/// Handle `meta cargo maintain [--checks a,b,...]`
+pub(crate) fn execute(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let names: Vec<String> = match args::take_value(&mut args, "--checks") {
+        Some(list) => list.split(',').map(|s| s.trim().to_string()).collect(),
+        None if !config.maintain.checks.is_empty() => config.maintain.checks.clone(),
+        None => DEFAULT_CHECKS.iter().map(|s| s.to_string()).collect(),
+    };
+    let checks = match resolve_checks(&names, config) {
+        Ok(c) => c,
+        Err(e) => return CommandResult::Error(e),
+    };
+
+    let mut labels = Vec::new();
+    let mut commands = Vec::new();
+    for repo in repos {
+        for (name, cmd) in &checks {
+            labels.push((repo.clone(), name.clone()));
+            commands.push(PlannedCommand {
+                dir: repo.clone(),
+                cmd: cmd.clone(),
+                env: None,
+            });
+        }
+    }
+
+    let results: Vec<(String, String, RunOutcome)> = labels
+        .into_iter()
+        .zip(runner::run_all(cwd, &commands, parallel))
+        .map(|((repo, check), outcome)| (repo, check, outcome))
+        .collect();
+
+    let report = render_report(repos, checks.len(), &results);
+    if results
+        .iter()
+        .any(|(_, _, o)| status_of(o) == CheckStatus::Failed)
+    {
+        CommandResult::Error(report)
+    } else {
+        CommandResult::Message(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn config() -> Config {
+        Config::parse(
+            r#"
+[maintain]
+checks = ["version", "broken", "missing"]
+
+[maintain.commands]
+version = "cargo --version"
+broken = "cargo locate-project --manifest-path missing/Cargo.toml"
+missing = "cargo definitely-not-installed"
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_unknown_check_is_an_error() {
+        let names = vec!["nope".to_string()];
+        assert!(resolve_checks(&names, &Config::default()).is_err());
+        let names = vec!["audit".to_string()];
+        assert_eq!(
+            resolve_checks(&names, &Config::default()).unwrap()[0].1,
+            "cargo audit"
+        );
+    }
+
+    #[test]
+    fn test_combined_report() {
+        let temp_dir = TempDir::new().unwrap();
+        let repos = vec![".".to_string()];
+        let result = execute(&[], &repos, temp_dir.path(), false, &config());
+        match result {
+            CommandResult::Error(report) => {
+                assert!(report.contains("version"));
+                assert!(report.contains("not installed"));
+                assert!(report.contains("1 passed, 1 failed, 1 skipped"));
+            }
+            _ => panic!("Expected Error result"),
+        }
+    }
+
+    #[test]
+    fn test_checks_flag_overrides_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let repos = vec![".".to_string()];
+        let args = vec!["--checks".to_string(), "version".to_string()];
+        match execute(&args, &repos, temp_dir.path(), false, &config()) {
+            CommandResult::Message(report) => assert!(report.contains("1 passed, 0 failed")),
+            _ => panic!("Expected Message result"),
+        }
+    }
+}
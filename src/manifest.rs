@@ -0,0 +1,215 @@
+//! Format-preserving Cargo.toml edits
+//!
+//! Manifests are edited line by line so comments, ordering and formatting
+//! survive: only the quoted value being changed is rewritten. Values written
+//! across several lines or as dotted keys (`serde.version = "1"`) are left
+//! alone.
+
+use crate::rename_dep::{is_dependency_table, key_is};
+
+/// Where a package takes its version from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionSource {
+    Literal(String),
+    /// `version.workspace = true`
+    Workspace,
+}
+
+/// The `[package]` version of a manifest
+pub(crate) fn package_version(text: &str) -> Option<VersionSource> {
+    let manifest: toml::Table = toml::from_str(text).ok()?;
+    match manifest.get("package")?.get("version")? {
+        toml::Value::String(v) => Some(VersionSource::Literal(v.clone())),
+        toml::Value::Table(t) if t.get("workspace").and_then(|w| w.as_bool()) == Some(true) => {
+            Some(VersionSource::Workspace)
+        }
+        _ => None,
+    }
+}
+
+/// Table name of a `[header]` or `[[header]]` line
+fn header(line: &str) -> Option<String> {
+    let trimmed = line.trim();
+    if !trimmed.starts_with('[') {
+        return None;
+    }
+    let name = trimmed.trim_start_matches('[');
+    let name = name.split(']').next().unwrap_or(name);
+    Some(name.trim().to_string())
+}
+
+/// `line` with the first quoted value after `key =` replaced by `new(old)`
+fn replace_value(line: &str, key: &str, new: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let mut search = 0;
+    while let Some(at) = line[search..].find(key) {
+        let start = search + at;
+        search = start + key.len();
+        // Only whole keys: `version` but not `rust-version`
+        let before = line[..start].chars().next_back();
+        if before.is_some_and(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+            continue;
+        }
+        let rest = line[search..].trim_start();
+        let Some(rest) = rest.strip_prefix('=') else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            continue;
+        };
+        let value_start = line.len() - rest.len() + 1;
+        let end = value_start + line[value_start..].find(quote)?;
+        let value = new(&line[value_start..end])?;
+        return Some(format!("{}{value}{}", &line[..value_start], &line[end..]));
+    }
+    None
+}
+
+/// `text` with `key` of table `section` set to `value`, or `None` if unchanged
+fn set_key(text: &str, section: &str, key: &str, value: &str) -> Option<String> {
+    let mut current = String::new();
+    let mut changed = false;
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        if let Some(name) = header(line) {
+            current = name;
+        } else if !changed && current == section && key_is(line, key) {
+            if let Some(new) =
+                replace_value(line, key, |old| (old != value).then(|| value.to_string()))
+            {
+                out.push_str(&new);
+                changed = true;
+                continue;
+            }
+        }
+        out.push_str(line);
+    }
+    changed.then_some(out)
+}
+
+/// Set the `[package]` version
+pub(crate) fn set_package_version(text: &str, version: &str) -> Option<String> {
+    set_key(text, "package", "version", version)
+}
+
+/// Set the `[workspace.package]` version inherited by members
+pub(crate) fn set_workspace_version(text: &str, version: &str) -> Option<String> {
+    set_key(text, "workspace.package", "version", version)
+}
+
+/// `req` pointing at `version`, keeping its operator (`^`, `=`, `~`)
+fn retarget(req: &str, version: &str) -> Option<String> {
+    if req.contains(',') || req.contains('*') {
+        return None;
+    }
+    let op: String = req
+        .chars()
+        .take_while(|c| matches!(c, '^' | '~' | '=' | '>' | '<' | ' '))
+        .collect();
+    let new = format!("{op}{version}");
+    (new != req).then_some(new)
+}
+
+/// Point the version requirement of every path or git dependency on `name`
+/// at `version`; registry-only dependencies keep their requirement
+pub(crate) fn set_dependency_version(text: &str, name: &str, version: &str) -> Option<String> {
+    let local = |line: &str| key_is(line, "path") || key_is(line, "git");
+    let inline_local = |line: &str| line.contains("path =") || line.contains("git =");
+    let names = |line: &str| key_is(line, name) || line.contains(&format!("package = \"{name}\""));
+
+    // One table (header plus body) at a time
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut out: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    let mut changed = false;
+    let mut start = 0;
+    while start < lines.len() {
+        let section = header(lines[start]).unwrap_or_default();
+        let end = (start + 1..lines.len())
+            .find(|&i| header(lines[i]).is_some())
+            .unwrap_or(lines.len());
+        let body = if header(lines[start]).is_some() {
+            start + 1..end
+        } else {
+            start..end
+        };
+        if is_dependency_table(&section) {
+            for i in body {
+                if names(lines[i]) && inline_local(lines[i]) {
+                    if let Some(new) =
+                        replace_value(lines[i], "version", |old| retarget(old, version))
+                    {
+                        out[i] = new;
+                        changed = true;
+                    }
+                }
+            }
+        } else if let Some((table, key)) = section.rsplit_once('.') {
+            let is_dependency = is_dependency_table(table)
+                && (key == name || lines[body.clone()].iter().any(|l| names(l)));
+            if is_dependency && lines[body.clone()].iter().any(|l| local(l)) {
+                for i in body {
+                    if key_is(lines[i], "version") {
+                        if let Some(new) =
+                            replace_value(lines[i], "version", |old| retarget(old, version))
+                        {
+                            out[i] = new;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+        start = end;
+    }
+    changed.then(|| out.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_versions() {
+        let text =
+            "[package]\nname = \"core\"\nversion = \"0.1.0\" # keep\nrust-version = \"1.70\"\n";
+        assert_eq!(
+            package_version(text),
+            Some(VersionSource::Literal("0.1.0".to_string()))
+        );
+        assert_eq!(
+            set_package_version(text, "0.2.0").unwrap(),
+            "[package]\nname = \"core\"\nversion = \"0.2.0\" # keep\nrust-version = \"1.70\"\n"
+        );
+        assert!(set_package_version(text, "0.1.0").is_none());
+
+        let member = "[package]\nname = \"cli\"\nversion.workspace = true\n";
+        assert_eq!(package_version(member), Some(VersionSource::Workspace));
+        let root = "[workspace]\nmembers = [\"cli\"]\n\n[workspace.package]\nversion = \"1.4.2\"\n";
+        assert!(set_workspace_version(root, "1.5.0")
+            .unwrap()
+            .ends_with("[workspace.package]\nversion = \"1.5.0\"\n"));
+    }
+
+    #[test]
+    fn test_dependency_versions() {
+        let text = r#"[dependencies]
+core = { path = "../core", version = "=0.1.0" }
+serde = { version = "1" }
+renamed = { package = "core", git = "https://example.com/core", version = "0.1" }
+
+[dev-dependencies]
+core = "0.1"
+
+[build-dependencies.core]
+path = "../core"
+version = "^0.1.0"
+"#;
+        let updated = set_dependency_version(text, "core", "0.2.0").unwrap();
+        assert!(updated.contains("core = { path = \"../core\", version = \"=0.2.0\" }"));
+        assert!(updated.contains("git = \"https://example.com/core\", version = \"0.2.0\" }"));
+        // Registry-only requirement untouched
+        assert!(updated.contains("[dev-dependencies]\ncore = \"0.1\"\n"));
+        assert!(updated.ends_with("path = \"../core\"\nversion = \"^0.2.0\"\n"));
+        assert!(set_dependency_version(text, "serde", "2.0.0").is_none());
+    }
+}
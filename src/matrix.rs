@@ -0,0 +1,182 @@
+//! `--target-matrix`: run build/test/bench for every repo × target
+//!
+//! Each repo runs the command once per `--target`, in-process, and the
+//! results are summarized as a grid with one row per repo and one column per
+//! target. `--format json` emits the same grid as JSON, so platform coverage
+//! can be tracked across the meta repo while porting.
+
+use crate::config::Config;
+use crate::runner::{self, RunOutcome};
+use crate::{limits, CommandResult, PlannedCommand};
+use colored::Colorize;
+use serde_json::json;
+use std::path::Path;
+
+/// Parse a comma-separated `--target-matrix` value
+pub fn parse_targets(list: &str) -> Result<Vec<String>, String> {
+    let targets: Vec<String> = list
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect();
+    if targets.is_empty() {
+        return Err("--target-matrix expects a comma-separated list of targets".to_string());
+    }
+    Ok(targets)
+}
+
+/// One command per repo and target, repo-major
+pub fn plan(cmd: &str, repos: &[String], targets: &[String]) -> Vec<PlannedCommand> {
+    repos
+        .iter()
+        .flat_map(|repo| {
+            targets.iter().map(move |target| PlannedCommand {
+                dir: repo.clone(),
+                cmd: format!("{cmd} --target {target}"),
+                env: None,
+            })
+        })
+        .collect()
+}
+
+/// The repo × target grid as text
+pub fn render_grid(repos: &[String], targets: &[String], outcomes: &[RunOutcome]) -> String {
+    let repo_width = repos.iter().map(String::len).max().unwrap_or(0).max(4);
+    let mut out = format!("{:repo_width$}", "repo");
+    for target in targets {
+        out.push_str(&format!("  {target}"));
+    }
+    out.push('\n');
+    for (row, repo) in repos.iter().enumerate() {
+        out.push_str(&format!("{repo:repo_width$}"));
+        for (col, target) in targets.iter().enumerate() {
+            let cell = if outcomes[row * targets.len() + col].success {
+                format!("{:width$}", "ok", width = target.len())
+                    .green()
+                    .to_string()
+            } else {
+                format!("{:width$}", "FAIL", width = target.len())
+                    .red()
+                    .to_string()
+            };
+            out.push_str(&format!("  {cell}"));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The repo × target grid as JSON
+pub fn render_json(
+    repos: &[String],
+    targets: &[String],
+    outcomes: &[RunOutcome],
+) -> serde_json::Value {
+    let rows: Vec<serde_json::Value> = repos
+        .iter()
+        .enumerate()
+        .map(|(row, repo)| {
+            let mut cells = serde_json::Map::new();
+            for (col, target) in targets.iter().enumerate() {
+                let outcome = &outcomes[row * targets.len() + col];
+                cells.insert(
+                    target.clone(),
+                    json!({
+                        "success": outcome.success,
+                        "exit_code": outcome.exit_code,
+                        "duration_ms": outcome.duration.as_millis() as u64,
+                    }),
+                );
+            }
+            json!({"repo": repo, "targets": cells})
+        })
+        .collect();
+    json!({"targets": targets, "repos": rows})
+}
+
+/// Run `cmd` for every repo × target and summarize the grid
+pub(crate) fn execute(
+    cmd: &str,
+    targets: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+    as_json: bool,
+) -> CommandResult {
+    let mut commands = plan(cmd, repos, targets);
+    if let Err(e) = limits::apply(&mut commands, &config.limits) {
+        return CommandResult::Error(e);
+    }
+    let limits = match runner::concurrency_for(&commands, &config.concurrency) {
+        Ok(l) => l,
+        Err(e) => return CommandResult::Error(e),
+    };
+    let outcomes = runner::run_all_limited(cwd, &commands, parallel, &limits);
+    let failed = outcomes.iter().any(|o| !o.success);
+
+    let out = if as_json {
+        serde_json::to_string_pretty(&render_json(repos, targets, &outcomes)).unwrap_or_default()
+    } else {
+        let mut out = render_grid(repos, targets, &outcomes);
+        for (outcome, target) in outcomes.iter().zip(targets.iter().cycle()) {
+            if !outcome.success {
+                let reason = outcome.stderr.trim().lines().last().unwrap_or("");
+                out.push_str(&format!(
+                    "{} {} ({target}): {reason}\n",
+                    "error:".red(),
+                    outcome.dir
+                ));
+            }
+        }
+        out
+    };
+    if failed {
+        CommandResult::Error(out)
+    } else {
+        CommandResult::Message(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::strings;
+
+    #[test]
+    fn test_plan_is_repo_major() {
+        let commands = plan(
+            "cargo build",
+            &strings(&["api", "web"]),
+            &parse_targets("x86_64-unknown-linux-gnu, wasm32-unknown-unknown").unwrap(),
+        );
+        assert_eq!(commands.len(), 4);
+        assert_eq!(commands[1].dir, "api");
+        assert_eq!(
+            commands[1].cmd,
+            "cargo build --target wasm32-unknown-unknown"
+        );
+        assert!(parse_targets(" , ").is_err());
+    }
+
+    #[test]
+    fn test_grid_and_json_cells() {
+        let repos = strings(&["api", "web"]);
+        let targets = strings(&["linux", "wasm"]);
+        let outcomes = vec![
+            RunOutcome::fixture("api", true),
+            RunOutcome::fixture("api", false),
+            RunOutcome::fixture("web", true),
+            RunOutcome::fixture("web", true),
+        ];
+        let grid = render_grid(&repos, &targets, &outcomes);
+        let lines: Vec<&str> = grid.lines().collect();
+        assert_eq!(lines[0], "repo  linux  wasm");
+        assert!(lines[1].starts_with("api ") && lines[1].contains("FAIL"));
+        assert!(!lines[2].contains("FAIL"));
+        let value = render_json(&repos, &targets, &outcomes);
+        assert_eq!(value["repos"][0]["targets"]["wasm"]["success"], false);
+        assert_eq!(value["repos"][1]["repo"], "web");
+    }
+}
@@ -0,0 +1,869 @@
+//! Package information from `cargo metadata`
+//!
+//! Output is reused until a file it came from is modified (the manifest,
+//! lockfile and cargo config, the local package manifests and the directories
+//! workspace member globs expand in): in memory for the life of the process,
+//! and across runs in `.meta-rust/metadata/` of the meta root set with
+//! [`with_cache_root`]. [`load_each`] loads many repos concurrently.
+
+use anyhow::{bail, Context};
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// Most `cargo metadata` processes running at once
+const MAX_CONCURRENT: usize = 8;
+
+/// `cargo metadata` output and the modification times of the files it was
+/// derived from
+struct Cached {
+    stamps: Vec<(PathBuf, Option<SystemTime>)>,
+    json: String,
+}
+
+/// Persisted `cargo metadata` output, relative to the meta root
+const CACHE_DIR: &str = ".meta-rust/metadata";
+
+/// Cargo config files read from a directory and each of its parents
+const CARGO_CONFIGS: &[&str] = &[".cargo/config.toml", ".cargo/config"];
+
+/// Output of earlier `cargo metadata` runs, keyed by directory and arguments
+static CACHE: Mutex<BTreeMap<(PathBuf, String), Cached>> = Mutex::new(BTreeMap::new());
+
+thread_local! {
+    /// Meta root the output of this thread's loads persists under
+    static CACHE_ROOT: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with loads persisting their output under the meta root `root`
+///
+/// Outside of it, output is only reused within the process.
+pub fn with_cache_root<T>(root: Option<&Path>, f: impl FnOnce() -> T) -> T {
+    let previous = CACHE_ROOT.with(|r| r.replace(root.map(Path::to_path_buf)));
+    let result = f();
+    CACHE_ROOT.with(|r| *r.borrow_mut() = previous);
+    result
+}
+
+/// Kind of a dependency edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DependencyKind {
+    Normal,
+    Dev,
+    Build,
+}
+
+/// A dependency declared by a package
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub name: String,
+    pub req: String,
+    pub kind: DependencyKind,
+    /// Local path for `path = "..."` dependencies
+    pub path: Option<PathBuf>,
+    /// `registry+...` or `git+...`; `None` for path dependencies
+    pub source: Option<String>,
+    /// Features requested on top of the defaults
+    pub features: Vec<String>,
+}
+
+/// A build target of a package (lib, bin, test, build script, ...)
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub name: String,
+    /// Target kinds as reported by cargo, e.g. `lib`, `bin`, `custom-build`
+    pub kinds: Vec<String>,
+    pub src_path: PathBuf,
+    /// Features the target needs (`required-features`)
+    pub required_features: Vec<String>,
+}
+
+/// A package as reported by `cargo metadata`
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    /// `None` for local packages, else e.g. `registry+https://...` or `git+...`
+    pub source: Option<String>,
+    /// Native library declared with the `links` manifest key
+    pub links: Option<String>,
+    /// Registries the package may be published to; `None` means any, an
+    /// empty list means `publish = false`
+    pub publish: Option<Vec<String>>,
+    pub manifest_path: PathBuf,
+    pub edition: String,
+    /// Minimum supported Rust version (`rust-version`)
+    pub rust_version: Option<String>,
+    pub dependencies: Vec<Dependency>,
+    pub targets: Vec<Target>,
+    /// Declared features and what each enables
+    pub features: BTreeMap<String, Vec<String>>,
+}
+
+impl Package {
+    /// Directory containing the package's Cargo.toml
+    pub fn root(&self) -> &Path {
+        self.manifest_path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// Whether the package is published at all (not `publish = false`)
+    pub fn is_publishable(&self) -> bool {
+        self.publish.as_ref().is_none_or(|r| !r.is_empty())
+    }
+
+    /// Whether the package has a target of `kind`
+    pub fn has_target(&self, kind: &str) -> bool {
+        self.targets
+            .iter()
+            .any(|t| t.kinds.iter().any(|k| k == kind))
+    }
+}
+
+#[cfg(test)]
+impl Package {
+    /// A local `0.1.0` package at `/ws/<name>` with no dependencies or targets
+    pub(crate) fn fixture(name: &str) -> Self {
+        Package {
+            name: name.to_string(),
+            version: "0.1.0".to_string(),
+            source: None,
+            links: None,
+            publish: None,
+            manifest_path: PathBuf::from(format!("/ws/{name}/Cargo.toml")),
+            edition: "2021".to_string(),
+            rust_version: None,
+            dependencies: Vec::new(),
+            targets: Vec::new(),
+            features: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn with_version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    /// Fetched from crates.io into `/reg/<name>-<version>`
+    pub(crate) fn in_registry(mut self) -> Self {
+        self.source = Some("registry+https://github.com/rust-lang/crates.io-index".to_string());
+        self.manifest_path =
+            PathBuf::from(format!("/reg/{}-{}/Cargo.toml", self.name, self.version));
+        self
+    }
+
+    pub(crate) fn with_root(mut self, root: impl AsRef<Path>) -> Self {
+        self.manifest_path = root.as_ref().join("Cargo.toml");
+        self
+    }
+
+    pub(crate) fn with_rust_version(mut self, rust_version: &str) -> Self {
+        self.rust_version = Some(rust_version.to_string());
+        self
+    }
+
+    /// Add normal dependencies on `names`
+    pub(crate) fn with_dependencies(mut self, names: &[&str]) -> Self {
+        self.dependencies
+            .extend(names.iter().map(|n| Dependency::fixture(n)));
+        self
+    }
+
+    pub(crate) fn with_links(mut self, links: &str) -> Self {
+        self.links = Some(links.to_string());
+        self
+    }
+
+    pub(crate) fn with_dependency(mut self, dependency: Dependency) -> Self {
+        self.dependencies.push(dependency);
+        self
+    }
+
+    /// Add a target of `kind` named after the package
+    pub(crate) fn with_target(mut self, kind: &str, src_path: impl Into<PathBuf>) -> Self {
+        self.targets.push(Target {
+            name: self.name.replace('-', "_"),
+            kinds: vec![kind.to_string()],
+            src_path: src_path.into(),
+            required_features: Vec::new(),
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+impl Dependency {
+    /// A normal `*` dependency on `name` with no path or source
+    pub(crate) fn fixture(name: &str) -> Self {
+        Dependency {
+            name: name.to_string(),
+            req: "*".to_string(),
+            kind: DependencyKind::Normal,
+            path: None,
+            source: None,
+            features: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_kind(mut self, kind: DependencyKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Resolved from crates.io
+    pub(crate) fn in_registry(mut self) -> Self {
+        self.source = Some("registry+https://github.com/rust-lang/crates.io-index".to_string());
+        self
+    }
+}
+
+/// Run `cargo metadata --no-deps` in `dir` and return its packages
+pub fn load_packages(dir: &Path) -> anyhow::Result<Vec<Package>> {
+    run_metadata(dir, &["metadata", "--no-deps", "--format-version", "1"])
+}
+
+/// Run `cargo metadata` in `dir` and return every package in the dependency
+/// graph, including registry and git dependencies
+pub fn load_all_packages(dir: &Path) -> anyhow::Result<Vec<Package>> {
+    run_metadata(dir, &["metadata", "--format-version", "1"])
+}
+
+/// `load` for every dir in `dirs`, a bounded number at a time, with the
+/// results in the order of `dirs`
+pub fn load_each<T: Send>(dirs: &[PathBuf], load: impl Fn(&Path) -> T + Sync) -> Vec<T> {
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(MAX_CONCURRENT)
+        .min(dirs.len());
+    if workers < 2 {
+        return dirs.iter().map(|d| load(d)).collect();
+    }
+    let root = CACHE_ROOT.with(|r| r.borrow().clone());
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<T>>> = Mutex::new((0..dirs.len()).map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                with_cache_root(root.as_deref(), || loop {
+                    let i = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(dir) = dirs.get(i) else {
+                        break;
+                    };
+                    let result = load(dir);
+                    results.lock().unwrap()[i] = Some(result);
+                })
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("every dir is loaded"))
+        .collect()
+}
+
+/// Manifests of the repo at `repo_dir`: its root Cargo.toml plus every
+/// package `cargo metadata --no-deps` reports
+pub fn manifest_paths(repo_dir: &Path) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let root = repo_dir.join("Cargo.toml");
+    if root.is_file() {
+        paths.push(root);
+    }
+    if let Ok(packages) = load_packages(repo_dir) {
+        paths.extend(packages.into_iter().map(|p| p.manifest_path));
+    }
+    let mut paths: Vec<PathBuf> = paths
+        .into_iter()
+        .map(|p| p.canonicalize().unwrap_or(p))
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// A package of the resolved dependency graph and its enabled features
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedFeatures {
+    pub name: String,
+    pub version: String,
+    pub features: Vec<String>,
+}
+
+/// Run `cargo metadata` in `dir` and return the features enabled for every
+/// package of the resolved graph
+pub fn load_resolved_features(dir: &Path) -> anyhow::Result<Vec<ResolvedFeatures>> {
+    parse_resolved_features(&metadata_json(dir, &["metadata", "--format-version", "1"])?)
+}
+
+/// Parse the `resolve` graph of `cargo metadata` JSON output
+pub fn parse_resolved_features(json: &str) -> anyhow::Result<Vec<ResolvedFeatures>> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).context("invalid cargo metadata output")?;
+    let Some(nodes) = value["resolve"]["nodes"].as_array() else {
+        bail!("cargo metadata output has no resolve graph");
+    };
+    let packages = value["packages"].as_array().cloned().unwrap_or_default();
+    Ok(nodes
+        .iter()
+        .filter_map(|node| {
+            let package = packages.iter().find(|p| p["id"] == node["id"])?;
+            Some(ResolvedFeatures {
+                name: package["name"].as_str()?.to_string(),
+                version: package["version"].as_str().unwrap_or("").to_string(),
+                features: node["features"]
+                    .as_array()
+                    .map(|f| {
+                        f.iter()
+                            .filter_map(|f| f.as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            })
+        })
+        .collect())
+}
+
+/// A package of the resolved dependency graph with what an SBOM records
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    /// `None` for local packages
+    pub source: Option<String>,
+    /// SPDX license expression from the manifest
+    pub license: Option<String>,
+    /// Whether the package is a member of the workspace
+    pub member: bool,
+    /// Ids of the packages this one depends on
+    pub dependencies: Vec<String>,
+}
+
+/// Run `cargo metadata` in `dir` and return every package of the resolved
+/// graph
+pub fn load_components(dir: &Path) -> anyhow::Result<Vec<Component>> {
+    parse_components(&metadata_json(dir, &["metadata", "--format-version", "1"])?)
+}
+
+/// Parse the packages and `resolve` graph of `cargo metadata` JSON output
+pub fn parse_components(json: &str) -> anyhow::Result<Vec<Component>> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).context("invalid cargo metadata output")?;
+    let Some(packages) = value["packages"].as_array() else {
+        bail!("cargo metadata output has no packages");
+    };
+    let ids = |v: &serde_json::Value| -> Vec<String> {
+        v.as_array()
+            .map(|a| {
+                a.iter()
+                    .filter_map(|id| id.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let members = ids(&value["workspace_members"]);
+    let nodes = value["resolve"]["nodes"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    Ok(packages
+        .iter()
+        .filter_map(|p| {
+            let id = p["id"].as_str()?.to_string();
+            let dependencies = nodes
+                .iter()
+                .find(|n| n["id"].as_str() == Some(id.as_str()))
+                .map(|n| ids(&n["dependencies"]))
+                .unwrap_or_default();
+            Some(Component {
+                name: p["name"].as_str().unwrap_or("").to_string(),
+                version: p["version"].as_str().unwrap_or("").to_string(),
+                source: p["source"].as_str().map(str::to_string),
+                license: p["license"].as_str().map(str::to_string),
+                member: members.contains(&id),
+                dependencies,
+                id,
+            })
+        })
+        .collect())
+}
+
+fn run_metadata(dir: &Path, args: &[&str]) -> anyhow::Result<Vec<Package>> {
+    parse_packages(&metadata_json(dir, args)?)
+}
+
+/// Modification time of `path`, `None` if it doesn't exist
+fn mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The files `json` was derived from: the manifest and lockfile of `dir`,
+/// the cargo config of `dir` and its parents, the manifest of every local
+/// package and the directories workspace member globs were expanded in
+fn stamps(dir: &Path, json: &str) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut files = vec![dir.join("Cargo.toml"), dir.join("Cargo.lock")];
+    for ancestor in dir.ancestors() {
+        files.extend(CARGO_CONFIGS.iter().map(|c| ancestor.join(c)));
+    }
+    files.extend(member_glob_dirs(dir));
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(json) {
+        for p in value["packages"].as_array().into_iter().flatten() {
+            if p["source"].is_null() {
+                if let Some(path) = p["manifest_path"].as_str() {
+                    files.push(PathBuf::from(path));
+                }
+            }
+        }
+    }
+    files.sort();
+    files.dedup();
+    files
+        .into_iter()
+        .map(|f| {
+            let time = mtime(&f);
+            (f, time)
+        })
+        .collect()
+}
+
+/// Directories whose entries decide what the `[workspace] members` globs of
+/// `dir/Cargo.toml` expand to
+fn member_glob_dirs(dir: &Path) -> Vec<PathBuf> {
+    let Some(manifest) = std::fs::read_to_string(dir.join("Cargo.toml"))
+        .ok()
+        .and_then(|text| toml::from_str::<toml::Table>(&text).ok())
+    else {
+        return Vec::new();
+    };
+    let members = manifest
+        .get("workspace")
+        .and_then(|w| w.get("members"))
+        .and_then(|m| m.as_array());
+    let mut globbed = Vec::new();
+    for pattern in members.into_iter().flatten().filter_map(|m| m.as_str()) {
+        let mut current = vec![dir.to_path_buf()];
+        for segment in pattern.split('/') {
+            if !segment.contains(['*', '?']) {
+                current = current.into_iter().map(|d| d.join(segment)).collect();
+                continue;
+            }
+            let matched = current
+                .iter()
+                .filter_map(|d| std::fs::read_dir(d).ok())
+                .flatten()
+                .flatten()
+                .filter(|e| e.path().is_dir())
+                .filter(|e| crate::glob::matches(segment, &e.file_name().to_string_lossy()))
+                .map(|e| e.path())
+                .collect();
+            globbed.append(&mut current);
+            current = matched;
+        }
+    }
+    globbed
+}
+
+impl Cached {
+    fn is_fresh(&self) -> bool {
+        self.stamps.iter().all(|(f, t)| mtime(f) == *t)
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let stamps: Vec<serde_json::Value> = self
+            .stamps
+            .iter()
+            .map(|(f, t)| {
+                let nanos = t
+                    .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                    .map(|d| d.as_nanos() as u64);
+                serde_json::json!([f.display().to_string(), nanos])
+            })
+            .collect();
+        serde_json::json!({ "stamps": stamps, "json": self.json })
+    }
+
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        let stamps = value["stamps"]
+            .as_array()?
+            .iter()
+            .map(|s| {
+                let time = s[1]
+                    .as_u64()
+                    .map(|n| SystemTime::UNIX_EPOCH + Duration::from_nanos(n));
+                Some((PathBuf::from(s[0].as_str()?), time))
+            })
+            .collect::<Option<_>>()?;
+        Some(Self {
+            stamps,
+            json: value["json"].as_str()?.to_string(),
+        })
+    }
+}
+
+/// Where the output for `key` persists: under the meta root set with
+/// [`with_cache_root`], if there is one
+fn cache_file(key: &(PathBuf, String)) -> Option<PathBuf> {
+    let root = CACHE_ROOT.with(|r| r.borrow().clone())?;
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    key.hash(&mut hasher);
+    Some(
+        root.join(CACHE_DIR)
+            .join(format!("{:016x}.json", hasher.finish())),
+    )
+}
+
+/// `cargo metadata` output for `dir`, reused while none of the files it was
+/// derived from has changed
+fn metadata_json(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let key = (
+        dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf()),
+        args.join(" "),
+    );
+    if let Some(cached) = CACHE.lock().ok().and_then(|cache| {
+        cache
+            .get(&key)
+            .filter(|c| c.is_fresh())
+            .map(|c| c.json.clone())
+    }) {
+        return Ok(cached);
+    }
+    let file = cache_file(&key);
+    let persisted = file
+        .as_ref()
+        .and_then(|f| std::fs::read_to_string(f).ok())
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .and_then(|value| Cached::from_json(&value))
+        .filter(Cached::is_fresh);
+    let cached = match persisted {
+        Some(cached) => cached,
+        None => {
+            let json = run_cargo_metadata(dir, args)?;
+            let cached = Cached {
+                stamps: stamps(dir, &json),
+                json,
+            };
+            // A cache that can't be written only costs the next run its speedup
+            if let Some(file) = &file {
+                let _ = file
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| {
+                        crate::state::write_atomic(file, cached.to_json().to_string().as_bytes())
+                    });
+            }
+            cached
+        }
+    };
+    let json = cached.json.clone();
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.insert(key, cached);
+    }
+    Ok(json)
+}
+
+fn run_cargo_metadata(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("cargo")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("failed to run cargo metadata")?;
+    if !output.status.success() {
+        bail!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parse the `packages` array of `cargo metadata` JSON output
+pub fn parse_packages(json: &str) -> anyhow::Result<Vec<Package>> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).context("invalid cargo metadata output")?;
+    let Some(packages) = value["packages"].as_array() else {
+        bail!("cargo metadata output has no packages");
+    };
+
+    let str_field = |v: &serde_json::Value, key: &str| v[key].as_str().unwrap_or("").to_string();
+    Ok(packages
+        .iter()
+        .map(|p| Package {
+            name: str_field(p, "name"),
+            version: str_field(p, "version"),
+            source: p["source"].as_str().map(str::to_string),
+            links: p["links"].as_str().map(str::to_string),
+            publish: p["publish"].as_array().map(|r| {
+                r.iter()
+                    .filter_map(|r| r.as_str().map(str::to_string))
+                    .collect()
+            }),
+            manifest_path: PathBuf::from(str_field(p, "manifest_path")),
+            edition: str_field(p, "edition"),
+            rust_version: p["rust_version"].as_str().map(str::to_string),
+            dependencies: p["dependencies"]
+                .as_array()
+                .map(|deps| {
+                    deps.iter()
+                        .map(|d| Dependency {
+                            name: str_field(d, "name"),
+                            req: str_field(d, "req"),
+                            kind: match d["kind"].as_str() {
+                                Some("dev") => DependencyKind::Dev,
+                                Some("build") => DependencyKind::Build,
+                                _ => DependencyKind::Normal,
+                            },
+                            path: d["path"].as_str().map(PathBuf::from),
+                            source: d["source"].as_str().map(str::to_string),
+                            features: d["features"]
+                                .as_array()
+                                .map(|f| {
+                                    f.iter()
+                                        .filter_map(|f| f.as_str().map(str::to_string))
+                                        .collect()
+                                })
+                                .unwrap_or_default(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            targets: p["targets"]
+                .as_array()
+                .map(|targets| {
+                    targets
+                        .iter()
+                        .map(|t| Target {
+                            name: str_field(t, "name"),
+                            kinds: t["kind"]
+                                .as_array()
+                                .map(|k| {
+                                    k.iter()
+                                        .filter_map(|k| k.as_str().map(str::to_string))
+                                        .collect()
+                                })
+                                .unwrap_or_default(),
+                            src_path: PathBuf::from(str_field(t, "src_path")),
+                            required_features: t["required-features"]
+                                .as_array()
+                                .map(|f| {
+                                    f.iter()
+                                        .filter_map(|f| f.as_str().map(str::to_string))
+                                        .collect()
+                                })
+                                .unwrap_or_default(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            features: p["features"]
+                .as_object()
+                .map(|features| {
+                    features
+                        .iter()
+                        .map(|(name, enables)| {
+                            let enables = enables
+                                .as_array()
+                                .map(|e| {
+                                    e.iter()
+                                        .filter_map(|e| e.as_str().map(str::to_string))
+                                        .collect()
+                                })
+                                .unwrap_or_default();
+                            (name.clone(), enables)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_packages() {
+        let json = r#"{
+            "packages": [{
+                "name": "app",
+                "version": "0.2.0",
+                "source": null,
+                "links": "app_native",
+                "publish": [],
+                "manifest_path": "/ws/app/Cargo.toml",
+                "edition": "2021",
+                "rust_version": "1.74",
+                "dependencies": [
+                    {"name": "core", "req": "^0.1", "kind": null, "path": "/ws/core"},
+                    {"name": "tempfile", "req": "^3", "kind": "dev"}
+                ],
+                "targets": [
+                    {"name": "app", "kind": ["bin"], "src_path": "/ws/app/src/main.rs", "required-features": ["cli"]},
+                    {"name": "build-script-build", "kind": ["custom-build"], "src_path": "/ws/app/build.rs"}
+                ]
+            }]
+        }"#;
+        let packages = parse_packages(json).unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].root(), Path::new("/ws/app"));
+        assert_eq!(packages[0].edition, "2021");
+        assert_eq!(packages[0].source, None);
+        assert_eq!(packages[0].links.as_deref(), Some("app_native"));
+        assert_eq!(packages[0].publish, Some(vec![]));
+        assert_eq!(packages[0].rust_version.as_deref(), Some("1.74"));
+        assert_eq!(
+            packages[0].dependencies[0].path,
+            Some(PathBuf::from("/ws/core"))
+        );
+        assert_eq!(packages[0].dependencies[1].kind, DependencyKind::Dev);
+        assert_eq!(packages[0].targets[0].required_features, vec!["cli"]);
+        assert!(packages[0].has_target("custom-build"));
+        assert!(!packages[0].has_target("lib"));
+    }
+
+    #[test]
+    fn test_parse_resolved_features() {
+        let json = r#"{
+            "packages": [
+                {"id": "tokio 1.38.0 (registry+x)", "name": "tokio", "version": "1.38.0"},
+                {"id": "app 0.1.0 (path+file:///ws/app)", "name": "app", "version": "0.1.0"}
+            ],
+            "resolve": {"nodes": [
+                {"id": "tokio 1.38.0 (registry+x)", "features": ["default", "full", "rt"]},
+                {"id": "app 0.1.0 (path+file:///ws/app)", "features": []}
+            ]}
+        }"#;
+        let resolved = parse_resolved_features(json).unwrap();
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved[0].name, "tokio");
+        assert_eq!(resolved[0].features, vec!["default", "full", "rt"]);
+        assert!(parse_resolved_features(r#"{"packages": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_components() {
+        let json = r#"{
+            "packages": [
+                {"id": "serde 1.0.200 (registry+x)", "name": "serde", "version": "1.0.200", "source": "registry+x", "license": "MIT OR Apache-2.0"},
+                {"id": "app 0.1.0 (path+file:///ws/app)", "name": "app", "version": "0.1.0", "source": null, "license": null}
+            ],
+            "workspace_members": ["app 0.1.0 (path+file:///ws/app)"],
+            "resolve": {"nodes": [
+                {"id": "serde 1.0.200 (registry+x)", "dependencies": []},
+                {"id": "app 0.1.0 (path+file:///ws/app)", "dependencies": ["serde 1.0.200 (registry+x)"]}
+            ]}
+        }"#;
+        let components = parse_components(json).unwrap();
+        assert_eq!(components[0].license.as_deref(), Some("MIT OR Apache-2.0"));
+        assert!(!components[0].member);
+        assert!(components[1].member);
+        assert_eq!(components[1].source, None);
+        assert_eq!(
+            components[1].dependencies,
+            vec!["serde 1.0.200 (registry+x)"]
+        );
+    }
+
+    #[test]
+    fn test_load_each_keeps_order() {
+        let dirs: Vec<PathBuf> = (0..20)
+            .map(|i| PathBuf::from(format!("/repo{i}")))
+            .collect();
+        let loaded = load_each(&dirs, |d| d.display().to_string());
+        assert_eq!(loaded[0], "/repo0");
+        assert_eq!(loaded[19], "/repo19");
+    }
+
+    #[test]
+    fn test_cached_metadata_follows_manifest_changes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let manifest = |name: &str| {
+            format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n")
+        };
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+        std::fs::write(dir.join("Cargo.toml"), manifest("first")).unwrap();
+        assert_eq!(load_packages(dir).unwrap()[0].name, "first");
+        assert_eq!(load_packages(dir).unwrap()[0].name, "first");
+
+        std::fs::write(dir.join("Cargo.toml"), manifest("second")).unwrap();
+        // Make sure the change is visible even on coarse-grained filesystems
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(dir.join("Cargo.toml"))
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!(load_packages(dir).unwrap()[0].name, "second");
+    }
+
+    #[test]
+    fn test_stamps_cover_cargo_config_and_member_globs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        std::fs::create_dir_all(dir.join("crates/a")).unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\", \"tools\"]\n",
+        )
+        .unwrap();
+        let files: Vec<PathBuf> = stamps(dir, "{}").into_iter().map(|(f, _)| f).collect();
+        assert!(files.contains(&dir.join(".cargo/config.toml")));
+        assert!(files.contains(&dir.join("crates")));
+        assert!(!files.contains(&dir.join("tools")));
+    }
+
+    #[test]
+    fn test_metadata_cache_persists_under_meta_root() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(root.join(".meta"), r#"{"projects": {}}"#).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"core\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        )
+        .unwrap();
+        // The meta root's own repo, loaded from worker threads
+        let dirs = vec![root.to_path_buf(), root.to_path_buf()];
+        let loaded = with_cache_root(Some(root), || load_each(&dirs, load_packages));
+        assert_eq!(loaded[1].as_ref().unwrap()[0].name, "core");
+        let entries: Vec<_> = std::fs::read_dir(root.join(CACHE_DIR))
+            .unwrap()
+            .flatten()
+            .collect();
+        assert_eq!(entries.len(), 1);
+
+        // A later process finds the stored output while the files are unchanged
+        let key = (
+            root.canonicalize().unwrap(),
+            "metadata --no-deps --format-version 1".to_string(),
+        );
+        let text = std::fs::read_to_string(entries[0].path()).unwrap();
+        let cached = Cached::from_json(&serde_json::from_str(&text).unwrap()).unwrap();
+        assert!(cached.is_fresh());
+        assert_eq!(
+            with_cache_root(Some(root), || cache_file(&key)).as_deref(),
+            Some(entries[0].path().as_path())
+        );
+        // Without a meta root nothing is persisted
+        assert_eq!(cache_file(&key), None);
+    }
+
+    #[test]
+    fn test_parse_packages_rejects_garbage() {
+        assert!(parse_packages("not json").is_err());
+    }
+}
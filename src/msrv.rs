@@ -0,0 +1,230 @@
+//! `meta cargo msrv-impact <dep>@<version>`: would a dependency bump raise
+//! anyone's MSRV?
+//!
+//! The `rust-version` the dependency declares at that version comes from the
+//! crates.io index and is compared with the `rust-version` of every package
+//! depending on it. Only the dependency's own declaration is checked; new
+//! transitive dependencies may require a newer toolchain still.
+
+use crate::metadata::{self, DependencyKind, Package};
+use crate::{project_path, publish, CommandResult};
+use colored::Colorize;
+use std::path::Path;
+
+/// `1.70` / `1.70.0` as a comparable triple
+fn version_key(version: &str) -> Option<[u64; 3]> {
+    let mut key = [0; 3];
+    for (slot, part) in key.iter_mut().zip(version.trim().split('.')) {
+        *slot = part.parse().ok()?;
+    }
+    Some(key)
+}
+
+/// The index record for `spec`: that exact version, or else the highest
+/// non-yanked version starting with it (`1.2` matches `1.2.7`)
+fn select_version<'a>(
+    records: &'a [serde_json::Value],
+    spec: &str,
+) -> Option<&'a serde_json::Value> {
+    let vers = |r: &serde_json::Value| r["vers"].as_str().unwrap_or("").to_string();
+    if let Some(exact) = records.iter().find(|r| vers(r) == spec) {
+        return Some(exact);
+    }
+    records
+        .iter()
+        .filter(|r| !r["yanked"].as_bool().unwrap_or(false))
+        .filter(|r| vers(r).starts_with(&format!("{spec}.")))
+        .filter(|r| !vers(r).contains('-'))
+        .max_by_key(|r| version_key(&vers(r)))
+}
+
+/// One package that depends on the bumped crate
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Impact {
+    repo: String,
+    package: String,
+    kind: DependencyKind,
+    /// The package's `rust-version`, if declared
+    declared: Option<String>,
+}
+
+impl Impact {
+    /// Whether `required` is newer than the declared MSRV
+    fn raises(&self, required: &str) -> bool {
+        match (
+            self.declared.as_deref().and_then(version_key),
+            version_key(required),
+        ) {
+            (Some(declared), Some(required)) => required > declared,
+            _ => false,
+        }
+    }
+}
+
+fn impacts(repos: &[(String, Vec<Package>)], dep: &str) -> Vec<Impact> {
+    let mut found = Vec::new();
+    for (repo, packages) in repos {
+        for pkg in packages {
+            let kinds = pkg.dependencies.iter().filter(|d| d.name == dep);
+            // A normal dependency matters most; dev and build uses only
+            // affect the repo's own builds
+            let Some(kind) = kinds.map(|d| d.kind).min() else {
+                continue;
+            };
+            found.push(Impact {
+                repo: repo.clone(),
+                package: pkg.name.clone(),
+                kind,
+                declared: pkg.rust_version.clone(),
+            });
+        }
+    }
+    found
+}
+
+fn render(dep: &str, version: &str, required: &str, impacts: &[Impact]) -> String {
+    let mut out = format!("{dep} {version} requires Rust {required}\n");
+    for i in impacts {
+        let kind = match i.kind {
+            DependencyKind::Normal => "",
+            DependencyKind::Dev => " [dev]",
+            DependencyKind::Build => " [build]",
+        };
+        let label = format!("{} ({}){kind}", i.repo, i.package);
+        let line = match &i.declared {
+            Some(declared) if i.raises(required) => format!(
+                "{} {label}: rust-version {declared} would have to become {required}",
+                "✗".red()
+            ),
+            Some(declared) => format!("{} {label}: rust-version {declared}", "✓".green()),
+            None => format!("{} {label}: no rust-version declared", "?".yellow()),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    let raised = impacts.iter().filter(|i| i.raises(required)).count();
+    out.push_str(&format!(
+        "\n{raised}/{} dependent packages would need a higher rust-version\n",
+        impacts.len()
+    ));
+    out
+}
+
+/// Handle `meta cargo msrv-impact <dep>@<version>`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let Some((dep, spec)) = args.first().and_then(|a| a.split_once('@')) else {
+        return CommandResult::Error("usage: meta cargo msrv-impact <dep>@<version>".to_string());
+    };
+    let records = match publish::crates_io_index(dep) {
+        Ok(body) => publish::index_records(&body),
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    let Some(record) = select_version(&records, spec) else {
+        return CommandResult::Error(format!("{dep} {spec} is not on crates.io"));
+    };
+    let version = record["vers"].as_str().unwrap_or(spec);
+
+    let mut loaded = Vec::new();
+    let dirs: Vec<_> = repos.iter().map(|r| project_path(cwd, r)).collect();
+    for (repo, result) in repos
+        .iter()
+        .zip(metadata::load_each(&dirs, metadata::load_packages))
+    {
+        match result {
+            Ok(packages) => loaded.push((repo.clone(), packages)),
+            Err(e) => {
+                return CommandResult::Error(format!(
+                    "{repo}: failed to load cargo metadata: {e:#}"
+                ))
+            }
+        }
+    }
+    let impacts = impacts(&loaded, dep);
+    if impacts.is_empty() {
+        return CommandResult::Message(format!("No repo depends on {dep}"));
+    }
+    let Some(required) = record["rust_version"].as_str() else {
+        return CommandResult::Message(format!(
+            "{dep} {version} declares no rust-version; {} dependent packages cannot be checked",
+            impacts.len()
+        ));
+    };
+    let text = render(dep, version, required, &impacts);
+    if impacts.iter().any(|i| i.raises(required)) {
+        CommandResult::Error(text)
+    } else {
+        CommandResult::Message(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Dependency;
+
+    #[test]
+    fn test_select_version() {
+        let records = publish::index_records(concat!(
+            "{\"vers\":\"1.2.0\",\"rust_version\":\"1.56\"}\n",
+            "{\"vers\":\"1.2.7\",\"rust_version\":\"1.63\"}\n",
+            "{\"vers\":\"1.2.8\",\"yanked\":true}\n",
+            "{\"vers\":\"1.3.0-rc.1\"}\n",
+        ));
+        assert_eq!(select_version(&records, "1.2").unwrap()["vers"], "1.2.7");
+        assert_eq!(
+            select_version(&records, "1.2.0").unwrap()["rust_version"],
+            "1.56"
+        );
+        assert!(select_version(&records, "2").is_none());
+    }
+
+    #[test]
+    fn test_impacts_against_declared_msrv() {
+        let repos = vec![
+            (
+                "core".to_string(),
+                vec![Package::fixture("core")
+                    .with_rust_version("1.60")
+                    .with_dependencies(&["serde"])],
+            ),
+            (
+                "app".to_string(),
+                vec![
+                    Package::fixture("app")
+                        .with_rust_version("1.70.0")
+                        .with_dependency(
+                            Dependency::fixture("serde").with_kind(DependencyKind::Dev),
+                        ),
+                    Package::fixture("tools").with_dependency(
+                        Dependency::fixture("serde").with_kind(DependencyKind::Build),
+                    ),
+                    Package::fixture("other").with_rust_version("1.50"),
+                ],
+            ),
+        ];
+        let found = impacts(&repos, "serde");
+        assert_eq!(found.len(), 3);
+        assert!(found[0].raises("1.63"));
+        assert!(!found[1].raises("1.63"));
+        assert!(!found[2].raises("1.63"));
+
+        let text = render("serde", "1.2.7", "1.63", &found);
+        assert!(
+            text.starts_with("serde 1.2.7 requires Rust 1.63\n"),
+            "{text}"
+        );
+        assert!(
+            text.contains("core (core): rust-version 1.60 would have to become 1.63"),
+            "{text}"
+        );
+        assert!(
+            text.contains("app (app) [dev]: rust-version 1.70.0"),
+            "{text}"
+        );
+        assert!(
+            text.contains("app (tools) [build]: no rust-version declared"),
+            "{text}"
+        );
+        assert!(text.contains("1/3 dependent packages"), "{text}");
+    }
+}
@@ -0,0 +1,97 @@
+//! NDJSON run records (`--output ndjson`)
+//!
+//! One JSON object per line and repo. Command output is not inlined: it is
+//! written to `.meta-rust/logs/` and each record refers to its files, so
+//! records stay small enough for line-oriented consumers.
+
+use crate::runner::RunOutcome;
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+/// Log directory, relative to the meta root
+const LOG_DIR: &str = ".meta-rust/logs";
+
+/// File stem for a repo dir, e.g. `libs~2Fcore` for `libs/core`
+///
+/// Bytes other than ASCII letters, digits, `-`, `_` and a non-leading `.`
+/// become `~XX`, so distinct dirs (`libs/core`, `libs-core`) never share a
+/// file.
+pub(crate) fn file_stem(dir: &str) -> String {
+    let mut stem = String::new();
+    for (i, b) in dir.bytes().enumerate() {
+        if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || (b == b'.' && i > 0) {
+            stem.push(b as char);
+        } else {
+            stem.push_str(&format!("~{b:02X}"));
+        }
+    }
+    stem
+}
+
+/// Write one log file, returning its path relative to the meta root
+fn write_log(cwd: &Path, name: &str, text: &str) -> Result<PathBuf> {
+    let relative = Path::new(LOG_DIR).join(name);
+    let path = cwd.join(&relative);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    std::fs::write(&path, text).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(relative)
+}
+
+/// One record per outcome, in plan order
+pub(crate) fn render(cwd: &Path, outcomes: &[RunOutcome]) -> Result<String> {
+    let mut out = String::new();
+    for o in outcomes {
+        let stem = file_stem(&o.dir);
+        let stdout = write_log(cwd, &format!("{stem}.stdout"), &o.stdout)?;
+        let stderr = write_log(cwd, &format!("{stem}.stderr"), &o.stderr)?;
+        let record = json!({
+            "project": o.dir,
+            "command": o.cmd,
+            "success": o.success,
+            "exit_code": o.exit_code,
+            "duration_ms": o.duration.as_millis() as u64,
+            "stdout": stdout.display().to_string(),
+            "stderr": stderr.display().to_string(),
+        });
+        out.push_str(&record.to_string());
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_file_stems_are_distinct() {
+        assert_eq!(file_stem("libs/core"), "libs~2Fcore");
+        assert_eq!(file_stem("libs-core"), "libs-core");
+        assert_eq!(file_stem("."), "~2E");
+        assert_eq!(file_stem("a~2Fb"), "a~7E2Fb");
+    }
+
+    #[test]
+    fn test_records_refer_to_logs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let outcome = RunOutcome::fixture("libs/core", false)
+            .with_stderr("error: boom\n")
+            .with_duration(Duration::from_millis(1200));
+        let out = render(temp_dir.path(), &[outcome]).unwrap();
+        let record: serde_json::Value = serde_json::from_str(out.trim()).unwrap();
+        assert_eq!(record["project"], "libs/core");
+        assert_eq!(record["exit_code"], 101);
+        assert_eq!(record["duration_ms"], 1200);
+        let stderr = record["stderr"].as_str().unwrap();
+        assert_eq!(stderr, ".meta-rust/logs/libs~2Fcore.stderr");
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join(stderr)).unwrap(),
+            "error: boom\n"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Delta notifications (`--notify-delta`)
+//!
+//! Each run's statuses are recorded in `.meta-rust/last-run.json`, keyed by
+//! command, and compared with the previous run of the same command. Only
+//! repos that newly fail or were newly fixed produce a console banner and,
+//! when `[notify] webhook` is set, a JSON POST (`{"text": ...}`, which Slack
+//! and most chat webhooks accept) sent with curl.
+
+use crate::runner::RunOutcome;
+use crate::state;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Last-run file, relative to the meta root
+const LAST_RUN_FILE: &str = ".meta-rust/last-run.json";
+
+/// Success of every repo in the last run of each command
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LastRun {
+    pub commands: BTreeMap<String, BTreeMap<String, bool>>,
+}
+
+impl LastRun {
+    /// Load the recorded statuses, starting empty when there are none
+    pub fn load(cwd: &Path) -> Result<Self> {
+        state::load(cwd, LAST_RUN_FILE)
+    }
+
+    fn save(&self, cwd: &Path) -> Result<()> {
+        state::save(cwd, LAST_RUN_FILE, self)
+    }
+}
+
+/// Repos whose status changed since the previous run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Delta {
+    /// Failing now, but not failing last time (or never run)
+    pub newly_failing: Vec<String>,
+    /// Passing now after failing last time
+    pub newly_fixed: Vec<String>,
+}
+
+impl Delta {
+    pub fn is_empty(&self) -> bool {
+        self.newly_failing.is_empty() && self.newly_fixed.is_empty()
+    }
+
+    fn text(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.newly_failing.is_empty() {
+            parts.push(format!("newly failing: {}", self.newly_failing.join(", ")));
+        }
+        if !self.newly_fixed.is_empty() {
+            parts.push(format!("newly fixed: {}", self.newly_fixed.join(", ")));
+        }
+        parts.join("; ")
+    }
+}
+
+/// Compare `outcomes` with `last` and record them in it
+pub fn update(last: &mut LastRun, outcomes: &[RunOutcome]) -> Delta {
+    let mut delta = Delta::default();
+    for o in outcomes {
+        let runs = last.commands.entry(o.cmd.clone()).or_default();
+        match (runs.get(&o.dir), o.success) {
+            (Some(false), true) => delta.newly_fixed.push(o.dir.clone()),
+            (Some(false), false) | (_, true) => {}
+            (_, false) => delta.newly_failing.push(o.dir.clone()),
+        }
+        runs.insert(o.dir.clone(), o.success);
+    }
+    delta
+}
+
+/// POST `delta` to `webhook`
+fn post(webhook: &str, delta: &Delta) -> Result<()> {
+    let body = json!({
+        "text": format!("meta cargo: {}", delta.text()),
+        "newly_failing": delta.newly_failing,
+        "newly_fixed": delta.newly_fixed,
+    });
+    let mut child = Command::new("curl")
+        .args(["-fsS", "-X", "POST", "-H", "Content-Type: application/json"])
+        .args(["--data-binary", "@-", webhook])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run curl")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(body.to_string().as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Record `outcomes` and render a banner for newly failing or fixed repos
+///
+/// Notification problems are reported in the banner rather than failing the
+/// run.
+pub(crate) fn notify(cwd: &Path, outcomes: &[RunOutcome], webhook: Option<&str>) -> String {
+    let mut last = match LastRun::load(cwd) {
+        Ok(l) => l,
+        Err(e) => return format!("warning: delta notification skipped: {e:#}\n"),
+    };
+    let delta = update(&mut last, outcomes);
+    let mut out = String::new();
+    if let Err(e) = last.save(cwd) {
+        out.push_str(&format!("warning: failed to record run statuses: {e:#}\n"));
+    }
+    if delta.is_empty() {
+        return out;
+    }
+    for repo in &delta.newly_failing {
+        out.push_str(&format!(
+            "{}\n",
+            format!("!!! NEWLY FAILING: {repo}").red().bold()
+        ));
+    }
+    for repo in &delta.newly_fixed {
+        out.push_str(&format!(
+            "{}\n",
+            format!("*** NEWLY FIXED: {repo}").green().bold()
+        ));
+    }
+    if let Some(webhook) = webhook {
+        if let Err(e) = post(webhook, &delta) {
+            out.push_str(&format!("warning: webhook notification failed: {e:#}\n"));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_only_changes_are_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = notify(
+            temp_dir.path(),
+            &[
+                RunOutcome::fixture("core", false),
+                RunOutcome::fixture("app", true),
+            ],
+            None,
+        );
+        assert!(first.contains("NEWLY FAILING: core"));
+
+        let again = notify(
+            temp_dir.path(),
+            &[
+                RunOutcome::fixture("core", false),
+                RunOutcome::fixture("app", true),
+            ],
+            None,
+        );
+        assert!(again.is_empty());
+
+        let mut last = LastRun::load(temp_dir.path()).unwrap();
+        let delta = update(
+            &mut last,
+            &[
+                RunOutcome::fixture("core", true),
+                RunOutcome::fixture("app", false),
+            ],
+        );
+        assert_eq!(delta.newly_fixed, vec!["core"]);
+        assert_eq!(delta.newly_failing, vec!["app"]);
+    }
+}
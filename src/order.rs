@@ -0,0 +1,199 @@
+//! Repo execution order (`--order alpha|config|deps|slowest-first`)
+//!
+//! `config` follows `[order] repos` in `.meta-rust.toml`, then the order of
+//! the meta project file. `slowest-first` uses the durations recorded by
+//! previous in-process runs, so long builds start while short ones fill in.
+
+use crate::config::Config;
+use crate::graph::CrateGraph;
+use crate::runner::RunOutcome;
+use crate::state;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Timings file, relative to the meta root
+const TIMINGS_FILE: &str = ".meta-rust/timings.json";
+
+/// How repos are ordered in the plan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Order {
+    Alpha,
+    Config,
+    Deps,
+    SlowestFirst,
+}
+
+impl Order {
+    pub(crate) fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "alpha" => Ok(Order::Alpha),
+            "config" => Ok(Order::Config),
+            "deps" => Ok(Order::Deps),
+            "slowest-first" => Ok(Order::SlowestFirst),
+            other => Err(format!(
+                "unknown order '{other}' (expected alpha, config, deps or slowest-first)"
+            )),
+        }
+    }
+}
+
+/// Last recorded duration of each repo, in seconds
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Timings {
+    pub repos: BTreeMap<String, f64>,
+}
+
+impl Timings {
+    /// Load recorded timings, starting empty when there are none
+    pub fn load(cwd: &Path) -> Result<Self> {
+        state::load(cwd, TIMINGS_FILE)
+    }
+
+    /// Record the durations of `outcomes`
+    pub fn record(cwd: &Path, outcomes: &[RunOutcome]) -> Result<()> {
+        let mut timings = Self::load(cwd)?;
+        for o in outcomes {
+            timings
+                .repos
+                .insert(o.dir.clone(), o.duration.as_secs_f64());
+        }
+        state::save(cwd, TIMINGS_FILE, &timings)
+    }
+}
+
+/// `repos` in the order given by `[order] repos`, then `declared`
+///
+/// Repos named in neither keep their relative order at the end.
+fn config_order(repos: &[String], config: &Config, declared: &[String]) -> Vec<String> {
+    let mut ordered: Vec<String> = Vec::new();
+    for repo in config.order.repos.iter().chain(declared) {
+        if repos.contains(repo) && !ordered.contains(repo) {
+            ordered.push(repo.clone());
+        }
+    }
+    for repo in repos {
+        if !ordered.contains(repo) {
+            ordered.push(repo.clone());
+        }
+    }
+    ordered
+}
+
+/// Slowest recorded repos first; repos without timings keep their order at the end
+fn slowest_first(repos: &[String], timings: &Timings) -> Vec<String> {
+    let mut ordered = repos.to_vec();
+    ordered.sort_by(|a, b| {
+        let ta = timings.repos.get(a).copied().unwrap_or(-1.0);
+        let tb = timings.repos.get(b).copied().unwrap_or(-1.0);
+        tb.total_cmp(&ta)
+    });
+    ordered
+}
+
+/// Repos whose metadata failed to load, with the error
+pub(crate) type Failed = Vec<(String, String)>;
+
+/// Reorder `repos` according to `order`
+///
+/// `declared` is the repo order of the meta project file. With `deps`, repos
+/// whose metadata fails to load keep their place among the others and are
+/// returned with their errors instead of failing the whole order.
+pub(crate) fn sort(
+    order: Order,
+    repos: &[String],
+    cwd: &Path,
+    config: &Config,
+    declared: &[String],
+) -> Result<(Vec<String>, Failed), String> {
+    match order {
+        Order::Alpha => {
+            let mut ordered = repos.to_vec();
+            ordered.sort();
+            Ok((ordered, Vec::new()))
+        }
+        Order::Config => Ok((config_order(repos, config, declared), Vec::new())),
+        Order::Deps => {
+            let (graph, failed) = CrateGraph::load_partial(repos, cwd);
+            Ok((graph.repo_order(repos), failed))
+        }
+        Order::SlowestFirst => Timings::load(cwd)
+            .map(|timings| (slowest_first(repos, &timings), Vec::new()))
+            .map_err(|e| format!("{e:#}")),
+    }
+}
+
+/// Repos grouped into dependency levels, for parallel `--order deps` runs,
+/// and the repos whose metadata failed to load
+pub(crate) fn dependency_levels(repos: &[String], cwd: &Path) -> (Vec<Vec<String>>, Failed) {
+    let (graph, failed) = CrateGraph::load_partial(repos, cwd);
+    (graph.repo_levels(repos), failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::strings;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_config_order() {
+        let config = Config::parse("[order]\nrepos = [\"tool\"]\n").unwrap();
+        let repos = strings(&[".", "app", "core", "tool"]);
+        let declared = strings(&["core", "app"]);
+        assert_eq!(
+            config_order(&repos, &config, &declared),
+            strings(&["tool", "core", "app", "."])
+        );
+    }
+
+    #[test]
+    fn test_slowest_first_from_recorded_timings() {
+        let temp_dir = TempDir::new().unwrap();
+        let outcome = |dir: &str, secs| {
+            RunOutcome::fixture(dir, true).with_duration(Duration::from_secs(secs))
+        };
+        Timings::record(temp_dir.path(), &[outcome("fast", 1), outcome("slow", 30)]).unwrap();
+        let repos = strings(&["new", "fast", "slow"]);
+        let ordered = sort(
+            Order::SlowestFirst,
+            &repos,
+            temp_dir.path(),
+            &Config::default(),
+            &[],
+        )
+        .unwrap()
+        .0;
+        assert_eq!(ordered, strings(&["slow", "fast", "new"]));
+        assert!(Order::parse("random").is_err());
+    }
+
+    #[test]
+    fn test_deps_order_keeps_repos_with_broken_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let write = |path: &str, text: &str| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        };
+        write(
+            "core/Cargo.toml",
+            "[package]\nname = \"core\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        );
+        write("core/src/lib.rs", "");
+        write("broken/Cargo.toml", "[package\nname = \"broken\"\n");
+        let repos = strings(&["broken", "core"]);
+        let (ordered, failed) = sort(Order::Deps, &repos, root, &Config::default(), &[]).unwrap();
+        assert_eq!(ordered.len(), 2);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, "broken");
+        assert!(
+            failed[0].1.starts_with("cargo metadata failed"),
+            "{}",
+            failed[0].1
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! `meta cargo outdated`: stale dependencies across repos, grouped by crate
+//!
+//! `cargo outdated --format json --root-deps-only` runs in every repo and the
+//! results are grouped by dependency, so one report shows e.g. that tokio is
+//! behind in 7 of 12 repos and at which versions. With `--workspace-upgrade`,
+//! every repo then runs `cargo update -p <crate>` for its dependencies that
+//! have a semver-compatible update; new major versions still need a manifest
+//! change.
+
+use crate::runner::{self, RunOutcome};
+use crate::{args, CommandResult, PlannedCommand};
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// cargo-outdated's placeholder for "no such version"
+const NONE: &str = "---";
+
+/// A dependency that is behind in one repo
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stale {
+    repo: String,
+    /// Locked version
+    project: String,
+    /// Newest semver-compatible version
+    compat: String,
+    latest: String,
+}
+
+impl Stale {
+    fn has_compatible_update(&self) -> bool {
+        self.compat != NONE && self.compat != self.project
+    }
+}
+
+/// Stale dependencies by crate name
+type Report = BTreeMap<String, Vec<Stale>>;
+
+/// Add one repo's `cargo outdated --format json` output (a JSON object per
+/// line, one per workspace member) to `report`
+fn parse(report: &mut Report, repo: &str, stdout: &str) -> Result<(), String> {
+    let mut parsed = false;
+    for line in stdout.lines().filter(|l| l.trim_start().starts_with('{')) {
+        let value: serde_json::Value = serde_json::from_str(line)
+            .map_err(|e| format!("invalid cargo outdated output: {e}"))?;
+        parsed = true;
+        for dep in value["dependencies"].as_array().into_iter().flatten() {
+            let field = |key: &str| dep[key].as_str().unwrap_or(NONE).to_string();
+            let stale = Stale {
+                repo: repo.to_string(),
+                project: field("project"),
+                compat: field("compat"),
+                latest: field("latest"),
+            };
+            let entries = report.entry(field("name")).or_default();
+            if !entries.contains(&stale) {
+                entries.push(stale);
+            }
+        }
+    }
+    if parsed {
+        Ok(())
+    } else {
+        Err("cargo outdated produced no report".to_string())
+    }
+}
+
+fn aggregate(outcomes: &[RunOutcome]) -> (Report, Vec<(String, String)>) {
+    let mut report = Report::new();
+    let mut failures = Vec::new();
+    for outcome in outcomes {
+        if let Err(e) = parse(&mut report, &outcome.dir, &outcome.stdout) {
+            let detail = outcome
+                .stderr
+                .trim()
+                .lines()
+                .last()
+                .unwrap_or(&e)
+                .to_string();
+            failures.push((outcome.dir.clone(), detail));
+        }
+    }
+    (report, failures)
+}
+
+fn render(report: &Report, failures: &[(String, String)], repos: usize) -> String {
+    // Most widespread first
+    let mut names: Vec<&String> = report.keys().collect();
+    names.sort_by_key(|name| {
+        let repos: BTreeSet<&str> = report[*name].iter().map(|s| s.repo.as_str()).collect();
+        std::cmp::Reverse(repos.len())
+    });
+    let mut out = String::new();
+    for name in names {
+        let entries = &report[name];
+        let affected: BTreeSet<&str> = entries.iter().map(|s| s.repo.as_str()).collect();
+        let latest = entries
+            .iter()
+            .map(|s| s.latest.as_str())
+            .find(|l| *l != NONE)
+            .unwrap_or(NONE);
+        out.push_str(&format!(
+            "{} is outdated in {} of {repos} repos (latest {latest})\n",
+            name.bold(),
+            affected.len()
+        ));
+        for s in entries {
+            let compat = if s.has_compatible_update() {
+                format!(", compatible {}", s.compat)
+            } else {
+                String::new()
+            };
+            out.push_str(&format!("  {}: {}{compat}\n", s.repo, s.project));
+        }
+    }
+    for (repo, reason) in failures {
+        out.push_str(&format!("{} {repo}: {reason}\n", "✗".red()));
+    }
+    if report.is_empty() && failures.is_empty() {
+        out.push_str("All dependencies are up to date\n");
+    }
+    out
+}
+
+/// `cargo update -p ...` for each repo with semver-compatible updates
+fn upgrade_commands(cargo: &str, report: &Report) -> Vec<PlannedCommand> {
+    let mut by_repo: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for (name, entries) in report {
+        for s in entries.iter().filter(|s| s.has_compatible_update()) {
+            by_repo.entry(&s.repo).or_default().insert(name);
+        }
+    }
+    by_repo
+        .into_iter()
+        .map(|(repo, names)| {
+            let mut cmd = format!("{cargo} update");
+            for name in names {
+                cmd.push_str(&format!(" -p {name}"));
+            }
+            PlannedCommand {
+                dir: repo.to_string(),
+                cmd,
+                env: None,
+            }
+        })
+        .collect()
+}
+
+/// Handle `meta cargo outdated [--workspace-upgrade] [cargo outdated args]`
+pub(crate) fn execute(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let upgrade = args::take_flag(&mut args, "--workspace-upgrade");
+    let mut cmd = format!("{cargo} outdated --format json --root-deps-only");
+    for arg in &args {
+        cmd.push(' ');
+        cmd.push_str(arg);
+    }
+    let commands: Vec<PlannedCommand> = repos
+        .iter()
+        .map(|repo| PlannedCommand {
+            dir: repo.clone(),
+            cmd: cmd.clone(),
+            env: None,
+        })
+        .collect();
+    let (report, failures) = aggregate(&runner::run_all(cwd, &commands, parallel));
+    let mut text = render(&report, &failures, repos.len());
+    let mut failed = !failures.is_empty();
+    if upgrade {
+        let updates = upgrade_commands(cargo, &report);
+        if updates.is_empty() {
+            text.push_str("\nNo semver-compatible updates to apply\n");
+        } else {
+            text.push('\n');
+        }
+        for outcome in runner::run_all(cwd, &updates, parallel) {
+            let mark = if outcome.success {
+                "✓".green()
+            } else {
+                failed = true;
+                "✗".red()
+            };
+            text.push_str(&format!("{mark} {}: {}\n", outcome.dir, outcome.cmd));
+        }
+    }
+    if failed {
+        CommandResult::Error(text)
+    } else {
+        CommandResult::Message(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORE: &str = r#"{"crate_name":"core","dependencies":[{"name":"tokio","project":"1.30.0","compat":"1.38.0","latest":"1.38.0","kind":"Normal","platform":null},{"name":"rand","project":"0.7.3","compat":"---","latest":"0.8.5","kind":"Normal","platform":null}]}"#;
+    const APP: &str = r#"{"crate_name":"app","dependencies":[{"name":"tokio","project":"1.36.0","compat":"1.38.0","latest":"1.38.0","kind":"Normal","platform":null}]}"#;
+
+    #[test]
+    fn test_grouped_by_dependency() {
+        let mut report = Report::new();
+        parse(&mut report, "core", CORE).unwrap();
+        parse(&mut report, "app", &format!("{APP}\n{APP}\n")).unwrap();
+        assert!(parse(&mut report, "broken", "error: no lockfile").is_err());
+        assert_eq!(report["tokio"].len(), 2);
+        assert_eq!(report["rand"].len(), 1);
+
+        let text = render(&report, &[], 3);
+        assert!(
+            text.contains("is outdated in 2 of 3 repos (latest 1.38.0)"),
+            "{text}"
+        );
+        assert!(
+            text.contains("  core: 1.30.0, compatible 1.38.0\n"),
+            "{text}"
+        );
+        assert!(text.contains("  core: 0.7.3\n"), "{text}");
+        // tokio is the most widespread
+        assert!(text.find("tokio").unwrap() < text.find("rand").unwrap());
+    }
+
+    #[test]
+    fn test_upgrade_commands_only_compatible_updates() {
+        let mut report = Report::new();
+        parse(&mut report, "core", CORE).unwrap();
+        parse(&mut report, "app", APP).unwrap();
+        let commands = upgrade_commands("cargo", &report);
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].dir, "app");
+        assert_eq!(commands[1].cmd, "cargo update -p tokio");
+    }
+}
@@ -0,0 +1,497 @@
+//! Result-consuming output modes
+//!
+//! When one of these is requested, the plan runs in-process so its results
+//! can be rendered; otherwise the plan is handed back to meta as usual. Runs
+//! that go in-process for other reasons (concurrency classes, `--order deps`)
+//! print every repo's output before the summary, as meta would have.
+
+use crate::args;
+use crate::build_cache;
+use crate::ci;
+use crate::html;
+use crate::junit;
+use crate::ndjson;
+use crate::notify;
+use crate::order::Timings;
+use crate::quickfix;
+use crate::report;
+use crate::runner::RunOutcome;
+use crate::runs::RunResult;
+use crate::tap;
+use crate::teamcity;
+use crate::CommandResult;
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+
+/// Machine-readable console formats selected with `--output`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    TeamCity,
+    Tap,
+    Quickfix,
+    Ndjson,
+}
+
+impl OutputFormat {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "teamcity" => Ok(OutputFormat::TeamCity),
+            "tap" => Ok(OutputFormat::Tap),
+            "quickfix" => Ok(OutputFormat::Quickfix),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            other => Err(format!(
+                "unsupported output format '{other}' (expected teamcity, tap, quickfix or ndjson)"
+            )),
+        }
+    }
+
+    /// Whether failures are reported inside the output itself
+    ///
+    /// NDJSON records carry each repo's status too, but they are what
+    /// `meta --json` prints, so a failing run still has to fail.
+    fn reports_in_band(self) -> bool {
+        matches!(self, OutputFormat::TeamCity | OutputFormat::Tap)
+    }
+}
+
+/// Output flags taken from the command line
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OutputOptions {
+    /// `--output <format>`: print results in a machine-readable format
+    pub format: Option<OutputFormat>,
+    /// `--tap-per-test`: one TAP test point per libtest case instead of per repo
+    pub tap_per_test: bool,
+    /// `--quickfix-file <path>`: destination for `--output quickfix`
+    pub quickfix_file: Option<PathBuf>,
+    /// `--ordered-output`: print each repo's full output, in plan order
+    pub ordered_output: bool,
+    /// `--ci-log-groups[=provider]`: wrap each repo's log in a collapsible section
+    pub ci_log_groups: Option<ci::Provider>,
+    /// `--report junit=<file>`: write a merged JUnit XML report
+    pub junit: Option<PathBuf>,
+    /// `--save-run <file>`: store the results for `meta cargo compare-runs`
+    pub save_run: Option<PathBuf>,
+    /// `--report-html <dir>`: write a static HTML report
+    pub report_html: Option<PathBuf>,
+    /// `--test-summary`: append a table of per-repo test counts
+    pub test_summary: bool,
+    /// `--notify-delta`: announce repos that newly fail or were newly fixed
+    pub notify_delta: bool,
+    /// `[notify] webhook` from the config
+    pub webhook: Option<String>,
+    /// Repos marked `allow_failure` in the config
+    pub allow_failure: Vec<String>,
+}
+
+impl OutputOptions {
+    /// Remove output flags from `args`
+    pub(crate) fn take(args: &mut Vec<String>) -> Result<Self, String> {
+        let format = args::take_value(args, "--output")
+            .map(|f| OutputFormat::parse(&f))
+            .transpose()?;
+        let ci_log_groups = match args::take_optional_value(args, "--ci-log-groups") {
+            Some(Some(name)) => Some(ci::Provider::parse(&name)?),
+            Some(None) => Some(ci::Provider::detect()?),
+            None => None,
+        };
+        let junit = match args::take_value(args, "--report") {
+            Some(report) => match report.split_once('=') {
+                Some(("junit", path)) if !path.is_empty() => Some(PathBuf::from(path)),
+                _ => {
+                    return Err(format!(
+                        "unsupported report '{report}' (expected junit=<file>)"
+                    ))
+                }
+            },
+            None => None,
+        };
+        Ok(OutputOptions {
+            format,
+            junit,
+            ordered_output: args::take_flag(args, "--ordered-output"),
+            ci_log_groups,
+            tap_per_test: args::take_flag(args, "--tap-per-test"),
+            quickfix_file: args::take_value(args, "--quickfix-file").map(PathBuf::from),
+            report_html: args::take_value(args, "--report-html").map(PathBuf::from),
+            test_summary: args::take_flag(args, "--test-summary"),
+            save_run: args::take_value(args, "--save-run").map(PathBuf::from),
+            notify_delta: args::take_flag(args, "--notify-delta"),
+            webhook: None,
+            allow_failure: Vec::new(),
+        })
+    }
+
+    /// Whether the plan has to run in-process
+    pub(crate) fn is_active(&self) -> bool {
+        self.format.is_some()
+            || self.ordered_output
+            || self.ci_log_groups.is_some()
+            || self.report_html.is_some()
+            || self.junit.is_some()
+            || self.save_run.is_some()
+            || self.test_summary
+            || self.notify_delta
+    }
+
+    /// Render `outcomes` in every requested format
+    ///
+    /// The plain summary fails when any command failed, so the exit code still
+    /// reflects the run. TeamCity and TAP report failures in-band and always go
+    /// to stdout, where CI tools read them.
+    pub(crate) fn deliver(&self, cwd: &Path, outcomes: &[RunOutcome]) -> CommandResult {
+        // Timings only feed `--order slowest-first`, so failing to save them
+        // must not fail the run
+        let _ = Timings::record(cwd, outcomes);
+        let mut text = match self.format {
+            Some(OutputFormat::TeamCity) => teamcity::render(outcomes),
+            Some(OutputFormat::Tap) => tap::render(outcomes, self.tap_per_test),
+            Some(OutputFormat::Quickfix) => {
+                let file = self
+                    .quickfix_file
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(quickfix::DEFAULT_FILE));
+                let path = cwd.join(file);
+                match quickfix::write(&path, cwd, outcomes) {
+                    Ok(n) => format!(
+                        "{}Quickfix: {n} entries written to {}\n",
+                        render_summary(outcomes, &self.allow_failure),
+                        path.display()
+                    ),
+                    Err(e) => return CommandResult::Error(format!("{e:#}")),
+                }
+            }
+            Some(OutputFormat::Ndjson) => match ndjson::render(cwd, outcomes) {
+                Ok(records) => records,
+                Err(e) => return CommandResult::Error(format!("{e:#}")),
+            },
+            None => render_summary(outcomes, &self.allow_failure),
+        };
+        if let Some(provider) = self.ci_log_groups {
+            text = ci::render(provider, cwd, outcomes) + &text;
+        } else if self.ordered_output || self.format.is_none() {
+            text = render_logs(outcomes) + &text;
+        }
+        if self.test_summary {
+            text.push_str(&report::render_test_table(outcomes));
+        }
+        if self.notify_delta {
+            text.push_str(&notify::notify(cwd, outcomes, self.webhook.as_deref()));
+        }
+        if let Some(file) = &self.save_run {
+            let path = cwd.join(file);
+            match RunResult::from_outcomes(outcomes).save(&path) {
+                Ok(()) => text.push_str(&format!("Run results: {}\n", path.display())),
+                Err(e) => return CommandResult::Error(format!("Failed to save run: {e:#}")),
+            }
+        }
+        if let Some(file) = &self.junit {
+            let path = cwd.join(file);
+            match junit::write(&path, outcomes) {
+                Ok(n) => text.push_str(&format!(
+                    "JUnit report: {n} test cases written to {}\n",
+                    path.display()
+                )),
+                Err(e) => {
+                    return CommandResult::Error(format!("Failed to write JUnit report: {e:#}"))
+                }
+            }
+        }
+        if let Some(dir) = &self.report_html {
+            match html::write_report(&cwd.join(dir), outcomes) {
+                Ok(index) => text.push_str(&format!("HTML report: {}\n", index.display())),
+                Err(e) => {
+                    return CommandResult::Error(format!("Failed to write HTML report: {e:#}"))
+                }
+            }
+        }
+        let in_band = self.format.is_some_and(OutputFormat::reports_in_band);
+        let failed = outcomes
+            .iter()
+            .any(|o| !o.success && !self.allow_failure.contains(&o.dir));
+        if !in_band && failed {
+            CommandResult::Error(text)
+        } else {
+            CommandResult::Message(text)
+        }
+    }
+}
+
+/// Every repo's buffered output under a header, in plan order
+///
+/// The runner collects outcomes in plan order regardless of which command
+/// finished first, so the result is the same for parallel and serial runs.
+fn render_logs(outcomes: &[RunOutcome]) -> String {
+    let mut out = String::new();
+    for o in outcomes {
+        out.push_str(&format!("==> {}: {}\n", o.dir, o.cmd));
+        let log = o.output();
+        out.push_str(&log);
+        if !log.is_empty() && !log.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Whether cargo refused to run because `--locked` found a stale Cargo.lock
+pub(crate) fn is_stale_lockfile(outcome: &RunOutcome) -> bool {
+    !outcome.success
+        && outcome
+            .output()
+            .contains("--locked was passed to prevent this")
+}
+
+/// Crates cargo reported it would change in the lockfile before refusing
+///
+/// Cargo prints e.g. `Adding serde v1.0.200` or `Updating log v0.4.20 ->
+/// v0.4.21` for each lockfile change it would have made.
+fn stale_crates(output: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for line in output.lines() {
+        let mut words = line.split_whitespace();
+        let verb = words.next().unwrap_or("");
+        if !["Adding", "Updating", "Downgrading", "Removing"].contains(&verb) {
+            continue;
+        }
+        let (Some(name), Some(version)) = (words.next(), words.next()) else {
+            continue;
+        };
+        let is_version = version
+            .strip_prefix('v')
+            .is_some_and(|v| v.starts_with(|c: char| c.is_ascii_digit()));
+        if is_version && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// `meta cargo update` command that refreshes a stale lockfile
+fn lockfile_fix(outcome: &RunOutcome) -> String {
+    let names = stale_crates(&outcome.output());
+    let selection = if names.is_empty() {
+        " --workspace".to_string()
+    } else {
+        names.iter().map(|n| format!(" -p {n}")).collect()
+    };
+    format!("meta cargo update{selection} --include {}", outcome.dir)
+}
+
+/// One status line per repo plus totals
+///
+/// Builds also show whether they were cached, incremental or cold. Repos
+/// whose lockfile is out of date get their own status and a fix; failures of
+/// `allowed` repos are warnings.
+fn render_summary(outcomes: &[RunOutcome], allowed: &[String]) -> String {
+    let mut out = String::new();
+    for o in outcomes {
+        let secs = o.duration.as_secs_f64();
+        let cache = build_cache::count_units(&format!("{}\n{}", o.stdout, o.stderr))
+            .state()
+            .map(|s| format!(", {}", s.as_str()))
+            .unwrap_or_default();
+        if is_stale_lockfile(o) {
+            out.push_str(&format!(
+                "{} {} (lockfile out of date, {secs:.1}s)\n    fix: {}\n",
+                "✗".red(),
+                o.dir,
+                lockfile_fix(o)
+            ));
+        } else if !o.success && allowed.contains(&o.dir) {
+            out.push_str(&format!(
+                "{} {} (allowed failure, {secs:.1}s{cache})\n",
+                "⚠".yellow(),
+                o.dir
+            ));
+        } else if o.success {
+            out.push_str(&format!("{} {} ({secs:.1}s{cache})\n", "✓".green(), o.dir));
+        } else {
+            let code = o
+                .exit_code
+                .map(|c| format!("exit {c}, "))
+                .unwrap_or_default();
+            out.push_str(&format!(
+                "{} {} ({code}{secs:.1}s{cache})\n",
+                "✗".red(),
+                o.dir
+            ));
+        }
+    }
+    let failed = outcomes.iter().filter(|o| !o.success).count();
+    let soft = outcomes
+        .iter()
+        .filter(|o| !o.success && allowed.contains(&o.dir))
+        .count();
+    let stale = outcomes.iter().filter(|o| is_stale_lockfile(o)).count();
+    out.push_str(&format!(
+        "{} passed, {} failed",
+        outcomes.len() - failed,
+        failed - soft
+    ));
+    if soft > 0 {
+        out.push_str(&format!(", {soft} allowed to fail"));
+    }
+    if stale > 0 {
+        out.push_str(&format!(" ({stale} with lockfile out of date)"));
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordered_logs() {
+        let logs = render_logs(&[
+            RunOutcome::fixture("b", true).with_stdout("second"),
+            RunOutcome::fixture("a", true),
+        ]);
+        assert_eq!(logs, "==> b: cargo build\nsecond\n==> a: cargo build\n");
+    }
+
+    #[test]
+    fn test_summary_shows_cache_state() {
+        let outcome = RunOutcome::fixture("app", true)
+            .with_stderr("   Compiling app v0.1.0\n    Finished `dev` profile\n");
+        // Without unit JSON a rebuild may have reused every dependency
+        let summary = render_summary(std::slice::from_ref(&outcome), &[]);
+        assert!(summary.contains("app (0.0s)"), "{summary}");
+
+        let json = outcome.with_stdout("{\"reason\":\"compiler-artifact\",\"fresh\":true}\n{\"reason\":\"compiler-artifact\",\"fresh\":false}\n");
+        let summary = render_summary(std::slice::from_ref(&json), &[]);
+        assert!(summary.contains("app (0.0s, incremental)"), "{summary}");
+        assert!(!json.output().contains("reason"));
+    }
+
+    #[test]
+    fn test_summary_reports_stale_lockfile() {
+        let outcome = RunOutcome::fixture("app", false)
+            .with_cmd("cargo build --locked")
+            .with_stderr("    Updating crates.io index\n     Locking 2 packages to latest compatible versions\n      Adding serde v1.0.200\n    Updating log v0.4.20 -> v0.4.21\nerror: the lock file /ws/app/Cargo.lock needs to be updated but --locked was passed to prevent this\n");
+        let summary = render_summary(std::slice::from_ref(&outcome), &[]);
+        assert!(summary.contains("app (lockfile out of date, 0.0s)"));
+        assert!(
+            summary.contains("fix: meta cargo update -p serde -p log --include app"),
+            "{summary}"
+        );
+        assert!(summary.contains("0 passed, 1 failed (1 with lockfile out of date)"));
+
+        let bare = outcome.with_stderr("error: the lock file /ws/app/Cargo.lock needs to be updated but --locked was passed to prevent this\n");
+        assert_eq!(
+            lockfile_fix(&bare),
+            "meta cargo update --workspace --include app"
+        );
+
+        // `--locked` alone is handed to meta with the plan as usual
+        let mut args = vec!["--locked".to_string()];
+        assert!(!OutputOptions::take(&mut args).unwrap().is_active());
+        assert_eq!(args, vec!["--locked"]);
+    }
+
+    #[test]
+    fn test_allowed_failures_do_not_fail_the_run() {
+        let outcome = RunOutcome::fixture("labs", false);
+        let options = OutputOptions {
+            allow_failure: vec!["labs".to_string()],
+            ..OutputOptions::default()
+        };
+        match options.deliver(Path::new("/nonexistent"), &[outcome]) {
+            CommandResult::Message(msg) => {
+                assert!(msg.contains("labs (allowed failure"));
+                assert!(msg.contains("0 passed, 0 failed, 1 allowed to fail"));
+            }
+            _ => panic!("Expected Message result"),
+        }
+    }
+
+    #[test]
+    fn test_in_process_run_keeps_compiler_output() {
+        let outcomes = [
+            RunOutcome::fixture("core", true),
+            RunOutcome::fixture("app", false)
+                .with_stderr("error[E0308]: mismatched types\n --> src/lib.rs:1:14\n"),
+        ];
+        match OutputOptions::default().deliver(Path::new("/nonexistent"), &outcomes) {
+            CommandResult::Error(text) => {
+                assert!(
+                    text.contains("==> app: cargo build\nerror[E0308]: mismatched types\n"),
+                    "{text}"
+                );
+                assert!(text.contains("1 passed, 1 failed"), "{text}");
+            }
+            _ => panic!("Expected Error result"),
+        }
+    }
+
+    #[test]
+    fn test_ndjson_run_fails_when_a_repo_fails() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        // What `meta --json` selects
+        let mut args = vec!["--output=ndjson".to_string()];
+        let mut options = OutputOptions::take(&mut args).unwrap();
+        let outcomes = [
+            RunOutcome::fixture("core", true),
+            RunOutcome::fixture("app", false),
+        ];
+        match options.deliver(temp_dir.path(), &outcomes) {
+            CommandResult::Error(records) => assert_eq!(records.lines().count(), 2),
+            _ => panic!("Expected Error result"),
+        }
+        options.allow_failure = vec!["app".to_string()];
+        match options.deliver(temp_dir.path(), &outcomes) {
+            CommandResult::Message(_) => {}
+            _ => panic!("Expected Message result"),
+        }
+    }
+
+    #[test]
+    fn test_take_report_html() {
+        let mut args = vec![
+            "--release".to_string(),
+            "--report-html".to_string(),
+            "out".to_string(),
+        ];
+        let options = OutputOptions::take(&mut args).unwrap();
+        assert!(options.is_active());
+        assert_eq!(options.report_html, Some(PathBuf::from("out")));
+        assert_eq!(args, vec!["--release"]);
+        assert!(!OutputOptions::take(&mut args).unwrap().is_active());
+    }
+
+    #[test]
+    fn test_take_output_format() {
+        let mut args = vec!["--output=teamcity".to_string()];
+        let options = OutputOptions::take(&mut args).unwrap();
+        assert_eq!(options.format, Some(OutputFormat::TeamCity));
+
+        let mut args = vec![
+            "--output".to_string(),
+            "tap".to_string(),
+            "--tap-per-test".to_string(),
+        ];
+        let options = OutputOptions::take(&mut args).unwrap();
+        assert_eq!(options.format, Some(OutputFormat::Tap));
+        assert!(options.tap_per_test);
+        assert!(args.is_empty());
+
+        let mut args = vec!["--ci-log-groups=buildkite".to_string()];
+        let options = OutputOptions::take(&mut args).unwrap();
+        assert_eq!(options.ci_log_groups, Some(ci::Provider::Buildkite));
+        assert!(options.is_active());
+
+        let mut args = vec!["--ordered-output".to_string()];
+        assert!(OutputOptions::take(&mut args).unwrap().ordered_output);
+
+        let mut args = vec!["--output".to_string(), "xml".to_string()];
+        assert!(OutputOptions::take(&mut args).is_err());
+
+        let mut args = vec!["--report=junit=out/junit.xml".to_string()];
+        let options = OutputOptions::take(&mut args).unwrap();
+        assert_eq!(options.junit, Some(PathBuf::from("out/junit.xml")));
+        assert!(options.is_active());
+        let mut args = vec!["--report".to_string(), "sonar=x".to_string()];
+        assert!(OutputOptions::take(&mut args).is_err());
+    }
+}
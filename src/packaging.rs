@@ -0,0 +1,390 @@
+//! `meta cargo dist packaging`: package manager manifests after a release
+//!
+//! For every app in the repos' dist manifests, a Homebrew formula
+//! (`Formula/<app>.rb`) and a Scoop manifest (`bucket/<app>.json`) are
+//! rendered with the release's download URLs and sha256 checksums and written
+//! to the `[dist.packaging] repo`, which is then committed unless
+//! `--no-commit` is given.
+
+use crate::config::{Config, PackagingConfig};
+use crate::dist::{self, DistArtifact, DistManifest};
+use crate::{args, cargo_config, git, project_path, CommandResult};
+use anyhow::{bail, Context};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+/// One downloadable archive of an app
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+    pub triple: String,
+    pub url: String,
+    pub sha256: String,
+}
+
+/// A released app with everything the manifests need
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackagedApp {
+    pub name: String,
+    pub version: String,
+    pub downloads: Vec<Download>,
+}
+
+/// `MyApp` for `my-app`, as Homebrew names formula classes
+fn class_name(app: &str) -> String {
+    app.split(['-', '_'])
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// The hex digest of a `.sha256` checksum file (`<hex>  <file>`)
+fn read_sha256(path: &Path) -> anyhow::Result<String> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    match text.split_whitespace().next() {
+        Some(hex) if hex.len() == 64 => Ok(hex.to_lowercase()),
+        _ => bail!("{} is not a sha256 checksum", path.display()),
+    }
+}
+
+/// The apps of `repo`'s dist manifest with URLs from `url_template`
+pub fn packaged_apps(
+    repo: &str,
+    manifest: &DistManifest,
+    url_template: &str,
+) -> anyhow::Result<Vec<PackagedApp>> {
+    let repo_name = Path::new(repo)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| repo.to_string());
+    let checksum_of = |artifact: &DistArtifact| -> anyhow::Result<String> {
+        let name = artifact
+            .checksum
+            .as_deref()
+            .with_context(|| format!("{} has no checksum artifact", artifact.name))?;
+        let checksum = manifest
+            .artifacts
+            .iter()
+            .find(|a| a.name == name)
+            .with_context(|| format!("checksum {name} is not in the dist manifest"))?;
+        read_sha256(&checksum.path)
+    };
+    let mut apps = Vec::new();
+    for (app, version) in &manifest.releases {
+        let mut downloads = Vec::new();
+        for artifact in manifest
+            .artifacts
+            .iter()
+            .filter(|a| a.kind == "executable-zip" && a.name.starts_with(&format!("{app}-")))
+        {
+            let Some(triple) = artifact.target_triples.first() else {
+                continue;
+            };
+            let url = url_template
+                .replace("{repo}", &repo_name)
+                .replace("{app}", app)
+                .replace("{tag}", &manifest.tag)
+                .replace("{version}", version)
+                .replace("{artifact}", &artifact.name);
+            downloads.push(Download {
+                triple: triple.clone(),
+                url,
+                sha256: checksum_of(artifact)?,
+            });
+        }
+        apps.push(PackagedApp {
+            name: app.clone(),
+            version: version.clone(),
+            downloads,
+        });
+    }
+    Ok(apps)
+}
+
+impl PackagedApp {
+    /// The download for the first matching target, preferring earlier ones
+    fn download(&self, triples: &[&str]) -> Option<&Download> {
+        triples
+            .iter()
+            .find_map(|t| self.downloads.iter().find(|d| d.triple == *t))
+    }
+}
+
+/// Homebrew formula for `app`, or `None` without macOS or Linux archives
+pub fn render_formula(app: &PackagedApp) -> Option<String> {
+    let platforms = [
+        (
+            "on_macos",
+            [
+                ("on_arm", vec!["aarch64-apple-darwin"]),
+                ("on_intel", vec!["x86_64-apple-darwin"]),
+            ],
+        ),
+        (
+            "on_linux",
+            [
+                (
+                    "on_arm",
+                    vec!["aarch64-unknown-linux-gnu", "aarch64-unknown-linux-musl"],
+                ),
+                (
+                    "on_intel",
+                    vec!["x86_64-unknown-linux-gnu", "x86_64-unknown-linux-musl"],
+                ),
+            ],
+        ),
+    ];
+    let mut body = String::new();
+    for (os, arches) in &platforms {
+        let mut os_body = String::new();
+        for (arch, triples) in arches {
+            if let Some(d) = app.download(triples) {
+                os_body.push_str(&format!(
+                    "    {arch} do\n      url \"{}\"\n      sha256 \"{}\"\n    end\n",
+                    d.url, d.sha256
+                ));
+            }
+        }
+        if !os_body.is_empty() {
+            body.push_str(&format!("  {os} do\n{os_body}  end\n\n"));
+        }
+    }
+    if body.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "class {} < Formula\n  version \"{}\"\n\n{body}  def install\n    bin.install \"{}\"\n  end\nend\n",
+        class_name(&app.name),
+        app.version,
+        app.name
+    ))
+}
+
+/// Scoop manifest for `app`, or `None` without Windows archives
+pub fn render_scoop(app: &PackagedApp) -> Option<String> {
+    let mut architecture = serde_json::Map::new();
+    for (arch, triples) in [
+        ("64bit", ["x86_64-pc-windows-msvc", "x86_64-pc-windows-gnu"]),
+        (
+            "arm64",
+            ["aarch64-pc-windows-msvc", "aarch64-pc-windows-gnullvm"],
+        ),
+    ] {
+        if let Some(d) = app.download(&triples) {
+            architecture.insert(arch.to_string(), json!({"url": d.url, "hash": d.sha256}));
+        }
+    }
+    if architecture.is_empty() {
+        return None;
+    }
+    let manifest = json!({
+        "version": app.version,
+        "architecture": architecture,
+        "bin": format!("{}.exe", app.name),
+    });
+    serde_json::to_string_pretty(&manifest)
+        .ok()
+        .map(|s| s + "\n")
+}
+
+/// Write the manifests of `apps` into `packaging_dir`, returning changed files
+fn write_manifests(
+    apps: &[PackagedApp],
+    packaging_dir: &Path,
+    config: &PackagingConfig,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let formula_dir = packaging_dir.join(config.formula_dir.as_deref().unwrap_or("Formula"));
+    let bucket_dir = packaging_dir.join(config.bucket_dir.as_deref().unwrap_or("bucket"));
+    let mut written = Vec::new();
+    for app in apps {
+        let files = [
+            (
+                formula_dir.join(format!("{}.rb", app.name)),
+                render_formula(app),
+            ),
+            (
+                bucket_dir.join(format!("{}.json", app.name)),
+                render_scoop(app),
+            ),
+        ];
+        for (path, content) in files {
+            let Some(content) = content else { continue };
+            if std::fs::read_to_string(&path).ok().as_deref() == Some(content.as_str()) {
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("failed to create {}", parent.display()))?;
+            }
+            std::fs::write(&path, content)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            written.push(path);
+        }
+    }
+    Ok(written)
+}
+
+/// Handle `meta cargo dist packaging [--no-commit]`
+pub(crate) fn execute(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    config: &Config,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let no_commit = args::take_flag(&mut args, "--no-commit");
+    let packaging = &config.dist.packaging;
+    let (Some(repo), Some(url)) = (&packaging.repo, &packaging.url) else {
+        return CommandResult::Error(
+            "meta cargo dist packaging needs [dist.packaging] repo and url in .meta-rust.toml"
+                .to_string(),
+        );
+    };
+    let root_config = cargo_config::root_config(cwd, config);
+    let mut apps = Vec::new();
+    for r in repos {
+        let dir = project_path(cwd, r);
+        if !dist::is_configured(&dir) {
+            continue;
+        }
+        let loaded = dist::load_manifest(&dir, root_config.as_deref())
+            .and_then(|manifest| packaged_apps(r, &manifest, url));
+        match loaded {
+            Ok(a) => apps.extend(a),
+            Err(e) => return CommandResult::Error(format!("{r}: {e:#}")),
+        }
+    }
+    if apps.is_empty() {
+        return CommandResult::Message("No released apps in the dist manifests".to_string());
+    }
+
+    let packaging_dir = project_path(cwd, repo);
+    let written = match write_manifests(&apps, &packaging_dir, packaging) {
+        Ok(w) => w,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    if written.is_empty() {
+        return CommandResult::Message("Packaging manifests are up to date".to_string());
+    }
+    let mut out = format!("Updated in {repo}:\n");
+    for path in &written {
+        let shown = path.strip_prefix(&packaging_dir).unwrap_or(path);
+        out.push_str(&format!("  {}\n", shown.display()));
+    }
+    if no_commit {
+        return CommandResult::Message(out);
+    }
+
+    let summary: Vec<String> = apps
+        .iter()
+        .map(|a| format!("{} {}", a.name, a.version))
+        .collect();
+    let message = format!("Update {}", summary.join(", "));
+    let files: Vec<String> = written
+        .iter()
+        .map(|p| {
+            p.strip_prefix(&packaging_dir)
+                .unwrap_or(p)
+                .display()
+                .to_string()
+        })
+        .collect();
+    let mut add = vec!["add", "--"];
+    add.extend(files.iter().map(String::as_str));
+    let committed = git::run(&packaging_dir, &add)
+        .and_then(|_| git::run(&packaging_dir, &["commit", "-m", &message]));
+    match committed {
+        Ok(_) => {
+            out.push_str(&format!("Committed \"{message}\"\n"));
+            CommandResult::Message(out)
+        }
+        Err(e) => CommandResult::Error(format!("{out}{e:#}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SHA: &str = "3b0c6e8b2a7d4f1e9c5a0b6d2e8f4a1c7b3d9e5f0a6c2b8d4e1f7a3c9b5d0e6f";
+
+    fn manifest(dir: &Path) -> DistManifest {
+        let artifact = |name: &str, triple: Option<&str>| {
+            let checksum = format!("{name}.sha256");
+            std::fs::write(dir.join(&checksum), format!("{SHA}  {name}\n")).unwrap();
+            vec![
+                DistArtifact {
+                    name: name.to_string(),
+                    kind: "executable-zip".to_string(),
+                    path: dir.join(name),
+                    checksum: Some(checksum.clone()),
+                    target_triples: triple.into_iter().map(str::to_string).collect(),
+                },
+                DistArtifact {
+                    name: checksum.clone(),
+                    kind: "checksum".to_string(),
+                    path: dir.join(&checksum),
+                    checksum: None,
+                    target_triples: Vec::new(),
+                },
+            ]
+        };
+        let mut artifacts = artifact(
+            "my-app-aarch64-apple-darwin.tar.xz",
+            Some("aarch64-apple-darwin"),
+        );
+        artifacts.extend(artifact(
+            "my-app-x86_64-pc-windows-msvc.zip",
+            Some("x86_64-pc-windows-msvc"),
+        ));
+        DistManifest {
+            tag: "v0.3.0".to_string(),
+            releases: vec![("my-app".to_string(), "0.3.0".to_string())],
+            artifacts,
+        }
+    }
+
+    #[test]
+    fn test_renders_formula_and_scoop() {
+        let temp_dir = TempDir::new().unwrap();
+        let apps = packaged_apps(
+            "tools/my-app",
+            &manifest(temp_dir.path()),
+            "https://github.com/acme/{repo}/releases/download/{tag}/{artifact}",
+        )
+        .unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].downloads.len(), 2);
+
+        let formula = render_formula(&apps[0]).unwrap();
+        assert!(formula.starts_with("class MyApp < Formula\n  version \"0.3.0\""));
+        assert!(formula.contains("  on_macos do\n    on_arm do\n      url \"https://github.com/acme/my-app/releases/download/v0.3.0/my-app-aarch64-apple-darwin.tar.xz\""));
+        assert!(formula.contains(&format!("sha256 \"{SHA}\"")));
+        assert!(!formula.contains("on_linux"));
+
+        let scoop: serde_json::Value =
+            serde_json::from_str(&render_scoop(&apps[0]).unwrap()).unwrap();
+        assert_eq!(scoop["architecture"]["64bit"]["hash"], SHA);
+        assert_eq!(scoop["bin"], "my-app.exe");
+    }
+
+    #[test]
+    fn test_write_manifests_skips_unchanged() {
+        let temp_dir = TempDir::new().unwrap();
+        let apps =
+            packaged_apps("my-app", &manifest(temp_dir.path()), "https://x/{artifact}").unwrap();
+        let out = temp_dir.path().join("tap");
+        let config = PackagingConfig::default();
+        let written = write_manifests(&apps, &out, &config).unwrap();
+        assert_eq!(written.len(), 2);
+        assert!(out.join("Formula/my-app.rb").is_file());
+        assert!(out.join("bucket/my-app.json").is_file());
+        assert!(write_manifests(&apps, &out, &config).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+//! Cached execution plans (`[cargo] plan_cache = true`)
+//!
+//! Resolving a plan can take seconds in a large meta repo: dependency
+//! ordering and several options run `cargo metadata` in every repo. With the
+//! cache enabled, a resolved plan is stored in `.meta-rust/plan-cache/` under
+//! a key derived from the command line, the selected projects and filters,
+//! the contents of every manifest, lockfile and cargo config involved, meta's
+//! own config files, and the `CARGO*`/`RUST*` environment. Any change to
+//! those resolves the plan again. Runs that depend on git state
+//! (`--affected`, `--predictive`) are never cached; `--no-plan-cache`
+//! bypasses the cache for one run.
+
+use crate::filters::ProjectFilters;
+use crate::{project_path, PlannedCommand};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Cached plans, relative to the meta root
+const CACHE_DIR: &str = ".meta-rust/plan-cache";
+
+/// Plans kept; the least recently written are removed
+const MAX_ENTRIES: usize = 50;
+
+/// Flags whose plans depend on git state
+const UNCACHEABLE_FLAGS: &[&str] = &["--affected", "--predictive"];
+
+/// Meta-root files that shape plans
+const ROOT_FILES: &[&str] = &[
+    ".meta",
+    ".meta.json",
+    ".meta.yaml",
+    ".meta.yml",
+    crate::config::CONFIG_FILE,
+    ".cargo/config.toml",
+    ".cargo/config",
+    ".meta-rust/timings.json",
+];
+
+/// Files in each project that shape its plan
+const PROJECT_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    "rust-toolchain",
+    "rust-toolchain.toml",
+    "deny.toml",
+    "xtask/Cargo.toml",
+    ".cargo/config.toml",
+    ".cargo/config",
+];
+
+/// Directories never searched for member manifests
+const SKIP_DIRS: &[&str] = &["target", "node_modules", "vendor"];
+
+/// Nested `Cargo.toml` files (workspace members) below `dir`
+fn member_manifests(dir: &Path, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut children: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && !SKIP_DIRS.contains(&name.as_ref())
+        })
+        .map(|e| e.path())
+        .collect();
+    children.sort();
+    for child in children {
+        let manifest = child.join("Cargo.toml");
+        if manifest.is_file() {
+            found.push(manifest);
+        }
+        member_manifests(&child, found);
+    }
+}
+
+fn hash_file(hasher: &mut DefaultHasher, path: &Path) {
+    path.hash(hasher);
+    // A missing file hashes differently from an empty one
+    std::fs::read(path).ok().hash(hasher);
+}
+
+/// Cache key of a plan, or `None` when the run must not be cached
+pub(crate) fn key(
+    command: &str,
+    args: &[String],
+    parallel: bool,
+    filters: &ProjectFilters,
+    projects: &[String],
+    cwd: &Path,
+) -> Option<String> {
+    let passed = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    if args[..passed]
+        .iter()
+        .any(|a| UNCACHEABLE_FLAGS.contains(&a.as_str()))
+    {
+        return None;
+    }
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    (command, args, parallel, projects).hash(&mut hasher);
+    (&filters.include, &filters.exclude).hash(&mut hasher);
+    let mut vars: Vec<(String, String)> = std::env::vars()
+        .filter(|(k, _)| k.starts_with("CARGO") || k.starts_with("RUST"))
+        .collect();
+    vars.sort();
+    vars.hash(&mut hasher);
+    for file in ROOT_FILES {
+        hash_file(&mut hasher, &cwd.join(file));
+    }
+    for project in projects {
+        let dir = project_path(cwd, project);
+        for file in PROJECT_FILES {
+            hash_file(&mut hasher, &dir.join(file));
+        }
+        let mut members = Vec::new();
+        member_manifests(&dir, &mut members);
+        for manifest in members {
+            hash_file(&mut hasher, &manifest);
+        }
+    }
+    Some(format!("{:016x}", hasher.finish()))
+}
+
+fn entry_path(cwd: &Path, key: &str) -> PathBuf {
+    cwd.join(CACHE_DIR).join(format!("{key}.json"))
+}
+
+/// The cached plan for `key`, if any
+pub(crate) fn load(cwd: &Path, key: &str) -> Option<Vec<PlannedCommand>> {
+    let text = std::fs::read_to_string(entry_path(cwd, key)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Store the plan for `key`, dropping the oldest entries beyond the limit
+///
+/// A cache that can't be written only costs the next run its speedup, so
+/// failures are reported as a warning.
+pub(crate) fn store(cwd: &Path, key: &str, commands: &[PlannedCommand]) {
+    let path = entry_path(cwd, key);
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| {
+            let text = serde_json::to_string(commands).map_err(std::io::Error::other)?;
+            std::fs::write(&path, text)
+        });
+    if let Err(e) = written {
+        eprintln!("warning: failed to cache plan in {}: {e}", path.display());
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(cwd.join(CACHE_DIR)) else {
+        return;
+    };
+    let mut entries: Vec<(std::time::SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .collect();
+    entries.sort();
+    let excess = entries.len().saturating_sub(MAX_ENTRIES);
+    for (_, old) in entries.into_iter().take(excess) {
+        let _ = std::fs::remove_file(old);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_key_follows_manifests_and_flags() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("core/crates/util")).unwrap();
+        std::fs::write(root.join("core/Cargo.toml"), "[workspace]\n").unwrap();
+        std::fs::write(root.join("core/crates/util/Cargo.toml"), "[package]\n").unwrap();
+        let projects = vec!["core".to_string()];
+        let filters = ProjectFilters::default();
+        let args = vec!["--release".to_string()];
+        let key_for = |args: &[String]| key("cargo build", args, false, &filters, &projects, root);
+
+        let first = key_for(&args).unwrap();
+        assert_eq!(key_for(&args).unwrap(), first);
+        assert_ne!(key_for(&[]).unwrap(), first);
+
+        std::fs::write(
+            root.join("core/crates/util/Cargo.toml"),
+            "[package]\nname = \"util\"\n",
+        )
+        .unwrap();
+        assert_ne!(key_for(&args).unwrap(), first);
+
+        assert!(key_for(&["--affected".to_string()]).is_none());
+        // After `--`, flags belong to cargo
+        assert!(key_for(&["--".to_string(), "--affected".to_string()]).is_some());
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let commands = vec![PlannedCommand {
+            dir: "core".to_string(),
+            cmd: "cargo build".to_string(),
+            env: None,
+        }];
+        assert!(load(temp_dir.path(), "abc").is_none());
+        store(temp_dir.path(), "abc", &commands);
+        assert_eq!(load(temp_dir.path(), "abc"), Some(commands));
+    }
+}
@@ -0,0 +1,333 @@
+//! `meta cargo platform-deps`: audit of platform-conditional dependencies
+//!
+//! Every `[target.<spec>.*dependencies]` table of every manifest is checked
+//! against the targets the organization ships (`[platforms] ship`, or
+//! `--targets`). A spec is either a target triple or a `cfg(...)` expression,
+//! evaluated against `rustc --print cfg --target <triple>`. Dependencies no
+//! shipped target would ever build are reported as cleanup candidates.
+
+use crate::{metadata, project_path, CommandResult};
+use anyhow::{bail, Context};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Dependency tables a target section can contain
+const DEP_TABLES: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// A parsed `cfg(...)` predicate
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cfg {
+    Name(String),
+    KeyValue(String, String),
+    All(Vec<Cfg>),
+    Any(Vec<Cfg>),
+    Not(Box<Cfg>),
+}
+
+/// `name` or `key = "value"` entries active for a target
+pub type CfgSet = Vec<(String, Option<String>)>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum CfgToken {
+    Ident(String),
+    Str(String),
+    Open,
+    Close,
+    Comma,
+    Eq,
+}
+
+fn cfg_tokens(s: &str) -> Result<Vec<CfgToken>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' => {
+                chars.next();
+            }
+            '(' | ')' | ',' | '=' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => CfgToken::Open,
+                    ')' => CfgToken::Close,
+                    ',' => CfgToken::Comma,
+                    _ => CfgToken::Eq,
+                });
+            }
+            '"' => {
+                chars.next();
+                let value: String = chars.by_ref().take_while(|&c| c != '"').collect();
+                tokens.push(CfgToken::Str(value));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                    ident.push(c);
+                    chars.next();
+                }
+                tokens.push(CfgToken::Ident(ident));
+            }
+            other => return Err(format!("unexpected '{other}' in cfg expression")),
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_predicate(tokens: &[CfgToken], pos: &mut usize) -> Result<Cfg, String> {
+    let Some(CfgToken::Ident(name)) = tokens.get(*pos) else {
+        return Err("expected a cfg name".to_string());
+    };
+    *pos += 1;
+    match tokens.get(*pos) {
+        Some(CfgToken::Eq) => match tokens.get(*pos + 1) {
+            Some(CfgToken::Str(value)) => {
+                *pos += 2;
+                Ok(Cfg::KeyValue(name.clone(), value.clone()))
+            }
+            _ => Err(format!("expected a string after '{name} ='")),
+        },
+        Some(CfgToken::Open) => {
+            *pos += 1;
+            let mut args = Vec::new();
+            while tokens.get(*pos) != Some(&CfgToken::Close) {
+                args.push(parse_predicate(tokens, pos)?);
+                match tokens.get(*pos) {
+                    Some(CfgToken::Comma) => *pos += 1,
+                    Some(CfgToken::Close) => {}
+                    _ => return Err(format!("unterminated {name}(...)")),
+                }
+            }
+            *pos += 1;
+            match name.as_str() {
+                "all" => Ok(Cfg::All(args)),
+                "any" => Ok(Cfg::Any(args)),
+                "not" if args.len() == 1 => Ok(Cfg::Not(Box::new(args.remove(0)))),
+                other => Err(format!("unsupported cfg operator '{other}'")),
+            }
+        }
+        _ => Ok(Cfg::Name(name.clone())),
+    }
+}
+
+/// Parse the inside of a `cfg(...)` spec
+pub fn parse_cfg(expr: &str) -> Result<Cfg, String> {
+    let tokens = cfg_tokens(expr)?;
+    let mut pos = 0;
+    let cfg = parse_predicate(&tokens, &mut pos)?;
+    if pos != tokens.len() {
+        return Err(format!("trailing input in cfg({expr})"));
+    }
+    Ok(cfg)
+}
+
+impl Cfg {
+    /// Whether the predicate holds for a target with `cfgs`
+    pub fn eval(&self, cfgs: &CfgSet) -> bool {
+        match self {
+            Cfg::Name(name) => cfgs.iter().any(|(k, v)| k == name && v.is_none()),
+            Cfg::KeyValue(key, value) => cfgs
+                .iter()
+                .any(|(k, v)| k == key && v.as_deref() == Some(value)),
+            Cfg::All(args) => args.iter().all(|a| a.eval(cfgs)),
+            Cfg::Any(args) => args.iter().any(|a| a.eval(cfgs)),
+            Cfg::Not(arg) => !arg.eval(cfgs),
+        }
+    }
+}
+
+/// Parse `rustc --print cfg` output
+pub fn parse_cfg_set(output: &str) -> CfgSet {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|l| match l.split_once('=') {
+            Some((k, v)) => (k.to_string(), Some(v.trim_matches('"').to_string())),
+            None => (l.to_string(), None),
+        })
+        .collect()
+}
+
+/// cfgs of `triple` from `rustc --print cfg --target <triple>`
+fn target_cfgs(triple: &str) -> anyhow::Result<CfgSet> {
+    let output = Command::new("rustc")
+        .args(["--print", "cfg", "--target", triple])
+        .output()
+        .context("failed to run rustc")?;
+    if !output.status.success() {
+        bail!(
+            "rustc --print cfg --target {triple} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(parse_cfg_set(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Whether a `[target.<spec>]` applies to any of the shipped `targets`
+pub fn spec_matches(spec: &str, targets: &[(String, CfgSet)]) -> Result<bool, String> {
+    match spec.strip_prefix("cfg(").and_then(|s| s.strip_suffix(')')) {
+        Some(expr) => {
+            let cfg = parse_cfg(expr)?;
+            Ok(targets.iter().any(|(_, cfgs)| cfg.eval(cfgs)))
+        }
+        None => Ok(targets.iter().any(|(triple, _)| triple == spec)),
+    }
+}
+
+/// `(spec, table, dependency)` of the target-specific dependencies in a manifest
+pub fn target_dependencies(manifest: &str) -> anyhow::Result<Vec<(String, String, String)>> {
+    let table: toml::Table = toml::from_str(manifest).context("invalid Cargo.toml")?;
+    let mut deps = Vec::new();
+    let Some(targets) = table.get("target").and_then(|t| t.as_table()) else {
+        return Ok(deps);
+    };
+    for (spec, section) in targets {
+        for kind in DEP_TABLES {
+            if let Some(entries) = section.get(*kind).and_then(|d| d.as_table()) {
+                for name in entries.keys() {
+                    deps.push((spec.clone(), kind.to_string(), name.clone()));
+                }
+            }
+        }
+    }
+    Ok(deps)
+}
+
+/// Handle `meta cargo platform-deps [--targets <triple,...>]`
+pub(crate) fn execute(
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    ship: &[String],
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let triples: Vec<String> = match crate::args::take_value(&mut args, "--targets") {
+        Some(list) => list.split(',').map(|t| t.trim().to_string()).collect(),
+        None => ship.to_vec(),
+    };
+    if triples.is_empty() {
+        return CommandResult::Error(
+            "no shipped targets: set [platforms] ship in .meta-rust.toml or pass --targets"
+                .to_string(),
+        );
+    }
+    let mut targets = Vec::new();
+    for triple in triples {
+        match target_cfgs(&triple) {
+            Ok(cfgs) => targets.push((triple, cfgs)),
+            Err(e) => return CommandResult::Error(format!("{e:#}")),
+        }
+    }
+
+    let mut unused: BTreeMap<PathBuf, Vec<(String, String, String)>> = BTreeMap::new();
+    let mut total = 0;
+    let mut failures = Vec::new();
+    for repo in repos {
+        for manifest in metadata::manifest_paths(&project_path(cwd, repo)) {
+            let deps = std::fs::read_to_string(&manifest)
+                .with_context(|| format!("failed to read {}", manifest.display()))
+                .and_then(|text| target_dependencies(&text));
+            let deps = match deps {
+                Ok(d) => d,
+                Err(e) => {
+                    failures.push(format!("{}: {e:#}", manifest.display()));
+                    continue;
+                }
+            };
+            total += deps.len();
+            for dep in deps {
+                match spec_matches(&dep.0, &targets) {
+                    Ok(true) => {}
+                    Ok(false) => unused.entry(manifest.clone()).or_default().push(dep),
+                    Err(e) => failures.push(format!("{}: {}: {e}", manifest.display(), dep.0)),
+                }
+            }
+        }
+    }
+
+    let shipped: Vec<&str> = targets.iter().map(|(t, _)| t.as_str()).collect();
+    let mut out = format!(
+        "{total} platform-conditional dependencies checked against {}\n",
+        shipped.join(", ")
+    );
+    for (manifest, deps) in &unused {
+        let shown = manifest.strip_prefix(cwd).unwrap_or(manifest);
+        out.push_str(&format!("{}:\n", shown.display()));
+        for (spec, kind, name) in deps {
+            out.push_str(&format!(
+                "  {} {name} ([target.'{spec}'.{kind}]) is never built for a shipped target\n",
+                "unused:".yellow()
+            ));
+        }
+    }
+    if unused.is_empty() {
+        out.push_str("Every platform-conditional dependency applies to a shipped target\n");
+    } else {
+        out.push_str("Consider removing these dependencies or their target sections\n");
+    }
+    for failure in &failures {
+        out.push_str(&format!("{} {failure}\n", "error:".red()));
+    }
+    if failures.is_empty() {
+        CommandResult::Message(out)
+    } else {
+        CommandResult::Error(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linux() -> CfgSet {
+        parse_cfg_set(
+            "debug_assertions\ntarget_arch=\"x86_64\"\ntarget_family=\"unix\"\ntarget_os=\"linux\"\nunix\n",
+        )
+    }
+
+    #[test]
+    fn test_cfg_eval() {
+        let cfgs = linux();
+        assert!(parse_cfg("unix").unwrap().eval(&cfgs));
+        assert!(!parse_cfg("windows").unwrap().eval(&cfgs));
+        assert!(
+            parse_cfg("all(target_os = \"linux\", not(target_arch = \"arm\"))")
+                .unwrap()
+                .eval(&cfgs)
+        );
+        assert!(
+            !parse_cfg("any(target_os = \"macos\", target_os = \"ios\")")
+                .unwrap()
+                .eval(&cfgs)
+        );
+        assert!(parse_cfg("all(unix").is_err());
+    }
+
+    #[test]
+    fn test_unused_target_dependencies() {
+        let manifest = r#"
+[package]
+name = "app"
+
+[target.'cfg(windows)'.dependencies]
+winapi = "0.3"
+
+[target.'cfg(unix)'.dev-dependencies]
+nix = "0.29"
+
+[target.wasm32-unknown-unknown.dependencies]
+wasm-bindgen = "0.2"
+"#;
+        let targets = vec![("x86_64-unknown-linux-gnu".to_string(), linux())];
+        let deps = target_dependencies(manifest).unwrap();
+        assert_eq!(deps.len(), 3);
+        let unused: Vec<&str> = deps
+            .iter()
+            .filter(|(spec, _, _)| !spec_matches(spec, &targets).unwrap())
+            .map(|(_, _, name)| name.as_str())
+            .collect();
+        assert_eq!(unused, vec!["winapi", "wasm-bindgen"]);
+    }
+}
@@ -0,0 +1,221 @@
+//! Experimental `--predictive` test ordering
+//!
+//! Every predictive run records which paths were changed and which repos
+//! failed. On the next run, repos are ordered by how often they failed when
+//! the currently changed paths (or their parent directories) were changed, so
+//! the likeliest failures run first and CI fails fast.
+
+use crate::affected;
+use crate::config::Config;
+use crate::output::OutputOptions;
+use crate::runner;
+use crate::state;
+use crate::{CommandResult, PlannedCommand};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// History file, relative to the meta root
+const HISTORY_FILE: &str = ".meta-rust/test-history.json";
+
+/// Runs kept in the history; older ones are dropped
+const MAX_RUNS: usize = 200;
+
+/// One recorded test run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HistoryRun {
+    /// Changed files and their parent directories, relative to the meta root
+    pub changed: Vec<String>,
+    /// Repos whose tests failed
+    pub failed: Vec<String>,
+}
+
+/// Recorded runs, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    pub runs: Vec<HistoryRun>,
+}
+
+impl History {
+    /// Load the history, starting empty when there is none
+    pub fn load(cwd: &Path) -> Result<Self> {
+        state::load(cwd, HISTORY_FILE)
+    }
+
+    /// Append `run` and write the history back, keeping the newest runs
+    pub fn record(&mut self, cwd: &Path, run: HistoryRun) -> Result<()> {
+        self.runs.push(run);
+        let excess = self.runs.len().saturating_sub(MAX_RUNS);
+        self.runs.drain(..excess);
+        state::save(cwd, HISTORY_FILE, self)
+    }
+
+    /// Likelihood that `repo` fails given `changed`
+    ///
+    /// The highest failure rate among runs sharing any changed path, with the
+    /// repo's overall failure rate as a small tiebreaker.
+    pub fn score(&self, repo: &str, changed: &BTreeSet<String>) -> f64 {
+        let mut best = 0.0f64;
+        for key in changed {
+            let runs: Vec<&HistoryRun> = self
+                .runs
+                .iter()
+                .filter(|r| r.changed.iter().any(|c| c == key))
+                .collect();
+            if runs.is_empty() {
+                continue;
+            }
+            let failures = runs
+                .iter()
+                .filter(|r| r.failed.iter().any(|f| f == repo))
+                .count();
+            best = best.max(failures as f64 / runs.len() as f64);
+        }
+        let overall = if self.runs.is_empty() {
+            0.0
+        } else {
+            let failures = self
+                .runs
+                .iter()
+                .filter(|r| r.failed.iter().any(|f| f == repo))
+                .count();
+            failures as f64 / self.runs.len() as f64
+        };
+        best + overall / 1000.0
+    }
+
+    /// `repos` ordered by descending score; ties keep their original order
+    pub fn order(&self, repos: &[String], changed: &BTreeSet<String>) -> Vec<String> {
+        let mut scored: Vec<(f64, &String)> =
+            repos.iter().map(|r| (self.score(r, changed), r)).collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().map(|(_, r)| r.clone()).collect()
+    }
+}
+
+/// A root-relative file plus each of its parent directories
+fn keys_for(repo: &str, file: &str) -> Vec<String> {
+    let full = if repo == "." {
+        file.to_string()
+    } else {
+        format!("{repo}/{file}")
+    };
+    let mut keys = vec![full.clone()];
+    let mut rest = full.as_str();
+    while let Some((parent, _)) = rest.rsplit_once('/') {
+        keys.push(parent.to_string());
+        rest = parent;
+    }
+    keys
+}
+
+/// Changed paths across all repos since `since`; repos git can't diff are skipped
+fn changed_keys(repos: &[String], cwd: &Path, since: &str, config: &Config) -> BTreeSet<String> {
+    let mut keys = BTreeSet::new();
+    for repo in repos {
+        let Ok(files) = affected::repo_changes(repo, repos, cwd, since, &config.changes.ignore)
+        else {
+            continue;
+        };
+        for file in files {
+            keys.extend(keys_for(repo, &file));
+        }
+    }
+    keys
+}
+
+/// Run `commands`, likeliest failures first, and record the results
+pub(crate) fn execute(
+    commands: Vec<PlannedCommand>,
+    cwd: &Path,
+    parallel: bool,
+    since: &str,
+    config: &Config,
+    output: &OutputOptions,
+) -> CommandResult {
+    let mut history = match History::load(cwd) {
+        Ok(h) => h,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    let repos: Vec<String> = commands.iter().map(|c| c.dir.clone()).collect();
+    let changed = changed_keys(&repos, cwd, since, config);
+    let mut commands = commands;
+    let order = history.order(&repos, &changed);
+    commands.sort_by_key(|c| order.iter().position(|r| *r == c.dir));
+
+    let limits = match runner::concurrency_for(&commands, &config.concurrency) {
+        Ok(l) => l,
+        Err(e) => return CommandResult::Error(e),
+    };
+    let outcomes = runner::run_all_limited(cwd, &commands, parallel, &limits);
+    let run = HistoryRun {
+        changed: changed.into_iter().collect(),
+        failed: outcomes
+            .iter()
+            .filter(|o| !o.success)
+            .map(|o| o.dir.clone())
+            .collect(),
+    };
+    if let Err(e) = history.record(cwd, run) {
+        return CommandResult::Error(format!("{e:#}"));
+    }
+    output.deliver(cwd, &outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn run(changed: &[&str], failed: &[&str]) -> HistoryRun {
+        HistoryRun {
+            changed: changed.iter().map(|s| s.to_string()).collect(),
+            failed: failed.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_keys_include_parent_dirs() {
+        assert_eq!(
+            keys_for("libs/core", "src/lib.rs"),
+            vec!["libs/core/src/lib.rs", "libs/core/src", "libs/core", "libs"]
+        );
+        assert_eq!(keys_for(".", "build.rs"), vec!["build.rs"]);
+    }
+
+    #[test]
+    fn test_order_by_correlated_failures() {
+        let history = History {
+            runs: vec![
+                run(&["core", "core/src"], &["app"]),
+                run(&["core", "core/src"], &["app"]),
+                run(&["tool"], &["tool"]),
+            ],
+        };
+        let repos: Vec<String> = ["core", "tool", "app"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let changed: BTreeSet<String> = ["core/src".to_string()].into();
+        assert_eq!(history.order(&repos, &changed), vec!["app", "tool", "core"]);
+
+        let nothing = BTreeSet::new();
+        assert_eq!(history.order(&repos, &nothing), vec!["app", "tool", "core"]);
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut history = History::load(temp_dir.path()).unwrap();
+        for _ in 0..MAX_RUNS + 5 {
+            history.runs.push(run(&[], &[]));
+        }
+        history
+            .record(temp_dir.path(), run(&["x"], &["y"]))
+            .unwrap();
+        let loaded = History::load(temp_dir.path()).unwrap();
+        assert_eq!(loaded.runs.len(), MAX_RUNS);
+        assert_eq!(loaded.runs.last(), Some(&run(&["x"], &["y"])));
+    }
+}
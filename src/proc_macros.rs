@@ -0,0 +1,192 @@
+//! `meta cargo proc-macro-impact`: what editing a shared derive costs
+//!
+//! Every crate that transitively depends on a proc-macro crate is rebuilt
+//! when the macro changes. For each proc-macro crate in the meta workspace
+//! this report lists those crates by repo, most far-reaching macro first,
+//! with the last recorded run time of the affected repos (see
+//! `--order slowest-first`) as an estimate of the rebuild cost.
+
+use crate::graph::CrateGraph;
+use crate::order::Timings;
+use crate::{args, CommandResult};
+use colored::Colorize;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Rebuild impact of one proc-macro crate
+#[derive(Debug, Clone, PartialEq)]
+struct Impact {
+    name: String,
+    repo: String,
+    /// Dependent crates by repo, the macro itself excluded
+    dependents: BTreeMap<String, Vec<String>>,
+    /// Sum of the recorded durations of the affected repos, if all have one
+    recorded_secs: Option<f64>,
+}
+
+impl Impact {
+    fn crate_count(&self) -> usize {
+        self.dependents.values().map(Vec::len).sum()
+    }
+}
+
+fn impacts(graph: &CrateGraph, timings: &Timings) -> Vec<Impact> {
+    let mut out: Vec<Impact> = graph
+        .crates
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.proc_macro)
+        .map(|(i, c)| {
+            let mut dependents: BTreeMap<String, Vec<String>> = BTreeMap::new();
+            for d in graph.dependents(&BTreeSet::from([i])) {
+                if d != i {
+                    let node = &graph.crates[d];
+                    dependents
+                        .entry(node.repo.clone())
+                        .or_default()
+                        .push(node.name.clone());
+                }
+            }
+            for names in dependents.values_mut() {
+                names.sort();
+            }
+            let mut repos: BTreeSet<&str> = dependents.keys().map(String::as_str).collect();
+            repos.insert(&c.repo);
+            let recorded_secs = repos.iter().map(|r| timings.repos.get(*r).copied()).sum();
+            Impact {
+                name: c.name.clone(),
+                repo: c.repo.clone(),
+                dependents,
+                recorded_secs,
+            }
+        })
+        .collect();
+    out.sort_by(|a, b| {
+        b.crate_count()
+            .cmp(&a.crate_count())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    out
+}
+
+fn render_text(impacts: &[Impact]) -> String {
+    if impacts.is_empty() {
+        return "No proc-macro crates in the meta workspace\n".to_string();
+    }
+    let mut out = String::new();
+    for impact in impacts {
+        let cost = impact
+            .recorded_secs
+            .map(|s| format!(", ~{s:.0}s recorded for those repos"))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "{} ({}): {} crates in {} repos rebuild{cost}\n",
+            impact.name.bold(),
+            impact.repo,
+            impact.crate_count(),
+            impact.dependents.len()
+        ));
+        for (repo, names) in &impact.dependents {
+            out.push_str(&format!("  {repo}: {}\n", names.join(", ")));
+        }
+    }
+    out
+}
+
+fn render_json(impacts: &[Impact]) -> serde_json::Value {
+    json!({
+        "proc_macros": impacts.iter().map(|i| json!({
+            "name": i.name,
+            "repo": i.repo,
+            "dependents": i.dependents,
+            "crates": i.crate_count(),
+            "recorded_seconds": i.recorded_secs,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// Handle `meta cargo proc-macro-impact [--format text|json]`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let format = args::take_value(&mut args, "--format").unwrap_or_else(|| "text".to_string());
+    if format != "text" && format != "json" {
+        return CommandResult::Error(format!(
+            "unsupported format '{format}' (expected text or json)"
+        ));
+    }
+    let graph = match CrateGraph::load(repos, cwd) {
+        Ok(graph) => graph,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    // Without recorded timings the report just leaves out the estimate
+    let timings = Timings::load(cwd).unwrap_or_default();
+    let impacts = impacts(&graph, &timings);
+    if format == "json" {
+        match serde_json::to_string_pretty(&render_json(&impacts)) {
+            Ok(text) => CommandResult::Message(text),
+            Err(e) => CommandResult::Error(format!("Failed to serialize report: {e}")),
+        }
+    } else {
+        CommandResult::Message(render_text(&impacts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::Package;
+
+    #[test]
+    fn test_impact_of_shared_derive() {
+        let graph = CrateGraph::from_packages(vec![
+            (
+                "macros".to_string(),
+                vec![
+                    Package::fixture("derive")
+                        .with_root("/ws/macros/derive")
+                        .with_target("proc-macro", ""),
+                    Package::fixture("small-derive")
+                        .with_root("/ws/macros/small")
+                        .with_target("proc-macro", ""),
+                ],
+            ),
+            (
+                "core".to_string(),
+                vec![Package::fixture("core")
+                    .with_target("lib", "")
+                    .with_dependencies(&["derive"])],
+            ),
+            (
+                "app".to_string(),
+                vec![
+                    Package::fixture("app")
+                        .with_target("lib", "")
+                        .with_dependencies(&["core", "small-derive"]),
+                    Package::fixture("app-cli")
+                        .with_root("/ws/app/cli")
+                        .with_target("bin", "")
+                        .with_dependencies(&["app"]),
+                ],
+            ),
+        ]);
+        let mut timings = Timings::default();
+        for (repo, secs) in [("macros", 10.0), ("core", 20.0), ("app", 30.0)] {
+            timings.repos.insert(repo.to_string(), secs);
+        }
+        let impacts = impacts(&graph, &timings);
+        assert_eq!(impacts.len(), 2);
+        assert_eq!(impacts[0].name, "derive");
+        assert_eq!(impacts[0].crate_count(), 3);
+        assert_eq!(impacts[0].dependents["app"], vec!["app", "app-cli"]);
+        assert_eq!(impacts[0].recorded_secs, Some(60.0));
+        assert_eq!(impacts[1].crate_count(), 2);
+
+        let text = render_text(&impacts);
+        assert!(
+            text.contains("(macros): 3 crates in 2 repos rebuild, ~60s recorded"),
+            "{text}"
+        );
+        assert_eq!(render_json(&impacts)["proc_macros"][1]["crates"], 2);
+    }
+}
@@ -0,0 +1,150 @@
+//! Progress frames for in-process runs
+//!
+//! When meta-rust runs a plan itself (output modes, lanes like `integration`),
+//! it is otherwise silent until the result is printed. Setting
+//! `META_RUST_PROGRESS` makes the runner write one JSON line per frame as
+//! commands start and finish: `stderr`, or `fd:<n>` for a descriptor the
+//! calling shim opened for the purpose (Unix only). stdout is left alone, as
+//! it carries the plugin response.
+//!
+//! A frame looks like
+//! `{"type":"progress","event":"finished","repo":"api","completed":3,"total":8,"percent":37}`.
+
+use serde_json::json;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// Environment variable selecting the progress channel
+pub const PROGRESS_ENV: &str = "META_RUST_PROGRESS";
+
+/// Where frames are written
+type Sink = Mutex<Box<dyn Write + Send>>;
+
+/// Open the channel named by `spec`, if it is valid
+fn open_sink(spec: &str) -> Option<Box<dyn Write + Send>> {
+    if spec == "stderr" {
+        return Some(Box::new(std::io::stderr()));
+    }
+    let fd: u32 = spec.strip_prefix("fd:")?.parse().ok()?;
+    if !cfg!(unix) {
+        return None;
+    }
+    let file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(format!("/dev/fd/{fd}"))
+        .ok()?;
+    Some(Box::new(file))
+}
+
+/// A single progress frame
+pub fn frame(event: &str, repo: &str, completed: usize, total: usize) -> String {
+    let percent = (completed * 100).checked_div(total).unwrap_or(100);
+    json!({
+        "type": "progress",
+        "event": event,
+        "repo": repo,
+        "completed": completed,
+        "total": total,
+        "percent": percent,
+    })
+    .to_string()
+}
+
+/// Progress of one run of `total` commands
+pub struct Progress {
+    total: usize,
+    completed: Mutex<usize>,
+    sink: Option<Sink>,
+}
+
+impl Progress {
+    /// Report to the channel in `META_RUST_PROGRESS`, or nowhere
+    pub fn from_env(total: usize) -> Self {
+        let sink = std::env::var(PROGRESS_ENV)
+            .ok()
+            .and_then(|spec| open_sink(&spec));
+        Self::with_sink(total, sink)
+    }
+
+    pub fn with_sink(total: usize, sink: Option<Box<dyn Write + Send>>) -> Self {
+        Progress {
+            total,
+            completed: Mutex::new(0),
+            sink: sink.map(Mutex::new),
+        }
+    }
+
+    fn emit(&self, line: &str) {
+        if let Some(sink) = &self.sink {
+            let mut sink = sink.lock().unwrap();
+            // Progress is best effort; a closed channel must not fail the run
+            let _ = writeln!(sink, "{line}");
+            let _ = sink.flush();
+        }
+    }
+
+    /// A command in `repo` started
+    pub fn started(&self, repo: &str) {
+        if self.sink.is_some() {
+            let completed = *self.completed.lock().unwrap();
+            self.emit(&frame("started", repo, completed, self.total));
+        }
+    }
+
+    /// A command in `repo` finished
+    pub fn finished(&self, repo: &str) {
+        let completed = {
+            let mut completed = self.completed.lock().unwrap();
+            *completed += 1;
+            *completed
+        };
+        self.emit(&frame("finished", repo, completed, self.total));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Sink that keeps what was written
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_frames_count_completions() {
+        let shared = Shared::default();
+        let progress = Progress::with_sink(2, Some(Box::new(shared.clone())));
+        progress.started("api");
+        progress.finished("api");
+        progress.finished("web");
+        let text = String::from_utf8(shared.0.lock().unwrap().clone()).unwrap();
+        let frames: Vec<serde_json::Value> = text
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0]["event"], "started");
+        assert_eq!(frames[1]["percent"], 50);
+        assert_eq!(frames[2]["repo"], "web");
+        assert_eq!(frames[2]["completed"], 2);
+    }
+
+    #[test]
+    fn test_invalid_channel_is_ignored() {
+        assert!(open_sink("stdout").is_none());
+        assert!(open_sink("fd:x").is_none());
+        assert!(open_sink("stderr").is_some());
+    }
+}
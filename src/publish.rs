@@ -0,0 +1,419 @@
+//! `meta cargo publish`: coordinated releases across repos
+//!
+//! Every publishable crate of the meta repo is published in dependency order,
+//! so each `cargo publish` verification builds against the freshly published
+//! versions of its dependencies. Versions already in the crates.io index are
+//! skipped, and after each upload the index is polled until the new version
+//! shows up before its dependents go out. `--dry-run` prints the plan only.
+//!
+//! With `--staging`, the same order is rehearsed against a local registry:
+//! repos depending on the published crates are then checked with their
+//! registry dependencies patched to the staging registry. Nothing reaches
+//! crates.io.
+
+use crate::config::Config;
+use crate::graph::CrateGraph;
+use crate::runner::{self, RunOutcome};
+use crate::{args, fixtures, CommandResult, PlannedCommand};
+use anyhow::Context;
+use colored::Colorize;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// Sparse index of crates.io
+const CRATES_IO_INDEX: &str = "https://index.crates.io";
+/// How long to wait for a published version to reach the index by default
+const DEFAULT_WAIT_SECS: u64 = 300;
+/// Pause between index polls
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// `--config` flags patching each `(name, version)` to `registry`
+fn patch_flags(published: &[(String, String)], registry: &str) -> String {
+    published
+        .iter()
+        .map(|(name, version)| {
+            format!(
+                " --config 'patch.crates-io.{name}={{ version = \"={version}\", registry = \"{registry}\" }}'"
+            )
+        })
+        .collect()
+}
+
+fn status_line(outcome: &RunOutcome, label: &str) -> String {
+    if outcome.success {
+        format!("  {} {label}\n", "ok".green())
+    } else {
+        let mut line = format!("  {} {label}\n", "FAIL".red());
+        for l in outcome
+            .stderr
+            .lines()
+            .filter(|l| l.starts_with("error"))
+            .take(5)
+        {
+            line.push_str(&format!("      {l}\n"));
+        }
+        line
+    }
+}
+
+/// Path of `name` in a sparse registry index, e.g. `se/rd/serde`
+fn index_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{name}"),
+        2 => format!("2/{name}"),
+        3 => format!("3/{}/{name}", &name[..1]),
+        _ => format!("{}/{}/{name}", &name[..2], &name[2..4]),
+    }
+}
+
+/// Records of a sparse index file (one JSON object per line)
+pub(crate) fn index_records(body: &str) -> Vec<serde_json::Value> {
+    body.lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Versions listed in a sparse index file
+fn indexed_versions(body: &str) -> Vec<String> {
+    index_records(body)
+        .iter()
+        .filter_map(|record| record["vers"].as_str().map(str::to_string))
+        .collect()
+}
+
+/// The crates.io index file of `name`; empty for an unknown crate
+pub(crate) fn crates_io_index(name: &str) -> anyhow::Result<String> {
+    let url = format!("{CRATES_IO_INDEX}/{}", index_path(name));
+    let output = Command::new("curl")
+        .args(["-sS", "-w", "\n%{http_code}", &url])
+        .output()
+        .context("failed to run curl")?;
+    let body = String::from_utf8_lossy(&output.stdout);
+    let (body, status) = body
+        .trim_end()
+        .rsplit_once('\n')
+        .unwrap_or(("", body.trim()));
+    match status {
+        "200" => Ok(body.to_string()),
+        "404" => Ok(String::new()),
+        _ => anyhow::bail!(
+            "crates.io index lookup for {name} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    }
+}
+
+/// Versions of `name` on crates.io
+fn crates_io_versions(name: &str) -> anyhow::Result<Vec<String>> {
+    Ok(indexed_versions(&crates_io_index(name)?))
+}
+
+/// What happens to one crate of the release
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Publish,
+    /// The version is already in the index
+    Published,
+}
+
+/// Whether the crate may go to `registry` (`None` for crates.io)
+fn allowed(publish: &Option<Vec<String>>, registry: Option<&str>) -> bool {
+    let registry = registry.unwrap_or("crates-io");
+    publish
+        .as_ref()
+        .is_none_or(|list| list.iter().any(|r| r == registry))
+}
+
+fn render_plan(graph: &CrateGraph, plan: &[(usize, Step)], target: &str) -> String {
+    let pending = plan.iter().filter(|(_, s)| *s == Step::Publish).count();
+    let mut out = format!("Release plan for {target} ({pending} to publish):\n");
+    for (n, (i, step)) in plan.iter().enumerate() {
+        let node = &graph.crates[*i];
+        let label = format!("{} {} ({})", node.name, node.version, node.repo);
+        match step {
+            Step::Publish => out.push_str(&format!("  {:>2}. {label}\n", n + 1)),
+            Step::Published => out.push_str(&format!(
+                "  {:>2}. {} {label}: already published\n",
+                n + 1,
+                "skip".dimmed()
+            )),
+        }
+    }
+    out
+}
+
+/// Poll the crates.io index until `name` lists `version`
+fn wait_for_index(name: &str, version: &str, timeout: Duration) -> anyhow::Result<()> {
+    let start = Instant::now();
+    loop {
+        if crates_io_versions(name)?.iter().any(|v| v == version) {
+            return Ok(());
+        }
+        if start.elapsed() >= timeout {
+            anyhow::bail!(
+                "{name} {version} did not appear in the crates.io index within {}s",
+                timeout.as_secs()
+            );
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Handle `meta cargo publish [--staging] [--registry <name>] [--dry-run]
+/// [--wait-timeout <secs>]`
+pub(crate) fn execute(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    if args::take_flag(&mut args, "--staging") {
+        return execute_staging(cargo, args, repos, cwd, parallel, config);
+    }
+    let registry = args::take_value(&mut args, "--registry");
+    let dry_run = args::take_flag(&mut args, "--dry-run");
+    let wait = match args::take_value(&mut args, "--wait-timeout") {
+        Some(secs) => match secs.parse() {
+            Ok(secs) => Duration::from_secs(secs),
+            Err(_) => {
+                return CommandResult::Error(format!(
+                    "--wait-timeout expects seconds, got '{secs}'"
+                ))
+            }
+        },
+        None => Duration::from_secs(config.publish.wait_timeout.unwrap_or(DEFAULT_WAIT_SECS)),
+    };
+
+    let graph = match CrateGraph::load(repos, cwd) {
+        Ok(g) => g,
+        Err(e) => return CommandResult::Error(format!("Failed to load crate graph: {e:#}")),
+    };
+    let mut plan = Vec::new();
+    for i in graph.crate_order() {
+        let node = &graph.crates[i];
+        if !node.publishable || !allowed(&node.publish, registry.as_deref()) {
+            continue;
+        }
+        // Only crates.io has a known index; other registries reject
+        // duplicates when publishing
+        let step = match registry {
+            Some(_) => Step::Publish,
+            None => match crates_io_versions(&node.name) {
+                Ok(versions) if versions.contains(&node.version) => Step::Published,
+                Ok(_) => Step::Publish,
+                Err(e) => return CommandResult::Error(format!("{e:#}")),
+            },
+        };
+        plan.push((i, step));
+    }
+    if plan.is_empty() {
+        return CommandResult::Message("No publishable crates".to_string());
+    }
+    let target = registry.as_deref().unwrap_or("crates.io");
+    let mut out = render_plan(&graph, &plan, target);
+    if dry_run {
+        return CommandResult::Message(out);
+    }
+
+    let mut extra = String::new();
+    for arg in &args {
+        extra.push(' ');
+        extra.push_str(arg);
+    }
+    let registry_flag = registry
+        .as_ref()
+        .map(|r| format!(" --registry {r}"))
+        .unwrap_or_default();
+    out.push('\n');
+    let mut count = 0;
+    for (i, step) in &plan {
+        if *step == Step::Published {
+            continue;
+        }
+        let node = &graph.crates[*i];
+        let planned = PlannedCommand {
+            dir: node.root.display().to_string(),
+            cmd: format!("{cargo} publish{registry_flag} -p {}{extra}", node.name),
+            env: None,
+        };
+        let outcome = runner::run_command(cwd, &planned);
+        out.push_str(&status_line(
+            &outcome,
+            &format!("{} {}", node.name, node.version),
+        ));
+        if !outcome.success {
+            out.push_str(&format!("Release stopped after {count} crates\n"));
+            return CommandResult::Error(out);
+        }
+        count += 1;
+        if registry.is_none() {
+            if let Err(e) = wait_for_index(&node.name, &node.version, wait) {
+                out.push_str(&format!("{e:#}\nRelease stopped after {count} crates\n"));
+                return CommandResult::Error(out);
+            }
+        }
+    }
+    out.push_str(&format!("Published {count} crates to {target}\n"));
+    CommandResult::Message(out)
+}
+
+/// Staged release rehearsal (`--staging`)
+fn execute_staging(
+    cargo: &str,
+    mut args: Vec<String>,
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+    config: &Config,
+) -> CommandResult {
+    let staging = &config.publish.staging;
+    let Some(registry) = args::take_value(&mut args, "--registry").or(staging.registry.clone())
+    else {
+        return CommandResult::Error(
+            "no staging registry: pass --registry <name> or set [publish.staging] registry"
+                .to_string(),
+        );
+    };
+
+    let graph = match CrateGraph::load(repos, cwd) {
+        Ok(g) => g,
+        Err(e) => return CommandResult::Error(format!("Failed to load crate graph: {e:#}")),
+    };
+    let order: Vec<usize> = graph
+        .crate_order()
+        .into_iter()
+        .filter(|&i| graph.crates[i].publishable)
+        .collect();
+    if order.is_empty() {
+        return CommandResult::Message("No publishable crates".to_string());
+    }
+
+    let running = match &staging.fixture {
+        Some(fixture) => {
+            match fixtures::start_all(std::slice::from_ref(fixture), cwd, &HashMap::new()) {
+                Ok(r) => r,
+                Err(e) => return CommandResult::Error(format!("{e:#}")),
+            }
+        }
+        None => Vec::new(),
+    };
+
+    let mut out = format!("Publishing {} crates to {registry}:\n", order.len());
+    let mut published = Vec::new();
+    let mut failed = false;
+    for &i in &order {
+        let node = &graph.crates[i];
+        // Staging validates the working tree, committed or not
+        let planned = PlannedCommand {
+            dir: node.root.display().to_string(),
+            cmd: format!(
+                "{cargo} publish --registry {registry} --allow-dirty -p {}",
+                node.name
+            ),
+            env: None,
+        };
+        let outcome = runner::run_command(cwd, &planned);
+        out.push_str(&status_line(
+            &outcome,
+            &format!("{} {}", node.name, node.version),
+        ));
+        if !outcome.success {
+            failed = true;
+            break;
+        }
+        published.push((node.name.clone(), node.version.clone()));
+    }
+
+    if !failed {
+        let names: BTreeSet<usize> = order.iter().copied().collect();
+        let dependents = graph.dependents(&names);
+        let downstream: Vec<String> = repos
+            .iter()
+            .filter(|r| graph.crates_in_repo(r).any(|i| dependents.contains(&i)))
+            .cloned()
+            .collect();
+        let patches = patch_flags(&published, &registry);
+        let commands: Vec<PlannedCommand> = downstream
+            .iter()
+            .map(|repo| PlannedCommand {
+                dir: repo.clone(),
+                cmd: format!("{cargo} check --workspace{patches}"),
+                env: None,
+            })
+            .collect();
+        out.push_str("\nChecking repos against the staged crates:\n");
+        for outcome in runner::run_all(cwd, &commands, parallel) {
+            failed |= !outcome.success;
+            out.push_str(&status_line(&outcome, &outcome.dir));
+        }
+    }
+
+    for warning in fixtures::stop_all(running) {
+        out.push_str(&format!("{} {warning}\n", "warning:".yellow()));
+    }
+    if failed {
+        out.push_str("Staged release failed\n");
+        CommandResult::Error(out)
+    } else {
+        out.push_str(&format!(
+            "Staged release of {} crates succeeded\n",
+            published.len()
+        ));
+        CommandResult::Message(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patch_flags() {
+        let flags = patch_flags(&[("core".to_string(), "0.2.0".to_string())], "local");
+        assert_eq!(
+            flags,
+            " --config 'patch.crates-io.core={ version = \"=0.2.0\", registry = \"local\" }'"
+        );
+    }
+
+    #[test]
+    fn test_index_path() {
+        assert_eq!(index_path("a"), "1/a");
+        assert_eq!(index_path("cc"), "2/cc");
+        assert_eq!(index_path("syn"), "3/s/syn");
+        assert_eq!(index_path("Serde_json"), "se/rd/serde_json");
+    }
+
+    #[test]
+    fn test_indexed_versions() {
+        let body = "{\"name\":\"core\",\"vers\":\"0.1.0\",\"deps\":[]}\n{\"name\":\"core\",\"vers\":\"0.2.0\",\"deps\":[]}\n";
+        assert_eq!(indexed_versions(body), vec!["0.1.0", "0.2.0"]);
+        assert!(indexed_versions("").is_empty());
+    }
+
+    #[test]
+    fn test_allowed_registries() {
+        assert!(allowed(&None, None));
+        assert!(allowed(&None, Some("internal")));
+        let internal = Some(vec!["internal".to_string()]);
+        assert!(!allowed(&internal, None));
+        assert!(allowed(&internal, Some("internal")));
+    }
+
+    #[test]
+    fn test_staging_requires_registry() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = Config::default();
+        let args = vec!["--staging".to_string()];
+        match execute("cargo", &args, &[], temp_dir.path(), false, &config) {
+            CommandResult::Error(msg) => assert!(msg.contains("no staging registry")),
+            _ => panic!("Expected Error result"),
+        }
+    }
+}
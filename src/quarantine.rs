@@ -0,0 +1,256 @@
+//! `meta cargo quarantine`: temporarily exclude broken repos
+//!
+//! Entries live in `.meta-rust/quarantine.json` with a reason and an expiry
+//! date. Quarantined repos are skipped with a warning; once an entry has
+//! expired every run fails until it is removed or renewed, so a quarantine
+//! cannot be forgotten.
+
+use crate::state;
+use crate::{args, CommandResult};
+use anyhow::Result;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Quarantine file, relative to the meta root
+const QUARANTINE_FILE: &str = ".meta-rust/quarantine.json";
+
+/// Why and until when a repo is quarantined
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub reason: String,
+    /// Last day of the quarantine, `YYYY-MM-DD`
+    pub until: String,
+}
+
+/// Quarantined repos, keyed by repo path
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Quarantine {
+    pub repos: BTreeMap<String, Entry>,
+}
+
+impl Quarantine {
+    /// Load the quarantine, starting empty when there is none
+    pub fn load(cwd: &Path) -> Result<Self> {
+        state::load(cwd, QUARANTINE_FILE)
+    }
+
+    fn save(&self, cwd: &Path) -> Result<()> {
+        state::save(cwd, QUARANTINE_FILE, self)
+    }
+
+    /// Entries whose last day is before `today`
+    pub fn expired<'a>(&'a self, today: &str) -> Vec<(&'a String, &'a Entry)> {
+        self.repos
+            .iter()
+            .filter(|(_, e)| e.until.as_str() < today)
+            .collect()
+    }
+}
+
+/// Whether `date` is a `YYYY-MM-DD` date
+fn is_date(date: &str) -> bool {
+    let parts: Vec<&str> = date.split('-').collect();
+    matches!(parts.as_slice(), [y, m, d]
+        if y.len() == 4 && m.len() == 2 && d.len() == 2
+            && [y, m, d].iter().all(|p| p.bytes().all(|b| b.is_ascii_digit()))
+            && (1..=12).contains(&m.parse::<u32>().unwrap_or(0))
+            && (1..=31).contains(&d.parse::<u32>().unwrap_or(0)))
+}
+
+/// `YYYY-MM-DD` of a day counted from 1970-01-01
+pub(crate) fn civil_date(days: i64) -> String {
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Today's date (UTC)
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    civil_date((secs / 86_400) as i64)
+}
+
+/// Drop quarantined repos from `repos`
+///
+/// Each skipped repo is announced on stderr. Fails when any entry has
+/// expired.
+pub(crate) fn skip(repos: Vec<String>, cwd: &Path) -> Result<Vec<String>, String> {
+    let quarantine = Quarantine::load(cwd).map_err(|e| format!("{e:#}"))?;
+    if quarantine.repos.is_empty() {
+        return Ok(repos);
+    }
+    let expired = quarantine.expired(&today());
+    if !expired.is_empty() {
+        let mut message = String::from("quarantine expired:\n");
+        for (repo, entry) in expired {
+            message.push_str(&format!(
+                "  {repo} (until {}): {}\n",
+                entry.until, entry.reason
+            ));
+        }
+        message.push_str(
+            "fix the repos and `meta cargo quarantine remove <repo>`, or renew with `add`",
+        );
+        return Err(message);
+    }
+    Ok(repos
+        .into_iter()
+        .filter(|repo| match quarantine.repos.get(repo) {
+            Some(entry) => {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "warning: skipping quarantined repo {repo} (until {}): {}",
+                        entry.until, entry.reason
+                    )
+                    .yellow()
+                    .bold()
+                );
+                false
+            }
+            None => true,
+        })
+        .collect())
+}
+
+/// Handle `meta cargo quarantine add <repo> --reason <text> --until <YYYY-MM-DD>`
+fn add(args: &[String], repos: &[String], cwd: &Path) -> Result<String> {
+    let mut args = args.to_vec();
+    let reason = args::take_value(&mut args, "--reason")
+        .ok_or_else(|| anyhow::Error::msg("--reason is required"))?;
+    let until = args::take_value(&mut args, "--until")
+        .ok_or_else(|| anyhow::Error::msg("--until <YYYY-MM-DD> is required"))?;
+    if !is_date(&until) {
+        anyhow::bail!("--until expects a YYYY-MM-DD date, got '{until}'");
+    }
+    let Some(repo) = args.first() else {
+        anyhow::bail!("usage: meta cargo quarantine add <repo> --reason <text> --until <date>");
+    };
+    if !repos.contains(repo) {
+        anyhow::bail!("'{repo}' is not a Rust repo of this meta workspace");
+    }
+    let mut quarantine = Quarantine::load(cwd)?;
+    quarantine.repos.insert(
+        repo.clone(),
+        Entry {
+            reason,
+            until: until.clone(),
+        },
+    );
+    quarantine.save(cwd)?;
+    Ok(format!("Quarantined {repo} until {until}"))
+}
+
+/// Handle `meta cargo quarantine remove <repo>`
+fn remove(args: &[String], cwd: &Path) -> Result<String> {
+    let Some(repo) = args.first() else {
+        anyhow::bail!("usage: meta cargo quarantine remove <repo>");
+    };
+    let mut quarantine = Quarantine::load(cwd)?;
+    if quarantine.repos.remove(repo).is_none() {
+        anyhow::bail!("{repo} is not quarantined");
+    }
+    quarantine.save(cwd)?;
+    Ok(format!("Removed {repo} from quarantine"))
+}
+
+/// Handle `meta cargo quarantine list`
+fn list(cwd: &Path) -> Result<String> {
+    let quarantine = Quarantine::load(cwd)?;
+    if quarantine.repos.is_empty() {
+        return Ok("No repos are quarantined".to_string());
+    }
+    let today = today();
+    let mut out = String::new();
+    for (repo, entry) in &quarantine.repos {
+        let status = if entry.until.as_str() < today.as_str() {
+            " [expired]"
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "{repo}: until {}{status}: {}\n",
+            entry.until, entry.reason
+        ));
+    }
+    Ok(out)
+}
+
+/// Handle `meta cargo quarantine add|remove|list`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let result = match args.split_first() {
+        Some((sub, rest)) if sub == "add" => add(rest, repos, cwd),
+        Some((sub, rest)) if sub == "remove" => remove(rest, cwd),
+        Some((sub, _)) if sub == "list" => list(cwd),
+        _ => {
+            return CommandResult::ShowHelp(Some(
+                "usage: meta cargo quarantine add|remove|list".to_string(),
+            ))
+        }
+    };
+    match result {
+        Ok(msg) => CommandResult::Message(msg),
+        Err(e) => CommandResult::Error(format!("{e:#}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::strings;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_civil_date() {
+        assert_eq!(civil_date(0), "1970-01-01");
+        assert_eq!(civil_date(19_782), "2024-02-29");
+        assert!(is_date("2026-10-14"));
+        assert!(!is_date("2026-13-01"));
+        assert!(!is_date("next week"));
+    }
+
+    #[test]
+    fn test_add_skip_and_expiry() {
+        let temp_dir = TempDir::new().unwrap();
+        let repos = strings(&["core", "labs"]);
+        let args = strings(&["labs", "--reason", "flaky linker", "--until", "9999-12-31"]);
+        match execute(
+            &[vec!["add".to_string()], args].concat(),
+            &repos,
+            temp_dir.path(),
+        ) {
+            CommandResult::Message(msg) => assert!(msg.contains("labs until 9999-12-31")),
+            _ => panic!("Expected Message result"),
+        }
+        assert_eq!(skip(repos.clone(), temp_dir.path()).unwrap(), vec!["core"]);
+
+        let mut quarantine = Quarantine::load(temp_dir.path()).unwrap();
+        quarantine.repos.get_mut("labs").unwrap().until = "2000-01-01".to_string();
+        quarantine.save(temp_dir.path()).unwrap();
+        let err = skip(repos.clone(), temp_dir.path()).unwrap_err();
+        assert!(
+            err.contains("labs (until 2000-01-01): flaky linker"),
+            "{err}"
+        );
+
+        match execute(&strings(&["remove", "labs"]), &repos, temp_dir.path()) {
+            CommandResult::Message(_) => {}
+            _ => panic!("Expected Message result"),
+        }
+        assert_eq!(skip(repos.clone(), temp_dir.path()).unwrap(), repos);
+    }
+}
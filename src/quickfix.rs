@@ -0,0 +1,106 @@
+//! Vim/Emacs quickfix file for diagnostics from every repo
+//!
+//! Each line is `file:line:col: level[code]: message` with the file relative
+//! to the meta root, which both `vim -q` and Emacs compilation mode parse.
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::runner::RunOutcome;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// File written when `--quickfix-file` is not given (Vim's default errorfile)
+pub(crate) const DEFAULT_FILE: &str = "errors.err";
+
+/// Join `file` onto the repo dir and make it relative to the meta root
+pub(crate) fn root_relative(cwd: &Path, repo: &str, file: &str) -> String {
+    let file = Path::new(file);
+    let path = if file.is_absolute() {
+        file.strip_prefix(cwd).unwrap_or(file).to_path_buf()
+    } else if repo == "." {
+        file.to_path_buf()
+    } else {
+        Path::new(repo).join(file)
+    };
+    let normalized: PathBuf = path
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect();
+    normalized.to_string_lossy().replace('\\', "/")
+}
+
+pub(crate) fn format_entry(cwd: &Path, repo: &str, d: &Diagnostic) -> String {
+    let level = match &d.code {
+        Some(code) => format!("{}[{code}]", d.level.as_str()),
+        None => d.level.as_str().to_string(),
+    };
+    format!(
+        "{}:{}:{}: {level}: {}",
+        root_relative(cwd, repo, &d.file),
+        d.line,
+        d.column,
+        d.message
+    )
+}
+
+/// Quickfix lines for all outcomes, with duplicates (e.g. the same warning
+/// reported for lib and test targets) removed
+pub(crate) fn render(cwd: &Path, outcomes: &[RunOutcome]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut lines = Vec::new();
+    for outcome in outcomes {
+        for d in diagnostics::parse(&outcome.output()) {
+            let line = format_entry(cwd, &outcome.dir, &d);
+            if seen.insert(line.clone()) {
+                lines.push(line);
+            }
+        }
+    }
+    lines
+}
+
+/// Write the quickfix file, returning the number of entries
+pub(crate) fn write(path: &Path, cwd: &Path, outcomes: &[RunOutcome]) -> Result<usize> {
+    let lines = render(cwd, outcomes);
+    let mut text = lines.join("\n");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    std::fs::write(path, text).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(lines.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths_are_relative_to_meta_root() {
+        let cwd = Path::new("/meta");
+        assert_eq!(root_relative(cwd, ".", "src/lib.rs"), "src/lib.rs");
+        assert_eq!(
+            root_relative(cwd, "libs/core", "./src/lib.rs"),
+            "libs/core/src/lib.rs"
+        );
+    }
+
+    #[test]
+    fn test_render_dedupes_entries() {
+        let warning = "warning: unused variable: `x`\n --> src/lib.rs:3:9\n";
+        let lines = render(
+            Path::new("/meta"),
+            &[
+                RunOutcome::fixture("core", true).with_stderr(&format!("{warning}{warning}")),
+                RunOutcome::fixture("app", true)
+                    .with_stderr("error[E0308]: mismatched types\n --> src/main.rs:5:13\n"),
+            ],
+        );
+        assert_eq!(
+            lines,
+            vec![
+                "core/src/lib.rs:3:9: warning: unused variable: `x`",
+                "app/src/main.rs:5:13: error[E0308]: mismatched types",
+            ]
+        );
+    }
+}
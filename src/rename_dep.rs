@@ -0,0 +1,296 @@
+//! `meta cargo rename-dep <old> <new>`: coordinated crate renames
+//!
+//! Rewrites dependency entries (keys, `[dependencies.<old>]` tables,
+//! `package = "<old>"` and feature references) in every manifest of every
+//! repo, plus paths rooted at the crate in Rust sources. Edits keep each
+//! line in place, so `--dry-run` prints a zero-context unified diff.
+
+use crate::env_audit::rust_files;
+use crate::grep_api::{tokenize, Tok};
+use crate::{args, metadata, project_path, CommandResult};
+use std::path::{Path, PathBuf};
+
+/// Whether a manifest section header names a dependency table
+pub(crate) fn is_dependency_table(header: &str) -> bool {
+    let last = header.rsplit('.').next().unwrap_or(header);
+    matches!(
+        last,
+        "dependencies" | "dev-dependencies" | "build-dependencies"
+    )
+}
+
+/// Key at the start of a TOML line, with its quotes, if it is `name`
+pub(crate) fn key_is(line: &str, name: &str) -> bool {
+    let trimmed = line.trim_start();
+    let rest = if let Some(quoted) = trimmed.strip_prefix('"') {
+        match quoted.strip_prefix(name) {
+            Some(r) if r.starts_with('"') => &r[1..],
+            _ => return false,
+        }
+    } else {
+        match trimmed.strip_prefix(name) {
+            Some(r) => r,
+            None => return false,
+        }
+    };
+    let rest = rest.trim_start();
+    rest.starts_with('=') || rest.starts_with('.')
+}
+
+/// A manifest with `old` renamed to `new`, or `None` if unchanged
+fn rename_in_manifest(text: &str, old: &str, new: &str) -> Option<String> {
+    let mut section = String::new();
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        let mut line = line.to_string();
+        if trimmed.starts_with('[') {
+            section = trimmed
+                .trim_matches(|c| c == '[' || c == ']')
+                .trim()
+                .to_string();
+            // `[dependencies.old]`
+            if let Some(table) = section.strip_suffix(&format!(".{old}")) {
+                if is_dependency_table(table) {
+                    line = line.replacen(&format!(".{old}]"), &format!(".{new}]"), 1);
+                    section = format!("{table}.{new}");
+                }
+            }
+        } else if is_dependency_table(&section) {
+            if key_is(&line, old) {
+                line = line.replacen(old, new, 1);
+            }
+            for quote in ['"', '\''] {
+                line = line.replace(
+                    &format!("package = {quote}{old}{quote}"),
+                    &format!("package = {quote}{new}{quote}"),
+                );
+            }
+        } else if section.rsplit('.').nth(1).is_some_and(is_dependency_table) {
+            // Inside `[dependencies.<name>]`
+            line = line.replace(
+                &format!("package = \"{old}\""),
+                &format!("package = \"{new}\""),
+            );
+        } else if section == "features" {
+            for (from, to) in [
+                (format!("\"{old}/"), format!("\"{new}/")),
+                (format!("\"{old}?/"), format!("\"{new}?/")),
+                (format!("\"dep:{old}\""), format!("\"dep:{new}\"")),
+                (format!("\"{old}\""), format!("\"{new}\"")),
+            ] {
+                // Feature names on the left-hand side stay as they are
+                match line.split_once('=') {
+                    Some((key, value)) if value.contains(&from) => {
+                        line = format!("{key}={}", value.replace(&from, &to));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        out.push_str(&line);
+    }
+    (out != text).then_some(out)
+}
+
+/// Rust source with paths rooted at crate `old` renamed, or `None`
+fn rename_in_source(src: &str, old: &str, new: &str) -> Option<String> {
+    let tokens = tokenize(src);
+    let punct = |i: usize, c: char| tokens.get(i).is_some_and(|t| t.tok == Tok::Punct(c));
+    let ident = |i: usize| match tokens.get(i).map(|t| &t.tok) {
+        Some(Tok::Ident(name)) => Some(name.as_str()),
+        _ => None,
+    };
+    let mut starts = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.tok != Tok::Ident(old.to_string()) {
+            continue;
+        }
+        let after_sep = i >= 2 && punct(i - 1, ':') && punct(i - 2, ':');
+        let after_dot = i >= 1 && punct(i - 1, '.');
+        let before_sep = punct(i + 1, ':') && punct(i + 2, ':');
+        let imported = i >= 1 && matches!(ident(i - 1), Some("use" | "crate"));
+        if !after_sep && !after_dot && (before_sep || imported) {
+            starts.push(token.start);
+        }
+    }
+    if starts.is_empty() {
+        return None;
+    }
+    let chars: Vec<char> = src.chars().collect();
+    let old_len = old.chars().count();
+    let mut out = String::with_capacity(src.len());
+    let mut pos = 0;
+    for start in starts {
+        out.extend(&chars[pos..start]);
+        out.push_str(new);
+        pos = start + old_len;
+    }
+    out.extend(&chars[pos..]);
+    Some(out)
+}
+
+/// Zero-context unified diff of a line-preserving edit
+fn diff(label: &str, before: &str, after: &str) -> String {
+    let mut out = format!("--- a/{label}\n+++ b/{label}\n");
+    for (n, (b, a)) in before.lines().zip(after.lines()).enumerate() {
+        if b != a {
+            out.push_str(&format!("@@ -{0} +{0} @@\n-{b}\n+{a}\n", n + 1));
+        }
+    }
+    out
+}
+
+/// Files of `repo` to rewrite, with their old and new contents
+fn repo_edits(
+    repo: &str,
+    repos: &[String],
+    cwd: &Path,
+    old: &str,
+    new: &str,
+) -> Vec<(PathBuf, String, String)> {
+    let dir = project_path(cwd, repo);
+    let mut edits = Vec::new();
+    for manifest in metadata::manifest_paths(&dir) {
+        if let Ok(text) = std::fs::read_to_string(&manifest) {
+            if let Some(renamed) = rename_in_manifest(&text, old, new) {
+                edits.push((manifest, text, renamed));
+            }
+        }
+    }
+
+    let nested: Vec<PathBuf> = repos
+        .iter()
+        .filter(|r| r.as_str() != repo && r.as_str() != ".")
+        .map(|r| project_path(cwd, r))
+        .collect();
+    let mut files = Vec::new();
+    rust_files(&dir, &nested, &mut files);
+    let (old_ident, new_ident) = (old.replace('-', "_"), new.replace('-', "_"));
+    for file in files {
+        if let Ok(src) = std::fs::read_to_string(&file) {
+            if let Some(renamed) = rename_in_source(&src, &old_ident, &new_ident) {
+                edits.push((file, src, renamed));
+            }
+        }
+    }
+    edits
+}
+
+/// Handle `meta cargo rename-dep <old> <new> [--dry-run]`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let dry_run = args::take_flag(&mut args, "--dry-run");
+    let [old, new] = args.as_slice() else {
+        return CommandResult::Error(
+            "usage: meta cargo rename-dep <old> <new> [--dry-run]".to_string(),
+        );
+    };
+
+    let cwd_canon = cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf());
+    let label = |p: &Path| {
+        p.strip_prefix(&cwd_canon)
+            .or_else(|_| p.strip_prefix(cwd))
+            .unwrap_or(p)
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    let mut out = String::new();
+    let mut count = 0;
+    for repo in repos {
+        for (path, before, after) in repo_edits(repo, repos, cwd, old, new) {
+            count += 1;
+            if dry_run {
+                out.push_str(&diff(&label(&path), &before, &after));
+            } else if let Err(e) = std::fs::write(&path, after) {
+                return CommandResult::Error(format!(
+                    "{out}Failed to write {}: {e}",
+                    path.display()
+                ));
+            } else {
+                out.push_str(&format!("updated {}\n", label(&path)));
+            }
+        }
+    }
+    if count == 0 {
+        return CommandResult::Message(format!("No references to {old} found"));
+    }
+    if dry_run {
+        out.push_str(&format!("{count} files would change (dry run)\n"));
+    } else {
+        out.push_str(&format!(
+            "{count} files updated; Cargo.lock files refresh on the next build\n"
+        ));
+    }
+    CommandResult::Message(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MANIFEST: &str = r#"[package]
+name = "app"
+
+[features]
+tls = ["old-net/tls", "dep:old-net"]
+
+[dependencies]
+old-net = { path = "../net", features = ["tls"] }
+serde = "1"
+alias = { package = "old-net", version = "1" }
+
+[target.'cfg(unix)'.dev-dependencies]
+old-net.workspace = true
+
+[build-dependencies.old-net]
+version = "1"
+"#;
+
+    #[test]
+    fn test_rename_in_manifest() {
+        let renamed = rename_in_manifest(MANIFEST, "old-net", "net").unwrap();
+        assert!(renamed.contains("tls = [\"net/tls\", \"dep:net\"]"));
+        assert!(renamed.contains("net = { path = \"../net\", features = [\"tls\"] }"));
+        assert!(renamed.contains("alias = { package = \"net\", version = \"1\" }"));
+        assert!(renamed.contains("net.workspace = true"));
+        assert!(renamed.contains("[build-dependencies.net]"));
+        assert!(!renamed.contains("old-net"));
+        assert!(rename_in_manifest(MANIFEST, "missing", "x").is_none());
+    }
+
+    #[test]
+    fn test_rename_in_source() {
+        let src = "use old_net::Client;\nextern crate old_net;\n// old_net::x\nfn f() { old_net::connect(); self.old_net; let s = \"old_net::y\"; }\n";
+        assert_eq!(
+            rename_in_source(src, "old_net", "net").unwrap(),
+            "use net::Client;\nextern crate net;\n// old_net::x\nfn f() { net::connect(); self.old_net; let s = \"old_net::y\"; }\n"
+        );
+    }
+
+    #[test]
+    fn test_dry_run_prints_diff() {
+        let temp_dir = TempDir::new().unwrap();
+        let app = temp_dir.path().join("app");
+        std::fs::create_dir_all(app.join("src")).unwrap();
+        std::fs::write(app.join("Cargo.toml"), MANIFEST).unwrap();
+        std::fs::write(app.join("src/lib.rs"), "use old_net::Client;\n").unwrap();
+        let args: Vec<String> = ["old-net", "net", "--dry-run"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        match execute(&args, &["app".to_string()], temp_dir.path()) {
+            CommandResult::Message(msg) => {
+                assert!(msg.contains("--- a/app/src/lib.rs"));
+                assert!(msg.contains("@@ -1 +1 @@\n-use old_net::Client;\n+use net::Client;"));
+                assert!(msg.contains("2 files would change (dry run)"));
+            }
+            _ => panic!("Expected Message result"),
+        }
+        assert_eq!(
+            std::fs::read_to_string(app.join("src/lib.rs")).unwrap(),
+            "use old_net::Client;\n"
+        );
+    }
+}
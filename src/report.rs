@@ -0,0 +1,89 @@
+//! Cross-repo test summary table (`--test-summary`)
+//!
+//! Counts come from libtest's per-test lines, so a repo that failed to build
+//! shows zero tests.
+
+use crate::libtest::{self, TestStatus};
+use crate::runner::RunOutcome;
+
+/// Test counts of one repo
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TestCounts {
+    pub passed: usize,
+    pub failed: usize,
+    pub ignored: usize,
+}
+
+impl TestCounts {
+    pub fn of(output: &str) -> Self {
+        let mut counts = TestCounts::default();
+        for case in libtest::parse(output) {
+            match case.status {
+                TestStatus::Passed => counts.passed += 1,
+                TestStatus::Failed => counts.failed += 1,
+                TestStatus::Ignored => counts.ignored += 1,
+            }
+        }
+        counts
+    }
+
+    fn add(&mut self, other: TestCounts) {
+        self.passed += other.passed;
+        self.failed += other.failed;
+        self.ignored += other.ignored;
+    }
+}
+
+/// One row per repo plus a total row, aligned to the widest repo name
+pub fn render_test_table(outcomes: &[RunOutcome]) -> String {
+    let width = outcomes
+        .iter()
+        .map(|o| o.dir.len())
+        .chain(["repo".len(), "total".len()])
+        .max()
+        .unwrap_or(0);
+    let row = |name: &str, c: TestCounts, secs: f64| {
+        format!(
+            "{name:<width$}  {:>6}  {:>6}  {:>7}  {:>7.1}s\n",
+            c.passed, c.failed, c.ignored, secs
+        )
+    };
+    let mut out = format!(
+        "{:<width$}  {:>6}  {:>6}  {:>7}  {:>8}\n",
+        "repo", "passed", "failed", "ignored", "time"
+    );
+    let mut total = TestCounts::default();
+    let mut total_secs = 0.0;
+    for o in outcomes {
+        let counts = TestCounts::of(&o.output());
+        let secs = o.duration.as_secs_f64();
+        out.push_str(&row(&o.dir, counts, secs));
+        total.add(counts);
+        total_secs += secs;
+    }
+    out.push_str(&row("total", total, total_secs));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_table_rows_and_totals() {
+        let outcome = |dir: &str, stdout: &str, ms| {
+            RunOutcome::fixture(dir, true)
+                .with_stdout(stdout)
+                .with_duration(Duration::from_millis(ms))
+        };
+        let table = render_test_table(&[
+            outcome("core", "test a ... ok\ntest b ... FAILED\n", 1500),
+            outcome("app", "test c ... ignored\ntest d ... ok\n", 500),
+        ]);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "repo   passed  failed  ignored      time");
+        assert_eq!(lines[1], "core        1       1        0      1.5s");
+        assert_eq!(lines[3], "total       2       1        1      2.0s");
+    }
+}
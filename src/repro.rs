@@ -0,0 +1,288 @@
+//! `meta cargo repro-check`: are the builds reproducible?
+//!
+//! Every repo is built twice from scratch, into two separate target dirs under
+//! `.meta-rust/repro/<repo>/`, with a normalized environment: the commit time
+//! as `SOURCE_DATE_EPOCH`, no incremental compilation, and
+//! `--remap-path-prefix` for the repo, the target dir and `CARGO_HOME`. The
+//! final artifacts of both builds are then compared by content hash. Without
+//! `--profile` or `--release` in the arguments, release builds are checked.
+
+use crate::runner::{self, RunOutcome};
+use crate::{debuginfo, git, integration, project_path, CommandResult, PlannedCommand};
+use colored::Colorize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Scratch space for the two builds, relative to the meta root
+const REPRO_DIR: &str = ".meta-rust/repro";
+/// Files in a profile dir that are not build outputs
+const IGNORED_EXTENSIONS: &[&str] = &["d"];
+
+/// 64-bit FNV-1a hash of `bytes`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Content hashes of the final artifacts of `profile` in `target`, keyed by
+/// their path relative to the profile dir (prefixed with the triple for cross
+/// builds)
+fn artifact_hashes(target: &Path, profile: &str) -> BTreeMap<String, u64> {
+    let mut hashes = BTreeMap::new();
+    for (dir, prefix) in debuginfo::profile_dirs(target, profile, Path::new("")) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let ignored = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| IGNORED_EXTENSIONS.contains(&e));
+            if !path.is_file() || ignored {
+                continue;
+            }
+            if let Ok(bytes) = std::fs::read(&path) {
+                let name = prefix.join(entry.file_name());
+                hashes.insert(name.to_string_lossy().replace('\\', "/"), fnv1a(&bytes));
+            }
+        }
+    }
+    hashes
+}
+
+/// Artifacts whose hashes differ between two builds, or that only one
+/// build produced
+fn differences(
+    first: &BTreeMap<String, u64>,
+    second: &BTreeMap<String, u64>,
+) -> Vec<(String, Option<u64>, Option<u64>)> {
+    let mut names: Vec<&String> = first.keys().chain(second.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let (a, b) = (first.get(name).copied(), second.get(name).copied());
+            (a != b).then(|| (name.clone(), a, b))
+        })
+        .collect()
+}
+
+/// The normalized build of `repo` into `target`
+fn build_command(cmd: &str, repo: &str, repo_dir: &Path, target: &Path) -> PlannedCommand {
+    let epoch = git::run(repo_dir, &["log", "-1", "--format=%ct"])
+        .map(|s| s.trim().to_string())
+        .unwrap_or_else(|_| "0".to_string());
+    let cargo_home = std::env::var("CARGO_HOME")
+        .ok()
+        .or_else(|| std::env::var("HOME").ok().map(|h| format!("{h}/.cargo")));
+    let mut rustflags = std::env::var("RUSTFLAGS").unwrap_or_default();
+    let mut remap = vec![
+        (repo_dir.display().to_string(), "/build"),
+        (target.display().to_string(), "/target"),
+    ];
+    if let Some(home) = cargo_home {
+        remap.push((home, "/cargo"));
+    }
+    for (from, to) in remap {
+        rustflags.push_str(&format!(" --remap-path-prefix={from}={to}"));
+    }
+    let env = HashMap::from([
+        ("SOURCE_DATE_EPOCH".to_string(), epoch),
+        ("CARGO_INCREMENTAL".to_string(), "0".to_string()),
+        ("CARGO_TARGET_DIR".to_string(), target.display().to_string()),
+        ("RUSTFLAGS".to_string(), rustflags.trim().to_string()),
+    ]);
+    PlannedCommand {
+        dir: repo.to_string(),
+        cmd: cmd.to_string(),
+        env: Some(env),
+    }
+}
+
+/// Result of one repo
+enum Verdict {
+    Reproducible(usize),
+    Differs(Vec<(String, Option<u64>, Option<u64>)>),
+    Failed(String),
+}
+
+fn verdict(outcomes: [&RunOutcome; 2], targets: [&Path; 2], profile: &str) -> Verdict {
+    if let Some(failed) = outcomes.iter().find(|o| !o.success) {
+        let reason = failed
+            .stderr
+            .trim()
+            .lines()
+            .last()
+            .unwrap_or("")
+            .to_string();
+        return Verdict::Failed(reason);
+    }
+    let first = artifact_hashes(targets[0], profile);
+    let second = artifact_hashes(targets[1], profile);
+    let diffs = differences(&first, &second);
+    if diffs.is_empty() {
+        Verdict::Reproducible(first.len())
+    } else {
+        Verdict::Differs(diffs)
+    }
+}
+
+fn render(results: &[(String, Verdict)]) -> String {
+    let hash = |h: &Option<u64>| match h {
+        Some(h) => format!("{h:016x}"),
+        None => "missing".to_string(),
+    };
+    let mut out = String::new();
+    let mut ok = 0;
+    for (repo, verdict) in results {
+        match verdict {
+            Verdict::Reproducible(n) => {
+                ok += 1;
+                out.push_str(&format!(
+                    "{} {repo}: {n} artifacts identical\n",
+                    "✓".green()
+                ));
+            }
+            Verdict::Differs(diffs) => {
+                out.push_str(&format!(
+                    "{} {repo}: {} artifacts differ\n",
+                    "✗".red(),
+                    diffs.len()
+                ));
+                for (name, a, b) in diffs {
+                    out.push_str(&format!("    {name}: {} vs {}\n", hash(a), hash(b)));
+                }
+            }
+            Verdict::Failed(reason) => {
+                out.push_str(&format!("{} {repo}: build failed: {reason}\n", "✗".red()));
+            }
+        }
+    }
+    out.push_str(&format!(
+        "\n{ok}/{} repos build reproducibly\n",
+        results.len()
+    ));
+    out
+}
+
+/// Handle `meta cargo repro-check [cargo build args]`
+pub(crate) fn execute(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let has_profile = args
+        .iter()
+        .any(|a| a == "--release" || a == "-r" || a.starts_with("--profile"));
+    if !has_profile {
+        args.insert(0, "--release".to_string());
+    }
+    let profile = integration::profile_dir(&args);
+    let mut cmd = format!("{cargo} build");
+    for arg in &args {
+        cmd.push(' ');
+        cmd.push_str(arg);
+    }
+
+    let scratch = cwd.join(REPRO_DIR);
+    let targets: Vec<[PathBuf; 2]> = repos
+        .iter()
+        .map(|repo| {
+            let name = if repo == "." {
+                "root".to_string()
+            } else {
+                repo.replace(['/', '\\'], "-")
+            };
+            let dir = scratch.join(name);
+            [dir.join("a"), dir.join("b")]
+        })
+        .collect();
+    for target in targets.iter().flatten() {
+        // Both builds start cold
+        let _ = std::fs::remove_dir_all(target);
+    }
+    let mut outcomes: Vec<Vec<RunOutcome>> = Vec::new();
+    for round in 0..2 {
+        let commands: Vec<PlannedCommand> = repos
+            .iter()
+            .zip(&targets)
+            .map(|(repo, t)| build_command(&cmd, repo, &project_path(cwd, repo), &t[round]))
+            .collect();
+        outcomes.push(runner::run_all(cwd, &commands, parallel));
+    }
+
+    let results: Vec<(String, Verdict)> = repos
+        .iter()
+        .enumerate()
+        .map(|(i, repo)| {
+            let v = verdict(
+                [&outcomes[0][i], &outcomes[1][i]],
+                [&targets[i][0], &targets[i][1]],
+                &profile,
+            );
+            (repo.clone(), v)
+        })
+        .collect();
+    let text = render(&results);
+    if results
+        .iter()
+        .all(|(_, v)| matches!(v, Verdict::Reproducible(_)))
+    {
+        CommandResult::Message(text)
+    } else {
+        CommandResult::Error(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_compare_builds() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = temp_dir.path().join("a");
+        let b = temp_dir.path().join("b");
+        for (target, stamp) in [(&a, "1"), (&b, "2")] {
+            let release = target.join("release");
+            std::fs::create_dir_all(release.join("deps")).unwrap();
+            std::fs::write(release.join("libcore.rlib"), "same").unwrap();
+            std::fs::write(release.join("app"), stamp).unwrap();
+            std::fs::write(release.join("app.d"), stamp).unwrap();
+        }
+        std::fs::write(a.join("release").join("extra"), "").unwrap();
+
+        let diffs = differences(
+            &artifact_hashes(&a, "release"),
+            &artifact_hashes(&b, "release"),
+        );
+        let names: Vec<&str> = diffs.iter().map(|d| d.0.as_str()).collect();
+        assert_eq!(names, vec!["app", "extra"]);
+        assert_eq!(diffs[1].2, None);
+
+        let text = render(&[
+            ("core".to_string(), Verdict::Reproducible(2)),
+            ("app".to_string(), Verdict::Differs(diffs)),
+        ]);
+        assert!(text.contains("core: 2 artifacts identical"), "{text}");
+        assert!(text.contains("app: 2 artifacts differ"), "{text}");
+        assert!(
+            text.contains("    extra: cbf29ce484222325 vs missing"),
+            "{text}"
+        );
+        assert!(text.contains("1/2 repos build reproducibly"), "{text}");
+    }
+}
@@ -0,0 +1,476 @@
+//! In-process execution of planned commands
+//!
+//! Most commands are handed back to meta as an execution plan. Commands that
+//! need the results (reports, summaries) run the plan here instead.
+
+use crate::config::ConcurrencyConfig;
+use crate::progress::Progress;
+use crate::PlannedCommand;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Class name that runs a repo with nothing else alongside it
+pub const EXCLUSIVE_CLASS: &str = "exclusive";
+
+/// Result of running one planned command
+#[derive(Debug, Clone)]
+pub struct RunOutcome {
+    pub dir: String,
+    pub cmd: String,
+    pub success: bool,
+    /// Exit code, or `None` if the process could not be started or was killed
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration: Duration,
+}
+
+impl RunOutcome {
+    /// stdout followed by stderr, without cargo's JSON messages
+    pub fn output(&self) -> String {
+        let mut out: String = self
+            .stdout
+            .split_inclusive('\n')
+            .filter(|line| !crate::build_cache::is_message(line))
+            .collect();
+        if !out.is_empty() && !out.ends_with('\n') && !self.stderr.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&self.stderr);
+        out
+    }
+}
+
+/// Outcomes built by hand for tests
+#[cfg(test)]
+impl RunOutcome {
+    /// A finished `cargo build` in `dir` exiting 0 or 101, with no output
+    pub(crate) fn fixture(dir: &str, success: bool) -> Self {
+        RunOutcome {
+            dir: dir.to_string(),
+            cmd: "cargo build".to_string(),
+            success,
+            exit_code: Some(if success { 0 } else { 101 }),
+            stdout: String::new(),
+            stderr: String::new(),
+            duration: Duration::from_millis(1),
+        }
+    }
+
+    pub(crate) fn with_cmd(mut self, cmd: &str) -> Self {
+        self.cmd = cmd.to_string();
+        self
+    }
+
+    pub(crate) fn with_stdout(mut self, stdout: &str) -> Self {
+        self.stdout = stdout.to_string();
+        self
+    }
+
+    pub(crate) fn with_stderr(mut self, stderr: &str) -> Self {
+        self.stderr = stderr.to_string();
+        self
+    }
+
+    pub(crate) fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+}
+
+/// Shell invocation for a command line
+pub(crate) fn shell(cmd: &str) -> Command {
+    if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.args(["/C", cmd]);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.args(["-c", cmd]);
+        c
+    }
+}
+
+/// Run a single planned command in its project directory under `cwd`
+pub fn run_command(cwd: &Path, planned: &PlannedCommand) -> RunOutcome {
+    let started = Instant::now();
+    let mut command = shell(&planned.cmd);
+    command.current_dir(crate::project_path(cwd, &planned.dir));
+    if let Some(env) = &planned.env {
+        command.envs(env);
+    }
+    let (success, exit_code, stdout, stderr) = match command.output() {
+        Ok(out) => (
+            out.status.success(),
+            out.status.code(),
+            String::from_utf8_lossy(&out.stdout).into_owned(),
+            String::from_utf8_lossy(&out.stderr).into_owned(),
+        ),
+        Err(e) => (false, None, String::new(), format!("failed to start: {e}")),
+    };
+    RunOutcome {
+        dir: planned.dir.clone(),
+        cmd: planned.cmd.clone(),
+        success,
+        exit_code,
+        stdout,
+        stderr,
+        duration: started.elapsed(),
+    }
+}
+
+/// [`run_command`], reporting to `progress`
+fn run_tracked(cwd: &Path, planned: &PlannedCommand, progress: &Progress) -> RunOutcome {
+    progress.started(&planned.dir);
+    let outcome = run_command(cwd, planned);
+    progress.finished(&planned.dir);
+    outcome
+}
+
+/// Run all commands, in parallel when requested, returning outcomes in plan order
+pub fn run_all(cwd: &Path, commands: &[PlannedCommand], parallel: bool) -> Vec<RunOutcome> {
+    let progress = Progress::from_env(commands.len());
+    if !parallel || commands.len() < 2 {
+        return commands
+            .iter()
+            .map(|c| run_tracked(cwd, c, &progress))
+            .collect();
+    }
+
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(commands.len());
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<RunOutcome>>> = Mutex::new(vec![None; commands.len()]);
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some(planned) = commands.get(i) else {
+                    break;
+                };
+                let outcome = run_tracked(cwd, planned, &progress);
+                results.lock().unwrap()[i] = Some(outcome);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|o| o.expect("every command produces an outcome"))
+        .collect()
+}
+
+/// Concurrency constraint of one planned command
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Concurrency {
+    #[default]
+    Unlimited,
+    /// At most `max` commands of the class run at once
+    Class { name: String, max: usize },
+    /// Runs alone
+    Exclusive,
+}
+
+/// Whether any repo is assigned a concurrency class
+pub fn has_classes(config: &ConcurrencyConfig) -> bool {
+    !config.repos.is_empty()
+}
+
+/// Resolve each command's class from `[concurrency]`
+pub fn concurrency_for(
+    commands: &[PlannedCommand],
+    config: &ConcurrencyConfig,
+) -> Result<Vec<Concurrency>, String> {
+    commands
+        .iter()
+        .map(|c| match config.repos.get(&c.dir) {
+            None => Ok(Concurrency::Unlimited),
+            Some(class) if class == EXCLUSIVE_CLASS => Ok(Concurrency::Exclusive),
+            Some(class) => match config.classes.get(class) {
+                Some(&max) => Ok(Concurrency::Class {
+                    name: class.clone(),
+                    max: max.max(1),
+                }),
+                None => Err(format!(
+                    "{}: unknown concurrency class '{class}' (define it in [concurrency.classes])",
+                    c.dir
+                )),
+            },
+        })
+        .collect()
+}
+
+/// Scheduler state shared by the workers of [`run_all_limited`]
+#[derive(Default)]
+struct Slots {
+    started: Vec<bool>,
+    running: usize,
+    running_by_class: HashMap<String, usize>,
+    exclusive_running: bool,
+}
+
+impl Slots {
+    /// Next command that may start now, in plan order
+    ///
+    /// A waiting exclusive command blocks everything after it, so it is not
+    /// starved by a stream of unconstrained ones.
+    fn next(&self, limits: &[Concurrency]) -> Option<usize> {
+        if self.exclusive_running {
+            return None;
+        }
+        for (i, limit) in limits.iter().enumerate() {
+            if self.started[i] {
+                continue;
+            }
+            match limit {
+                Concurrency::Unlimited => return Some(i),
+                Concurrency::Class { name, max } => {
+                    if self.running_by_class.get(name).copied().unwrap_or(0) < *max {
+                        return Some(i);
+                    }
+                }
+                Concurrency::Exclusive => {
+                    return (self.running == 0).then_some(i);
+                }
+            }
+        }
+        None
+    }
+
+    fn start(&mut self, i: usize, limit: &Concurrency) {
+        self.started[i] = true;
+        self.running += 1;
+        match limit {
+            Concurrency::Unlimited => {}
+            Concurrency::Class { name, .. } => {
+                *self.running_by_class.entry(name.clone()).or_default() += 1;
+            }
+            Concurrency::Exclusive => self.exclusive_running = true,
+        }
+    }
+
+    fn finish(&mut self, limit: &Concurrency) {
+        self.running -= 1;
+        match limit {
+            Concurrency::Unlimited => {}
+            Concurrency::Class { name, .. } => {
+                if let Some(n) = self.running_by_class.get_mut(name) {
+                    *n -= 1;
+                }
+            }
+            Concurrency::Exclusive => self.exclusive_running = false,
+        }
+    }
+}
+
+/// Like [`run_all`], but never exceeds the per-class limits in `limits`
+/// (one entry per command)
+pub fn run_all_limited(
+    cwd: &Path,
+    commands: &[PlannedCommand],
+    parallel: bool,
+    limits: &[Concurrency],
+) -> Vec<RunOutcome> {
+    if !parallel || commands.len() < 2 || limits.iter().all(|l| *l == Concurrency::Unlimited) {
+        return run_all(cwd, commands, parallel);
+    }
+
+    let workers = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(commands.len());
+    let slots = Mutex::new(Slots {
+        started: vec![false; commands.len()],
+        ..Slots::default()
+    });
+    let changed = Condvar::new();
+    let progress = Progress::from_env(commands.len());
+    let results: Mutex<Vec<Option<RunOutcome>>> = Mutex::new(vec![None; commands.len()]);
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let mut state = slots.lock().unwrap();
+                let i = loop {
+                    if state.started.iter().all(|&s| s) {
+                        return;
+                    }
+                    match state.next(limits) {
+                        Some(i) => break i,
+                        None => state = changed.wait(state).unwrap(),
+                    }
+                };
+                state.start(i, &limits[i]);
+                drop(state);
+
+                let outcome = run_tracked(cwd, &commands[i], &progress);
+                results.lock().unwrap()[i] = Some(outcome);
+
+                slots.lock().unwrap().finish(&limits[i]);
+                changed.notify_all();
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|o| o.expect("every command produces an outcome"))
+        .collect()
+}
+
+/// Run `levels` of repos one after another, each level in parallel
+///
+/// Commands of repos missing from `levels` run last. Outcomes are in plan
+/// order.
+pub fn run_levels(
+    cwd: &Path,
+    commands: &[PlannedCommand],
+    limits: &[Concurrency],
+    levels: &[Vec<String>],
+) -> Vec<RunOutcome> {
+    let mut groups: Vec<Vec<usize>> = levels
+        .iter()
+        .map(|level| {
+            (0..commands.len())
+                .filter(|&i| level.contains(&commands[i].dir))
+                .collect()
+        })
+        .collect();
+    groups.push(
+        (0..commands.len())
+            .filter(|&i| !levels.iter().any(|l| l.contains(&commands[i].dir)))
+            .collect(),
+    );
+
+    let mut results: Vec<Option<RunOutcome>> = vec![None; commands.len()];
+    for group in groups.iter().filter(|g| !g.is_empty()) {
+        let level: Vec<PlannedCommand> = group.iter().map(|&i| commands[i].clone()).collect();
+        let level_limits: Vec<Concurrency> = group.iter().map(|&i| limits[i].clone()).collect();
+        let outcomes = run_all_limited(cwd, &level, true, &level_limits);
+        for (&i, outcome) in group.iter().zip(outcomes) {
+            results[i] = Some(outcome);
+        }
+    }
+    results
+        .into_iter()
+        .map(|o| o.expect("every command produces an outcome"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn planned(cmd: &str) -> PlannedCommand {
+        PlannedCommand {
+            dir: ".".to_string(),
+            cmd: cmd.to_string(),
+            env: None,
+        }
+    }
+
+    #[test]
+    fn test_run_captures_output_and_status() {
+        let outcome = run_command(Path::new("."), &planned("cargo --version"));
+        assert!(outcome.success);
+        assert!(outcome.stdout.contains("cargo"));
+
+        let outcome = run_command(
+            Path::new("."),
+            &planned("cargo locate-project --manifest-path missing/Cargo.toml"),
+        );
+        assert!(!outcome.success);
+        assert!(!outcome.stderr.is_empty());
+    }
+
+    #[test]
+    fn test_parallel_run_preserves_order() {
+        let commands = vec![
+            planned("cargo --version"),
+            planned("cargo locate-project --manifest-path missing/Cargo.toml"),
+            planned("cargo --version"),
+        ];
+        let outcomes = run_all(Path::new("."), &commands, true);
+        let statuses: Vec<bool> = outcomes.iter().map(|o| o.success).collect();
+        assert_eq!(statuses, vec![true, false, true]);
+    }
+
+    #[test]
+    fn test_slots_respect_classes() {
+        let heavy = Concurrency::Class {
+            name: "heavy".to_string(),
+            max: 1,
+        };
+        let limits = vec![
+            heavy.clone(),
+            heavy.clone(),
+            Concurrency::Unlimited,
+            Concurrency::Exclusive,
+            Concurrency::Unlimited,
+        ];
+        let mut slots = Slots {
+            started: vec![false; limits.len()],
+            ..Slots::default()
+        };
+        slots.start(0, &limits[0]);
+        // The second heavy repo waits; the unconstrained one may start
+        assert_eq!(slots.next(&limits), Some(2));
+        slots.start(2, &limits[2]);
+        // The exclusive repo waits for everything and blocks what follows it
+        assert_eq!(slots.next(&limits), None);
+        slots.finish(&limits[0]);
+        assert_eq!(slots.next(&limits), Some(1));
+    }
+
+    #[test]
+    fn test_limited_run_completes_in_order() {
+        let commands = vec![
+            planned("cargo --version"),
+            planned("cargo --version"),
+            planned("cargo locate-project --manifest-path missing/Cargo.toml"),
+        ];
+        let limits = vec![
+            Concurrency::Exclusive,
+            Concurrency::Class {
+                name: "heavy".to_string(),
+                max: 1,
+            },
+            Concurrency::Unlimited,
+        ];
+        let outcomes = run_all_limited(Path::new("."), &commands, true, &limits);
+        let statuses: Vec<bool> = outcomes.iter().map(|o| o.success).collect();
+        assert_eq!(statuses, vec![true, true, false]);
+    }
+
+    #[test]
+    fn test_levels_run_dependencies_first() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for dir in ["app", "base"] {
+            std::fs::create_dir(temp_dir.path().join(dir)).unwrap();
+        }
+        let commands = vec![
+            PlannedCommand {
+                dir: "app".to_string(),
+                cmd: "test -f ../base/done".to_string(),
+                env: None,
+            },
+            PlannedCommand {
+                dir: "base".to_string(),
+                cmd: "touch done".to_string(),
+                env: None,
+            },
+        ];
+        let levels = vec![vec!["base".to_string()], vec!["app".to_string()]];
+        let limits = vec![Concurrency::Unlimited; 2];
+        let outcomes = run_levels(temp_dir.path(), &commands, &limits, &levels);
+        assert_eq!(outcomes[0].dir, "app");
+        assert!(outcomes.iter().all(|o| o.success));
+    }
+}
@@ -0,0 +1,314 @@
+//! Stored run results (`--save-run <file>`) and `meta cargo compare-runs`
+//!
+//! A run result records each repo's status, duration, warning count and,
+//! when the command printed an llvm-cov JSON export, its line coverage.
+//! Comparing two results lists per-repo regressions and improvements, e.g.
+//! a PR run against a main-branch artifact.
+
+use crate::coverage;
+use crate::diagnostics::{self, Level};
+use crate::runner::RunOutcome;
+use crate::{args, CommandResult};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+
+/// Duration changes below this many milliseconds are noise
+const MIN_DURATION_DELTA_MS: u64 = 1000;
+/// Relative duration change that counts as slower or faster
+const DURATION_RATIO: f64 = 0.2;
+
+/// One repo of a stored run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepoResult {
+    pub repo: String,
+    pub success: bool,
+    pub duration_ms: u64,
+    pub warnings: usize,
+    /// Line coverage percentage, when the run measured it
+    #[serde(default)]
+    pub coverage: Option<f64>,
+}
+
+/// A stored run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunResult {
+    pub repos: Vec<RepoResult>,
+}
+
+impl RunResult {
+    pub fn from_outcomes(outcomes: &[RunOutcome]) -> Self {
+        let repos = outcomes
+            .iter()
+            .map(|o| {
+                let diags = diagnostics::parse(&o.output());
+                RepoResult {
+                    repo: o.dir.clone(),
+                    success: o.success,
+                    duration_ms: o.duration.as_millis() as u64,
+                    warnings: diags.iter().filter(|d| d.level == Level::Warning).count(),
+                    coverage: coverage::parse_llvm_cov(&o.stdout)
+                        .ok()
+                        .map(|c| c.percent()),
+                }
+            })
+            .collect();
+        RunResult { repos }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("invalid {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+/// A difference between two runs of one repo
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub repo: String,
+    /// What changed, e.g. `status`, `duration`, `warnings` or `coverage`
+    pub metric: &'static str,
+    pub before: String,
+    pub after: String,
+    pub regression: bool,
+}
+
+/// Differences from `a` (the baseline) to `b`
+pub fn compare(a: &RunResult, b: &RunResult) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let mut change = |repo: &str, metric, before: String, after: String, regression| {
+        changes.push(Change {
+            repo: repo.to_string(),
+            metric,
+            before,
+            after,
+            regression,
+        })
+    };
+    let status = |ok: bool| if ok { "passed" } else { "failed" }.to_string();
+    for new in &b.repos {
+        let Some(old) = a.repos.iter().find(|r| r.repo == new.repo) else {
+            change(
+                &new.repo,
+                "status",
+                "absent".to_string(),
+                status(new.success),
+                !new.success,
+            );
+            continue;
+        };
+        if old.success != new.success {
+            change(
+                &new.repo,
+                "status",
+                status(old.success),
+                status(new.success),
+                !new.success,
+            );
+        }
+        let delta = new.duration_ms.abs_diff(old.duration_ms);
+        if delta >= MIN_DURATION_DELTA_MS && delta as f64 > old.duration_ms as f64 * DURATION_RATIO
+        {
+            change(
+                &new.repo,
+                "duration",
+                format!("{:.1}s", old.duration_ms as f64 / 1000.0),
+                format!("{:.1}s", new.duration_ms as f64 / 1000.0),
+                new.duration_ms > old.duration_ms,
+            );
+        }
+        if old.warnings != new.warnings {
+            change(
+                &new.repo,
+                "warnings",
+                old.warnings.to_string(),
+                new.warnings.to_string(),
+                new.warnings > old.warnings,
+            );
+        }
+        if let (Some(before), Some(after)) = (old.coverage, new.coverage) {
+            if (after - before).abs() >= 0.01 {
+                change(
+                    &new.repo,
+                    "coverage",
+                    format!("{before:.2}%"),
+                    format!("{after:.2}%"),
+                    after < before,
+                );
+            }
+        }
+    }
+    for old in a
+        .repos
+        .iter()
+        .filter(|o| !b.repos.iter().any(|n| n.repo == o.repo))
+    {
+        change(
+            &old.repo,
+            "status",
+            status(old.success),
+            "absent".to_string(),
+            false,
+        );
+    }
+    changes
+}
+
+fn render_text(changes: &[Change]) -> String {
+    if changes.is_empty() {
+        return "No differences between the runs\n".to_string();
+    }
+    let mut out = String::new();
+    for (title, regression) in [("Regressions", true), ("Improvements", false)] {
+        let group: Vec<&Change> = changes
+            .iter()
+            .filter(|c| c.regression == regression)
+            .collect();
+        if group.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("{title}:\n"));
+        for c in group {
+            out.push_str(&format!(
+                "  {}: {} {} -> {}\n",
+                c.repo, c.metric, c.before, c.after
+            ));
+        }
+    }
+    out
+}
+
+fn render_json(changes: &[Change]) -> String {
+    let entries: Vec<serde_json::Value> = changes
+        .iter()
+        .map(|c| {
+            json!({
+                "repo": c.repo,
+                "metric": c.metric,
+                "before": c.before,
+                "after": c.after,
+                "regression": c.regression,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&json!({
+        "regressions": changes.iter().filter(|c| c.regression).count(),
+        "changes": entries,
+    }))
+    .unwrap_or_default()
+}
+
+/// Handle `meta cargo compare-runs <a.json> <b.json> [--format text|json]`
+///
+/// Fails when `b` regressed against `a`.
+pub(crate) fn execute(args: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let format = args::take_value(&mut args, "--format").unwrap_or_else(|| "text".to_string());
+    if format != "text" && format != "json" {
+        return CommandResult::Error(format!(
+            "unsupported format '{format}' (expected text or json)"
+        ));
+    }
+    let [a, b] = args.as_slice() else {
+        return CommandResult::Error(
+            "usage: meta cargo compare-runs <a.json> <b.json> [--format json]".to_string(),
+        );
+    };
+    let runs = RunResult::load(&cwd.join(a)).and_then(|a| Ok((a, RunResult::load(&cwd.join(b))?)));
+    let (a, b) = match runs {
+        Ok(r) => r,
+        Err(e) => return CommandResult::Error(format!("{e:#}")),
+    };
+    let changes = compare(&a, &b);
+    let out = if format == "json" {
+        render_json(&changes)
+    } else {
+        render_text(&changes)
+    };
+    if changes.iter().any(|c| c.regression) {
+        CommandResult::Error(out)
+    } else {
+        CommandResult::Message(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn repo(name: &str, success: bool, duration_ms: u64, warnings: usize) -> RepoResult {
+        RepoResult {
+            repo: name.to_string(),
+            success,
+            duration_ms,
+            warnings,
+            coverage: None,
+        }
+    }
+
+    #[test]
+    fn test_compare_classifies_changes() {
+        let mut a = RunResult {
+            repos: vec![repo("core", true, 10_000, 2), repo("app", false, 4_000, 0)],
+        };
+        let mut b = RunResult {
+            repos: vec![repo("core", false, 10_500, 3), repo("app", true, 1_000, 0)],
+        };
+        a.repos[0].coverage = Some(80.0);
+        b.repos[0].coverage = Some(78.5);
+        let changes = compare(&a, &b);
+        let summary: Vec<(&str, &str, bool)> = changes
+            .iter()
+            .map(|c| (c.repo.as_str(), c.metric, c.regression))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("core", "status", true),
+                ("core", "warnings", true),
+                ("core", "coverage", true),
+                ("app", "status", false),
+                ("app", "duration", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_execute_fails_on_regressions() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = RunResult {
+            repos: vec![repo("core", true, 1_000, 0)],
+        };
+        let b = RunResult {
+            repos: vec![repo("core", false, 1_000, 0)],
+        };
+        a.save(&temp_dir.path().join("a.json")).unwrap();
+        b.save(&temp_dir.path().join("b.json")).unwrap();
+        let args = vec![
+            "a.json".to_string(),
+            "b.json".to_string(),
+            "--format".to_string(),
+            "json".to_string(),
+        ];
+        match execute(&args, temp_dir.path()) {
+            CommandResult::Error(out) => {
+                let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+                assert_eq!(value["regressions"], 1);
+                assert_eq!(value["changes"][0]["after"], "failed");
+            }
+            _ => panic!("Expected Error result"),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! `meta cargo rustc`: rustc flag passthrough for each repo's primary package
+//!
+//! `cargo rustc` compiles a single package and, when extra rustc flags are
+//! given, a single target. Each repo's primary package is selected with `-p`
+//! (the package at the repo root, else its first member) and its library is
+//! selected with `--lib` unless the user picked a package or target.
+
+use crate::metadata::{self, Package};
+use crate::{project_path, CommandResult, PlannedCommand};
+use std::path::Path;
+
+/// Flags that already select the package
+const PACKAGE_FLAGS: &[&str] = &["-p", "--package"];
+
+/// Flags that already select the target
+const TARGET_FLAGS: &[&str] = &[
+    "--lib",
+    "--bin",
+    "--bins",
+    "--example",
+    "--examples",
+    "--test",
+    "--tests",
+    "--bench",
+    "--benches",
+    "--all-targets",
+];
+
+/// Whether any cargo argument (before `--`) is, or starts with, one of `flags`
+fn has_flag(args: &[String], flags: &[&str]) -> bool {
+    args.iter().take_while(|a| a.as_str() != "--").any(|a| {
+        flags
+            .iter()
+            .any(|f| a == f || a.starts_with(&format!("{f}=")))
+    })
+}
+
+/// The package at the repo root, or else the first workspace member
+fn primary_package<'a>(repo_dir: &Path, packages: &'a [Package]) -> Option<&'a Package> {
+    let manifest = repo_dir.join("Cargo.toml");
+    let canon = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+    packages
+        .iter()
+        .find(|p| canon(&p.manifest_path) == canon(&manifest))
+        .or_else(|| packages.first())
+}
+
+/// Command line for one repo
+fn command_for(cargo: &str, package: Option<&Package>, args: &[String]) -> String {
+    let mut cmd = format!("{cargo} rustc");
+    if let Some(pkg) = package {
+        if !has_flag(args, PACKAGE_FLAGS) {
+            cmd.push_str(&format!(" -p {}", pkg.name));
+            if !has_flag(args, TARGET_FLAGS) && pkg.has_target("lib") {
+                cmd.push_str(" --lib");
+            }
+        }
+    }
+    for arg in args {
+        cmd.push(' ');
+        cmd.push_str(arg);
+    }
+    cmd
+}
+
+/// Handle `meta cargo rustc [cargo args] [-- rustc flags]`
+pub(crate) fn execute(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+) -> Result<Vec<PlannedCommand>, CommandResult> {
+    repos
+        .iter()
+        .map(|repo| {
+            let dir = project_path(cwd, repo);
+            let packages = match metadata::load_packages(&dir) {
+                Ok(packages) => packages,
+                Err(e) => {
+                    return Ok(crate::errored_command(
+                        repo,
+                        &format!("cargo metadata failed: {e:#}"),
+                    ))
+                }
+            };
+            Ok(PlannedCommand {
+                dir: repo.clone(),
+                cmd: command_for(cargo, primary_package(&dir, &packages), args),
+                env: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::strings;
+
+    #[test]
+    fn test_selects_primary_package_and_lib() {
+        let pkg = Package::fixture("core")
+            .with_target("lib", "")
+            .with_target("bin", "");
+        assert_eq!(
+            command_for("cargo", Some(&pkg), &strings(&["--", "--emit", "asm"])),
+            "cargo rustc -p core --lib -- --emit asm"
+        );
+        let bin = Package::fixture("tool").with_target("bin", "");
+        assert_eq!(
+            command_for("cargo", Some(&bin), &strings(&["--print", "cfg"])),
+            "cargo rustc -p tool --print cfg"
+        );
+    }
+
+    #[test]
+    fn test_user_selection_is_kept() {
+        let pkg = Package::fixture("core")
+            .with_target("lib", "")
+            .with_target("bin", "");
+        assert_eq!(
+            command_for(
+                "cargo",
+                Some(&pkg),
+                &strings(&["--bin", "core", "--", "--lib"])
+            ),
+            "cargo rustc -p core --bin core -- --lib"
+        );
+        assert_eq!(
+            command_for("cargo", Some(&pkg), &strings(&["-p", "other"])),
+            "cargo rustc -p other"
+        );
+    }
+
+    #[test]
+    fn test_primary_package_prefers_repo_root() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = Package::fixture("root")
+            .with_target("lib", "")
+            .with_root(temp_dir.path());
+        let packages = vec![Package::fixture("member").with_target("lib", ""), root];
+        assert_eq!(
+            primary_package(temp_dir.path(), &packages).map(|p| p.name.as_str()),
+            Some("root")
+        );
+    }
+}
@@ -0,0 +1,398 @@
+//! `meta cargo sbom`: one software bill of materials for the meta workspace
+//!
+//! The resolved graph of every repo (`cargo metadata`) is merged into a
+//! single CycloneDX 1.5 or SPDX 2.3 JSON document. A crate locked at the same
+//! version from the same source in several repos appears once, identified by
+//! its package URL, with the dependencies of all repos combined. The
+//! document's timestamp honours `SOURCE_DATE_EPOCH` for reproducible output.
+
+use crate::metadata::{self, Component};
+use crate::quarantine::civil_date;
+use crate::{args, project_path, CommandResult};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// crates.io, as a git or sparse index
+const CRATES_IO: &[&str] = &[
+    "registry+https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
+
+/// A crate of the merged graph
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Entry {
+    name: String,
+    version: String,
+    license: Option<String>,
+    /// Repos declaring it as a workspace member
+    member_of: BTreeSet<String>,
+    /// Package URLs of its dependencies
+    depends_on: BTreeSet<String>,
+}
+
+/// Package URL of a crate
+fn purl(name: &str, version: &str, source: Option<&str>) -> String {
+    let base = format!("pkg:cargo/{name}@{version}");
+    match source {
+        None => base,
+        Some(s) if CRATES_IO.contains(&s) => base,
+        Some(s) => {
+            let (kind, url) = s.split_once('+').unwrap_or(("", s));
+            let url = url.split(['?', '#']).next().unwrap_or(url);
+            match kind {
+                "git" => format!("{base}?vcs_url=git%2B{url}"),
+                _ => format!("{base}?repository_url={url}"),
+            }
+        }
+    }
+}
+
+/// Merge the components of each repo, keyed by package URL
+fn merge(repos: &[(String, Vec<Component>)]) -> BTreeMap<String, Entry> {
+    let mut merged: BTreeMap<String, Entry> = BTreeMap::new();
+    for (repo, components) in repos {
+        let purls: BTreeMap<&str, String> = components
+            .iter()
+            .map(|c| {
+                (
+                    c.id.as_str(),
+                    purl(&c.name, &c.version, c.source.as_deref()),
+                )
+            })
+            .collect();
+        for c in components {
+            let entry = merged.entry(purls[c.id.as_str()].clone()).or_default();
+            entry.name.clone_from(&c.name);
+            entry.version.clone_from(&c.version);
+            if entry.license.is_none() {
+                entry.license.clone_from(&c.license);
+            }
+            if c.member {
+                entry.member_of.insert(repo.clone());
+            }
+            entry.depends_on.extend(
+                c.dependencies
+                    .iter()
+                    .filter_map(|d| purls.get(d.as_str()).cloned()),
+            );
+        }
+    }
+    merged
+}
+
+/// `YYYY-MM-DDTHH:MM:SSZ` of `SOURCE_DATE_EPOCH`, or of now
+fn timestamp() -> String {
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+    let time = secs % 86_400;
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        civil_date((secs / 86_400) as i64),
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+fn cyclonedx(
+    product: &str,
+    merged: &BTreeMap<String, Entry>,
+    timestamp: &str,
+) -> serde_json::Value {
+    let root = format!("meta:{product}");
+    let components: Vec<serde_json::Value> = merged
+        .iter()
+        .map(|(purl, e)| {
+            let mut component = serde_json::Map::new();
+            let kind = if e.member_of.is_empty() {
+                "library"
+            } else {
+                "application"
+            };
+            component.insert("type".to_string(), json!(kind));
+            component.insert("bom-ref".to_string(), json!(purl));
+            component.insert("name".to_string(), json!(e.name));
+            component.insert("version".to_string(), json!(e.version));
+            component.insert("purl".to_string(), json!(purl));
+            if let Some(license) = &e.license {
+                component.insert("licenses".to_string(), json!([{ "expression": license }]));
+            }
+            serde_json::Value::Object(component)
+        })
+        .collect();
+    let members: Vec<&String> = merged
+        .iter()
+        .filter(|(_, e)| !e.member_of.is_empty())
+        .map(|(purl, _)| purl)
+        .collect();
+    let mut dependencies = vec![json!({ "ref": root, "dependsOn": members })];
+    dependencies.extend(
+        merged
+            .iter()
+            .map(|(purl, e)| json!({ "ref": purl, "dependsOn": e.depends_on })),
+    );
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "version": 1,
+        "metadata": {
+            "timestamp": timestamp,
+            "tools": {
+                "components": [{
+                    "type": "application",
+                    "name": "meta-rust",
+                    "version": env!("CARGO_PKG_VERSION"),
+                }],
+            },
+            "component": { "type": "application", "bom-ref": root, "name": product },
+        },
+        "components": components,
+        "dependencies": dependencies,
+    })
+}
+
+/// SPDX element id for a package URL
+fn spdx_id(purl: &str) -> String {
+    let id: String = purl
+        .trim_start_matches("pkg:cargo/")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("SPDXRef-Package-{id}")
+}
+
+fn spdx(product: &str, merged: &BTreeMap<String, Entry>, timestamp: &str) -> serde_json::Value {
+    let packages: Vec<serde_json::Value> = merged
+        .iter()
+        .map(|(purl, e)| {
+            json!({
+                "SPDXID": spdx_id(purl),
+                "name": e.name,
+                "versionInfo": e.version,
+                "downloadLocation": "NOASSERTION",
+                "filesAnalyzed": false,
+                "licenseConcluded": "NOASSERTION",
+                "licenseDeclared": e.license.as_deref().unwrap_or("NOASSERTION"),
+                "externalRefs": [{
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": purl,
+                }],
+            })
+        })
+        .collect();
+    let mut relationships = Vec::new();
+    for (purl, e) in merged {
+        if !e.member_of.is_empty() {
+            relationships.push(json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": spdx_id(purl),
+            }));
+        }
+        for dep in &e.depends_on {
+            relationships.push(json!({
+                "spdxElementId": spdx_id(purl),
+                "relationshipType": "DEPENDS_ON",
+                "relatedSpdxElement": spdx_id(dep),
+            }));
+        }
+    }
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": product,
+        "documentNamespace": format!("https://spdx.org/spdxdocs/{product}-{timestamp}"),
+        "creationInfo": {
+            "created": timestamp,
+            "creators": [format!("Tool: meta-rust-{}", env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": packages,
+        "relationships": relationships,
+    })
+}
+
+/// Handle `meta cargo sbom [--format cyclonedx|spdx] [--name <product>]
+/// [--output <file>]`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let format = args::take_value(&mut args, "--format").unwrap_or_else(|| "cyclonedx".to_string());
+    let output = args::take_value(&mut args, "--output");
+    let product = args::take_value(&mut args, "--name").unwrap_or_else(|| {
+        cwd.canonicalize()
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "meta".to_string())
+    });
+    let render = match format.as_str() {
+        "cyclonedx" => cyclonedx,
+        "spdx" => spdx,
+        other => {
+            return CommandResult::Error(format!(
+                "unsupported format '{other}' (expected cyclonedx or spdx)"
+            ))
+        }
+    };
+
+    let mut loaded = Vec::new();
+    let dirs: Vec<_> = repos.iter().map(|r| project_path(cwd, r)).collect();
+    for (repo, result) in repos
+        .iter()
+        .zip(metadata::load_each(&dirs, metadata::load_components))
+    {
+        match result {
+            Ok(components) => loaded.push((repo.clone(), components)),
+            Err(e) => {
+                return CommandResult::Error(format!(
+                    "{repo}: failed to load cargo metadata: {e:#}"
+                ))
+            }
+        }
+    }
+    let merged = merge(&loaded);
+    let text = match serde_json::to_string_pretty(&render(&product, &merged, &timestamp())) {
+        Ok(text) => text,
+        Err(e) => return CommandResult::Error(format!("Failed to serialize SBOM: {e}")),
+    };
+    match output {
+        Some(path) => match std::fs::write(cwd.join(&path), format!("{text}\n")) {
+            Ok(()) => CommandResult::Message(format!(
+                "Wrote {format} SBOM of {} crates from {} repos to {path}",
+                merged.len(),
+                repos.len()
+            )),
+            Err(e) => CommandResult::Error(format!("Failed to write {path}: {e}")),
+        },
+        None => CommandResult::Message(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn component(id: &str, source: Option<&str>, member: bool, deps: &[&str]) -> Component {
+        let (name, version) = id.split_once('@').unwrap();
+        Component {
+            id: id.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+            source: source.map(str::to_string),
+            license: Some("MIT".to_string()),
+            member,
+            dependencies: deps.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    fn repos() -> Vec<(String, Vec<Component>)> {
+        let io = Some(CRATES_IO[1]);
+        vec![
+            (
+                "core".to_string(),
+                vec![
+                    component("core@0.1.0", None, true, &["serde@1.0.200"]),
+                    component("serde@1.0.200", io, false, &[]),
+                ],
+            ),
+            (
+                "app".to_string(),
+                vec![
+                    component("app@0.2.0", None, true, &["serde@1.0.200", "util@0.3.0"]),
+                    component("serde@1.0.200", io, false, &[]),
+                    component(
+                        "util@0.3.0",
+                        Some("git+https://example.com/util?rev=abc#abc"),
+                        false,
+                        &[],
+                    ),
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_purl() {
+        assert_eq!(
+            purl("serde", "1.0.0", Some(CRATES_IO[0])),
+            "pkg:cargo/serde@1.0.0"
+        );
+        assert_eq!(
+            purl(
+                "util",
+                "0.3.0",
+                Some("git+https://example.com/util?rev=abc#abc")
+            ),
+            "pkg:cargo/util@0.3.0?vcs_url=git%2Bhttps://example.com/util"
+        );
+        assert_eq!(
+            purl(
+                "internal",
+                "2.0.0",
+                Some("sparse+https://corp.example/index/")
+            ),
+            "pkg:cargo/internal@2.0.0?repository_url=https://corp.example/index/"
+        );
+    }
+
+    #[test]
+    fn test_duplicates_merge_across_repos() {
+        let merged = merge(&repos());
+        assert_eq!(merged.len(), 4);
+        assert!(merged["pkg:cargo/core@0.1.0"].member_of.contains("core"));
+        assert!(merged["pkg:cargo/serde@1.0.200"].member_of.is_empty());
+        let app = &merged["pkg:cargo/app@0.2.0"];
+        assert!(app.depends_on.contains("pkg:cargo/serde@1.0.200"));
+        assert!(app
+            .depends_on
+            .contains("pkg:cargo/util@0.3.0?vcs_url=git%2Bhttps://example.com/util"));
+
+        let bom = cyclonedx("product", &merged, "2024-01-01T00:00:00Z");
+        assert_eq!(bom["components"].as_array().unwrap().len(), 4);
+        assert_eq!(bom["dependencies"][0]["ref"], "meta:product");
+        assert_eq!(
+            bom["dependencies"][0]["dependsOn"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(bom["components"][0]["licenses"][0]["expression"], "MIT");
+
+        let doc = spdx("product", &merged, "2024-01-01T00:00:00Z");
+        assert_eq!(doc["packages"].as_array().unwrap().len(), 4);
+        let describes = doc["relationships"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|r| r["relationshipType"] == "DESCRIBES")
+            .count();
+        assert_eq!(describes, 2);
+        assert_eq!(
+            spdx_id("pkg:cargo/serde@1.0.200"),
+            "SPDXRef-Package-serde-1.0.200"
+        );
+    }
+
+    #[test]
+    fn test_timestamp_format() {
+        let t = timestamp();
+        assert_eq!(t.len(), "2024-01-01T00:00:00Z".len(), "{t}");
+        assert!(t.ends_with('Z'));
+    }
+}
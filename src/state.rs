@@ -0,0 +1,74 @@
+//! JSON state files under the meta root's `.meta-rust/`
+//!
+//! A state file that doesn't exist yet loads as the empty state. Writes go to
+//! a temporary file next to the target that is then renamed over it, so an
+//! interrupted run or two runs saving at once never leave a truncated file.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::Path;
+
+/// Load the state in `file` (relative to `cwd`), starting empty when there
+/// is none
+pub(crate) fn load<T: DeserializeOwned + Default>(cwd: &Path, file: &str) -> Result<T> {
+    let path = cwd.join(file);
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("invalid {}", path.display()))
+}
+
+/// Write `state` to `file` (relative to `cwd`)
+pub(crate) fn save<T: Serialize>(cwd: &Path, file: &str, state: &T) -> Result<()> {
+    let path = cwd.join(file);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    write_atomic(&path, serde_json::to_string_pretty(state)?.as_bytes())
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Replace `path` with `contents` through a temporary file and a rename
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", std::process::id()));
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_save_and_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let cwd = temp_dir.path();
+        let file = ".meta-rust/state.json";
+        let empty: BTreeMap<String, u32> = load(cwd, file).unwrap();
+        assert!(empty.is_empty());
+
+        let state = BTreeMap::from([("core".to_string(), 3)]);
+        save(cwd, file, &state).unwrap();
+        save(cwd, file, &state).unwrap();
+        assert_eq!(load::<BTreeMap<String, u32>>(cwd, file).unwrap(), state);
+        // Nothing but the state file is left behind
+        let names: Vec<_> = std::fs::read_dir(cwd.join(".meta-rust"))
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name())
+            .collect();
+        assert_eq!(names, vec!["state.json"]);
+
+        std::fs::write(cwd.join(file), "{").unwrap();
+        assert!(load::<BTreeMap<String, u32>>(cwd, file).is_err());
+    }
+}
@@ -0,0 +1,354 @@
+//! Symbolication bundles and `meta cargo symbolicate`
+//!
+//! `meta cargo dist` keeps a bundle per repo and release under
+//! `.meta-rust/symbols/<repo>/<tag>/`: the binaries as built, their separated
+//! debug info and a `build.json` with the commit, toolchain and profile. Cross
+//! builds go to a subdirectory per target triple.
+//!
+//! `meta cargo symbolicate <file|->` reads a backtrace from a stripped
+//! production binary and resolves its frames against the bundles with
+//! `addr2line`. Frames are recognised as `app(+0x1a2b)` (glibc) or
+//! `app+0x1a2b`; bare absolute addresses need `--base`, the load address of
+//! the binary.
+
+use crate::debuginfo;
+use crate::metadata;
+use crate::{args, git, CommandResult};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where bundles live unless `--symbols-dir` says otherwise
+pub const DEFAULT_DIR: &str = ".meta-rust/symbols";
+/// Metadata file of a bundle
+const BUILD_INFO: &str = "build.json";
+/// Resolves addresses; `llvm-addr2line` accepts the same arguments
+const ADDR2LINE: &str = "addr2line";
+
+/// What was built for a release, stored as `build.json`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub repo: String,
+    pub tag: String,
+    pub commit: Option<String>,
+    /// `rustc -V` of the repo's toolchain
+    pub rustc: Option<String>,
+    pub profile: String,
+    /// Seconds since the Unix epoch
+    pub built_at: u64,
+    /// Binaries, relative to the bundle
+    pub binaries: Vec<String>,
+    /// Number of separated debug info files
+    pub symbols: usize,
+}
+
+/// Copy the binaries and symbols of `repo` into `dir` and write `build.json`
+pub(crate) fn write_bundle(
+    repo_dir: &Path,
+    target: &Path,
+    profile: &str,
+    dir: &Path,
+    mut info: BuildInfo,
+) -> anyhow::Result<BuildInfo> {
+    let bins: Vec<String> = metadata::load_packages(repo_dir)?
+        .into_iter()
+        .flat_map(|p| p.targets)
+        .filter(|t| t.kinds.iter().any(|k| k == "bin"))
+        .map(|t| t.name)
+        .collect();
+    for (from, to) in debuginfo::profile_dirs(target, profile, dir) {
+        for bin in &bins {
+            for name in [bin.clone(), format!("{bin}.exe")] {
+                if !from.join(&name).is_file() {
+                    continue;
+                }
+                std::fs::create_dir_all(&to)
+                    .with_context(|| format!("failed to create {}", to.display()))?;
+                debuginfo::copy_recursive(&from.join(&name), &to.join(&name))?;
+                let rel = to.join(&name);
+                let rel = rel.strip_prefix(dir).unwrap_or(&rel);
+                info.binaries.push(rel.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    info.symbols = debuginfo::collect_symbols(target, profile, dir)?;
+    info.commit = git::run(repo_dir, &["rev-parse", "HEAD"])
+        .ok()
+        .map(|s| s.trim().to_string());
+    info.rustc = Command::new("rustc")
+        .arg("-V")
+        .current_dir(repo_dir)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+    info.profile = profile.to_string();
+    info.built_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    std::fs::write(dir.join(BUILD_INFO), serde_json::to_string_pretty(&info)?)
+        .with_context(|| format!("failed to write {}", dir.join(BUILD_INFO).display()))?;
+    Ok(info)
+}
+
+/// Every bundle under `root`, as `(bundle dir, build info)`
+pub fn find_bundles(root: &Path) -> Vec<(PathBuf, BuildInfo)> {
+    let mut bundles = Vec::new();
+    for repo in std::fs::read_dir(root).into_iter().flatten().flatten() {
+        for release in std::fs::read_dir(repo.path())
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            let dir = release.path();
+            let Ok(text) = std::fs::read_to_string(dir.join(BUILD_INFO)) else {
+                continue;
+            };
+            if let Ok(info) = serde_json::from_str::<BuildInfo>(&text) {
+                bundles.push((dir, info));
+            }
+        }
+    }
+    bundles.sort_by_key(|b| std::cmp::Reverse(b.1.built_at));
+    bundles
+}
+
+fn parse_hex(text: &str) -> Option<u64> {
+    let digits: String = text
+        .strip_prefix("0x")?
+        .chars()
+        .take_while(|c| c.is_ascii_hexdigit())
+        .collect();
+    u64::from_str_radix(&digits, 16).ok()
+}
+
+/// File name of the binary a frame points into, from `path/app`
+fn binary_name(path: &str) -> Option<String> {
+    let name = path.rsplit(['/', '\\']).next()?;
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Binary (when named) and offset of a backtrace frame
+pub fn parse_frame(line: &str, base: Option<u64>) -> Option<(Option<String>, u64)> {
+    if let Some(at) = line.find("(+0x") {
+        let offset = parse_hex(&line[at + 2..])?;
+        let path = line[..at].split_whitespace().last()?;
+        return Some((binary_name(path), offset));
+    }
+    for token in line.split_whitespace() {
+        if let Some((path, offset)) = token.split_once("+0x") {
+            if let Some(offset) = parse_hex(&format!("0x{offset}")) {
+                if !path.is_empty() {
+                    return Some((binary_name(path), offset));
+                }
+            }
+        }
+    }
+    let base = base?;
+    let addr = line.split_whitespace().find_map(parse_hex)?;
+    Some((None, addr.checked_sub(base)?))
+}
+
+/// Binary in `bundles` a frame resolves against: the newest one with its name
+///
+/// Frames that name no binary only resolve when the newest bundle has a
+/// single one.
+fn pick_binary(
+    bundles: &[(PathBuf, BuildInfo)],
+    name: Option<&str>,
+    fallback: Option<&str>,
+) -> Option<PathBuf> {
+    let Some(name) = name.or(fallback) else {
+        let (dir, info) = bundles.first()?;
+        return match info.binaries.as_slice() {
+            [bin] => Some(dir.join(bin)),
+            _ => None,
+        };
+    };
+    bundles.iter().find_map(|(dir, info)| {
+        info.binaries
+            .iter()
+            .find(|b| {
+                let file = b.rsplit('/').next().unwrap_or(b);
+                file == name || file.strip_suffix(".exe") == Some(name)
+            })
+            .map(|b| dir.join(b))
+    })
+}
+
+/// `text` with the resolved location below every frame found in `resolved`
+pub fn annotate(
+    text: &str,
+    frames: &BTreeMap<usize, (PathBuf, u64)>,
+    resolved: &BTreeMap<(PathBuf, u64), String>,
+) -> String {
+    let mut out = String::new();
+    for (i, line) in text.lines().enumerate() {
+        out.push_str(line);
+        out.push('\n');
+        if let Some(location) = frames.get(&i).and_then(|f| resolved.get(f)) {
+            out.push_str(&format!("      -> {location}\n"));
+        }
+    }
+    out
+}
+
+/// `addr2line` for `offsets` in `binary`, one `function at file:line` each
+fn addr2line(binary: &Path, offsets: &[u64]) -> anyhow::Result<Vec<String>> {
+    let output = Command::new(ADDR2LINE)
+        .args(["-f", "-C", "-p", "-e"])
+        .arg(binary)
+        .args(offsets.iter().map(|o| format!("{o:#x}")))
+        .output()
+        .with_context(|| format!("failed to run {ADDR2LINE} (install binutils or llvm)"))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{ADDR2LINE} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+fn symbolicate(args: &[String], cwd: &Path) -> anyhow::Result<String> {
+    let mut args = args.to_vec();
+    let root = args::take_value(&mut args, "--symbols-dir")
+        .map(|d| cwd.join(d))
+        .unwrap_or_else(|| cwd.join(DEFAULT_DIR));
+    let repo = args::take_value(&mut args, "--repo");
+    let tag = args::take_value(&mut args, "--tag");
+    let binary = args::take_value(&mut args, "--binary");
+    let base = match args::take_value(&mut args, "--base") {
+        Some(b) => Some(parse_hex(&b).with_context(|| format!("invalid --base '{b}'"))?),
+        None => None,
+    };
+    let Some(input) = args.first() else {
+        anyhow::bail!("usage: meta cargo symbolicate <backtrace file|-> [--repo <repo>] [--tag <tag>] [--binary <name>] [--base <0xaddr>]");
+    };
+    let mut text = String::new();
+    if input == "-" {
+        std::io::stdin().read_to_string(&mut text)?;
+    } else {
+        text = std::fs::read_to_string(cwd.join(input))
+            .with_context(|| format!("failed to read {input}"))?;
+    }
+
+    let bundles: Vec<(PathBuf, BuildInfo)> = find_bundles(&root)
+        .into_iter()
+        .filter(|(_, info)| repo.as_ref().is_none_or(|r| &info.repo == r))
+        .filter(|(_, info)| tag.as_ref().is_none_or(|t| &info.tag == t))
+        .collect();
+    if bundles.is_empty() {
+        anyhow::bail!(
+            "no symbol bundles in {} (they are written by `meta cargo dist`)",
+            root.display()
+        );
+    }
+    let mut frames = BTreeMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let Some((name, offset)) = parse_frame(line, base) else {
+            continue;
+        };
+        if let Some(bin) = pick_binary(&bundles, name.as_deref(), binary.as_deref()) {
+            frames.insert(i, (bin, offset));
+        }
+    }
+    let mut by_binary: BTreeMap<&PathBuf, Vec<u64>> = BTreeMap::new();
+    for (bin, offset) in frames.values() {
+        by_binary.entry(bin).or_default().push(*offset);
+    }
+    let mut resolved = BTreeMap::new();
+    for (bin, offsets) in by_binary {
+        for (offset, location) in offsets.iter().zip(addr2line(bin, &offsets)?) {
+            if !location.starts_with("??") {
+                resolved.insert((bin.clone(), *offset), location);
+            }
+        }
+    }
+    Ok(annotate(&text, &frames, &resolved))
+}
+
+/// Handle `meta cargo symbolicate <backtrace file|-> [options]`
+pub(crate) fn execute(args: &[String], cwd: &Path) -> CommandResult {
+    match symbolicate(args, cwd) {
+        Ok(text) => CommandResult::Message(text),
+        Err(e) => CommandResult::Error(format!("{e:#}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_frame_formats() {
+        assert_eq!(
+            parse_frame("/usr/bin/app(+0x1a2b) [0x55d4c3a1b2c3]", None),
+            Some((Some("app".to_string()), 0x1a2b))
+        );
+        assert_eq!(
+            parse_frame("   3: app+0x42 - <unknown>", None),
+            Some((Some("app".to_string()), 0x42))
+        );
+        assert_eq!(
+            parse_frame("   3:     0x5600000010ff - <unknown>", Some(0x560000000000)),
+            Some((None, 0x10ff))
+        );
+        assert_eq!(
+            parse_frame("   3:     0x5600000010ff - <unknown>", None),
+            None
+        );
+        assert_eq!(
+            parse_frame("thread 'main' panicked at src/main.rs:4:5", None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bundles_pick_newest_binary() {
+        let temp_dir = TempDir::new().unwrap();
+        for (tag, built_at) in [("v1.0.0", 10), ("v1.1.0", 20)] {
+            let dir = temp_dir.path().join("app").join(tag);
+            std::fs::create_dir_all(&dir).unwrap();
+            let info = BuildInfo {
+                repo: "app".to_string(),
+                tag: tag.to_string(),
+                built_at,
+                binaries: vec!["app".to_string(), "aarch64-apple-darwin/app".to_string()],
+                ..BuildInfo::default()
+            };
+            std::fs::write(dir.join(BUILD_INFO), serde_json::to_string(&info).unwrap()).unwrap();
+        }
+        let bundles = find_bundles(temp_dir.path());
+        assert_eq!(bundles[0].1.tag, "v1.1.0");
+        assert_eq!(
+            pick_binary(&bundles, Some("app"), None),
+            Some(temp_dir.path().join("app").join("v1.1.0").join("app"))
+        );
+        assert_eq!(pick_binary(&bundles, Some("other"), None), None);
+        // Unnamed frames are ambiguous with several binaries
+        assert_eq!(pick_binary(&bundles, None, None), None);
+    }
+
+    #[test]
+    fn test_annotate_resolved_frames() {
+        let text = "panicked\n   0: app(+0x10)\n   1: app(+0x20)\n";
+        let bin = PathBuf::from("/b/app");
+        let frames = BTreeMap::from([(1, (bin.clone(), 0x10)), (2, (bin.clone(), 0x20))]);
+        let resolved = BTreeMap::from([((bin, 0x10), "app::run at src/main.rs:4".to_string())]);
+        assert_eq!(
+            annotate(text, &frames, &resolved),
+            "panicked\n   0: app(+0x10)\n      -> app::run at src/main.rs:4\n   1: app(+0x20)\n"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! `meta cargo sysdeps`: preflight check for system libraries and tools
+//!
+//! Requirements come from `[sysdeps]` in `.meta-rust.toml`. Tools are looked
+//! up on PATH; libraries are probed with `pkg-config --exists`, honoring the
+//! `PKG_CONFIG` override the way the `pkg-config` crate does.
+
+use crate::config::SysdepsConfig;
+use crate::CommandResult;
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A single system requirement
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Requirement {
+    /// Executable that must be on PATH
+    Tool(String),
+    /// pkg-config module, optionally with a version constraint
+    PkgConfig(String),
+}
+
+impl std::fmt::Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Requirement::Tool(name) => write!(f, "tool {name}"),
+            Requirement::PkgConfig(spec) => write!(f, "pkg-config {spec}"),
+        }
+    }
+}
+
+/// Requirements of `repo`: the global ones followed by its own
+pub fn requirements(config: &SysdepsConfig, repo: &str) -> Vec<Requirement> {
+    let repo_deps = config.repos.get(repo);
+    let tools = config
+        .tools
+        .iter()
+        .chain(repo_deps.into_iter().flat_map(|r| &r.tools));
+    let libs = config
+        .pkg_config
+        .iter()
+        .chain(repo_deps.into_iter().flat_map(|r| &r.pkg_config));
+    let mut reqs: Vec<Requirement> = tools
+        .map(|t| Requirement::Tool(t.clone()))
+        .chain(libs.map(|l| Requirement::PkgConfig(l.clone())))
+        .collect();
+    reqs.dedup();
+    reqs
+}
+
+/// First executable named `tool` in `dirs`
+fn find_in(dirs: impl IntoIterator<Item = PathBuf>, tool: &str) -> Option<PathBuf> {
+    let names: Vec<String> = if cfg!(windows) && Path::new(tool).extension().is_none() {
+        vec![
+            format!("{tool}.exe"),
+            format!("{tool}.cmd"),
+            format!("{tool}.bat"),
+        ]
+    } else {
+        vec![tool.to_string()]
+    };
+    dirs.into_iter()
+        .flat_map(|dir| names.iter().map(move |n| dir.join(n)))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Check one requirement on this machine, returning why it is unmet
+fn probe(req: &Requirement) -> Result<(), String> {
+    match req {
+        Requirement::Tool(tool) => {
+            let path = std::env::var_os("PATH").unwrap_or_default();
+            match find_in(std::env::split_paths(&path), tool) {
+                Some(_) => Ok(()),
+                None => Err("not found on PATH".to_string()),
+            }
+        }
+        Requirement::PkgConfig(spec) => {
+            let pkg_config = std::env::var("PKG_CONFIG").unwrap_or_else(|_| "pkg-config".into());
+            match Command::new(&pkg_config).args(["--exists", spec]).status() {
+                Ok(status) if status.success() => Ok(()),
+                Ok(_) => Err("not found by pkg-config (or version too old)".to_string()),
+                Err(e) => Err(format!("could not run {pkg_config}: {e}")),
+            }
+        }
+    }
+}
+
+/// Handle `meta cargo sysdeps`
+pub(crate) fn execute(repos: &[String], config: &SysdepsConfig) -> CommandResult {
+    let mut results: BTreeMap<Requirement, Result<(), String>> = BTreeMap::new();
+    let mut out = String::new();
+    let mut missing = 0;
+    let mut checked = 0;
+    for repo in repos {
+        let reqs = requirements(config, repo);
+        let failures: Vec<(Requirement, String)> = reqs
+            .into_iter()
+            .filter_map(|req| {
+                checked += 1;
+                let result = results.entry(req.clone()).or_insert_with(|| probe(&req));
+                result.clone().err().map(|why| (req, why))
+            })
+            .collect();
+        if failures.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("{repo}:\n"));
+        for (req, why) in failures {
+            missing += 1;
+            out.push_str(&format!("  {} {req}: {why}\n", "missing".red()));
+        }
+    }
+
+    if checked == 0 {
+        return CommandResult::Message(
+            "No system dependencies declared (add [sysdeps] to .meta-rust.toml)".to_string(),
+        );
+    }
+    if missing == 0 {
+        return CommandResult::Message(format!(
+            "All {} system dependencies found for {} repos",
+            results.len(),
+            repos.len()
+        ));
+    }
+    out.push_str(&format!(
+        "{missing} missing system dependencies; install them before building\n"
+    ));
+    CommandResult::Error(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_requirements_merge_global_and_repo() {
+        let config = Config::parse(
+            "[sysdeps]\ntools = [\"protoc\"]\n[sysdeps.repos.api]\ntools = [\"cmake\"]\npkg_config = [\"openssl\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            requirements(&config.sysdeps, "api"),
+            vec![
+                Requirement::Tool("protoc".to_string()),
+                Requirement::Tool("cmake".to_string()),
+                Requirement::PkgConfig("openssl".to_string()),
+            ]
+        );
+        assert_eq!(requirements(&config.sysdeps, "web").len(), 1);
+    }
+
+    #[test]
+    fn test_find_tool_in_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        let name = if cfg!(windows) {
+            "protoc.exe"
+        } else {
+            "protoc"
+        };
+        let tool = temp_dir.path().join(name);
+        std::fs::write(&tool, "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let dirs = || vec![temp_dir.path().to_path_buf()];
+        assert_eq!(find_in(dirs(), "protoc"), Some(tool));
+        assert_eq!(find_in(dirs(), "cmake"), None);
+    }
+
+    #[test]
+    fn test_missing_tool_is_error() {
+        let config =
+            Config::parse("[sysdeps]\ntools = [\"definitely-not-installed-tool\"]\n").unwrap();
+        match execute(&["api".to_string()], &config.sysdeps) {
+            CommandResult::Error(msg) => {
+                assert!(msg.contains("api:"));
+                assert!(msg.contains("tool definitely-not-installed-tool"));
+            }
+            _ => panic!("Expected Error result"),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! TAP (Test Anything Protocol) output for run results
+//!
+//! By default each repo is one test point. In per-test mode every libtest case
+//! becomes a test point named `<repo>: <test>`; repos with no parsed tests
+//! (build failures, non-test commands) still get a single point.
+
+use crate::libtest::{self, TestStatus};
+use crate::runner::RunOutcome;
+
+/// Number of output lines included in a failure's YAML block
+const TAIL_LINES: usize = 20;
+
+struct Point {
+    ok: bool,
+    description: String,
+    directive: Option<&'static str>,
+    diagnostics: Vec<(&'static str, String)>,
+    output: String,
+}
+
+/// TAP descriptions must not contain `#`, which starts a directive
+fn description(text: &str) -> String {
+    text.replace('#', "\\#")
+}
+
+fn tail(output: &str) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    lines[lines.len().saturating_sub(TAIL_LINES)..].join("\n")
+}
+
+fn repo_point(outcome: &RunOutcome) -> Point {
+    let mut diagnostics = vec![("command", outcome.cmd.clone())];
+    if let Some(code) = outcome.exit_code {
+        diagnostics.push(("exit_code", code.to_string()));
+    }
+    diagnostics.push(("duration_ms", outcome.duration.as_millis().to_string()));
+    Point {
+        ok: outcome.success,
+        description: description(&outcome.dir),
+        directive: None,
+        diagnostics,
+        output: if outcome.success {
+            String::new()
+        } else {
+            tail(&outcome.output())
+        },
+    }
+}
+
+fn points(outcomes: &[RunOutcome], per_test: bool) -> Vec<Point> {
+    let mut points = Vec::new();
+    for outcome in outcomes {
+        let cases = if per_test {
+            libtest::parse(&outcome.output())
+        } else {
+            Vec::new()
+        };
+        if cases.is_empty() {
+            points.push(repo_point(outcome));
+            continue;
+        }
+        let any_failed = cases.iter().any(|c| c.status == TestStatus::Failed);
+        for case in cases {
+            points.push(Point {
+                ok: case.status != TestStatus::Failed,
+                description: description(&format!("{}: {}", outcome.dir, case.name)),
+                directive: (case.status == TestStatus::Ignored).then_some("SKIP ignored"),
+                diagnostics: Vec::new(),
+                output: case.output,
+            });
+        }
+        // A failed run whose tests all passed broke somewhere else
+        if !outcome.success && !any_failed {
+            points.push(repo_point(outcome));
+        }
+    }
+    points
+}
+
+/// Render `outcomes` as a TAP version 13 stream
+pub(crate) fn render(outcomes: &[RunOutcome], per_test: bool) -> String {
+    let points = points(outcomes, per_test);
+    let mut out = format!("TAP version 13\n1..{}\n", points.len());
+    for (i, point) in points.iter().enumerate() {
+        let status = if point.ok { "ok" } else { "not ok" };
+        out.push_str(&format!("{status} {} - {}", i + 1, point.description));
+        if let Some(directive) = point.directive {
+            out.push_str(&format!(" # {directive}"));
+        }
+        out.push('\n');
+        if point.ok {
+            continue;
+        }
+        out.push_str("  ---\n");
+        for (key, value) in &point.diagnostics {
+            out.push_str(&format!("  {key}: {value:?}\n"));
+        }
+        if !point.output.is_empty() {
+            out.push_str("  output: |\n");
+            for line in point.output.lines() {
+                out.push_str(&format!("    {line}\n"));
+            }
+        }
+        out.push_str("  ...\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_per_repo() {
+        let out = render(
+            &[
+                RunOutcome::fixture("core", true)
+                    .with_cmd("cargo test")
+                    .with_stdout("test a ... ok\n"),
+                RunOutcome::fixture("app", false)
+                    .with_cmd("cargo test")
+                    .with_stdout("error: could not compile\n"),
+            ],
+            false,
+        );
+        assert!(out.starts_with("TAP version 13\n1..2\n"));
+        assert!(out.contains("ok 1 - core\n"));
+        assert!(out
+            .contains("not ok 2 - app\n  ---\n  command: \"cargo test\"\n  exit_code: \"101\"\n"));
+        assert!(out.contains("    error: could not compile\n  ...\n"));
+    }
+
+    #[test]
+    fn test_render_per_test() {
+        let out = render(
+            &[
+                RunOutcome::fixture("core", false)
+                    .with_stdout("test a ... ok\ntest b ... ignored\ntest c ... FAILED\n"),
+                RunOutcome::fixture("tool", true).with_stdout("Finished\n"),
+            ],
+            true,
+        );
+        assert!(out.contains("1..4\n"));
+        assert!(out.contains("ok 1 - core: a\n"));
+        assert!(out.contains("ok 2 - core: b # SKIP ignored\n"));
+        assert!(out.contains("not ok 3 - core: c\n"));
+        assert!(out.contains("ok 4 - tool\n"));
+    }
+}
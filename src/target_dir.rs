@@ -0,0 +1,271 @@
+//! Effective cargo target directory of each repo
+//!
+//! Cargo resolves `build.target-dir` from `CARGO_TARGET_DIR`, then `--config`
+//! (the meta-root config, see [`crate::cargo_config`]), then the nearest
+//! `.cargo/config.toml` from the repo upwards, and falls back to `./target`.
+//! Features that look at build output use this instead of assuming `target`.
+
+use crate::cargo_config;
+use crate::config::Config;
+use crate::{project_path, CommandResult};
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::path::{Path, PathBuf};
+
+/// Config file names cargo reads inside a `.cargo` directory
+const CONFIG_NAMES: &[&str] = &["config.toml", "config"];
+
+/// Where a repo's target directory comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// The `CARGO_TARGET_DIR` environment variable
+    Env,
+    /// The meta-root `.cargo/config.toml`, passed with `--config`
+    MetaRoot,
+    /// A `.cargo/config.toml` in or above the repo
+    Config(PathBuf),
+    /// Cargo's default `target` next to the workspace root
+    Default,
+}
+
+/// A repo's effective target directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetDir {
+    pub path: PathBuf,
+    pub source: Source,
+    /// Repo-level `build.target-dir` that a higher-precedence setting overrides
+    pub overridden: Option<PathBuf>,
+}
+
+/// `build.target-dir` from one cargo config file, resolved like cargo does
+/// (relative to the directory containing `.cargo`)
+pub fn configured_target_dir(file: &Path) -> Result<Option<PathBuf>> {
+    let text = std::fs::read_to_string(file)
+        .with_context(|| format!("failed to read {}", file.display()))?;
+    let table: toml::Table =
+        toml::from_str(&text).with_context(|| format!("invalid {}", file.display()))?;
+    let Some(dir) = table
+        .get("build")
+        .and_then(|b| b.get("target-dir"))
+        .and_then(|d| d.as_str())
+    else {
+        return Ok(None);
+    };
+    let base = file
+        .parent()
+        .and_then(Path::parent)
+        .unwrap_or(Path::new("."));
+    Ok(Some(base.join(dir)))
+}
+
+/// Nearest config file at or above `dir` that sets `build.target-dir`
+fn nearest_config(dir: &Path) -> Result<Option<(PathBuf, PathBuf)>> {
+    for ancestor in dir.ancestors() {
+        for name in CONFIG_NAMES {
+            let file = ancestor.join(".cargo").join(name);
+            if file.is_file() {
+                if let Some(target) = configured_target_dir(&file)? {
+                    return Ok(Some((file, target)));
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Resolve the target directory of the repo at `repo_dir`
+///
+/// `root_config` is the meta-root cargo config passed with `--config`, if any.
+pub fn resolve(repo_dir: &Path, root_config: Option<&Path>) -> Result<TargetDir> {
+    let repo_setting = nearest_config(repo_dir)?;
+    let root_setting = match root_config {
+        Some(file) => configured_target_dir(file)?,
+        None => None,
+    };
+    // A setting found by walking up into the meta root itself is not an override
+    let repo_override = repo_setting.clone().filter(|(file, _)| {
+        root_config.is_none_or(|root| {
+            let canon = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+            canon(file) != canon(root)
+        })
+    });
+
+    if let Some(env) = std::env::var_os("CARGO_TARGET_DIR").filter(|v| !v.is_empty()) {
+        return Ok(TargetDir {
+            path: repo_dir.join(env),
+            source: Source::Env,
+            overridden: repo_override.map(|(_, t)| t),
+        });
+    }
+    if let Some(path) = root_setting {
+        return Ok(TargetDir {
+            path,
+            source: Source::MetaRoot,
+            overridden: repo_override.map(|(_, t)| t),
+        });
+    }
+    Ok(match repo_setting {
+        Some((file, path)) => TargetDir {
+            path,
+            source: Source::Config(file),
+            overridden: None,
+        },
+        None => TargetDir {
+            path: repo_dir.join("target"),
+            source: Source::Default,
+            overridden: None,
+        },
+    })
+}
+
+/// Subdirectory of a shared target dir for the profile and `--target` in `args`
+pub fn namespace(args: &[String]) -> String {
+    let profile = crate::integration::profile_dir(args);
+    let mut triple = None;
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--target" {
+            triple = args.get(i + 1).cloned();
+        } else if let Some(t) = arg.strip_prefix("--target=") {
+            triple = Some(t.to_string());
+        }
+    }
+    match triple {
+        Some(triple) => format!("{profile}-{triple}"),
+        None => profile,
+    }
+}
+
+/// Point every command whose repo has a configured (shared) target dir at a
+/// subdirectory per profile and target triple
+///
+/// Repos using their own default `target` are left alone.
+pub(crate) fn split_by_profile(
+    commands: &mut [crate::PlannedCommand],
+    args: &[String],
+    cwd: &Path,
+    root_config: Option<&Path>,
+) {
+    let namespace = namespace(args);
+    for planned in commands {
+        let Ok(target) = resolve(&project_path(cwd, &planned.dir), root_config) else {
+            continue;
+        };
+        if target.source == Source::Default {
+            continue;
+        }
+        planned.env.get_or_insert_with(Default::default).insert(
+            "CARGO_TARGET_DIR".to_string(),
+            target.path.join(&namespace).display().to_string(),
+        );
+    }
+}
+
+/// Handle `meta cargo target-dirs`: list each repo's target dir and conflicts
+pub(crate) fn execute(repos: &[String], cwd: &Path, config: &Config) -> CommandResult {
+    let root_config = cargo_config::root_config(cwd, config);
+    let mut out = String::from("Target directories:\n");
+    let mut warnings = Vec::new();
+    for repo in repos {
+        let target = match resolve(&project_path(cwd, repo), root_config.as_deref()) {
+            Ok(t) => t,
+            Err(e) => {
+                warnings.push(format!("{repo}: {e:#}"));
+                continue;
+            }
+        };
+        let source = match &target.source {
+            Source::Env => "CARGO_TARGET_DIR".to_string(),
+            Source::MetaRoot => "meta-root .cargo/config.toml".to_string(),
+            Source::Config(file) => file.strip_prefix(cwd).unwrap_or(file).display().to_string(),
+            Source::Default => "default".to_string(),
+        };
+        let path = target.path.strip_prefix(cwd).unwrap_or(&target.path);
+        out.push_str(&format!("  {repo}: {} ({source})\n", path.display()));
+        if let Some(ignored) = &target.overridden {
+            warnings.push(format!(
+                "{repo}: build.target-dir = {} is overridden by {source}",
+                ignored.display()
+            ));
+        }
+    }
+    for warning in &warnings {
+        out.push_str(&format!("{} {warning}\n", "warning:".yellow()));
+    }
+    CommandResult::Message(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_config(dir: &Path, text: &str) -> PathBuf {
+        std::fs::create_dir_all(dir.join(".cargo")).unwrap();
+        let file = dir.join(".cargo/config.toml");
+        std::fs::write(&file, text).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_repo_config_target_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("core");
+        std::fs::create_dir(&repo).unwrap();
+        assert_eq!(resolve(&repo, None).unwrap().source, Source::Default);
+
+        let file = write_config(&repo, "[build]\ntarget-dir = \"../shared\"\n");
+        let target = resolve(&repo, None).unwrap();
+        assert_eq!(target.path, repo.join("../shared"));
+        assert_eq!(target.source, Source::Config(file));
+    }
+
+    #[test]
+    fn test_split_by_profile() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(namespace(&[]), "debug");
+        assert_eq!(
+            namespace(&args(&["--release", "--target", "aarch64-apple-darwin"])),
+            "release-aarch64-apple-darwin"
+        );
+
+        let temp_dir = TempDir::new().unwrap();
+        let shared = temp_dir.path().join("shared");
+        std::fs::create_dir_all(shared.join(".cargo")).unwrap();
+        std::fs::write(
+            shared.join(".cargo/config.toml"),
+            "[build]\ntarget-dir = \"../cache\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir(temp_dir.path().join("own")).unwrap();
+        let mut commands: Vec<crate::PlannedCommand> = ["shared", "own"]
+            .iter()
+            .map(|dir| crate::PlannedCommand {
+                dir: dir.to_string(),
+                cmd: "cargo build --release".to_string(),
+                env: None,
+            })
+            .collect();
+        split_by_profile(&mut commands, &args(&["--release"]), temp_dir.path(), None);
+        let env = commands[0].env.as_ref().unwrap();
+        assert!(env["CARGO_TARGET_DIR"].ends_with("release"));
+        assert!(commands[1].env.is_none());
+    }
+
+    #[test]
+    fn test_meta_root_overrides_repo() {
+        let temp_dir = TempDir::new().unwrap();
+        let root_file = write_config(temp_dir.path(), "build.target-dir = \"/tmp/meta\"\n");
+        let repo = temp_dir.path().join("core");
+        std::fs::create_dir(&repo).unwrap();
+
+        // Found by walking up: the meta root setting, not a repo override
+        let target = resolve(&repo, Some(&root_file)).unwrap();
+        assert_eq!(target.source, Source::MetaRoot);
+        assert_eq!(target.overridden, None);
+
+        write_config(&repo, "[build]\ntarget-dir = \"out\"\n");
+        let target = resolve(&repo, Some(&root_file)).unwrap();
+        assert_eq!(target.path, PathBuf::from("/tmp/meta"));
+        assert_eq!(target.overridden, Some(repo.join("out")));
+    }
+}
@@ -0,0 +1,168 @@
+//! Parameterized meta commands from `[tasks.<name>]`
+//!
+//! A task names a meta cargo command line with `{param}` placeholders:
+//!
+//! ```toml
+//! [tasks.release]
+//! run = "version {level} --commit --tag"
+//! params = [{ name = "level", prompt = "Release level", default = "patch" }]
+//! ```
+//!
+//! `meta cargo release --level minor` fills the parameter from the flag. A
+//! missing one is asked for on a terminal, offering the default, and outside a
+//! terminal (or with `CI` set) takes the default, so the same task works
+//! interactively and in CI. Remaining arguments are appended to the command.
+//!
+//! meta sends the plugin request on stdin, so the terminal is the controlling
+//! one (`/dev/tty`, `CONIN$` on Windows) rather than stdin.
+
+use crate::args;
+use crate::config::TaskConfig;
+use std::io::{BufRead, BufReader, IsTerminal, Write};
+
+/// The controlling terminal, opened for reading answers
+fn open_tty() -> std::io::Result<std::fs::File> {
+    let path = if cfg!(windows) { "CONIN$" } else { "/dev/tty" };
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+}
+
+/// Whether missing parameters may be asked for
+pub(crate) fn interactive() -> bool {
+    std::env::var_os("CI").is_none() && std::io::stderr().is_terminal() && open_tty().is_ok()
+}
+
+/// Ask on stderr and read one line from the terminal
+pub(crate) fn prompt_tty(question: &str) -> Option<String> {
+    let tty = open_tty().ok()?;
+    eprint!("{question}");
+    std::io::stderr().flush().ok()?;
+    let mut line = String::new();
+    BufReader::new(tty).read_line(&mut line).ok()?;
+    Some(line.trim().to_string())
+}
+
+/// The meta command (`cargo <sub>`) and arguments `task` expands to
+///
+/// `ask` is called for each parameter without a `--<name>` flag when
+/// `interactive` is set; an empty answer takes the default.
+pub(crate) fn expand(
+    name: &str,
+    task: &TaskConfig,
+    args: &[String],
+    interactive: bool,
+    mut ask: impl FnMut(&str) -> Option<String>,
+) -> Result<(String, Vec<String>), String> {
+    let mut args = args.to_vec();
+    let mut values = Vec::new();
+    for param in &task.params {
+        let value = match args::take_value(&mut args, &format!("--{}", param.name)) {
+            Some(v) => v,
+            None if interactive => {
+                let label = param.prompt.as_deref().unwrap_or(&param.name);
+                let question = match &param.default {
+                    Some(default) => format!("{label} [{default}]: "),
+                    None => format!("{label}: "),
+                };
+                match ask(&question).filter(|a| !a.is_empty()) {
+                    Some(answer) => answer,
+                    None => param.default.clone().ok_or_else(|| {
+                        format!("task '{name}': no value given for '{}'", param.name)
+                    })?,
+                }
+            }
+            None => param.default.clone().ok_or_else(|| {
+                format!(
+                    "task '{name}': missing --{} (no default, and not running interactively)",
+                    param.name
+                )
+            })?,
+        };
+        values.push((format!("{{{}}}", param.name), value));
+    }
+
+    let mut words = task.run.split_whitespace().map(|word| {
+        values
+            .iter()
+            .fold(word.to_string(), |w, (placeholder, value)| {
+                w.replace(placeholder, value)
+            })
+    });
+    let Some(sub) = words.next() else {
+        return Err(format!("task '{name}': `run` is empty"));
+    };
+    let mut expanded: Vec<String> = words.collect();
+    expanded.extend(args);
+    Ok((format!("cargo {sub}"), expanded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::strings;
+    use crate::config::Config;
+
+    fn release() -> TaskConfig {
+        let config = Config::parse(
+            "[tasks.release]\nrun = \"version {level} --commit --tag\"\nparams = [{ name = \"level\", prompt = \"Release level\", default = \"patch\" }]\n\n[tasks.deploy]\nrun = \"run -p deploy -- {env}\"\nparams = [{ name = \"env\" }]\n",
+        )
+        .unwrap();
+        assert_eq!(config.tasks["deploy"].params[0].prompt, None);
+        config.tasks["release"].clone()
+    }
+
+    #[test]
+    fn test_expand_from_flags_and_defaults() {
+        let task = release();
+        let never = |_: &str| -> Option<String> { panic!("should not prompt") };
+        let (command, args) = expand(
+            "release",
+            &task,
+            &strings(&["--level", "minor", "--dry-run"]),
+            true,
+            never,
+        )
+        .unwrap();
+        assert_eq!(command, "cargo version");
+        assert_eq!(args, strings(&["minor", "--commit", "--tag", "--dry-run"]));
+
+        let (_, args) = expand("release", &task, &[], false, never).unwrap();
+        assert_eq!(args[0], "patch");
+    }
+
+    #[test]
+    fn test_expand_prompts_when_interactive() {
+        let task = release();
+        let mut asked = Vec::new();
+        let (_, args) = expand("release", &task, &[], true, |q| {
+            asked.push(q.to_string());
+            Some("major".to_string())
+        })
+        .unwrap();
+        assert_eq!(asked, vec!["Release level [patch]: "]);
+        assert_eq!(args[0], "major");
+
+        // An empty answer takes the default
+        let (_, args) = expand("release", &task, &[], true, |_| Some(String::new())).unwrap();
+        assert_eq!(args[0], "patch");
+    }
+
+    #[test]
+    fn test_expand_missing_param_without_default() {
+        let config = Config::parse(
+            "[tasks.deploy]\nrun = \"run -p deploy -- {env}\"\nparams = [{ name = \"env\" }]\n",
+        )
+        .unwrap();
+        let task = &config.tasks["deploy"];
+        let err = expand("deploy", task, &[], false, |_| None).unwrap_err();
+        assert!(err.contains("missing --env"), "{err}");
+        let (command, args) = expand("deploy", task, &strings(&["--env", "prod"]), false, |_| {
+            None
+        })
+        .unwrap();
+        assert_eq!(command, "cargo run");
+        assert_eq!(args, strings(&["-p", "deploy", "--", "prod"]));
+    }
+}
@@ -0,0 +1,150 @@
+//! TeamCity service messages for run results
+//!
+//! Repos whose output contains libtest results become test suites with one
+//! test per libtest case; every other repo becomes a block, and failures are
+//! reported as build problems. Totals are published as build statistics.
+
+use crate::diagnostics;
+use crate::libtest::{self, TestStatus};
+use crate::runner::RunOutcome;
+
+/// Escape a value for a service message attribute
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '|' => out.push_str("||"),
+            '\'' => out.push_str("|'"),
+            '\n' => out.push_str("|n"),
+            '\r' => out.push_str("|r"),
+            '[' => out.push_str("|["),
+            ']' => out.push_str("|]"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Format `##teamcity[name key='value' ...]`
+fn message(name: &str, attrs: &[(&str, &str)]) -> String {
+    let mut out = format!("##teamcity[{name}");
+    for (key, value) in attrs {
+        out.push_str(&format!(" {key}='{}'", escape(value)));
+    }
+    out.push_str("]\n");
+    out
+}
+
+fn render_repo(outcome: &RunOutcome, out: &mut String) {
+    let repo = outcome.dir.as_str();
+    let flow = [("flowId", repo)];
+    let cases = libtest::parse(&outcome.output());
+
+    if cases.is_empty() {
+        out.push_str(&message("blockOpened", &[("name", repo), flow[0]]));
+    } else {
+        out.push_str(&message("testSuiteStarted", &[("name", repo), flow[0]]));
+        for case in &cases {
+            let name = case.name.as_str();
+            out.push_str(&message("testStarted", &[("name", name), flow[0]]));
+            match case.status {
+                TestStatus::Passed => {}
+                TestStatus::Ignored => {
+                    out.push_str(&message("testIgnored", &[("name", name), flow[0]]));
+                }
+                TestStatus::Failed => out.push_str(&message(
+                    "testFailed",
+                    &[
+                        ("name", name),
+                        ("message", "test failed"),
+                        ("details", &case.output),
+                        flow[0],
+                    ],
+                )),
+            }
+            out.push_str(&message("testFinished", &[("name", name), flow[0]]));
+        }
+    }
+
+    if !outcome.success {
+        let code = outcome
+            .exit_code
+            .map(|c| format!("exit code {c}"))
+            .unwrap_or_else(|| "terminated".to_string());
+        out.push_str(&message(
+            "buildProblem",
+            &[
+                (
+                    "description",
+                    &format!("{repo}: `{}` failed ({code})", outcome.cmd),
+                ),
+                ("identity", repo),
+            ],
+        ));
+    }
+
+    if cases.is_empty() {
+        out.push_str(&message("blockClosed", &[("name", repo), flow[0]]));
+    } else {
+        out.push_str(&message("testSuiteFinished", &[("name", repo), flow[0]]));
+    }
+}
+
+/// Render every outcome as TeamCity service messages
+pub(crate) fn render(outcomes: &[RunOutcome]) -> String {
+    let mut out = String::new();
+    let (mut errors, mut warnings) = (0, 0);
+    for outcome in outcomes {
+        render_repo(outcome, &mut out);
+        let (e, w) = diagnostics::counts(&diagnostics::parse(&outcome.output()));
+        errors += e;
+        warnings += w;
+    }
+
+    let failed = outcomes.iter().filter(|o| !o.success).count();
+    let total_ms: u128 = outcomes.iter().map(|o| o.duration.as_millis()).sum();
+    for (key, value) in [
+        ("meta.repos.total", outcomes.len().to_string()),
+        ("meta.repos.failed", failed.to_string()),
+        ("meta.diagnostics.errors", errors.to_string()),
+        ("meta.diagnostics.warnings", warnings.to_string()),
+        ("meta.duration.ms", total_ms.to_string()),
+    ] {
+        out.push_str(&message(
+            "buildStatisticValue",
+            &[("key", key), ("value", &value)],
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("it's [a|b]\n"), "it|'s |[a||b|]|n");
+    }
+
+    #[test]
+    fn test_render_tests_and_problems() {
+        let test = |dir: &str, success: bool, stdout: &str| {
+            RunOutcome::fixture(dir, success)
+                .with_cmd("cargo test")
+                .with_stdout(stdout)
+        };
+        let out = render(&[
+            test("core", true, "test a ... ok\n"),
+            test("app", false, "test b ... FAILED\n"),
+            test("tool", false, "error: could not compile\n"),
+        ]);
+        assert!(out.contains("##teamcity[testSuiteStarted name='core' flowId='core']"));
+        assert!(out.contains("##teamcity[testFailed name='b'"));
+        assert!(out.contains("##teamcity[blockOpened name='tool' flowId='tool']"));
+        assert!(
+            out.contains("buildProblem description='tool: `cargo test` failed (exit code 101)'")
+        );
+        assert!(out.contains("##teamcity[buildStatisticValue key='meta.repos.failed' value='2']"));
+    }
+}
@@ -0,0 +1,466 @@
+//! Rust toolchains pinned by the repos (`rust-toolchain(.toml)`)
+//!
+//! `meta cargo toolchains` lists every distinct pin with the repos using it;
+//! `meta cargo toolchain bump` moves every repo to a new release at once.
+//! Release ages are estimated from the six-week release train, so the report
+//! works offline: 1.0 shipped on 2015-05-15 and every minor follows 42 days on.
+
+use crate::metadata;
+use crate::runner;
+use crate::{args, project_path, CommandResult, PlannedCommand};
+use colored::Colorize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Days since the Unix epoch of the 1.0.0 release
+const RUST_1_0_DAY: i64 = 16_570;
+/// Days between stable releases
+const RELEASE_CYCLE_DAYS: i64 = 42;
+/// Releases behind the current stable at which a pin counts as old
+const OLD_RELEASES: i64 = 4;
+/// Releases behind the current stable at which a pin counts as very old
+const VERY_OLD_RELEASES: i64 = 8;
+
+/// Toolchain requirements of one repo
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Toolchain {
+    pub channel: Option<String>,
+    pub components: Vec<String>,
+    pub targets: Vec<String>,
+}
+
+/// Read `rust-toolchain.toml` or the legacy `rust-toolchain` file in `dir`
+pub fn read_toolchain(dir: &Path) -> Option<Toolchain> {
+    let text = ["rust-toolchain.toml", "rust-toolchain"]
+        .iter()
+        .find_map(|f| std::fs::read_to_string(dir.join(f)).ok())?;
+    let text = text.trim();
+    // The legacy file may hold only a channel name
+    if !text.contains('=') {
+        return Some(Toolchain {
+            channel: Some(text.to_string()).filter(|c| !c.is_empty()),
+            ..Toolchain::default()
+        });
+    }
+    let table = toml::from_str::<toml::Table>(text).ok()?;
+    let section = table.get("toolchain")?;
+    let list = |key: &str| -> Vec<String> {
+        section
+            .get(key)
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    Some(Toolchain {
+        channel: section
+            .get("channel")
+            .and_then(|v| v.as_str())
+            .map(str::to_string),
+        components: list("components"),
+        targets: list("targets"),
+    })
+}
+
+/// Days since the Unix epoch of a civil date (proleptic Gregorian)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Minor version of the newest stable release out on `today`
+fn current_minor(today: i64) -> i64 {
+    (today - RUST_1_0_DAY) / RELEASE_CYCLE_DAYS
+}
+
+/// Release day of a channel: `1.76`, `1.76.0`, or a dated `nightly-`/`beta-`
+/// channel. `None` for floating channels like `stable`.
+fn release_day(channel: &str) -> Option<i64> {
+    if let Some((_, date)) = channel.split_once('-') {
+        let parts: Vec<i64> = date.split('-').filter_map(|p| p.parse().ok()).collect();
+        return match parts.as_slice() {
+            [y, m, d] => Some(days_from_civil(*y, *m, *d)),
+            _ => None,
+        };
+    }
+    let mut parts = channel.split('.');
+    match (parts.next(), parts.next().map(str::parse::<i64>)) {
+        (Some("1"), Some(Ok(minor))) => Some(RUST_1_0_DAY + minor * RELEASE_CYCLE_DAYS),
+        _ => None,
+    }
+}
+
+/// Age assessment of a pinned channel on `today`
+fn staleness(channel: &str, today: i64) -> Option<String> {
+    let behind = (today - release_day(channel)?) / RELEASE_CYCLE_DAYS;
+    let current = current_minor(today);
+    if behind >= VERY_OLD_RELEASES {
+        Some(format!(
+            "very old: about {behind} releases behind 1.{current}"
+        ))
+    } else if behind >= OLD_RELEASES {
+        Some(format!("old: about {behind} releases behind 1.{current}"))
+    } else {
+        None
+    }
+}
+
+fn today() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| (d.as_secs() / 86_400) as i64)
+        .unwrap_or(RUST_1_0_DAY)
+}
+
+/// Repos by pinned channel; unpinned repos are keyed by `None`
+pub fn pins(repos: &[String], cwd: &Path) -> BTreeMap<Option<String>, Vec<String>> {
+    let mut pins: BTreeMap<Option<String>, Vec<String>> = BTreeMap::new();
+    for repo in repos {
+        let channel = read_toolchain(&project_path(cwd, repo)).and_then(|t| t.channel);
+        pins.entry(channel).or_default().push(repo.clone());
+    }
+    pins
+}
+
+fn render_text(pins: &BTreeMap<Option<String>, Vec<String>>, today: i64) -> String {
+    let pinned = pins.keys().filter(|k| k.is_some()).count();
+    let mut out = format!("{pinned} distinct toolchain pins:\n");
+    for (channel, repos) in pins {
+        let name = channel.as_deref().unwrap_or("(unpinned, rustup default)");
+        out.push_str(&format!("  {name}: {}\n", repos.join(", ")));
+        if let Some(note) = channel.as_deref().and_then(|c| staleness(c, today)) {
+            out.push_str(&format!("      {} {note}\n", "warning:".yellow()));
+        }
+    }
+    out
+}
+
+fn render_json(pins: &BTreeMap<Option<String>, Vec<String>>, today: i64) -> serde_json::Value {
+    json!({
+        "current_stable_estimate": format!("1.{}", current_minor(today)),
+        "toolchains": pins.iter().map(|(channel, repos)| json!({
+            "channel": channel,
+            "repos": repos,
+            "warning": channel.as_deref().and_then(|c| staleness(c, today)),
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// Handle `meta cargo toolchains [--format text|json]`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let format = args::take_value(&mut args, "--format").unwrap_or_else(|| "text".to_string());
+    let pins = pins(repos, cwd);
+    let today = today();
+    match format.as_str() {
+        "text" => CommandResult::Message(render_text(&pins, today)),
+        "json" => match serde_json::to_string_pretty(&render_json(&pins, today)) {
+            Ok(text) => CommandResult::Message(text),
+            Err(e) => CommandResult::Error(format!("Failed to serialize toolchains: {e}")),
+        },
+        other => CommandResult::Error(format!(
+            "unsupported format '{other}' (expected text or json)"
+        )),
+    }
+}
+
+/// `text` of a toolchain file with its channel set to `to`, if it changes
+fn rewrite_channel(text: &str, to: &str) -> Option<String> {
+    if !text.contains('=') {
+        let new = format!("{to}\n");
+        return (text.trim() != to).then_some(new);
+    }
+    rewrite_key(text, "channel", to)
+}
+
+/// `text` of a manifest with every `rust-version` set to `to`, if it changes
+fn rewrite_rust_version(text: &str, to: &str) -> Option<String> {
+    rewrite_key(text, "rust-version", to)
+}
+
+/// Replace the string value of every `key = "..."` line, keeping layout
+fn rewrite_key(text: &str, key: &str, value: &str) -> Option<String> {
+    let mut changed = false;
+    let lines: Vec<String> = text
+        .split_inclusive('\n')
+        .map(|line| {
+            let trimmed = line.trim_start();
+            let Some(rest) = trimmed.strip_prefix(key) else {
+                return line.to_string();
+            };
+            let Some(rest) = rest.trim_start().strip_prefix('=') else {
+                return line.to_string();
+            };
+            let rest = rest.trim_start();
+            let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+                return line.to_string();
+            };
+            let Some(end) = rest[1..].find(quote) else {
+                return line.to_string();
+            };
+            if &rest[1..=end] == value {
+                return line.to_string();
+            }
+            changed = true;
+            let indent = &line[..line.len() - trimmed.len()];
+            let prefix = &trimmed[..trimmed.len() - rest.len()];
+            format!("{indent}{prefix}{quote}{value}{quote}{}", &rest[end + 2..])
+        })
+        .collect();
+    changed.then(|| lines.concat())
+}
+
+/// Files of `repo_dir` to rewrite for a bump to `to`, with their new contents
+fn planned_edits(repo_dir: &Path, to: &str) -> Vec<(PathBuf, String)> {
+    let mut edits = Vec::new();
+    for file in ["rust-toolchain.toml", "rust-toolchain"] {
+        let path = repo_dir.join(file);
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            if let Some(new) = rewrite_channel(&text, to) {
+                edits.push((path, new));
+            }
+            break;
+        }
+    }
+    for path in metadata::manifest_paths(repo_dir) {
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            if let Some(new) = rewrite_rust_version(&text, to) {
+                edits.push((path, new));
+            }
+        }
+    }
+    edits
+}
+
+/// Whether `version` is a plain `1.N` or `1.N.P` release
+fn is_release(version: &str) -> bool {
+    let parts: Vec<&str> = version.split('.').collect();
+    (2..=3).contains(&parts.len())
+        && parts[0] == "1"
+        && parts
+            .iter()
+            .all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Handle `meta cargo toolchain bump --to <version> [--dry-run] [--no-check]`
+///
+/// Rewrites the channel of existing rust-toolchain files and every
+/// `rust-version` field, then runs `cargo check` with the new toolchain in
+/// each repo and reports the ones that break.
+pub(crate) fn execute_bump(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+) -> CommandResult {
+    let mut args = args.to_vec();
+    let Some(to) = args::take_value(&mut args, "--to") else {
+        return CommandResult::Error("toolchain bump requires --to <version>".to_string());
+    };
+    if !is_release(&to) {
+        return CommandResult::Error(format!(
+            "--to expects a stable release such as 1.84 or 1.84.0, got '{to}'"
+        ));
+    }
+    let dry_run = args::take_flag(&mut args, "--dry-run");
+    let check = !args::take_flag(&mut args, "--no-check") && !dry_run;
+
+    let verb = if dry_run { "Would update" } else { "Updated" };
+    let mut out = format!("{verb} files for toolchain {to}:\n");
+    let mut failures = Vec::new();
+    for repo in repos {
+        let repo_dir = project_path(cwd, repo);
+        let edits = planned_edits(&repo_dir, &to);
+        if edits.is_empty() {
+            continue;
+        }
+        let files: Vec<String> = edits
+            .iter()
+            .map(|(p, _)| p.strip_prefix(&repo_dir).unwrap_or(p).display().to_string())
+            .collect();
+        out.push_str(&format!("  {repo}: {}\n", files.join(", ")));
+        if dry_run {
+            continue;
+        }
+        for (path, text) in edits {
+            if let Err(e) = std::fs::write(&path, text) {
+                failures.push(format!("{}: {e}", path.display()));
+            }
+        }
+    }
+    if !failures.is_empty() {
+        return CommandResult::Error(format!(
+            "{out}Failed to write:\n  {}",
+            failures.join("\n  ")
+        ));
+    }
+    if !check {
+        return CommandResult::Message(out);
+    }
+
+    // `+<toolchain>` must come right after `cargo`
+    let cargo = cargo.replacen("cargo", &format!("cargo +{to}"), 1);
+    let commands: Vec<PlannedCommand> = repos
+        .iter()
+        .map(|repo| PlannedCommand {
+            dir: repo.clone(),
+            cmd: format!("{cargo} check --workspace --all-targets"),
+            env: None,
+        })
+        .collect();
+    let outcomes = runner::run_all(cwd, &commands, parallel);
+    out.push_str(&format!("\ncargo check with {to}:\n"));
+    let mut broken = 0;
+    for outcome in &outcomes {
+        if outcome.success {
+            out.push_str(&format!("  {} {}\n", "ok".green(), outcome.dir));
+            continue;
+        }
+        broken += 1;
+        out.push_str(&format!("  {} {}\n", "FAIL".red(), outcome.dir));
+        for line in outcome
+            .stderr
+            .lines()
+            .filter(|l| l.starts_with("error"))
+            .take(5)
+        {
+            out.push_str(&format!("      {line}\n"));
+        }
+    }
+    if broken == 0 {
+        out.push_str(&format!("All {} repos build with {to}\n", outcomes.len()));
+        CommandResult::Message(out)
+    } else {
+        out.push_str(&format!(
+            "{broken} of {} repos break on {to}\n",
+            outcomes.len()
+        ));
+        CommandResult::Error(out)
+    }
+}
+
+/// Handle `meta cargo toolchain <subcommand>`
+pub(crate) fn execute_subcommand(
+    cargo: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    parallel: bool,
+) -> CommandResult {
+    match args.split_first() {
+        Some((sub, rest)) if sub == "bump" => execute_bump(cargo, rest, repos, cwd, parallel),
+        _ => CommandResult::ShowHelp(Some(
+            "usage: meta cargo toolchain bump --to <version>".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_toolchain_files() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("rust-toolchain.toml"),
+            "[toolchain]\nchannel = \"1.76.0\"\ncomponents = [\"clippy\"]\ntargets = [\"wasm32-unknown-unknown\"]\n",
+        )
+        .unwrap();
+        let toolchain = read_toolchain(temp_dir.path()).unwrap();
+        assert_eq!(toolchain.channel.as_deref(), Some("1.76.0"));
+        assert_eq!(toolchain.targets, vec!["wasm32-unknown-unknown"]);
+
+        let legacy = TempDir::new().unwrap();
+        std::fs::write(legacy.path().join("rust-toolchain"), "nightly-2024-05-01\n").unwrap();
+        assert_eq!(
+            read_toolchain(legacy.path()).unwrap().channel.as_deref(),
+            Some("nightly-2024-05-01")
+        );
+        assert!(read_toolchain(TempDir::new().unwrap().path()).is_none());
+    }
+
+    #[test]
+    fn test_release_estimates() {
+        assert_eq!(days_from_civil(2015, 5, 15), RUST_1_0_DAY);
+        let jan_2025 = days_from_civil(2025, 1, 20);
+        assert_eq!(current_minor(jan_2025), 84);
+        assert_eq!(staleness("1.83.0", jan_2025), None);
+        assert!(staleness("1.78", jan_2025).unwrap().starts_with("old"));
+        assert!(staleness("1.70.0", jan_2025)
+            .unwrap()
+            .starts_with("very old"));
+        assert!(staleness("nightly-2023-01-01", jan_2025)
+            .unwrap()
+            .starts_with("very old"));
+        assert_eq!(staleness("stable", jan_2025), None);
+    }
+
+    #[test]
+    fn test_pins_group_repos() {
+        let temp_dir = TempDir::new().unwrap();
+        for (repo, channel) in [("a", "1.76.0"), ("b", "1.76.0")] {
+            std::fs::create_dir(temp_dir.path().join(repo)).unwrap();
+            std::fs::write(temp_dir.path().join(repo).join("rust-toolchain"), channel).unwrap();
+        }
+        std::fs::create_dir(temp_dir.path().join("c")).unwrap();
+        let repos: Vec<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        let pins = pins(&repos, temp_dir.path());
+        assert_eq!(pins[&Some("1.76.0".to_string())], vec!["a", "b"]);
+        assert_eq!(pins[&None], vec!["c"]);
+    }
+
+    #[test]
+    fn test_rewrite_keeps_layout() {
+        let toolchain = "[toolchain]\nchannel = \"1.76.0\" # pinned\ncomponents = [\"clippy\"]\n";
+        assert_eq!(
+            rewrite_channel(toolchain, "1.84").unwrap(),
+            "[toolchain]\nchannel = \"1.84\" # pinned\ncomponents = [\"clippy\"]\n"
+        );
+        assert_eq!(rewrite_channel("1.76.0\n", "1.84").unwrap(), "1.84\n");
+        assert!(rewrite_channel("1.84\n", "1.84").is_none());
+
+        let manifest = "[package]\nname = \"a\"\nrust-version = \"1.70\"\n\n[lints]\nrust-version.workspace = true\n";
+        assert_eq!(
+            rewrite_rust_version(manifest, "1.84").unwrap(),
+            "[package]\nname = \"a\"\nrust-version = \"1.84\"\n\n[lints]\nrust-version.workspace = true\n"
+        );
+    }
+
+    #[test]
+    fn test_bump_dry_run_lists_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = temp_dir.path().join("api");
+        std::fs::create_dir(&repo).unwrap();
+        std::fs::write(repo.join("rust-toolchain"), "1.76.0\n").unwrap();
+        let args: Vec<String> = ["--to", "1.84", "--dry-run"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        match execute_bump("cargo", &args, &["api".to_string()], temp_dir.path(), false) {
+            CommandResult::Message(msg) => assert!(msg.contains("api: rust-toolchain")),
+            _ => panic!("Expected Message result"),
+        }
+        assert_eq!(
+            std::fs::read_to_string(repo.join("rust-toolchain")).unwrap(),
+            "1.76.0\n"
+        );
+
+        let args = vec!["--to".to_string(), "stable".to_string()];
+        match execute_bump("cargo", &args, &["api".to_string()], temp_dir.path(), false) {
+            CommandResult::Error(msg) => assert!(msg.contains("stable release")),
+            _ => panic!("Expected Error result"),
+        }
+    }
+}
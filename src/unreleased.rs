@@ -0,0 +1,212 @@
+//! `meta cargo unreleased`: what has changed since each repo's last release
+//!
+//! The last release of a repo is the newest tag reachable from HEAD (`git
+//! describe --tags --abbrev=0`, optionally limited with `--match <glob>`).
+//! Commits after it that touch the project directory are unreleased; the age
+//! of the oldest one shows how long a change has been waiting to ship.
+
+use crate::{args, git, project_path, CommandResult};
+use colored::Colorize;
+use serde_json::json;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Release state of one repo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoStatus {
+    pub repo: String,
+    /// Last release tag, `None` when the repo was never tagged
+    pub tag: Option<String>,
+    /// Commits since the tag (all commits without one)
+    pub commits: usize,
+    /// Commit time of the oldest unreleased commit, in Unix seconds
+    pub oldest: Option<i64>,
+    pub error: Option<String>,
+}
+
+/// Release state of the project at `dir`
+fn status(repo: &str, dir: &Path, pattern: Option<&str>) -> RepoStatus {
+    let mut describe = vec!["describe", "--tags", "--abbrev=0"];
+    if let Some(pattern) = pattern {
+        describe.extend(["--match", pattern]);
+    }
+    let tag = git::run(dir, &describe)
+        .ok()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty());
+    let range = match &tag {
+        Some(tag) => format!("{tag}..HEAD"),
+        None => "HEAD".to_string(),
+    };
+    match git::run(
+        dir,
+        &["log", "--reverse", "--format=%ct", &range, "--", "."],
+    ) {
+        Ok(log) => {
+            let times: Vec<i64> = log.lines().filter_map(|l| l.trim().parse().ok()).collect();
+            RepoStatus {
+                repo: repo.to_string(),
+                tag,
+                commits: times.len(),
+                oldest: times.first().copied(),
+                error: None,
+            }
+        }
+        Err(e) => RepoStatus {
+            repo: repo.to_string(),
+            tag,
+            commits: 0,
+            oldest: None,
+            error: Some(format!("{e:#}")),
+        },
+    }
+}
+
+fn age_days(oldest: Option<i64>, now: i64) -> Option<i64> {
+    oldest.map(|t| (now - t).max(0) / 86_400)
+}
+
+fn render_text(statuses: &[RepoStatus], now: i64) -> String {
+    let mut out = String::new();
+    for s in statuses {
+        let age = match age_days(s.oldest, now) {
+            Some(days) => format!(", oldest {days} days old"),
+            None => String::new(),
+        };
+        let line = match (&s.error, &s.tag, s.commits) {
+            (Some(e), _, _) => format!("{} {}: {e}", "?".yellow(), s.repo),
+            (None, Some(tag), 0) => format!("{} {}: up to date with {tag}", "✓".green(), s.repo),
+            (None, Some(tag), n) => format!(
+                "{} {}: {n} unreleased commits since {tag}{age}",
+                "●".yellow(),
+                s.repo
+            ),
+            (None, None, n) => format!(
+                "{} {}: no release tag ({n} commits{age})",
+                "●".yellow(),
+                s.repo
+            ),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    let pending = statuses
+        .iter()
+        .filter(|s| s.error.is_none() && s.commits > 0)
+        .count();
+    out.push_str(&format!(
+        "\n{pending}/{} repos have unreleased commits\n",
+        statuses.len()
+    ));
+    out
+}
+
+fn render_json(statuses: &[RepoStatus], now: i64) -> serde_json::Value {
+    json!({
+        "repos": statuses.iter().map(|s| json!({
+            "repo": s.repo,
+            "tag": s.tag,
+            "unreleased_commits": s.commits,
+            "oldest_commit_age_days": age_days(s.oldest, now),
+            "error": s.error,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+/// Handle `meta cargo unreleased [--match <glob>] [--format text|json]`
+pub(crate) fn execute(args: &[String], repos: &[String], cwd: &Path) -> CommandResult {
+    let mut args = args.to_vec();
+    let format = args::take_value(&mut args, "--format").unwrap_or_else(|| "text".to_string());
+    let pattern = args::take_value(&mut args, "--match");
+    let mut statuses: Vec<RepoStatus> = repos
+        .iter()
+        .map(|repo| status(repo, &project_path(cwd, repo), pattern.as_deref()))
+        .collect();
+    // Most unreleased work first
+    statuses.sort_by_key(|s| std::cmp::Reverse(s.commits));
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    match format.as_str() {
+        "text" => CommandResult::Message(render_text(&statuses, now)),
+        "json" => match serde_json::to_string_pretty(&render_json(&statuses, now)) {
+            Ok(text) => CommandResult::Message(text),
+            Err(e) => CommandResult::Error(format!("Failed to serialize release status: {e}")),
+        },
+        other => CommandResult::Error(format!(
+            "unsupported format '{other}' (expected text or json)"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_COMMITTER_DATE", "1700000000 +0000")
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    #[test]
+    fn test_commits_since_last_tag() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        git(dir, &["init", "-q"]);
+        for msg in ["one", "two", "three"] {
+            std::fs::write(dir.join(msg), "").unwrap();
+            git(dir, &["add", "-A"]);
+            git(dir, &["commit", "-q", "-m", msg]);
+            if msg == "one" {
+                git(dir, &["tag", "v0.1.0"]);
+            }
+        }
+        let s = status("core", dir, None);
+        assert_eq!(s.tag.as_deref(), Some("v0.1.0"));
+        assert_eq!(s.commits, 2);
+        assert_eq!(s.oldest, Some(1_700_000_000));
+        assert_eq!(status("core", dir, Some("release-*")).tag, None);
+        assert_eq!(status("core", dir, Some("release-*")).commits, 3);
+    }
+
+    #[test]
+    fn test_render_text() {
+        let now = 1_700_000_000 + 10 * 86_400;
+        let statuses = vec![
+            RepoStatus {
+                repo: "core".to_string(),
+                tag: Some("v1.2.0".to_string()),
+                commits: 3,
+                oldest: Some(1_700_000_000),
+                error: None,
+            },
+            RepoStatus {
+                repo: "app".to_string(),
+                tag: Some("v0.4.0".to_string()),
+                commits: 0,
+                oldest: None,
+                error: None,
+            },
+        ];
+        let text = render_text(&statuses, now);
+        assert!(
+            text.contains("core: 3 unreleased commits since v1.2.0, oldest 10 days old"),
+            "{text}"
+        );
+        assert!(text.contains("app: up to date with v0.4.0"), "{text}");
+        assert!(text.contains("1/2 repos have unreleased commits"), "{text}");
+        assert_eq!(
+            render_json(&statuses, now)["repos"][0]["oldest_commit_age_days"],
+            10
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Delegating meta commands to a repo's xtask
+//!
+//! Repos following the xtask pattern keep their automation in a workspace
+//! binary run as `cargo run -p xtask -- <task>`. `[xtask.tasks]` maps meta
+//! commands (`ci`, `test`, ...) to tasks for every repo that has an
+//! `xtask/Cargo.toml`; `[xtask.repos.<repo>]` maps them for one repo
+//! regardless of layout. A mapped repo runs its task instead of the cargo
+//! subcommand, and commands cargo doesn't know, like `meta cargo ci`, run on
+//! the mapped repos only.
+
+use crate::config::XtaskConfig;
+use crate::{project_path, PlannedCommand};
+use std::path::Path;
+
+/// Package running the tasks unless `[xtask] package` says otherwise
+const DEFAULT_PACKAGE: &str = "xtask";
+
+/// Task `repo` runs for the meta subcommand `sub`, if any
+fn task_for<'a>(config: &'a XtaskConfig, repo: &str, sub: &str, cwd: &Path) -> Option<&'a str> {
+    if let Some(task) = config.repos.get(repo).and_then(|tasks| tasks.get(sub)) {
+        return Some(task);
+    }
+    let task = config.tasks.get(sub)?;
+    project_path(cwd, repo)
+        .join("xtask")
+        .join("Cargo.toml")
+        .is_file()
+        .then_some(task.as_str())
+}
+
+/// Command line running `task` with the remaining meta arguments
+fn xtask_command(cargo: &str, config: &XtaskConfig, task: &str, args: &[String]) -> String {
+    let package = config.package.as_deref().unwrap_or(DEFAULT_PACKAGE);
+    let mut cmd = format!("{cargo} run -q -p {package} -- {task}");
+    for arg in args {
+        cmd.push(' ');
+        cmd.push_str(arg);
+    }
+    cmd
+}
+
+/// Replace the planned cargo command of every repo that maps `sub` to a task
+pub(crate) fn apply(
+    commands: &mut [PlannedCommand],
+    cargo: &str,
+    sub: &str,
+    args: &[String],
+    cwd: &Path,
+    config: &XtaskConfig,
+) {
+    for planned in commands {
+        if let Some(task) = task_for(config, &planned.dir, sub, cwd) {
+            planned.cmd = xtask_command(cargo, config, task, args);
+        }
+    }
+}
+
+/// Plan `sub` on the repos that map it to a task; empty when none do
+pub(crate) fn plan(
+    cargo: &str,
+    sub: &str,
+    args: &[String],
+    repos: &[String],
+    cwd: &Path,
+    config: &XtaskConfig,
+) -> Vec<PlannedCommand> {
+    repos
+        .iter()
+        .filter_map(|repo| {
+            task_for(config, repo, sub, cwd).map(|task| PlannedCommand {
+                dir: repo.clone(),
+                cmd: xtask_command(cargo, config, task, args),
+                env: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_tasks_apply_to_repos_with_xtask() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("api/xtask")).unwrap();
+        std::fs::write(temp_dir.path().join("api/xtask/Cargo.toml"), "").unwrap();
+        std::fs::create_dir(temp_dir.path().join("web")).unwrap();
+        let config =
+            Config::parse("[xtask.tasks]\nci = \"ci\"\n\n[xtask.repos.web]\ntest = \"test-all\"\n")
+                .unwrap();
+        let repos = vec!["api".to_string(), "web".to_string()];
+
+        let ci = plan("cargo", "ci", &[], &repos, temp_dir.path(), &config.xtask);
+        assert_eq!(ci.len(), 1);
+        assert_eq!(ci[0].dir, "api");
+        assert_eq!(ci[0].cmd, "cargo run -q -p xtask -- ci");
+
+        let mut tests: Vec<PlannedCommand> = repos
+            .iter()
+            .map(|r| PlannedCommand {
+                dir: r.clone(),
+                cmd: "cargo test --quiet".to_string(),
+                env: None,
+            })
+            .collect();
+        let args = vec!["--quiet".to_string()];
+        apply(
+            &mut tests,
+            "cargo",
+            "test",
+            &args,
+            temp_dir.path(),
+            &config.xtask,
+        );
+        assert_eq!(tests[0].cmd, "cargo test --quiet");
+        assert_eq!(tests[1].cmd, "cargo run -q -p xtask -- test-all --quiet");
+    }
+}